@@ -2,10 +2,12 @@
 //!
 //! These commands handle PTY operations invoked from the frontend.
 
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 use tauri::{AppHandle, State};
 
-use crate::pty::{PtyManager, PtySpawnConfig, PtyInfo, ShellType};
+use crate::pty::{PtyManager, PtySpawnConfig, PtyInfo, PtyMetrics, ShellType};
 
 /// Tauri state wrapper for PTY manager
 pub struct PtyState(pub Arc<PtyManager>);
@@ -48,8 +50,34 @@ pub async fn write_pty(
         .map_err(|e| e.to_string())
 }
 
+/// Paste text into a PTY session, bracketed if the shell enabled it
+#[tauri::command]
+pub async fn write_paste(
+    pty_id: String,
+    text: String,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state
+        .0
+        .write_paste(&pty_id, &text)
+        .map_err(|e| e.to_string())
+}
+
+/// Turn a PTY session's input echo on or off (Unix only)
+#[tauri::command]
+pub async fn set_echo(
+    pty_id: String,
+    enabled: bool,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state
+        .0
+        .set_echo(&pty_id, enabled)
+        .map_err(|e| format!("{:#}", e))
+}
+
 /// Send interrupt signal (Ctrl+C) to a PTY session
-/// This is a dedicated command that uses Windows API on Windows
+/// This writes ETX (0x03), which the PTY turns into SIGINT or CTRL_C_EVENT
 #[tauri::command]
 pub async fn send_interrupt(
     pty_id: String,
@@ -57,7 +85,7 @@ pub async fn send_interrupt(
 ) -> Result<(), String> {
     log::info!("[send_interrupt] Sending interrupt to PTY {}", &pty_id[..8.min(pty_id.len())]);
     
-    // Use the PTY manager's send_ctrl_c which uses Windows API
+    // send_ctrl_c writes ETX (0x03); the PTY turns it into SIGINT or CTRL_C_EVENT
     state.0.send_ctrl_c(&pty_id).map_err(|e| e.to_string())?;
     
     log::info!("[send_interrupt] Interrupt sent to PTY {}", &pty_id[..8.min(pty_id.len())]);
@@ -65,6 +93,22 @@ pub async fn send_interrupt(
     Ok(())
 }
 
+/// Update a session's tracked working directory
+///
+/// With `send_cd`, a `cd` into the directory is also typed into the shell.
+#[tauri::command]
+pub async fn set_working_directory(
+    pty_id: String,
+    path: String,
+    send_cd: Option<bool>,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state
+        .0
+        .set_working_directory(&pty_id, Path::new(&path), send_cd.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
 /// Resize a PTY session
 #[tauri::command]
 pub async fn resize_pty(
@@ -79,6 +123,60 @@ pub async fn resize_pty(
         .map_err(|e| e.to_string())
 }
 
+/// Resize a PTY session to fit a viewport in pixels, given the font's cell
+/// size; returns the `[rows, cols]` applied
+#[tauri::command]
+pub async fn resize_pty_by_pixels(
+    pty_id: String,
+    pixel_width: u32,
+    pixel_height: u32,
+    cell_width: f64,
+    cell_height: f64,
+    state: State<'_, PtyState>,
+) -> Result<(u16, u16), String> {
+    state
+        .0
+        .resize_by_pixels(&pty_id, pixel_width, pixel_height, cell_width, cell_height)
+        .map(|size| (size.rows, size.cols))
+        .map_err(|e| e.to_string())
+}
+
+/// Resize every PTY session to the same dimensions (e.g. on window resize)
+#[tauri::command]
+pub async fn resize_all_pty(
+    rows: u16,
+    cols: u16,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    let failures = state.0.resize_all(rows, cols);
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    Err(failures
+        .iter()
+        .map(|(id, e)| format!("{}: {:#}", id, e))
+        .collect::<Vec<_>>()
+        .join("; "))
+}
+
+/// Type a command line (plus Enter) into every running PTY session
+///
+/// Returns the outcome per session ID: `None` if the command was sent,
+/// otherwise the error.
+#[tauri::command]
+pub async fn broadcast_command(
+    command: String,
+    state: State<'_, PtyState>,
+) -> Result<HashMap<String, Option<String>>, String> {
+    Ok(state
+        .0
+        .broadcast_command(&command)
+        .into_iter()
+        .map(|(id, result)| (id, result.err().map(|e| format!("{:#}", e))))
+        .collect())
+}
+
 /// Kill a PTY session
 #[tauri::command]
 pub async fn kill_pty(
@@ -91,6 +189,35 @@ pub async fn kill_pty(
         .map_err(|e| e.to_string())
 }
 
+/// Detach a PTY session from its window without killing it
+///
+/// The shell keeps running and its output is held until `attach_pty`.
+#[tauri::command]
+pub async fn detach_pty(
+    pty_id: String,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state
+        .0
+        .detach(&pty_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Attach a detached PTY session to the calling window
+///
+/// Output produced while detached is delivered first.
+#[tauri::command]
+pub async fn attach_pty(
+    pty_id: String,
+    app_handle: AppHandle,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state
+        .0
+        .attach(&pty_id, app_handle)
+        .map_err(|e| e.to_string())
+}
+
 /// Kill child processes of a PTY session (not the shell itself)
 /// Returns the number of child processes killed
 #[tauri::command]
@@ -107,6 +234,57 @@ pub async fn kill_child_processes(
     Ok(count)
 }
 
+/// Replay an asciinema recording as a virtual session
+///
+/// Returns the session ID its output events are emitted under.
+/// `speed` defaults to 1.0 (real time).
+#[tauri::command]
+pub async fn replay_recording(
+    path: String,
+    speed: Option<f64>,
+    app_handle: AppHandle,
+    state: State<'_, PtyState>,
+) -> Result<String, String> {
+    state
+        .0
+        .replay(Path::new(&path), speed.unwrap_or(1.0), app_handle)
+        .map_err(|e| e.to_string())
+}
+
+/// Mirror a session's raw output bytes to a file, for debugging
+///
+/// Follow it with `tail -f` while reproducing a rendering problem.
+#[tauri::command]
+pub async fn start_raw_capture(
+    pty_id: String,
+    path: String,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state
+        .0
+        .start_raw_capture(&pty_id, Path::new(&path))
+        .map_err(|e| e.to_string())
+}
+
+/// Stop a raw output capture
+#[tauri::command]
+pub async fn stop_raw_capture(
+    pty_id: String,
+    state: State<'_, PtyState>,
+) -> Result<(), String> {
+    state
+        .0
+        .stop_raw_capture(&pty_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Shut down every PTY session (called by the frontend on window close)
+#[tauri::command]
+pub async fn shutdown_all(state: State<'_, PtyState>) -> Result<(), String> {
+    state.0.shutdown_all();
+    Ok(())
+}
+
 /// Get information about a PTY session
 #[tauri::command]
 pub async fn get_pty_info(
@@ -116,6 +294,24 @@ pub async fn get_pty_info(
     Ok(state.0.get_info(&pty_id))
 }
 
+/// Get a PTY session's recent output (for views created after it started)
+#[tauri::command]
+pub async fn get_pty_scrollback(
+    pty_id: String,
+    state: State<'_, PtyState>,
+) -> Result<Option<String>, String> {
+    Ok(state.0.get_scrollback(&pty_id))
+}
+
+/// Get a PTY session's output totals (bytes read, chunks emitted, uptime)
+#[tauri::command]
+pub async fn get_pty_metrics(
+    pty_id: String,
+    state: State<'_, PtyState>,
+) -> Result<Option<PtyMetrics>, String> {
+    Ok(state.0.get_metrics(&pty_id))
+}
+
 /// List all active PTY sessions
 #[tauri::command]
 pub async fn list_pty_sessions(
@@ -137,6 +333,8 @@ pub async fn spawn_default_shell(
         working_directory,
         rows: 24,
         cols: 80,
+        env: None,
+        scrollback_bytes: None,
     };
 
     state
@@ -0,0 +1,261 @@
+//! Arithmetic expansion for `$((...))`
+//!
+//! Integer expressions as other shells evaluate them: `+ - * / %`,
+//! comparisons and `&& || !` (which give 1 or 0), parentheses, and
+//! variable names standing for their value (0 when unset or empty).
+//! Overflow wraps around.
+
+use std::fmt;
+
+/// Why an expression couldn't be evaluated
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArithmeticError {
+    /// The expression isn't well formed
+    Syntax(String),
+    DivisionByZero,
+    /// A variable whose value isn't an integer
+    NotANumber(String),
+}
+
+impl fmt::Display for ArithmeticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArithmeticError::Syntax(message) => write!(f, "syntax error: {}", message),
+            ArithmeticError::DivisionByZero => write!(f, "division by 0"),
+            ArithmeticError::NotANumber(name) => write!(f, "{}: value is not a number", name),
+        }
+    }
+}
+
+impl std::error::Error for ArithmeticError {}
+
+/// The expression of a `$((expr))`, given the body of the `$(...)` the
+/// lexer read it as (`(expr)`); `None` for a command substitution
+pub fn expression(body: &str) -> Option<&str> {
+    body.strip_prefix('(')?.strip_suffix(')')
+}
+
+/// Evaluate `expr`, looking variables up with `lookup`
+pub fn evaluate(
+    expr: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<i64, ArithmeticError> {
+    let tokens = tokenize(expr)?;
+    if tokens.is_empty() {
+        // `$(( ))` is 0, as in bash
+        return Ok(0);
+    }
+
+    let mut evaluator = Evaluator {
+        tokens,
+        pos: 0,
+        lookup: &lookup,
+    };
+    let value = evaluator.expression(0)?;
+    match evaluator.tokens.get(evaluator.pos) {
+        None => Ok(value),
+        Some(token) => Err(ArithmeticError::Syntax(format!("unexpected {}", token))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Operator(&'static str),
+    LeftParen,
+    RightParen,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "'{}'", n),
+            Token::Name(name) => write!(f, "'{}'", name),
+            Token::Operator(op) => write!(f, "'{}'", op),
+            Token::LeftParen => write!(f, "'('"),
+            Token::RightParen => write!(f, "')'"),
+        }
+    }
+}
+
+/// Operators, two-character ones first so they win over their prefixes
+const OPERATORS: &[&str] = &[
+    "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "!",
+];
+
+/// Binary operators and their precedence (higher binds tighter)
+const BINARY: &[(&str, u8)] = &[
+    ("||", 1),
+    ("&&", 2),
+    ("==", 3),
+    ("!=", 3),
+    ("<", 4),
+    ("<=", 4),
+    (">", 4),
+    (">=", 4),
+    ("+", 5),
+    ("-", 5),
+    ("*", 6),
+    ("/", 6),
+    ("%", 6),
+];
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, ArithmeticError> {
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() {
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+            let number = rest[..len]
+                .parse()
+                .map_err(|_| ArithmeticError::Syntax(format!("bad number '{}'", &rest[..len])))?;
+            tokens.push(Token::Number(number));
+            len
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..len].to_string()));
+            len
+        } else if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::LeftParen } else { Token::RightParen });
+            1
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Operator(op));
+            op.len()
+        } else {
+            return Err(ArithmeticError::Syntax(format!("unexpected '{}'", c)));
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Precedence climbing over the tokens of one expression
+struct Evaluator<'a, F> {
+    tokens: Vec<Token>,
+    pos: usize,
+    lookup: &'a F,
+}
+
+impl<F: Fn(&str) -> Option<String>> Evaluator<'_, F> {
+    /// Evaluate binary operators binding at least as tightly as `min_precedence`
+    fn expression(&mut self, min_precedence: u8) -> Result<i64, ArithmeticError> {
+        let mut left = self.unary()?;
+        while let Some((op, precedence)) = self.binary_operator() {
+            if precedence < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let right = self.expression(precedence + 1)?;
+            left = apply(op, left, right)?;
+        }
+        Ok(left)
+    }
+
+    /// The binary operator at the current position, if any
+    fn binary_operator(&self) -> Option<(&'static str, u8)> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Operator(op)) => BINARY.iter().find(|(binary, _)| binary == op).copied(),
+            _ => None,
+        }
+    }
+
+    fn unary(&mut self) -> Result<i64, ArithmeticError> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Operator("-")) => Ok(self.unary()?.wrapping_neg()),
+            Some(Token::Operator("+")) => self.unary(),
+            Some(Token::Operator("!")) => Ok((self.unary()? == 0) as i64),
+            Some(Token::Number(n)) => Ok(n),
+            Some(Token::Name(name)) => {
+                let value = (self.lookup)(&name).unwrap_or_default();
+                match value.trim() {
+                    "" => Ok(0),
+                    value => value.parse().map_err(|_| ArithmeticError::NotANumber(name)),
+                }
+            }
+            Some(Token::LeftParen) => {
+                let value = self.expression(0)?;
+                match self.tokens.get(self.pos) {
+                    Some(Token::RightParen) => {
+                        self.pos += 1;
+                        Ok(value)
+                    }
+                    _ => Err(ArithmeticError::Syntax("missing ')'".to_string())),
+                }
+            }
+            Some(token) => Err(ArithmeticError::Syntax(format!("unexpected {}", token))),
+            None => Err(ArithmeticError::Syntax("operand expected".to_string())),
+        }
+    }
+}
+
+fn apply(op: &str, left: i64, right: i64) -> Result<i64, ArithmeticError> {
+    Ok(match op {
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err(ArithmeticError::DivisionByZero),
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        "<" => (left < right) as i64,
+        "<=" => (left <= right) as i64,
+        ">" => (left > right) as i64,
+        ">=" => (left >= right) as i64,
+        "==" => (left == right) as i64,
+        "!=" => (left != right) as i64,
+        "&&" => (left != 0 && right != 0) as i64,
+        "||" => (left != 0 || right != 0) as i64,
+        _ => unreachable!("not a binary operator: {}", op),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let lookup = |name: &str| match name {
+            "x" => Some("5".to_string()),
+            "empty" => Some(String::new()),
+            "word" => Some("abc".to_string()),
+            _ => None,
+        };
+        let cases: &[(&str, Result<i64, ArithmeticError>)] = &[
+            ("2 + 3 * 4", Ok(14)),
+            ("(2 + 3) * 4", Ok(20)),
+            ("10 - 4 - 3", Ok(3)),
+            ("7 / 2 * 2", Ok(6)),
+            ("-7 / 2", Ok(-3)),
+            ("-7 % 3", Ok(-1)),
+            ("2 * -3", Ok(-6)),
+            ("x*2", Ok(10)),
+            ("unset + empty + 1", Ok(1)),
+            ("1 + 2 < 4", Ok(1)),
+            ("3 >= 4", Ok(0)),
+            ("x == 5 && x != 6", Ok(1)),
+            ("0 || !0", Ok(1)),
+            ("!x", Ok(0)),
+            ("  ", Ok(0)),
+            ("9223372036854775807 + 1", Ok(i64::MIN)),
+            ("1 / 0", Err(ArithmeticError::DivisionByZero)),
+            ("5 % (x - 5)", Err(ArithmeticError::DivisionByZero)),
+            ("word + 1", Err(ArithmeticError::NotANumber("word".to_string()))),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(&evaluate(expr, lookup), expected, "{}", expr);
+        }
+
+        for malformed in ["1 +", "(1 + 2", "1 2", "2 ** 3", "1 = 2", "3 $ 4", "08x"] {
+            let result = evaluate(malformed, lookup);
+            assert!(matches!(result, Err(ArithmeticError::Syntax(_))), "{}: {:?}", malformed, result);
+        }
+
+        assert_eq!(expression("(1 + 2)"), Some("1 + 2"));
+        assert_eq!(expression("echo (1)"), None);
+    }
+}
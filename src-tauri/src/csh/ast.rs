@@ -11,6 +11,9 @@ pub struct Command {
     pub name: String,
     /// Command arguments
     pub args: Vec<String>,
+    /// How each argument was quoted, which decides how it is expanded
+    /// (missing entries count as unquoted)
+    pub quoted_args: Vec<Quoting>,
     /// Environment variable assignments for this command only
     pub env_assignments: Vec<(String, String)>,
 }
@@ -20,6 +23,7 @@ impl Command {
         Self {
             name,
             args: Vec::new(),
+            quoted_args: Vec::new(),
             env_assignments: Vec::new(),
         }
     }
@@ -28,6 +32,7 @@ impl Command {
         Self {
             name,
             args,
+            quoted_args: Vec::new(),
             env_assignments: Vec::new(),
         }
     }
@@ -43,6 +48,18 @@ impl fmt::Display for Command {
     }
 }
 
+/// How a word was quoted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Quoting {
+    /// Gets brace, variable, substitution and wildcard expansion
+    #[default]
+    Unquoted,
+    /// "..." gets variables and substitutions but no wildcards
+    Double,
+    /// '...' is taken literally
+    Single,
+}
+
 /// Type of I/O redirection
 #[derive(Debug, Clone, PartialEq)]
 pub enum RedirectType {
@@ -50,12 +67,16 @@ pub enum RedirectType {
     StdoutOverwrite,
     /// >> file (append stdout)
     StdoutAppend,
+    /// >| file (overwrite stdout even when noclobber is set)
+    StdoutClobber,
     /// < file (read stdin)
     StdinRead,
     /// 2> file (redirect stderr)
     StderrOverwrite,
     /// 2>> file (append stderr)
     StderrAppend,
+    /// 2>&1 (stderr to wherever stdout goes at that point; no target)
+    StderrToStdout,
     /// &> file (redirect both stdout and stderr)
     BothOverwrite,
     /// &>> file (append both stdout and stderr)
@@ -179,6 +200,8 @@ pub enum Statement {
     For {
         variable: String,
         items: Vec<String>,
+        /// How each item was quoted, as in `Command::quoted_args`
+        quoted_items: Vec<Quoting>,
         body: Vec<Statement>,
     },
     /// Function definition
@@ -186,6 +209,12 @@ pub enum Statement {
         name: String,
         body: Vec<Statement>,
     },
+    /// `a && if ...; fi || b`: statements joined by `&&` and `||` where
+    /// some are compound, run left to right like a command line
+    AndOr {
+        first: Box<Statement>,
+        rest: Vec<(LogicalOp, Statement)>,
+    },
     /// Return from function
     Return(Option<i32>),
     /// Break from loop
@@ -195,6 +224,10 @@ pub enum Statement {
 }
 
 /// Result of command execution
+///
+/// `code` is kept exactly as the process reported it, which on Windows
+/// can be far outside 0-255 (NTSTATUS values like 0xC0000005 show up as
+/// negative numbers). Only 0 is success; `$?` uses `posix_code`.
 #[derive(Debug, Clone)]
 pub struct ExitStatus {
     pub code: i32,
@@ -213,4 +246,41 @@ impl ExitStatus {
     pub fn is_success(&self) -> bool {
         self.code == 0
     }
+
+    /// The status as POSIX shells report it in `$?`
+    pub fn posix_code(&self) -> u8 {
+        posix_exit_code(self.code)
+    }
+}
+
+impl From<std::process::ExitStatus> for ExitStatus {
+    fn from(status: std::process::ExitStatus) -> Self {
+        // Killed by a signal: report 128+n like other shells
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return Self {
+                    code: 128 + signal,
+                    signal: Some(signal),
+                };
+            }
+        }
+
+        Self::failure(status.code().unwrap_or(1))
+    }
+}
+
+/// Fold an exit code into 0-255
+///
+/// Out-of-range codes keep their low byte, except that a failure is never
+/// folded into 0 (which would read as success); those become 1.
+pub fn posix_exit_code(code: i32) -> u8 {
+    match u8::try_from(code) {
+        Ok(code) => code,
+        Err(_) => match code as u8 {
+            0 => 1,
+            low => low,
+        },
+    }
 }
@@ -0,0 +1,165 @@
+//! Brace expansion: `file{1,2}.txt` and `{1..5}` in command arguments
+
+/// Most words one brace expansion may produce
+pub const MAX_WORDS: usize = 100_000;
+
+/// Expand every brace group in `word`, left to right
+///
+/// A group is a comma list (`{a,b}`, which may nest) or an integer range
+/// (`{1..5}`, `{5..1}`). Anything else, such as `{}` or `{a}`, is kept
+/// as written, and `${VAR}` and `$(...)` are left for variable expansion.
+/// So is a word that would expand to more than `MAX_WORDS` words.
+pub fn expand(word: &str) -> Vec<String> {
+    expand_within(word, MAX_WORDS).unwrap_or_else(|| vec![word.to_string()])
+}
+
+/// `expand`, or `None` as soon as more than `limit` words come out
+fn expand_within(word: &str, limit: usize) -> Option<Vec<String>> {
+    let Some((open, close, alternatives)) = find_group(word) else {
+        return Some(vec![word.to_string()]);
+    };
+
+    let prefix = &word[..open];
+    let suffix = &word[close + 1..];
+    let mut words = Vec::new();
+    for alternative in alternatives {
+        let rest = expand_within(&format!("{}{}", alternative, suffix), limit - words.len())?;
+        words.extend(rest.into_iter().map(|rest| format!("{}{}", prefix, rest)));
+        if words.len() > limit {
+            return None;
+        }
+    }
+    Some(words)
+}
+
+/// The first brace group in `word`: its `{` and `}` positions and the
+/// words it stands for
+fn find_group(word: &str) -> Option<(usize, usize, Vec<String>)> {
+    let bytes = word.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'$' if matches!(bytes.get(i + 1), Some(b'{' | b'(')) => {
+                i = skip_substitution(bytes, i + 1);
+                continue;
+            }
+            b'{' => {
+                if let Some((close, alternatives)) = parse_group(word, i) {
+                    return Some((i, close, alternatives));
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Index just past the bracket matching the one at `open`
+fn skip_substitution(bytes: &[u8], open: usize) -> usize {
+    let (left, right) = if bytes[open] == b'{' { (b'{', b'}') } else { (b'(', b')') };
+    let mut depth = 0;
+    for (i, &b) in bytes.iter().enumerate().skip(open) {
+        if b == left {
+            depth += 1;
+        } else if b == right {
+            depth -= 1;
+            if depth == 0 {
+                return i + 1;
+            }
+        }
+    }
+    bytes.len()
+}
+
+/// The closing brace and alternatives of a group opening at `open`
+fn parse_group(word: &str, open: usize) -> Option<(usize, Vec<String>)> {
+    let bytes = word.as_bytes();
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut i = open + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'$' if matches!(bytes.get(i + 1), Some(b'{' | b'(')) => {
+                i = skip_substitution(bytes, i + 1);
+                continue;
+            }
+            b'{' => depth += 1,
+            b'}' if depth > 0 => depth -= 1,
+            b'}' => {
+                let body = &word[open + 1..i];
+                if commas.is_empty() {
+                    return range(body).map(|alternatives| (i, alternatives));
+                }
+                let mut alternatives = Vec::new();
+                let mut start = open + 1;
+                for comma in commas.into_iter().chain([i]) {
+                    alternatives.push(word[start..comma].to_string());
+                    start = comma + 1;
+                }
+                return Some((i, alternatives));
+            }
+            b',' if depth == 0 => commas.push(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The numbers of a `start..end` range, counting down if `end` is smaller
+///
+/// `None` for a range of more than `MAX_WORDS` numbers.
+fn range(body: &str) -> Option<Vec<String>> {
+    let (start, end) = body.split_once("..")?;
+    let start: i64 = start.parse().ok()?;
+    let end: i64 = end.parse().ok()?;
+    if start.abs_diff(end) >= MAX_WORDS as u64 {
+        return None;
+    }
+    let numbers: Vec<i64> = if start <= end {
+        (start..=end).collect()
+    } else {
+        (end..=start).rev().collect()
+    };
+    Some(numbers.iter().map(|n| n.to_string()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lists_and_ranges() {
+        assert_eq!(expand("file{1,2,3}.txt"), vec!["file1.txt", "file2.txt", "file3.txt"]);
+        assert_eq!(expand("{1..5}"), vec!["1", "2", "3", "4", "5"]);
+        assert_eq!(expand("{3..-1}"), vec!["3", "2", "1", "0", "-1"]);
+        assert_eq!(expand("{a,b}{1..2}"), vec!["a1", "a2", "b1", "b2"]);
+        assert_eq!(expand("x{,.bak}"), vec!["x", "x.bak"]);
+
+        // Nested groups expand inside their alternative
+        assert_eq!(expand("{a,b{c,d}}"), vec!["a", "bc", "bd"]);
+        assert_eq!(expand("{a,{1..2}x}y"), vec!["ay", "1xy", "2xy"]);
+        // An unmatched or invalid outer brace keeps the inner group working
+        assert_eq!(expand("{x{a,b}"), vec!["{xa", "{xb"]);
+        assert_eq!(expand("{q{a,b}}"), vec!["{qa}", "{qb}"]);
+    }
+
+    #[test]
+    fn test_non_groups_are_kept() {
+        for word in ["{}", "{a}", "{a..b}", "{1..}", "a,b", "{a,b", "a}b"] {
+            assert_eq!(expand(word), vec![word]);
+        }
+        assert_eq!(expand("${HOME}"), vec!["${HOME}"]);
+        assert_eq!(expand("$(echo {a,b})"), vec!["$(echo {a,b})"]);
+        assert_eq!(expand("${A}{x,y}"), vec!["${A}x", "${A}y"]);
+    }
+
+    #[test]
+    fn test_oversized_expansions_are_kept() {
+        assert_eq!(expand("{1..9999999999}"), vec!["{1..9999999999}"]);
+        assert_eq!(expand("{-9223372036854775808..9223372036854775807}").len(), 1);
+        assert_eq!(expand("{1..100}{1..100}{1..100}"), vec!["{1..100}{1..100}{1..100}"]);
+        assert_eq!(expand("{1..100}{1..1000}").len(), MAX_WORDS);
+    }
+}
@@ -24,6 +24,10 @@ pub fn execute_alias(args: &[String], env: &mut Environment) -> BuiltinResult {
         return BuiltinResult::success_with_output(output);
     }
 
+    if args[0] == "--resolve" || args[0] == "-r" {
+        return resolve(&args[1..], env);
+    }
+
     for arg in args {
         if let Some((name, value)) = arg.split_once('=') {
             // Remove surrounding quotes if present
@@ -48,6 +52,22 @@ pub fn execute_alias(args: &[String], env: &mut Environment) -> BuiltinResult {
     BuiltinResult::success()
 }
 
+/// alias --resolve NAME... - print the command each alias finally runs
+fn resolve(names: &[String], env: &Environment) -> BuiltinResult {
+    if names.is_empty() {
+        return BuiltinResult::failure(1, "alias: --resolve: not enough arguments\n".to_string());
+    }
+
+    let mut output = String::new();
+    for name in names {
+        match env.expand_alias(name) {
+            Some(expansion) => output.push_str(&format!("{}\n", expansion)),
+            None => return BuiltinResult::failure(1, format!("alias: {}: not found\n", name)),
+        }
+    }
+    BuiltinResult::success_with_output(output)
+}
+
 /// unalias command - remove aliases
 pub fn execute_unalias(args: &[String], env: &mut Environment) -> BuiltinResult {
     if args.is_empty() {
@@ -68,3 +88,25 @@ pub fn execute_unalias(args: &[String], env: &mut Environment) -> BuiltinResult
 
     BuiltinResult::success()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_prints_full_expansion() {
+        let mut env = Environment::new();
+        let args = |list: &[&str]| -> Vec<String> { list.iter().map(|s| s.to_string()).collect() };
+        execute_alias(&args(&["g=git", "gs=g status", "gss=gs -s"]), &mut env);
+
+        let result = execute_alias(&args(&["--resolve", "gss", "g"]), &mut env);
+        assert_eq!(result.output.as_deref(), Some("git status -s\ngit\n"));
+
+        // Plain lookup still shows the alias as defined
+        let result = execute_alias(&args(&["gss"]), &mut env);
+        assert_eq!(result.output.as_deref(), Some("alias gss='gs -s'\n"));
+
+        assert!(!execute_alias(&args(&["--resolve", "nope"]), &mut env).status.is_success());
+        assert!(!execute_alias(&args(&["--resolve"]), &mut env).status.is_success());
+    }
+}
@@ -1,29 +1,52 @@
 //! cat - Concatenate and display file contents
 
-use crate::csh::builtins::BuiltinResult;
+use crate::csh::builtins::{BuiltinResult, OutputContext};
 use crate::csh::environment::Environment;
+use crate::csh::highlight::{self, Highlighter};
+use crate::csh::text;
 use std::fs;
 use std::path::PathBuf;
 
-pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
+pub fn execute(args: &[String], env: &Environment, ctx: OutputContext) -> BuiltinResult {
     if args.is_empty() {
         return BuiltinResult::failure(1, "cat: missing file operand\n".to_string());
     }
 
     let mut show_line_numbers = false;
     let mut show_ends = false;
+    let mut show_tabs = false;
+    let mut show_nonprinting = false;
+    let mut color = false;
+    let mut keep_bom = false;
     let mut files: Vec<PathBuf> = Vec::new();
 
     // Parse arguments
     for arg in args {
-        if arg.starts_with('-') && arg.len() > 1 {
+        if arg == "--color" {
+            color = true;
+        } else if arg == "--keep-bom" {
+            keep_bom = true;
+        } else if arg.starts_with('-') && arg.len() > 1 {
             for c in arg[1..].chars() {
                 match c {
                     'n' => show_line_numbers = true,
                     'E' => show_ends = true,
+                    'T' => show_tabs = true,
+                    'v' => show_nonprinting = true,
+                    'A' => {
+                        show_nonprinting = true;
+                        show_ends = true;
+                        show_tabs = true;
+                    }
                     'e' => {
+                        show_nonprinting = true;
                         show_ends = true;
                     }
+                    't' => {
+                        show_nonprinting = true;
+                        show_tabs = true;
+                    }
+                    'c' => color = true,
                     _ => {}
                 }
             }
@@ -52,21 +75,92 @@ pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
         return BuiltinResult::failure(1, "cat: missing file operand\n".to_string());
     }
 
+    // Escape codes are only useful on a real terminal, and would hide the
+    // markers of -v/-T
+    let color = color && ctx.is_terminal && !show_nonprinting && !show_tabs;
+
     let mut output = String::new();
     let mut line_number = 1;
 
     for file_path in &files {
-        match fs::read_to_string(file_path) {
-            Ok(contents) => {
-                for line in contents.lines() {
+        match fs::read(file_path) {
+            Ok(bytes) if show_nonprinting || show_tabs => {
+                // Work on raw bytes so control and invalid UTF-8 bytes
+                // are shown rather than lost in decoding
+                let mut bytes = bytes.as_slice();
+                if !keep_bom {
+                    bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+                }
+                if bytes.is_empty() {
+                    continue;
+                }
+                // `$` marks real newlines only, so not an unterminated last line
+                let (bytes, terminated) = match bytes.strip_suffix(b"\n") {
+                    Some(bytes) => (bytes, true),
+                    None => (bytes, false),
+                };
+
+                let mut lines = bytes.split(|&b| b == b'\n').peekable();
+                while let Some(line) = lines.next() {
                     if show_line_numbers {
                         output.push_str(&format!("{:6}  ", line_number));
                         line_number += 1;
                     }
 
-                    output.push_str(line);
+                    if show_nonprinting {
+                        for &byte in line {
+                            push_visible(&mut output, byte, show_tabs);
+                        }
+                    } else {
+                        output.push_str(&String::from_utf8_lossy(line).replace('\t', "^I"));
+                    }
 
-                    if show_ends {
+                    if show_ends && (terminated || lines.peek().is_some()) {
+                        output.push('$');
+                    }
+
+                    output.push('\n');
+                }
+            }
+            Ok(bytes) => {
+                let contents = match String::from_utf8(bytes) {
+                    Ok(contents) => contents,
+                    Err(_) => {
+                        return BuiltinResult::failure(
+                            1,
+                            format!("cat: {}: stream did not contain valid UTF-8 (try -v)\n", file_path.display()),
+                        );
+                    }
+                };
+                let contents = if keep_bom {
+                    contents.as_str()
+                } else {
+                    text::strip_bom(&contents)
+                };
+
+                let mut highlighter = if color {
+                    file_path
+                        .extension()
+                        .and_then(|ext| highlight::rules_for_extension(&ext.to_string_lossy()))
+                        .map(Highlighter::new)
+                } else {
+                    None
+                };
+
+                let terminated = contents.ends_with('\n');
+                let mut lines = contents.lines().peekable();
+                while let Some(line) = lines.next() {
+                    if show_line_numbers {
+                        output.push_str(&format!("{:6}  ", line_number));
+                        line_number += 1;
+                    }
+
+                    match highlighter.as_mut() {
+                        Some(h) => output.push_str(&h.highlight_line(line)),
+                        None => output.push_str(line),
+                    }
+
+                    if show_ends && (terminated || lines.peek().is_some()) {
                         output.push('$');
                     }
 
@@ -84,3 +178,112 @@ pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
 
     BuiltinResult::success_with_output(output)
 }
+
+/// The UTF-8 byte order mark as raw bytes
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Append `byte` using GNU cat's `-v` notation: `^X` for control
+/// characters, `^?` for DEL, `M-` for bytes with the high bit set
+/// (tabs are left alone unless `show_tabs`, then `^I`)
+fn push_visible(output: &mut String, byte: u8, show_tabs: bool) {
+    match byte {
+        b'\t' if !show_tabs => output.push('\t'),
+        0x80..=0xFF => {
+            output.push_str("M-");
+            push_visible(output, byte - 0x80, true);
+        }
+        0x7F => output.push_str("^?"),
+        0x00..=0x1F => {
+            output.push('^');
+            output.push((byte + 0x40) as char);
+        }
+        _ => output.push(byte as char),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_sample(dir: &tempfile::TempDir) -> String {
+        let path = dir.path().join("main.rs");
+        fs::write(&path, "fn main() {\n    // greet\n    println!(\"hi\");\n}\n").unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_color_highlights_rust_on_terminal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_sample(&dir);
+        let env = Environment::new();
+
+        let result = execute(&["-c".to_string(), path], &env, OutputContext::terminal());
+        let output = result.output.unwrap();
+        assert!(output.contains("\x1b[1;35mfn\x1b[0m"));
+        assert!(output.contains("\x1b[90m// greet\x1b[0m"));
+        assert!(output.contains("\x1b[32m\"hi\"\x1b[0m"));
+    }
+
+    #[test]
+    fn test_strips_bom_and_crlf() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("win.txt");
+        fs::write(&path, "\u{feff}first\r\nsecond\r\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+        let env = Environment::new();
+
+        let result = execute(&["--keep-bom".to_string(), path.clone()], &env, OutputContext::redirected());
+        assert!(result.output.unwrap().starts_with('\u{feff}'));
+
+        let result = execute(&[path], &env, OutputContext::redirected());
+        assert_eq!(result.output.unwrap(), "first\nsecond\n");
+    }
+
+    #[test]
+    fn test_show_all_markers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ws.txt");
+        fs::write(&path, b"a\tb  \r\n\x1b[0m\x7f\n\xe9\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+        let env = Environment::new();
+
+        let result = execute(&["-A".to_string(), path.clone()], &env, OutputContext::redirected());
+        assert_eq!(result.output.unwrap(), "a^Ib  ^M$\n^[[0m^?$\nM-i$\n");
+
+        // -T alone only marks tabs; markers compose with -n
+        let result = execute(&["-nT".to_string(), path], &env, OutputContext::redirected());
+        let output = result.output.unwrap();
+        assert!(output.starts_with("     1  a^Ib  \r\n     2  \x1b[0m\x7f\n"));
+    }
+
+    #[test]
+    fn test_ends_mark_only_real_newlines() {
+        let dir = tempfile::tempdir().unwrap();
+        let empty = dir.path().join("empty.txt");
+        let open = dir.path().join("open.txt");
+        fs::write(&empty, "").unwrap();
+        fs::write(&open, "a\nb").unwrap();
+        let env = Environment::new();
+        let cat = |flag: &str, path: &std::path::Path| {
+            let args = [flag.to_string(), path.to_string_lossy().to_string()];
+            execute(&args, &env, OutputContext::redirected()).output.unwrap()
+        };
+
+        assert_eq!(cat("-A", &empty), "");
+        assert_eq!(cat("-E", &empty), "");
+        assert_eq!(cat("-A", &open), "a$\nb\n");
+        assert_eq!(cat("-E", &open), "a$\nb\n");
+    }
+
+    #[test]
+    fn test_color_disabled_when_redirected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_sample(&dir);
+        let env = Environment::new();
+
+        let result = execute(&["--color".to_string(), path], &env, OutputContext::redirected());
+        let output = result.output.unwrap();
+        assert!(!output.contains('\x1b'));
+        assert!(output.starts_with("fn main() {\n"));
+    }
+}
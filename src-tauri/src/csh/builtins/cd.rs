@@ -69,15 +69,16 @@ pub fn execute(args: &[String], env: &mut Environment) -> BuiltinResult {
     }
 
     // Change directory
-    if let Err(e) = env.set_cwd(target) {
+    if let Err(e) = env.set_cwd(target.clone()) {
         return BuiltinResult::failure(1, format!("cd: {}\n", e));
     }
+    env.record_visited_dir(target);
 
     BuiltinResult::success()
 }
 
 /// Normalize path by removing Windows extended path prefix (\\?\)
-fn normalize_path(path: PathBuf) -> PathBuf {
+pub fn normalize_path(path: PathBuf) -> PathBuf {
     let path_str = path.to_string_lossy();
     
     // Remove \\?\ prefix on Windows
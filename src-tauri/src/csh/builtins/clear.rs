@@ -1,8 +1,14 @@
 //! clear - Clear the terminal screen
 
-use crate::csh::builtins::BuiltinResult;
+use crate::csh::builtins::{BuiltinResult, OutputContext};
+
+pub fn execute(ctx: OutputContext) -> BuiltinResult {
+    // Escape codes are only meaningful to a terminal; `clear > file` and
+    // captured output get nothing
+    if !ctx.is_terminal {
+        return BuiltinResult::success();
+    }
 
-pub fn execute() -> BuiltinResult {
     // ANSI escape sequence to clear screen and move cursor to home
     BuiltinResult::success_with_output("\x1b[2J\x1b[H".to_string())
 }
@@ -0,0 +1,143 @@
+//! diag - Print environment diagnostics for bug reports
+
+use std::fs;
+use std::path::Path;
+
+use crate::csh::builtins::BuiltinResult;
+use crate::csh::environment::Environment;
+use crate::csh::history::History;
+use crate::csh::shell::VERSION;
+use crate::pty::types::ShellType;
+
+/// Shells Connexio can start besides CSH itself
+const SHELLS: [ShellType; 4] = [
+    ShellType::PowerShell,
+    ShellType::Cmd,
+    ShellType::Wsl,
+    ShellType::GitBash,
+];
+
+/// diag - print the report (also `csh --diagnose`)
+pub fn execute(env: &Environment, history: &History) -> BuiltinResult {
+    BuiltinResult::success_with_output(report(env, history, &probe_raw_mode()))
+}
+
+/// Whether the terminal can be switched to raw mode (what line editing needs)
+///
+/// Raw mode is switched straight back off again.
+pub fn probe_raw_mode() -> Result<(), String> {
+    crossterm::terminal::enable_raw_mode().map_err(|e| e.to_string())?;
+    let _ = crossterm::terminal::disable_raw_mode();
+    Ok(())
+}
+
+/// The diagnostics report, given the outcome of the raw mode probe
+pub fn report(env: &Environment, history: &History, raw_mode: &Result<(), String>) -> String {
+    let mut lines: Vec<(&str, String)> = vec![
+        ("version", format!("CSH {}", VERSION)),
+        ("platform", format!("{} {}", std::env::consts::OS, std::env::consts::ARCH)),
+        ("cwd", env.cwd().display().to_string()),
+    ];
+    for name in ["TERM", "COLORTERM", "SHELL"] {
+        let value = env.get_value(name).filter(|v| !v.is_empty());
+        lines.push((name, value.unwrap_or_else(|| "(unset)".to_string())));
+    }
+    lines.push(("history file", history_file(history)));
+
+    for (name, stream) in [
+        ("stdin tty", atty::Stream::Stdin),
+        ("stdout tty", atty::Stream::Stdout),
+        ("stderr tty", atty::Stream::Stderr),
+    ] {
+        lines.push((name, yes_no(atty::is(stream)).to_string()));
+    }
+    let raw = match raw_mode {
+        Ok(()) => "available".to_string(),
+        Err(e) => format!("unavailable ({})", e),
+    };
+    lines.push(("raw mode", raw));
+
+    let mut output = String::from("CSH diagnostics\n");
+    push_section(&mut output, &lines);
+
+    output.push_str("\nShells\n");
+    let path_var = env.get_value("PATH").unwrap_or_default();
+    let shells: Vec<(&str, String)> = SHELLS
+        .iter()
+        .map(|shell| {
+            let command = shell.get_command();
+            let status = if find_command(&command, &path_var) { "found" } else { "not found" };
+            (shell.display_name(), format!("{} ({})", command, status))
+        })
+        .collect();
+    push_section(&mut output, &shells);
+
+    output
+}
+
+/// Append `label  value` lines with the values aligned
+fn push_section(output: &mut String, lines: &[(&str, String)]) {
+    let width = lines.iter().map(|(label, _)| label.len()).max().unwrap_or(0) + 2;
+    for (label, value) in lines {
+        output.push_str(&format!("  {:<width$}{}\n", label, value, width = width));
+    }
+}
+
+/// History file path with its entry count and size on disk
+fn history_file(history: &History) -> String {
+    let Some(path) = history.file_path() else {
+        return "(none, history is not saved)".to_string();
+    };
+    match fs::metadata(path) {
+        Ok(meta) => format!("{} ({} entries, {} bytes)", path.display(), history.len(), meta.len()),
+        Err(_) => format!("{} (not created yet)", path.display()),
+    }
+}
+
+/// Whether `command` is an existing file, or a file in one of the `PATH` dirs
+fn find_command(command: &str, path_var: &str) -> bool {
+    let path = Path::new(command);
+    if path.is_absolute() {
+        return path.is_file();
+    }
+    std::env::split_paths(path_var).any(|dir| dir.join(command).is_file())
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_lists_key_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let history_path = dir.path().join("csh_history");
+        fs::write(&history_path, "ls\npwd\n").unwrap();
+        let history = History::with_file(100, history_path.clone());
+        let mut env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        env.set("TERM", "xterm-256color");
+        env.set("COLORTERM", "");
+
+        let text = report(&env, &history, &Err("no tty".to_string()));
+        assert!(text.starts_with("CSH diagnostics\n"));
+        assert!(text.contains(&format!("  version       CSH {}\n", VERSION)));
+        assert!(text.contains(&format!("  cwd           {}\n", dir.path().display())));
+        assert!(text.contains("  TERM          xterm-256color\n"));
+        assert!(text.contains("  COLORTERM     (unset)\n"));
+        assert!(text.contains(&format!("  history file  {} (2 entries, 7 bytes)\n", history_path.display())));
+        assert!(text.contains("  stdin tty     "));
+        assert!(text.contains("  raw mode      unavailable (no tty)\n"));
+        assert!(text.contains("\nShells\n  PowerShell "));
+
+        let text = report(&env, &History::new(10), &Ok(()));
+        assert!(text.contains("  history file  (none, history is not saved)\n"));
+        assert!(text.contains("  raw mode      available\n"));
+    }
+}
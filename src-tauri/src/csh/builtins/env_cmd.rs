@@ -3,15 +3,88 @@
 use crate::csh::builtins::BuiltinResult;
 use crate::csh::environment::Environment;
 
-pub fn execute(env: &Environment) -> BuiltinResult {
+pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
+    let mut null_separated = false;
+    let mut local_only = false;
+    let mut prefix: Option<&str> = None;
+
+    // Parse arguments
+    for arg in args {
+        match arg.as_str() {
+            "-0" | "--null" => null_separated = true,
+            "-l" | "--local" => local_only = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return BuiltinResult::failure(1, format!("env: invalid option '{}'\n", arg));
+            }
+            _ => prefix = Some(arg),
+        }
+    }
+
+    let separator = if null_separated { '\0' } else { '\n' };
     let mut output = String::new();
 
-    let mut vars: Vec<_> = env.get_all().into_iter().collect();
+    let vars = if local_only { env.get_local() } else { env.get_all() };
+    let mut vars: Vec<_> = vars
+        .into_iter()
+        .filter(|(key, _)| prefix.is_none_or(|p| key.starts_with(p)))
+        .collect();
     vars.sort_by(|a, b| a.0.cmp(&b.0));
 
     for (key, value) in vars {
-        output.push_str(&format!("{}={}\n", key, value));
+        output.push_str(&format!("{}={}{}", key, value, separator));
     }
 
     BuiltinResult::success_with_output(output)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_env() -> Environment {
+        let mut env = Environment::new();
+        env.set("CSH_TEST_B", "2");
+        env.set("CSH_TEST_A", "1");
+        env
+    }
+
+    #[test]
+    fn test_prefix_filter() {
+        let result = execute(&["CSH_TEST_".to_string()], &test_env());
+        assert_eq!(result.output.unwrap(), "CSH_TEST_A=1\nCSH_TEST_B=2\n");
+    }
+
+    #[test]
+    fn test_local_leaves_out_inherited_variables() {
+        let mut env = Environment::with_vars(std::env::temp_dir(), std::env::vars());
+        env.set("CSH_TEST_LOCAL", "1");
+        env.export("CSH_TEST_EXPORTED", Some("2"));
+        let inherited = std::env::vars().map(|(k, _)| k).find(|k| !env.get_local().contains_key(k));
+
+        let result = execute(&["--local".to_string()], &env);
+        let output = result.output.unwrap();
+        let mut names: Vec<&str> = output.lines().filter_map(|l| l.split_once('=')).map(|(k, _)| k).collect();
+        // Not every machine has a hostname to report
+        names.retain(|name| *name != "HOSTNAME");
+        assert_eq!(
+            names,
+            vec!["CSH_TEST_EXPORTED", "CSH_TEST_LOCAL", "HOME", "PWD", "SHELL_NAME", "SHELL_VERSION", "USER"]
+        );
+
+        // The default still lists everything
+        let all = execute(&[], &env).output.unwrap();
+        if let Some(name) = inherited {
+            assert!(all.contains(&format!("{}=", name)));
+        }
+    }
+
+    #[test]
+    fn test_null_separator() {
+        let args = ["--null".to_string(), "CSH_TEST_".to_string()];
+        let result = execute(&args, &test_env());
+        assert_eq!(result.output.unwrap(), "CSH_TEST_A=1\0CSH_TEST_B=2\0");
+
+        let result = execute(&["-0".to_string(), "CSH_TEST_A".to_string()], &test_env());
+        assert_eq!(result.output.unwrap(), "CSH_TEST_A=1\0");
+    }
+}
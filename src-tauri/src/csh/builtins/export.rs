@@ -7,16 +7,33 @@ pub fn execute(args: &[String], env: &mut Environment) -> BuiltinResult {
     if args.is_empty() {
         // Show all exported variables
         let mut output = String::new();
-        let mut vars: Vec<_> = env.get_exports().into_iter().collect();
+        let mut vars: Vec<(String, Option<String>)> = env
+            .get_exports()
+            .into_iter()
+            .map(|(key, value)| (key, Some(value)))
+            .chain(env.get_exported_unset().into_iter().map(|key| (key, None)))
+            .collect();
         vars.sort_by(|a, b| a.0.cmp(&b.0));
 
         for (key, value) in vars {
-            output.push_str(&format!("export {}=\"{}\"\n", key, value));
+            match value {
+                Some(value) => output.push_str(&format!("export {}=\"{}\"\n", key, value)),
+                // Exported but never assigned
+                None => output.push_str(&format!("export {}\n", key)),
+            }
         }
 
         return BuiltinResult::success_with_output(output);
     }
 
+    // export -n NAME... removes the export property
+    if args[0] == "-n" {
+        for name in &args[1..] {
+            env.unexport(name);
+        }
+        return BuiltinResult::success();
+    }
+
     for arg in args {
         if let Some((name, value)) = arg.split_once('=') {
             env.export(name, Some(value));
@@ -28,3 +45,46 @@ pub fn execute(args: &[String], env: &mut Environment) -> BuiltinResult {
 
     BuiltinResult::success()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(env: &mut Environment) -> String {
+        execute(&[], env).output.unwrap()
+    }
+
+    #[test]
+    fn test_export_without_value_until_assigned() {
+        let mut env = Environment::new();
+        let name = "CSH_EXPORT_TEST_PENDING";
+
+        execute(&[name.to_string()], &mut env);
+        assert!(listing(&mut env).contains(&format!("export {}\n", name)));
+        assert_eq!(env.get_value(name), None);
+
+        env.set(name, "");
+        let output = listing(&mut env);
+        assert!(output.contains(&format!("export {}=\"\"\n", name)));
+        assert!(!output.contains(&format!("export {}\n", name)));
+    }
+
+    #[test]
+    fn test_export_n_keeps_value_locally() {
+        let mut env = Environment::new();
+        execute(&["CSH_EXPORT_TEST_N=value".to_string()], &mut env);
+
+        execute(&["-n".to_string(), "CSH_EXPORT_TEST_N".to_string()], &mut env);
+        assert!(!env.get_exports().contains_key("CSH_EXPORT_TEST_N"));
+        assert_eq!(env.get_value("CSH_EXPORT_TEST_N").as_deref(), Some("value"));
+    }
+
+    #[test]
+    fn test_export_existing_keeps_value() {
+        let mut env = Environment::new();
+        env.set("CSH_EXPORT_TEST_LOCAL", "kept");
+
+        execute(&["CSH_EXPORT_TEST_LOCAL".to_string()], &mut env);
+        assert!(listing(&mut env).contains("export CSH_EXPORT_TEST_LOCAL=\"kept\"\n"));
+    }
+}
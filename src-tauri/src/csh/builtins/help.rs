@@ -1,46 +1,451 @@
 //! help - Display help information
+//!
+//! The builtin list in `help` and the per-command pages in `help <cmd>`
+//! are both rendered from `GROUPS`. On a terminal, pages get bold section
+//! headers and colored options, and long output goes through `$PAGER`.
 
-use crate::csh::builtins::BuiltinResult;
+use std::io::Write;
+use std::process::{Command, Stdio};
 
-pub fn execute(args: &[String]) -> BuiltinResult {
-    if args.is_empty() {
-        let help = r#"
-CSH - Connexio Shell v0.1.0
+use terminal_size::{terminal_size, Height};
 
-A modern shell for the Connexio terminal.
+use crate::csh::builtins::{BuiltinResult, OutputContext};
+use crate::csh::environment::Environment;
+use crate::csh::shell::VERSION;
 
-BUILT-IN COMMANDS:
-  cd [dir]          Change directory (~ for home, - for previous)
-  pwd               Print working directory
-  ls [-la]          List directory contents
-  cat <file>        Display file contents
-  echo [text]       Print text to output
-  clear / cls       Clear the screen
+const BOLD: &str = "\x1b[1m";
+const OPTION_COLOR: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
 
-  env               Display all environment variables
-  set VAR=value     Set a shell variable
-  export VAR=value  Export variable to environment
-  unset VAR         Remove a variable
-
-  alias name=cmd    Create an alias
-  unalias name      Remove an alias
-  history [n]       Show command history
-
-  which cmd         Locate a command
-  help [cmd]        Show this help
+/// Documentation for one builtin
+struct BuiltinDoc {
+    /// Names the builtin answers to (first is the primary name)
+    names: &'static [&'static str],
+    /// Short usage shown in the builtin list
+    usage: &'static str,
+    /// One-line description
+    summary: &'static str,
+    /// Full usage lines
+    synopsis: &'static [&'static str],
+    /// Flags and what they do
+    options: &'static [(&'static str, &'static str)],
+    /// Example invocations and what they do
+    examples: &'static [(&'static str, &'static str)],
+    /// Anything else worth knowing
+    notes: &'static str,
+}
 
-  exit [code]       Exit the shell
+/// Builtins, grouped as they appear in the builtin list
+const GROUPS: &[&[BuiltinDoc]] = &[
+    &[
+        BuiltinDoc {
+            names: &["cd"],
+            usage: "cd [dir]",
+            summary: "Change directory (~ for home, - for previous)",
+            synopsis: &["cd [directory]"],
+            options: &[],
+            examples: &[
+                ("cd", "Go to home directory"),
+                ("cd -", "Go to previous directory"),
+                ("cd ~/path", "Go to path relative to home"),
+            ],
+            notes: "",
+        },
+        BuiltinDoc {
+            names: &["pwd"],
+            usage: "pwd",
+            summary: "Print working directory",
+            synopsis: &["pwd"],
+            options: &[],
+            examples: &[],
+            notes: "",
+        },
+        BuiltinDoc {
+            names: &["realpath"],
+            usage: "realpath <path>",
+            summary: "Print canonical absolute paths",
+            synopsis: &["realpath [-e|-m] <path...>"],
+            options: &[
+                ("-e", "Every component must exist"),
+                ("-m", "No component needs to exist"),
+            ],
+            examples: &[("realpath ../src", "Absolute path of ../src with symlinks resolved")],
+            notes: "By default every component except the last must exist.",
+        },
+        BuiltinDoc {
+            names: &["open", "start"],
+            usage: "open <file|dir|url>",
+            summary: "Open with the default application",
+            synopsis: &["open <file|dir|url...>"],
+            options: &[],
+            examples: &[
+                ("open report.pdf", "Open a document in its default viewer"),
+                ("open .", "Show the current directory in the file manager"),
+                ("open https://example.com", "Open a URL in the browser"),
+            ],
+            notes: "Uses start on Windows, open on macOS and xdg-open elsewhere.",
+        },
+        BuiltinDoc {
+            names: &["ls", "dir"],
+            usage: "ls [-la]",
+            summary: "List directory contents",
+            synopsis: &["ls [options] [path...]"],
+            options: &[
+                ("-a", "Show hidden files"),
+                ("-l", "Long format with details"),
+                ("-s", "Show file sizes"),
+                ("-i, --icons", "Show file type icons"),
+                ("-I, --no-icons", "Hide file type icons"),
+                ("--icons=THEME", "Icon set: emoji, nerd (Nerd Font) or none"),
+                ("--tree", "Show directories as a tree"),
+                ("-L, --follow-symlinks", "Descend into symlinked directories in --tree"),
+                ("--max-depth N", "Show at most N levels in --tree"),
+                ("--max-entries N", "Stop --tree after N entries"),
+                ("--ignore PATTERNS", "Leave out names matching comma-separated globs"),
+                ("--no-color", "Don't color entries"),
+            ],
+            examples: &[("ls --tree --ignore=.git,node_modules", "Tree without the usual bulk")],
+            notes: "Set CSH_LS_ICONS to emoji, nerd or none to change the default icons. \
+                    Emoji width is measured at startup; where the terminal can't \
+                    report it, set CSH_EMOJI_WIDTH to 1 or 2.",
+        },
+        BuiltinDoc {
+            names: &["cat", "type"],
+            usage: "cat <file>",
+            summary: "Display file contents",
+            synopsis: &["cat [options] <file...>"],
+            options: &[
+                ("-n", "Show line numbers"),
+                ("-E", "Show $ at end of lines"),
+                ("-T", "Show tabs as ^I"),
+                ("-v", "Show control characters as ^X and M-"),
+                ("-A", "Same as -vET"),
+                ("-c, --color", "Syntax highlight by file extension"),
+                ("--keep-bom", "Don't strip a UTF-8 byte order mark"),
+            ],
+            examples: &[],
+            notes: "",
+        },
+        BuiltinDoc {
+            names: &["ln"],
+            usage: "ln [-sf] src dst",
+            summary: "Create a hard or symbolic link",
+            synopsis: &["ln [options] <target> [link_name]"],
+            options: &[
+                ("-s", "Create a symbolic link"),
+                ("-f", "Replace an existing link_name"),
+            ],
+            examples: &[],
+            notes: "If link_name is a directory, the link is created inside it.",
+        },
+        BuiltinDoc {
+            names: &["echo"],
+            usage: "echo [text]",
+            summary: "Print text to output",
+            synopsis: &["echo [options] [text...]"],
+            options: &[
+                ("-n", "Don't add newline at end"),
+                ("-e", "Enable escape sequences (\\n, \\t, etc.)"),
+                ("-E", "Disable escape sequences (default)"),
+            ],
+            examples: &[],
+            notes: "",
+        },
+        BuiltinDoc {
+            names: &["clear", "cls"],
+            usage: "clear / cls",
+            summary: "Clear the screen",
+            synopsis: &["clear"],
+            options: &[],
+            examples: &[],
+            notes: "Does nothing when output is redirected or captured.",
+        },
+    ],
+    &[
+        BuiltinDoc {
+            names: &["env"],
+            usage: "env [-0l] [prefix]",
+            summary: "Display environment variables",
+            synopsis: &["env [options] [prefix]"],
+            options: &[
+                ("-0, --null", "End each entry with NUL instead of newline"),
+                ("-l, --local", "Leave out variables inherited unchanged from the parent process"),
+            ],
+            examples: &[],
+            notes: "With a prefix, only variables whose names start with it are shown.",
+        },
+        BuiltinDoc {
+            names: &["set"],
+            usage: "set VAR=value",
+            summary: "Set a shell variable or option",
+            synopsis: &["set [VAR=value]", "set --local", "set -o|+o [option]"],
+            options: &[
+                ("-l, --local", "List only variables the shell set, not inherited ones"),
+                ("-o option", "Enable a shell option"),
+                ("+o option", "Disable a shell option"),
+            ],
+            examples: &[
+                ("set -o", "List shell options"),
+                ("set +o", "Print commands that restore the current options"),
+            ],
+            notes: "",
+        },
+        BuiltinDoc {
+            names: &["export"],
+            usage: "export VAR=value",
+            summary: "Export variable to environment",
+            synopsis: &["export [VAR[=value]...]", "export -n VAR..."],
+            options: &[("-n", "Stop exporting variable")],
+            examples: &[
+                ("export", "Show exported variables"),
+                ("export VAR=val", "Set and export variable"),
+            ],
+            notes: "",
+        },
+        BuiltinDoc {
+            names: &["unset"],
+            usage: "unset VAR",
+            summary: "Remove a variable",
+            synopsis: &["unset VAR..."],
+            options: &[],
+            examples: &[],
+            notes: "",
+        },
+        BuiltinDoc {
+            names: &["shift"],
+            usage: "shift [n]",
+            summary: "Drop positional parameters",
+            synopsis: &["shift [N]"],
+            options: &[],
+            examples: &[("shift 2", "Drop $1 and $2; $3 becomes $1")],
+            notes: "N defaults to 1. Fails without changing anything if there are fewer than N.",
+        },
+        BuiltinDoc {
+            names: &["path-add"],
+            usage: "path-add [-a] dir",
+            summary: "Add directories to PATH",
+            synopsis: &["path-add [-a|-p] <dir...>"],
+            options: &[
+                ("-p, --prepend", "Put directories first (default)"),
+                ("-a, --append", "Put directories last"),
+            ],
+            examples: &[("path-add ~/bin", "Search ~/bin before anything else")],
+            notes: "Directories must exist. One already in PATH is moved instead of added twice.",
+        },
+        BuiltinDoc {
+            names: &["path-remove"],
+            usage: "path-remove dir",
+            summary: "Remove directories from PATH",
+            synopsis: &["path-remove <dir...>"],
+            options: &[],
+            examples: &[],
+            notes: "",
+        },
+    ],
+    &[
+        BuiltinDoc {
+            names: &["alias"],
+            usage: "alias name=cmd",
+            summary: "Create an alias",
+            synopsis: &["alias [name=value]", "alias --resolve <name...>"],
+            options: &[("-r, --resolve", "Print the command an alias finally runs")],
+            examples: &[
+                ("alias", "Show all aliases"),
+                ("alias ll='ls -l'", "Create alias"),
+                ("alias --resolve ll", "Show ll with any nested aliases expanded"),
+            ],
+            notes: "An alias whose first word is another alias expands that one too.",
+        },
+        BuiltinDoc {
+            names: &["unalias"],
+            usage: "unalias name",
+            summary: "Remove an alias",
+            synopsis: &["unalias name"],
+            options: &[],
+            examples: &[],
+            notes: "",
+        },
+        BuiltinDoc {
+            names: &["history"],
+            usage: "history [n]",
+            summary: "Show command history",
+            synopsis: &["history [-v] [n]", "history -c"],
+            options: &[
+                ("-c", "Clear history"),
+                ("-v", "Also show exit status and directory"),
+            ],
+            examples: &[
+                ("history", "Show all history"),
+                ("history 10", "Show last 10 commands"),
+                ("set -o extendedhistory", "Record status and directory for history -v"),
+            ],
+            notes: "Re-run entries with !n (event number), !-n (n commands back) or !! (last command). \
+                    Commands starting with a space, matching a HISTIGNORE pattern (globs \
+                    separated by :) or looking like they hold a password or token are \
+                    not recorded; set CSH_HISTIGNORE_SECRETS=0 to record the latter.",
+        },
+    ],
+    &[
+        BuiltinDoc {
+            names: &["which", "where"],
+            usage: "which cmd",
+            summary: "Locate a command",
+            synopsis: &["which cmd..."],
+            options: &[],
+            examples: &[],
+            notes: "",
+        },
+        BuiltinDoc {
+            names: &["help"],
+            usage: "help [cmd]",
+            summary: "Show this help",
+            synopsis: &["help [cmd]"],
+            options: &[],
+            examples: &[],
+            notes: "Long output is shown through $PAGER when it is set.",
+        },
+        BuiltinDoc {
+            names: &["diag"],
+            usage: "diag",
+            summary: "Print diagnostics for bug reports",
+            synopsis: &["diag"],
+            options: &[],
+            examples: &[],
+            notes: "Shows the version, terminal capabilities, history file and installed shells. \
+                    Also available as csh --diagnose.",
+        },
+        BuiltinDoc {
+            names: &["true"],
+            usage: "true",
+            summary: "Do nothing, successfully",
+            synopsis: &["true"],
+            options: &[],
+            examples: &[],
+            notes: "",
+        },
+        BuiltinDoc {
+            names: &["false"],
+            usage: "false",
+            summary: "Do nothing, unsuccessfully",
+            synopsis: &["false"],
+            options: &[],
+            examples: &[],
+            notes: "",
+        },
+        BuiltinDoc {
+            names: &["yes"],
+            usage: "yes [text]",
+            summary: "Print a line until stopped",
+            synopsis: &["yes [STRING...]"],
+            options: &[],
+            examples: &[
+                ("yes", "Print 'y' forever (Ctrl+C to stop)"),
+                ("yes | installer", "Answer y to every question installer asks"),
+            ],
+            notes: "Stops on Ctrl+C or when the command reading from it exits.",
+        },
+        BuiltinDoc {
+            names: &["timeout"],
+            usage: "timeout <duration> <cmd>",
+            summary: "Run a command with a time limit",
+            synopsis: &["timeout [-s SIGNAL] [-k DURATION] <duration> <command> [args...]"],
+            options: &[
+                ("-s SIGNAL", "Signal to send when time is up (default TERM)"),
+                ("-k DURATION", "Also send KILL if still running this long after"),
+            ],
+            examples: &[
+                ("timeout 10 make test", "Stop the tests after 10 seconds"),
+                ("timeout -k 5 1m ./server", "TERM after a minute, KILL 5 seconds later"),
+            ],
+            notes: "Durations take s, m, h or d (seconds by default). Exits with 124 on timeout, \
+                    137 if the command had to be killed. Builtins are not timed.",
+        },
+        BuiltinDoc {
+            names: &["wait"],
+            usage: "wait [%n|pid]",
+            summary: "Wait for background jobs to finish",
+            synopsis: &["wait [%JOB|PID...]"],
+            options: &[],
+            examples: &[
+                ("wait", "Wait for every background job"),
+                ("wait %1", "Wait for job 1 and return its exit status"),
+            ],
+            notes: "Returns the last job's exit status. Ctrl+C stops waiting; the jobs keep running.",
+        },
+        BuiltinDoc {
+            names: &["kill"],
+            usage: "kill [-sig] %n|pid",
+            summary: "Send a signal to processes or jobs",
+            synopsis: &["kill [-SIGNAL | -s SIGNAL] %JOB|PID..."],
+            options: &[("-s SIGNAL", "Signal to send, by name or number (default TERM)")],
+            examples: &[
+                ("kill %1", "Stop background job 1"),
+                ("kill -9 1234", "Force process 1234 to exit"),
+            ],
+            notes: "On Windows every target is ended, whatever the signal.",
+        },
+    ],
+    &[
+        BuiltinDoc {
+            names: &["exit"],
+            usage: "exit [code]",
+            summary: "Exit the shell",
+            synopsis: &["exit [code]"],
+            options: &[],
+            examples: &[],
+            notes: "",
+        },
+        BuiltinDoc {
+            names: &["break"],
+            usage: "break",
+            summary: "Leave the innermost loop",
+            synopsis: &["break"],
+            options: &[],
+            examples: &[("for f in *.log; do cat $f; break; done", "Show only the first log")],
+            notes: "Outside a loop this is an error.",
+        },
+        BuiltinDoc {
+            names: &["continue"],
+            usage: "continue",
+            summary: "Skip to the next loop iteration",
+            synopsis: &["continue"],
+            options: &[],
+            examples: &[],
+            notes: "Outside a loop this is an error.",
+        },
+        BuiltinDoc {
+            names: &["return"],
+            usage: "return [code]",
+            summary: "Leave the running function",
+            synopsis: &["return [code]"],
+            options: &[],
+            examples: &[("first() { echo $1; return 3; }", "Print $1, then fail with 3")],
+            notes: "Without a code the function returns the status of the last command. \
+                    Outside a function this is an error.",
+        },
+    ],
+];
 
-OPERATORS:
+const OVERVIEW_REST: &str = r#"OPERATORS:
   cmd1 | cmd2       Pipe output of cmd1 to cmd2
   cmd1 && cmd2      Run cmd2 only if cmd1 succeeds
   cmd1 || cmd2      Run cmd2 only if cmd1 fails
   cmd1 ; cmd2       Run both commands sequentially
   cmd &             Run command in background
 
+CONTROL FLOW:
+  if cmd; then ...; elif cmd; then ...; else ...; fi
+                    Run the branch of the first command that succeeds
+  for v in a b; do ...; done
+                    Run the body with $v set to each word in turn
+  while cmd; do ...; done
+                    Run the body for as long as cmd succeeds
+  name() { ...; }   Define a function; its arguments are $1, $2...
+
 REDIRECTIONS:
   cmd > file        Redirect stdout to file (overwrite)
   cmd >> file       Redirect stdout to file (append)
+  cmd >| file       Overwrite file even with noclobber set
   cmd < file        Read stdin from file
   cmd 2> file       Redirect stderr to file
   cmd &> file       Redirect stdout and stderr to file
@@ -50,6 +455,7 @@ VARIABLES:
   ${VAR}            Expand variable (explicit form)
   $?                Last command's exit code
   $$                Shell's process ID
+  $((expr))         Integer arithmetic, e.g. $((x * 2 + 1))
 
 SPECIAL KEYS:
   Up/Down           Navigate command history
@@ -59,21 +465,193 @@ SPECIAL KEYS:
 
 For more info: https://github.com/yandanp/connexio
 "#;
-        return BuiltinResult::success_with_output(help.to_string());
+
+pub fn execute(args: &[String], env: &Environment, ctx: OutputContext) -> BuiltinResult {
+    let styled = ctx.is_terminal;
+
+    let text = match args.first() {
+        None => overview(styled),
+        Some(cmd) => match find(cmd) {
+            Some(doc) => render(doc, styled),
+            None => return BuiltinResult::failure(1, format!("help: no help for '{}'\n", cmd)),
+        },
+    };
+
+    if styled && page(&text, env) {
+        return BuiltinResult::success();
     }
 
-    // Help for specific command
-    let cmd = &args[0];
-    let help = match cmd.as_str() {
-        "cd" => "cd [directory]\n  Change the current directory.\n  cd        - Go to home directory\n  cd -      - Go to previous directory\n  cd ~/path - Go to path relative to home\n",
-        "ls" => "ls [options] [path...]\n  List directory contents.\n  -a  Show hidden files\n  -l  Long format with details\n  -s  Show file sizes\n",
-        "cat" => "cat [options] <file...>\n  Display file contents.\n  -n  Show line numbers\n  -E  Show $ at end of lines\n",
-        "echo" => "echo [options] [text...]\n  Print text to output.\n  -n  Don't add newline at end\n  -e  Enable escape sequences (\\n, \\t, etc.)\n",
-        "alias" => "alias [name=value]\n  Create or display aliases.\n  alias           - Show all aliases\n  alias ll='ls -l' - Create alias\n",
-        "export" => "export [VAR=value]\n  Export variables to environment.\n  export          - Show exported variables\n  export VAR=val  - Set and export variable\n",
-        "history" => "history [n]\n  Display command history.\n  history     - Show all history\n  history 10  - Show last 10 commands\n  history -c  - Clear history\n",
-        _ => return BuiltinResult::failure(1, format!("help: no help for '{}'\n", cmd)),
+    BuiltinResult::success_with_output(text)
+}
+
+fn find(name: &str) -> Option<&'static BuiltinDoc> {
+    GROUPS
+        .iter()
+        .flat_map(|group| group.iter())
+        .find(|doc| doc.names.contains(&name))
+}
+
+/// The flags documented for builtin `name`, each with its description
+///
+/// Every spelling of an entry is listed (`-i, --icons` gives both), and
+/// argument placeholders are dropped: `--max-depth N` gives `--max-depth`
+/// and `--icons=THEME` gives `--icons=`.
+pub fn option_flags(name: &str) -> Vec<(&'static str, &'static str)> {
+    let Some(doc) = find(name) else {
+        return Vec::new();
     };
+    let mut flags: Vec<(&'static str, &'static str)> = Vec::new();
+    for (term, description) in doc.options {
+        for spelling in term.split(", ") {
+            let flag = spelling.split(' ').next().unwrap_or(spelling);
+            let flag = match flag.find('=') {
+                Some(i) => &flag[..=i],
+                None => flag,
+            };
+            if flag.starts_with('-') && !flags.iter().any(|(f, _)| *f == flag) {
+                flags.push((flag, description));
+            }
+        }
+    }
+    flags
+}
+
+fn heading(title: &str, styled: bool) -> String {
+    if styled {
+        format!("{}{}{}\n", BOLD, title, RESET)
+    } else {
+        format!("{}\n", title)
+    }
+}
+
+/// The general help page, with the builtin list generated from `GROUPS`
+fn overview(styled: bool) -> String {
+    let mut text = format!(
+        "\nCSH - Connexio Shell v{}\n\nA modern shell for the Connexio terminal.\n\n",
+        VERSION
+    );
+
+    text.push_str(&heading("BUILT-IN COMMANDS:", styled));
+    for (i, group) in GROUPS.iter().enumerate() {
+        if i > 0 {
+            text.push('\n');
+        }
+        for doc in group.iter() {
+            text.push_str(&format!("  {:<17} {}\n", doc.usage, doc.summary));
+        }
+    }
+    text.push('\n');
 
-    BuiltinResult::success_with_output(help.to_string())
+    for line in OVERVIEW_REST.lines() {
+        if line.ends_with(':') {
+            text.push_str(&heading(line, styled));
+        } else {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+
+    text
+}
+
+/// A man-style page for one builtin
+fn render(doc: &BuiltinDoc, styled: bool) -> String {
+    let mut text = heading("NAME", styled);
+    text.push_str(&format!("    {} - {}\n", doc.names.join(", "), doc.summary));
+
+    text.push('\n');
+    text.push_str(&heading("SYNOPSIS", styled));
+    for line in doc.synopsis {
+        text.push_str(&format!("    {}\n", line));
+    }
+
+    for (title, entries) in [("OPTIONS", doc.options), ("EXAMPLES", doc.examples)] {
+        if entries.is_empty() {
+            continue;
+        }
+
+        text.push('\n');
+        text.push_str(&heading(title, styled));
+        let width = entries.iter().map(|(term, _)| term.len()).max().unwrap_or(0) + 2;
+        for (term, description) in entries {
+            // Pad outside the color codes so columns still line up
+            let padding = " ".repeat(width - term.len());
+            if styled {
+                text.push_str(&format!("    {}{}{}{}{}\n", OPTION_COLOR, term, RESET, padding, description));
+            } else {
+                text.push_str(&format!("    {}{}{}\n", term, padding, description));
+            }
+        }
+    }
+
+    if !doc.notes.is_empty() {
+        text.push('\n');
+        text.push_str(&heading("NOTES", styled));
+        text.push_str(&format!("    {}\n", doc.notes));
+    }
+
+    text
+}
+
+/// Show `text` through `$PAGER` if it doesn't fit on screen
+///
+/// Returns false when the text should be printed directly instead.
+fn page(text: &str, env: &Environment) -> bool {
+    let Some(pager) = env.get_value("PAGER").filter(|p| !p.trim().is_empty()) else {
+        return false;
+    };
+    let Some((_, Height(rows))) = terminal_size() else {
+        return false;
+    };
+    if text.lines().count() < rows as usize {
+        return false;
+    }
+
+    let mut parts = pager.split_whitespace();
+    let Some(program) = parts.next() else {
+        return false;
+    };
+    let mut child = match Command::new(program).args(parts).stdin(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user quitting the pager early closes the pipe; that's fine
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    let _ = child.wait();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csh::builtins::Builtins;
+
+    #[test]
+    fn test_every_builtin_has_a_page() {
+        for name in Builtins::new().list() {
+            assert!(find(name).is_some(), "no help for {}", name);
+        }
+    }
+
+    #[test]
+    fn test_page_formatting_follows_output() {
+        let env = Environment::new();
+        let args = ["ls".to_string()];
+
+        let styled = render(find("ls").unwrap(), true);
+        assert!(styled.contains("\x1b[1mSYNOPSIS\x1b[0m\n"));
+        assert!(styled.contains("\x1b[36m-a\x1b[0m"));
+
+        let plain = execute(&args, &env, OutputContext::redirected()).output.unwrap();
+        assert!(plain.starts_with("NAME\n    ls, dir - List directory contents\n"));
+        assert!(plain.contains("\nSYNOPSIS\n    ls [options] [path...]\n"));
+        assert!(plain.contains("\nOPTIONS\n    -a"));
+        assert!(!plain.contains('\x1b'));
+
+        let overview = execute(&[], &env, OutputContext::redirected()).output.unwrap();
+        assert!(overview.starts_with(&format!("\nCSH - Connexio Shell v{}\n", VERSION)));
+    }
 }
@@ -3,25 +3,73 @@
 use crate::csh::builtins::BuiltinResult;
 use crate::csh::history::History;
 
+/// history [-c] [-v] [N] - list the last N commands (`-v` adds status and directory)
 pub fn execute(args: &[String], history: &mut History) -> BuiltinResult {
-    // Parse count argument
-    let count = if args.is_empty() {
-        history.len()
-    } else if args[0] == "-c" {
-        // Clear history
-        history.clear();
-        return BuiltinResult::success();
-    } else {
-        args[0].parse::<usize>().unwrap_or(history.len())
-    };
+    let mut verbose = false;
+    let mut count = history.len();
+    for arg in args {
+        match arg.as_str() {
+            "-c" => {
+                // Clear history
+                history.clear();
+                return BuiltinResult::success();
+            }
+            "-v" => verbose = true,
+            _ => count = arg.parse::<usize>().unwrap_or(history.len()),
+        }
+    }
 
     let mut output = String::new();
     let entries = history.last_n(count);
-    let start_idx = history.len().saturating_sub(count);
+    let start_idx = history.len() - entries.len();
 
     for (i, entry) in entries.iter().enumerate() {
-        output.push_str(&format!("{:5}  {}\n", start_idx + i + 1, entry));
+        let number = history.number_at(start_idx + i);
+        if verbose {
+            // Entries run without `extendedhistory` have no details
+            let (status, cwd) = match history.details_by_number(number) {
+                Some(details) => (details.status.to_string(), details.cwd.display().to_string()),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            output.push_str(&format!("{:5}  {:>3}  {}  {}\n", number, status, cwd, entry));
+        } else {
+            output.push_str(&format!("{:5}  {}\n", number, entry));
+        }
     }
 
     BuiltinResult::success_with_output(output)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbers_match_bang_expansion() {
+        let mut history = History::new(3);
+        for cmd in ["one", "two", "three", "four", "five"] {
+            history.add(cmd.to_string());
+        }
+
+        let output = execute(&["2".to_string()], &mut history).output.unwrap();
+        for line in output.lines() {
+            let (number, command) = line.trim_start().split_once("  ").unwrap();
+            let expanded = history.expand(&format!("!{}", number)).unwrap();
+            assert_eq!(expanded.as_deref(), Some(command));
+        }
+        assert_eq!(output, "    4  four\n    5  five\n");
+    }
+
+    #[test]
+    fn test_verbose_shows_status_and_directory() {
+        let mut history = History::new(10);
+        history.add("ls".to_string());
+        history.add("make".to_string());
+        history.annotate_last(2, std::path::PathBuf::from("/src"));
+
+        let output = execute(&["-v".to_string()], &mut history).output.unwrap();
+        assert_eq!(output, "    1    -  -  ls\n    2    2  /src  make\n");
+        let output = execute(&["-v".to_string(), "1".to_string()], &mut history).output.unwrap();
+        assert_eq!(output, "    2    2  /src  make\n");
+    }
+}
@@ -0,0 +1,98 @@
+//! kill - Send a signal to processes or background jobs
+
+use crate::csh::ast::ExitStatus;
+use crate::csh::builtins::timeout::parse_signal;
+use crate::csh::builtins::BuiltinResult;
+use crate::csh::jobs::JobTable;
+
+const SIGTERM: i32 = 15;
+
+/// kill [-SIGNAL | -s SIGNAL] %N|PID... - signal each target (TERM by default)
+///
+/// `%N` names a background job of this shell. The status is 1 if any
+/// target could not be signalled.
+pub fn execute(args: &[String], jobs: &JobTable) -> BuiltinResult {
+    let (signal, targets) = match parse_options(args) {
+        Ok(parsed) => parsed,
+        Err(message) => return BuiltinResult::failure(1, message),
+    };
+    if targets.is_empty() {
+        let usage = "kill: usage: kill [-SIGNAL | -s SIGNAL] %N|PID...\n";
+        return BuiltinResult::failure(2, usage.to_string());
+    }
+
+    let mut errors = String::new();
+    for arg in targets {
+        let pid = if let Some(number) = arg.strip_prefix('%') {
+            let id = number.parse::<usize>().ok();
+            match jobs.list().iter().find(|job| Some(job.id) == id) {
+                Some(job) => job.pid,
+                None => {
+                    errors.push_str(&format!("kill: {}: no such job\n", arg));
+                    continue;
+                }
+            }
+        } else if let Ok(pid) = arg.parse() {
+            pid
+        } else {
+            errors.push_str(&format!("kill: {}: arguments must be process or job IDs\n", arg));
+            continue;
+        };
+        if let Err(e) = send(pid, signal) {
+            errors.push_str(&format!("kill: ({}) - {}\n", pid, e));
+        }
+    }
+
+    if errors.is_empty() {
+        BuiltinResult::success()
+    } else {
+        BuiltinResult {
+            status: ExitStatus::failure(1),
+            output: None,
+            error: Some(errors),
+            should_exit: false,
+            exit_code: None,
+        }
+    }
+}
+
+/// The signal to send and the targets after it
+fn parse_options(args: &[String]) -> Result<(i32, &[String]), String> {
+    let (name, targets) = match args.first().map(String::as_str) {
+        Some("-s") => match args.get(1) {
+            Some(name) => (name.as_str(), &args[2..]),
+            None => return Err("kill: -s: option requires an argument\n".to_string()),
+        },
+        Some("--") => return Ok((SIGTERM, &args[1..])),
+        Some(option) if option.len() > 1 && option.starts_with('-') => (&option[1..], &args[1..]),
+        _ => return Ok((SIGTERM, args)),
+    };
+    let signal = parse_signal(name).ok_or(format!("kill: {}: invalid signal\n", name))?;
+    Ok((signal, targets))
+}
+
+/// Send `signal` to process `pid`
+#[cfg(unix)]
+fn send(pid: u32, signal: i32) -> Result<(), String> {
+    use nix::sys::signal::{kill, Signal};
+    use nix::unistd::Pid;
+
+    let signal = Signal::try_from(signal).map_err(|_| format!("{}: invalid signal", signal))?;
+    kill(Pid::from_raw(pid as i32), signal).map_err(|e| e.desc().to_string())
+}
+
+/// End process `pid` (Windows has no signals to send)
+#[cfg(windows)]
+fn send(pid: u32, _signal: i32) -> Result<(), String> {
+    let status = std::process::Command::new("taskkill")
+        .args(["/F", "/PID", &pid.to_string()])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err("no such process".to_string())
+    }
+}
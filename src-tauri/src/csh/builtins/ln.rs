@@ -0,0 +1,179 @@
+//! ln - Create links between files
+
+use crate::csh::builtins::BuiltinResult;
+use crate::csh::environment::Environment;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
+    let mut symbolic = false;
+    let mut force = false;
+    let mut operands: Vec<&String> = Vec::new();
+
+    // Parse arguments
+    for arg in args {
+        if arg.starts_with('-') && arg.len() > 1 {
+            for c in arg[1..].chars() {
+                match c {
+                    's' => symbolic = true,
+                    'f' => force = true,
+                    _ => {
+                        return BuiltinResult::failure(1, format!("ln: invalid option -- '{}'\n", c));
+                    }
+                }
+            }
+        } else {
+            operands.push(arg);
+        }
+    }
+
+    let (target, link_arg) = match operands.as_slice() {
+        [target] => (*target, None),
+        [target, link] => (*target, Some(*link)),
+        [] => return BuiltinResult::failure(1, "ln: missing file operand\n".to_string()),
+        _ => return BuiltinResult::failure(1, "ln: too many arguments\n".to_string()),
+    };
+
+    let target_path = resolve(target, env);
+
+    // Like `ln`, a missing or directory link name means "same name, in there"
+    let mut link_path = match link_arg {
+        Some(link) => resolve(link, env),
+        None => env.cwd().clone(),
+    };
+    if link_path.is_dir() && !link_path.is_symlink() {
+        match Path::new(target).file_name() {
+            Some(name) => link_path = link_path.join(name),
+            None => {
+                return BuiltinResult::failure(1, format!("ln: {}: cannot derive link name\n", target));
+            }
+        }
+    }
+
+    if force && link_path.symlink_metadata().is_ok() {
+        if let Err(e) = fs::remove_file(&link_path) {
+            return BuiltinResult::failure(
+                1,
+                format!("ln: cannot remove '{}': {}\n", link_path.display(), e),
+            );
+        }
+    }
+
+    let result = if symbolic {
+        // Symlink targets are stored as given, relative to the link's directory
+        create_symlink(Path::new(target), &link_path)
+    } else {
+        fs::hard_link(&target_path, &link_path)
+    };
+
+    match result {
+        Ok(()) => BuiltinResult::success(),
+        Err(e) => BuiltinResult::failure(
+            1,
+            format!("ln: cannot create link '{}': {}\n", link_path.display(), describe(&e)),
+        ),
+    }
+}
+
+/// Resolve a path argument relative to the shell's cwd
+fn resolve(arg: &str, env: &Environment) -> PathBuf {
+    let path = if let Some(rest) = arg.strip_prefix("~/") {
+        match env.get_value("HOME") {
+            Some(home) => PathBuf::from(home).join(rest),
+            None => PathBuf::from(arg),
+        }
+    } else {
+        PathBuf::from(arg)
+    };
+
+    if path.is_absolute() {
+        path
+    } else {
+        env.cwd().join(path)
+    }
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    use std::os::windows::fs::{symlink_dir, symlink_file};
+
+    // Windows needs to know up front whether the target is a directory
+    let resolved = if target.is_absolute() {
+        target.to_path_buf()
+    } else {
+        link.parent().unwrap_or(Path::new("")).join(target)
+    };
+
+    if resolved.is_dir() {
+        symlink_dir(target, link)
+    } else {
+        symlink_file(target, link)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn create_symlink(_target: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "symbolic links are not supported on this platform",
+    ))
+}
+
+/// Turn OS errors into a message the user can act on
+fn describe(error: &io::Error) -> String {
+    // ERROR_PRIVILEGE_NOT_HELD
+    #[cfg(windows)]
+    if error.raw_os_error() == Some(1314) {
+        return "creating symbolic links requires Developer Mode or administrator privileges"
+            .to_string();
+    }
+
+    error.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env_in(dir: &Path) -> Environment {
+        Environment::with_vars(dir.to_path_buf(), std::env::vars())
+    }
+
+    #[test]
+    fn test_symlink_resolves_to_target() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("target.txt"), "hello").unwrap();
+        let env = env_in(dir.path());
+
+        let args = ["-s".to_string(), "target.txt".to_string(), "link.txt".to_string()];
+        let result = execute(&args, &env);
+        if cfg!(windows) && !result.status.is_success() {
+            // Symlinks need Developer Mode or elevation on Windows
+            return;
+        }
+        assert!(result.status.is_success());
+
+        let link = dir.path().join("link.txt");
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from("target.txt"));
+        assert_eq!(fs::read_to_string(&link).unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_hard_link_into_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("data.txt"), "shared").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let env = env_in(dir.path());
+
+        let result = execute(&["data.txt".to_string(), "sub".to_string()], &env);
+        assert!(result.status.is_success());
+        assert_eq!(fs::read_to_string(dir.path().join("sub/data.txt")).unwrap(), "shared");
+    }
+}
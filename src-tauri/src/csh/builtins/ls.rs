@@ -2,16 +2,18 @@
 //!
 //! Features:
 //! - Grid layout with auto-column calculation
-//! - File type icons (Unicode emoji)
+//! - File type icons (emoji or Nerd Font, see `icons`)
 //! - Color coding (directories, executables, etc.)
 //! - Long format with permissions and metadata
-//! - Tree view support
+//! - Tree view support, with depth / entry limits for huge trees
 
 use crate::csh::builtins::BuiltinResult;
 use crate::csh::environment::Environment;
+use crate::csh::icons::{self, IconTheme};
+use crate::csh::walk::{walk, WalkOptions};
 use chrono::{DateTime, Local};
 use std::fs::{self, Metadata};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use terminal_size::{terminal_size, Width};
 use unicode_width::UnicodeWidthStr;
 
@@ -48,105 +50,15 @@ impl FileEntry {
     }
 
     /// Get icon for this file type
-    fn icon(&self) -> &'static str {
-        if self.is_dir {
-            return "📁";
-        }
-        if self.is_symlink {
-            return "🔗";
-        }
-
-        // Match by extension
-        let ext = self.name.rsplit('.').next().unwrap_or("").to_lowercase();
-        match ext.as_str() {
-            // Programming languages
-            "rs" => "🦀",
-            "py" => "🐍",
-            "js" | "mjs" | "cjs" => "📜",
-            "ts" | "tsx" | "mts" => "📘",
-            "jsx" => "⚛️",
-            "go" => "🔷",
-            "rb" => "💎",
-            "php" => "🐘",
-            "java" | "class" | "jar" => "☕",
-            "c" | "h" => "🔧",
-            "cpp" | "cc" | "cxx" | "hpp" => "⚙️",
-            "cs" => "🎯",
-            "swift" => "🦅",
-            "kt" | "kts" => "🎨",
-            "lua" => "🌙",
-            "sh" | "bash" | "zsh" | "fish" => "🐚",
-            "ps1" | "psm1" => "💠",
-
-            // Config & Data
-            "json" => "📋",
-            "yaml" | "yml" => "⚙️",
-            "toml" => "📦",
-            "xml" => "📰",
-            "csv" => "📊",
-            "sql" => "🗃️",
-            "env" => "🔐",
-            "ini" | "cfg" | "conf" => "🔩",
-
-            // Documents
-            "md" | "markdown" => "📝",
-            "txt" => "📄",
-            "pdf" => "📕",
-            "doc" | "docx" => "📘",
-            "xls" | "xlsx" => "📗",
-            "ppt" | "pptx" => "📙",
-            "rtf" => "📃",
-
-            // Media
-            "png" | "jpg" | "jpeg" | "gif" | "bmp" | "ico" | "webp" => "🖼️",
-            "svg" => "🎨",
-            "mp3" | "wav" | "flac" | "ogg" | "m4a" => "🎵",
-            "mp4" | "mkv" | "avi" | "mov" | "webm" => "🎬",
-
-            // Archives
-            "zip" | "rar" | "7z" | "tar" | "gz" | "bz2" | "xz" => "📦",
-
-            // Executables
-            "exe" | "msi" => "⚡",
-            "bat" | "cmd" => "🖥️",
-            "app" | "dmg" => "📱",
-            "deb" | "rpm" => "📦",
-
-            // Web
-            "html" | "htm" => "🌐",
-            "css" | "scss" | "sass" | "less" => "🎨",
-            "vue" => "💚",
-            "svelte" => "🧡",
-
-            // Lock files
-            "lock" => "🔒",
-
-            // Git
-            "gitignore" | "gitattributes" | "gitmodules" => "🔀",
-
-            // Docker
-            "dockerfile" => "🐳",
-
-            // License
-            "license" | "licence" => "📜",
-
-            // Default
-            _ => {
-                if self.is_executable {
-                    "⚡"
-                } else {
-                    "📄"
-                }
-            }
-        }
+    fn icon(&self, theme: &IconTheme) -> &'static str {
+        theme.icon(&self.name, self.is_dir, self.is_symlink, self.is_executable)
     }
 
     /// Get colored name for display
-    fn colored_name(&self, with_icon: bool) -> String {
-        let icon = if with_icon {
-            format!("{} ", self.icon())
-        } else {
-            String::new()
+    fn colored_name(&self, icons: Option<&IconTheme>) -> String {
+        let icon = match icons {
+            Some(theme) => format!("{} ", self.icon(theme)),
+            None => String::new(),
         };
 
         let name_with_suffix = if self.is_dir {
@@ -172,8 +84,8 @@ impl FileEntry {
     }
 
     /// Get display width (for grid calculation)
-    fn display_width(&self, with_icon: bool) -> usize {
-        let icon_width = if with_icon { 3 } else { 0 }; // icon + space
+    fn display_width(&self, options: &LsOptions) -> usize {
+        let icon_width = options.icons.map_or(0, |_| options.icon_width + 1); // icon + space
         let suffix_width = if self.is_dir { 1 } else { 0 }; // trailing /
         icon_width + self.name.width() + suffix_width
     }
@@ -185,21 +97,47 @@ struct LsOptions {
     show_hidden: bool,
     long_format: bool,
     show_size: bool,
-    show_icons: bool,
+    /// Icon theme, or `None` to hide icons
+    icons: Option<&'static IconTheme>,
+    /// Columns the terminal gives each icon (see `IconTheme::columns`)
+    icon_width: usize,
     one_per_line: bool,
     tree: bool,
+    /// Descend into symlinked directories in `--tree`
+    follow_symlinks: bool,
     no_color: bool,
+    /// Deepest level shown by `--tree` (1 = the directory's own entries)
+    max_depth: Option<usize>,
+    /// Most entries `--tree` prints before stopping
+    max_entries: Option<usize>,
+    /// Names to leave out (`--ignore`)
+    ignore: Vec<glob::Pattern>,
+}
+
+impl LsOptions {
+    fn is_ignored(&self, name: &str) -> bool {
+        self.ignore.iter().any(|pattern| pattern.matches(name))
+    }
 }
 
 pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
+    // Icons on by default, in the theme named by CSH_LS_ICONS if set
+    let default_icons = match env.get_value("CSH_LS_ICONS") {
+        Some(name) => match parse_icons(&name) {
+            Ok(theme) => theme,
+            Err(e) => return BuiltinResult::failure(2, format!("ls: CSH_LS_ICONS: {}\n", e)),
+        },
+        None => Some(&icons::EMOJI),
+    };
     let mut options = LsOptions {
-        show_icons: true, // Icons on by default
+        icons: default_icons,
         ..Default::default()
     };
     let mut paths: Vec<PathBuf> = Vec::new();
 
     // Parse arguments
-    for arg in args {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
         if arg.starts_with('-') && arg.len() > 1 && !arg.starts_with("--") {
             for c in arg[1..].chars() {
                 match c {
@@ -208,19 +146,49 @@ pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
                     's' => options.show_size = true,
                     '1' => options.one_per_line = true,
                     'h' => {} // Human readable (always on)
-                    'i' => options.show_icons = true,
-                    'I' => options.show_icons = false,
+                    'L' => options.follow_symlinks = true,
+                    'i' => options.icons = default_icons.or(Some(&icons::EMOJI)),
+                    'I' => options.icons = None,
                     _ => {}
                 }
             }
         } else if arg == "--tree" {
             options.tree = true;
+        } else if arg == "--follow-symlinks" {
+            options.follow_symlinks = true;
         } else if arg == "--no-icons" {
-            options.show_icons = false;
+            options.icons = None;
         } else if arg == "--icons" {
-            options.show_icons = true;
+            options.icons = default_icons.or(Some(&icons::EMOJI));
+        } else if let Some(name) = arg.strip_prefix("--icons=") {
+            match parse_icons(name) {
+                Ok(theme) => options.icons = theme,
+                Err(e) => return BuiltinResult::failure(2, format!("ls: {}\n", e)),
+            }
         } else if arg == "--no-color" {
             options.no_color = true;
+        } else if let Some(value) = option_value(arg, "--max-depth", &mut args) {
+            match value.and_then(|v| parse_limit("--max-depth", &v)) {
+                Ok(depth) => options.max_depth = Some(depth),
+                Err(e) => return BuiltinResult::failure(2, e),
+            }
+        } else if let Some(value) = option_value(arg, "--max-entries", &mut args) {
+            match value.and_then(|v| parse_limit("--max-entries", &v)) {
+                Ok(count) => options.max_entries = Some(count),
+                Err(e) => return BuiltinResult::failure(2, e),
+            }
+        } else if let Some(value) = option_value(arg, "--ignore", &mut args) {
+            // Comma-separated glob patterns, matched against names
+            let patterns = value.and_then(|v| {
+                v.split(',')
+                    .filter(|p| !p.is_empty())
+                    .map(|p| glob::Pattern::new(p).map_err(|e| format!("ls: --ignore: {}: {}\n", p, e)))
+                    .collect::<Result<Vec<_>, _>>()
+            });
+            match patterns {
+                Ok(patterns) => options.ignore.extend(patterns),
+                Err(e) => return BuiltinResult::failure(2, e),
+            }
         } else if !arg.starts_with('-') {
             let path = expand_path(arg, env);
             paths.push(path);
@@ -232,6 +200,12 @@ pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
         paths.push(env.cwd().clone());
     }
 
+    let configured_width = env
+        .get_value("CSH_EMOJI_WIDTH")
+        .and_then(|width| width.parse().ok())
+        .filter(|width| (1..=2).contains(width));
+    options.icon_width = options.icons.map_or(0, |theme| theme.columns(configured_width));
+
     let mut output = String::new();
 
     for (idx, path) in paths.iter().enumerate() {
@@ -242,7 +216,18 @@ pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
             output.push_str(&format!("\x1b[1;35m{}:\x1b[0m\n", path.display()));
         }
 
-        match list_directory(path, &options) {
+        let listing = if options.tree {
+            let label = if path == env.cwd() {
+                ".".to_string()
+            } else {
+                path.display().to_string()
+            };
+            format_tree(path, &label, &options)
+        } else {
+            list_directory(path, &options)
+        };
+
+        match listing {
             Ok(dir_output) => output.push_str(&dir_output),
             Err(e) => {
                 return BuiltinResult::failure(1, format!("ls: {}: {}\n", path.display(), e));
@@ -253,6 +238,17 @@ pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
     BuiltinResult::success_with_output(output)
 }
 
+/// Parse an icon theme name; `none` turns icons off
+fn parse_icons(name: &str) -> Result<Option<&'static IconTheme>, String> {
+    if name == "none" {
+        return Ok(None);
+    }
+    match icons::theme_by_name(name) {
+        Some(theme) => Ok(Some(theme)),
+        None => Err(format!("unknown icon theme '{}' (expected emoji, nerd or none)", name)),
+    }
+}
+
 fn expand_path(arg: &str, env: &Environment) -> PathBuf {
     let path = if arg.starts_with("~/") {
         if let Some(home) = env.get_value("HOME") {
@@ -279,6 +275,7 @@ fn list_directory(path: &PathBuf, options: &LsOptions) -> Result<String, std::io
         .filter_map(|e| e.ok())
         .filter_map(|e| FileEntry::from_dir_entry(&e))
         .filter(|f| options.show_hidden || !f.is_hidden)
+        .filter(|f| !options.is_ignored(&f.name))
         .collect();
 
     // Sort: directories first, then alphabetically (case-insensitive)
@@ -306,19 +303,23 @@ fn list_directory(path: &PathBuf, options: &LsOptions) -> Result<String, std::io
 
 /// Format as grid layout (default)
 fn format_grid(files: &[FileEntry], options: &LsOptions) -> String {
-    if files.is_empty() {
-        return String::new();
-    }
-
     // Get terminal width
     let term_width = terminal_size()
         .map(|(Width(w), _)| w as usize)
         .unwrap_or(80);
+    format_grid_in(files, options, term_width)
+}
+
+/// Format as a grid filling `term_width` columns
+fn format_grid_in(files: &[FileEntry], options: &LsOptions, term_width: usize) -> String {
+    if files.is_empty() {
+        return String::new();
+    }
 
     // Calculate max width of entries
     let max_width = files
         .iter()
-        .map(|f| f.display_width(options.show_icons))
+        .map(|f| f.display_width(options))
         .max()
         .unwrap_or(10);
 
@@ -331,8 +332,8 @@ fn format_grid(files: &[FileEntry], options: &LsOptions) -> String {
     // Build grid output
     let mut output = String::new();
     for (i, file) in files.iter().enumerate() {
-        let display = file.colored_name(options.show_icons);
-        let actual_width = file.display_width(options.show_icons);
+        let display = file.colored_name(options.icons);
+        let actual_width = file.display_width(options);
 
         output.push_str(&display);
 
@@ -351,11 +352,104 @@ fn format_grid(files: &[FileEntry], options: &LsOptions) -> String {
     output
 }
 
+/// Format the whole tree under `root` (`--tree`)
+fn format_tree(root: &Path, label: &str, options: &LsOptions) -> Result<String, std::io::Error> {
+    let mut output = format!("\x1b[1;34m{}\x1b[0m\n", label);
+    // Whether the entry open at each depth was the last of its siblings
+    let mut last_at_depth: Vec<bool> = Vec::new();
+
+    let walk_options = WalkOptions {
+        follow_symlinks: options.follow_symlinks,
+    };
+    let include = |entry: &fs::DirEntry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        (options.show_hidden || !name.starts_with('.')) && !options.is_ignored(&name)
+    };
+    let mut printed = 0;
+    let mut depth_cut = false;
+    let mut entries_cut = false;
+
+    walk(root, &walk_options, include, |entry| {
+        if options.max_entries.is_some_and(|max| printed >= max) {
+            entries_cut = true;
+            return false;
+        }
+        printed += 1;
+
+        last_at_depth.truncate(entry.depth - 1);
+        for last in &last_at_depth {
+            output.push_str(if *last { "    " } else { "\u{2502}   " });
+        }
+        output.push_str(if entry.is_last { "\u{2514}\u{2500}\u{2500} " } else { "\u{251c}\u{2500}\u{2500} " });
+        last_at_depth.push(entry.is_last);
+
+        match FileEntry::from_dir_entry(&entry.entry) {
+            Some(file) => output.push_str(&file.colored_name(options.icons)),
+            None => output.push_str(&entry.entry.file_name().to_string_lossy()),
+        }
+        if entry.is_symlink {
+            if let Ok(target) = fs::read_link(entry.entry.path()) {
+                output.push_str(&format!(" -> {}", target.display()));
+            }
+        }
+        if entry.is_loop {
+            output.push_str(" \x1b[33m[recursive, not followed]\x1b[0m");
+        }
+        output.push('\n');
+
+        if options.max_depth.is_some_and(|max| entry.depth >= max) {
+            // Only worth a notice if something was actually left out
+            if entry.is_dir && !depth_cut {
+                depth_cut = fs::read_dir(entry.entry.path()).is_ok_and(|mut d| d.next().is_some());
+            }
+            return false;
+        }
+        true
+    })?;
+
+    if entries_cut {
+        output.push_str(&format!(
+            "\x1b[33m[stopped after {} entries; use --max-entries to change]\x1b[0m\n",
+            printed
+        ));
+    }
+    if depth_cut {
+        output.push_str(&format!(
+            "\x1b[33m[deeper than {} levels not shown; use --max-depth to change]\x1b[0m\n",
+            options.max_depth.unwrap_or_default()
+        ));
+    }
+
+    Ok(output)
+}
+
+/// Value of `--name=value` or `--name value`; `None` if `arg` is another option
+fn option_value<'a>(
+    arg: &str,
+    name: &str,
+    rest: &mut impl Iterator<Item = &'a String>,
+) -> Option<Result<String, String>> {
+    if arg == name {
+        Some(rest.next().cloned().ok_or_else(|| format!("ls: {} requires an argument\n", name)))
+    } else {
+        let value = arg.strip_prefix(name)?.strip_prefix('=')?;
+        Some(Ok(value.to_string()))
+    }
+}
+
+/// A positive count for `--max-depth` / `--max-entries`
+fn parse_limit(name: &str, value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("ls: {}: invalid number '{}'\n", name, value)),
+    }
+}
+
 /// Format as one entry per line
 fn format_one_per_line(files: &[FileEntry], options: &LsOptions) -> String {
     let mut output = String::new();
     for file in files {
-        output.push_str(&file.colored_name(options.show_icons));
+        output.push_str(&file.colored_name(options.icons));
         output.push('\n');
     }
     output
@@ -370,10 +464,9 @@ fn format_long(files: &[FileEntry], options: &LsOptions) -> String {
     output.push_str(&format!("\x1b[90mtotal {}\x1b[0m\n", format_size(total)));
 
     for file in files {
-        let icon = if options.show_icons {
-            format!("{} ", file.icon())
-        } else {
-            String::new()
+        let icon = match options.icons {
+            Some(theme) => format!("{} ", file.icon(theme)),
+            None => String::new(),
         };
 
         let type_char = if file.is_dir {
@@ -417,7 +510,7 @@ fn format_long(files: &[FileEntry], options: &LsOptions) -> String {
     output
 }
 
-fn format_size(bytes: u64) -> String {
+pub(crate) fn format_size(bytes: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = KB * 1024;
     const GB: u64 = MB * 1024;
@@ -459,3 +552,114 @@ fn is_executable_file(name: &str, _metadata: &Metadata) -> bool {
     #[cfg(not(unix))]
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(dir: &std::path::Path, args: &[&str]) -> String {
+        let env = Environment::with_vars(dir.to_path_buf(), std::env::vars());
+        let mut args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        args.push("-1".to_string());
+        execute(&args, &env).output.unwrap()
+    }
+
+    #[test]
+    fn test_icon_themes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "").unwrap();
+
+        assert_eq!(list(dir.path(), &[]), "🦀 main.rs\n");
+        assert_eq!(list(dir.path(), &["--icons=nerd"]), "\u{e7a8} main.rs\n");
+        assert_eq!(list(dir.path(), &["--icons=none"]), "main.rs\n");
+
+        let mut env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        env.set("CSH_LS_ICONS", "nerdfont");
+        let output = execute(&["-1".to_string()], &env).output.unwrap();
+        assert_eq!(output, "\u{e7a8} main.rs\n");
+
+        let result = execute(&["--icons=fancy".to_string()], &env);
+        assert!(result.error.unwrap().contains("unknown icon theme"));
+    }
+
+    #[test]
+    fn test_grid_uses_measured_icon_width() {
+        let entry = |name: &str| FileEntry {
+            name: name.to_string(),
+            is_dir: false,
+            is_executable: false,
+            is_symlink: false,
+            is_hidden: false,
+            size: 0,
+            modified: Local::now(),
+        };
+        let files = [entry("a"), entry("bbb")];
+
+        for icon_width in [1, 2] {
+            let options = LsOptions {
+                icons: Some(&icons::EMOJI),
+                icon_width,
+                ..Default::default()
+            };
+            // Draw each emoji as wide as this terminal would
+            let icon = "#".repeat(icon_width);
+            let grid: String = format_grid_in(&files, &options, 80)
+                .chars()
+                .map(|c| if c.is_ascii() { c.to_string() } else { icon.clone() })
+                .collect();
+            // The second column starts right after the widest entry and its gap
+            let second = grid.find(&format!("{} bbb", icon)).unwrap();
+            assert_eq!(second, icon_width + 1 + "bbb".len() + 2, "{:?}", grid);
+        }
+    }
+
+    #[test]
+    fn test_tree_limits_and_ignore() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b/c/d")).unwrap();
+        fs::write(dir.path().join("a/b/c/d/deep.txt"), "").unwrap();
+        fs::create_dir_all(dir.path().join("node_modules/pkg")).unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        for i in 0..5 {
+            fs::write(dir.path().join(format!("file{}.txt", i)), "").unwrap();
+        }
+        let entries = |tree: &str| tree.lines().filter(|line| line.contains("\u{2500}\u{2500} ")).count();
+
+        let tree = list(dir.path(), &["--tree", "--icons=none", "-a", "--ignore=node_modules,.git"]);
+        assert!(!tree.contains("node_modules") && !tree.contains(".git"), "{}", tree);
+        assert!(tree.contains("deep.txt"));
+
+        // Levels below the limit are cut, with a notice
+        let tree = list(dir.path(), &["--tree", "--icons=none", "--ignore", "node_modules", "--max-depth", "2"]);
+        assert!(tree.contains("b/") && !tree.contains("c/"), "{}", tree);
+        assert!(tree.contains("deeper than 2 levels not shown"), "{}", tree);
+
+        let tree = list(dir.path(), &["--tree", "--icons=none", "--max-entries=3"]);
+        assert_eq!(entries(&tree), 3);
+        assert!(tree.contains("stopped after 3 entries"), "{}", tree);
+
+        // A deep enough limit prints no notice
+        let tree = list(dir.path(), &["--tree", "--icons=none", "--max-depth=9"]);
+        assert!(!tree.contains("not shown"), "{}", tree);
+        let env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        assert!(!execute(&["--max-depth=0".to_string()], &env).status.is_success());
+        assert!(!execute(&["--max-entries".to_string()], &env).status.is_success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_tree_stops_at_symlink_loop() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/main.rs"), "").unwrap();
+        std::os::unix::fs::symlink("..", dir.path().join("sub/up")).unwrap();
+
+        let tree = list(dir.path(), &["--tree", "--icons=none"]);
+        assert!(tree.contains("\u{2514}\u{2500}\u{2500} main.rs\n"), "{}", tree);
+        assert!(tree.contains("up\x1b[0m -> ..\n"), "{}", tree);
+
+        let followed = list(dir.path(), &["--tree", "-L", "--icons=none"]);
+        assert!(followed.contains("-> .. \x1b[33m[recursive, not followed]"), "{}", followed);
+        assert_eq!(followed.lines().count(), 4);
+    }
+}
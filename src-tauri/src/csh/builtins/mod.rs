@@ -4,21 +4,32 @@ pub mod alias;
 pub mod cat;
 pub mod cd;
 pub mod clear;
+pub mod diag;
 pub mod echo;
 pub mod env_cmd;
 pub mod exit;
 pub mod export;
 pub mod history_cmd;
+pub mod kill;
+pub mod ln;
 pub mod ls;
+pub mod open;
+pub mod path_cmd;
 pub mod pwd;
+pub mod realpath;
 pub mod set;
+pub mod shift;
+pub mod timeout;
 pub mod unset;
+pub mod wait;
 pub mod which;
+pub mod yes;
 pub mod help;
 
 use crate::csh::ast::ExitStatus;
 use crate::csh::environment::Environment;
 use crate::csh::history::History;
+use crate::csh::interrupt;
 
 /// Result of a built-in command execution
 #[derive(Debug)]
@@ -72,6 +83,24 @@ impl BuiltinResult {
     }
 }
 
+/// Describes where a builtin's output is going
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputContext {
+    /// Output goes straight to an interactive terminal
+    /// (false when redirected to a file, piped, or captured)
+    pub is_terminal: bool,
+}
+
+impl OutputContext {
+    pub fn terminal() -> Self {
+        Self { is_terminal: true }
+    }
+
+    pub fn redirected() -> Self {
+        Self { is_terminal: false }
+    }
+}
+
 /// Built-in commands handler
 pub struct Builtins {
     /// List of built-in command names
@@ -84,7 +113,9 @@ impl Builtins {
             commands: vec![
                 "cd", "pwd", "echo", "exit", "clear", "cls", "ls", "dir", "cat", "type",
                 "env", "set", "unset", "export", "alias", "unalias", "history",
-                "which", "where", "help", "true", "false",
+                "which", "where", "help", "true", "false", "ln", "path-add", "path-remove",
+                "yes", "realpath", "diag", "wait", "kill", "open", "start", "timeout", "shift",
+                "break", "continue", "return",
             ],
         }
     }
@@ -106,24 +137,33 @@ impl Builtins {
         args: &[String],
         env: &mut Environment,
         history: &mut History,
+        ctx: OutputContext,
     ) -> BuiltinResult {
         match name {
             "cd" => cd::execute(args, env),
             "pwd" => pwd::execute(env),
+            "realpath" => realpath::execute(args, env),
+            "open" | "start" => open::execute(args, env),
             "echo" => echo::execute(args),
             "exit" => exit::execute(args),
-            "clear" | "cls" => clear::execute(),
+            "clear" | "cls" => clear::execute(ctx),
             "ls" | "dir" => ls::execute(args, env),
-            "cat" | "type" => cat::execute(args, env),
-            "env" => env_cmd::execute(env),
+            "cat" | "type" => cat::execute(args, env, ctx),
+            "ln" => ln::execute(args, env),
+            "path-add" => path_cmd::execute_add(args, env),
+            "path-remove" => path_cmd::execute_remove(args, env),
+            "yes" => yes::execute(args, &mut std::io::stdout(), interrupt::flag()),
+            "env" => env_cmd::execute(args, env),
             "set" => set::execute(args, env),
             "unset" => unset::execute(args, env),
+            "shift" => shift::execute(args, env),
             "export" => export::execute(args, env),
             "alias" => alias::execute_alias(args, env),
             "unalias" => alias::execute_unalias(args, env),
             "history" => history_cmd::execute(args, history),
             "which" | "where" => which::execute(args, env),
-            "help" => help::execute(args),
+            "help" => help::execute(args, env, ctx),
+            "diag" => diag::execute(env, history),
             "true" => BuiltinResult::success(),
             "false" => BuiltinResult::failure(1, String::new()),
             _ => BuiltinResult::failure(1, format!("csh: {}: command not found\n", name)),
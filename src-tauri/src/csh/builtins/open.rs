@@ -0,0 +1,151 @@
+//! open - Open files, directories and URLs with their default application
+
+use std::io;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::csh::ast::ExitStatus;
+use crate::csh::builtins::cd::normalize_path;
+use crate::csh::builtins::realpath::expand_tilde;
+use crate::csh::builtins::BuiltinResult;
+use crate::csh::environment::Environment;
+
+/// open TARGET... (also `start`) - hand each target to the system opener
+pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
+    execute_with(args, env, &mut system_open)
+}
+
+/// `open` with the opener injected, called with an absolute path or a URL
+pub fn execute_with(
+    args: &[String],
+    env: &Environment,
+    opener: &mut dyn FnMut(&str) -> io::Result<()>,
+) -> BuiltinResult {
+    if args.is_empty() {
+        return BuiltinResult::failure(1, "open: missing operand\n".to_string());
+    }
+
+    let mut errors = String::new();
+    for arg in args {
+        let target = if is_url(arg) {
+            arg.clone()
+        } else {
+            let path = resolve(arg, env);
+            if !path.exists() {
+                errors.push_str(&format!("open: {}: No such file or directory\n", arg));
+                continue;
+            }
+            path.display().to_string()
+        };
+        if let Err(e) = opener(&target) {
+            errors.push_str(&format!("open: {}: {}\n", arg, e));
+        }
+    }
+
+    if errors.is_empty() {
+        return BuiltinResult::success();
+    }
+    BuiltinResult {
+        status: ExitStatus::failure(1),
+        output: None,
+        error: Some(errors),
+        should_exit: false,
+        exit_code: None,
+    }
+}
+
+/// `http://...`, `mailto:...` and the like (but not `C:\...`)
+fn is_url(arg: &str) -> bool {
+    let Some((scheme, _)) = arg.split_once(':') else {
+        return false;
+    };
+    scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+}
+
+/// `arg` as an absolute path, with `~/` expanded
+fn resolve(arg: &str, env: &Environment) -> PathBuf {
+    let path = env.cwd().join(expand_tilde(arg, env));
+    path.canonicalize().map(normalize_path).unwrap_or(path)
+}
+
+/// The command that hands `target` to the desktop's default handler
+///
+/// On Windows this is the shell's URL handler rather than `cmd /c start`,
+/// which would run whatever follows a `&` or `|` in the target.
+fn opener_command(target: &str) -> Command {
+    #[cfg(windows)]
+    let (program, args) = ("rundll32", vec!["url.dll,FileProtocolHandler", target]);
+    #[cfg(target_os = "macos")]
+    let (program, args) = ("open", vec![target]);
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let (program, args) = ("xdg-open", vec![target]);
+
+    let mut command = Command::new(program);
+    command.args(args);
+    command
+}
+
+/// Open `target` with the desktop's default handler, without waiting for it
+fn system_open(target: &str) -> io::Result<()> {
+    let mut child = opener_command(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Reap the launcher once it exits
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_targets_are_resolved_before_opening() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir_path = dir.path().canonicalize().unwrap();
+        fs::write(dir_path.join("report.pdf"), "").unwrap();
+        let mut env = Environment::with_vars(dir_path.clone(), std::env::vars());
+        env.set("HOME", &dir_path.display().to_string());
+
+        let mut opened = Vec::new();
+        let args: Vec<String> = ["report.pdf", "~/report.pdf", ".", "https://example.com/a?b"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let result = execute_with(&args, &env, &mut |target| {
+            opened.push(target.to_string());
+            Ok(())
+        });
+        assert!(result.status.is_success());
+        let report = dir_path.join("report.pdf").display().to_string();
+        assert_eq!(
+            opened,
+            vec![report.clone(), report, dir_path.display().to_string(), args[3].clone()]
+        );
+
+        // Missing files are reported without calling the opener
+        let mut opened = Vec::new();
+        let result = execute_with(&["missing.txt".to_string()], &env, &mut |target| {
+            opened.push(target.to_string());
+            Ok(())
+        });
+        assert_eq!(result.status.code, 1);
+        assert_eq!(result.error.unwrap(), "open: missing.txt: No such file or directory\n");
+        assert!(opened.is_empty());
+    }
+
+    #[test]
+    fn test_opener_passes_target_as_one_argument() {
+        let target = "https://example.com/?a=1&b=2|x";
+        let command = opener_command(target);
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args.last().unwrap().to_str(), Some(target));
+        assert!(command.get_program() != "cmd");
+    }
+}
@@ -0,0 +1,152 @@
+//! path-add / path-remove - Edit PATH without typing separators
+
+use crate::csh::builtins::BuiltinResult;
+use crate::csh::environment::Environment;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// path-add [-a] DIR... - put directories at the front (or end) of PATH
+///
+/// Directories must exist. One already in PATH is moved rather than
+/// added twice.
+pub fn execute_add(args: &[String], env: &mut Environment) -> BuiltinResult {
+    let mut append = false;
+    let mut dirs: Vec<PathBuf> = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "-a" | "--append" => append = true,
+            "-p" | "--prepend" => append = false,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return BuiltinResult::failure(2, format!("path-add: invalid option '{}'\n", arg));
+            }
+            _ => {
+                let dir = resolve(arg, env);
+                if !dir.is_dir() {
+                    return BuiltinResult::failure(1, format!("path-add: {}: not a directory\n", arg));
+                }
+                dirs.push(dir);
+            }
+        }
+    }
+    if dirs.is_empty() {
+        return BuiltinResult::failure(1, "path-add: missing directory\n".to_string());
+    }
+
+    let mut entries: Vec<PathBuf> = env.path_entries()
+        .into_iter()
+        .filter(|entry| !dirs.iter().any(|dir| same_entry(entry, dir)))
+        .collect();
+    if append {
+        entries.extend(dirs);
+    } else {
+        entries.splice(0..0, dirs);
+    }
+
+    store(entries, env, "path-add")
+}
+
+/// path-remove DIR... - drop directories from PATH
+pub fn execute_remove(args: &[String], env: &mut Environment) -> BuiltinResult {
+    if args.is_empty() {
+        return BuiltinResult::failure(1, "path-remove: missing directory\n".to_string());
+    }
+
+    let mut entries = env.path_entries();
+    let mut missing = String::new();
+    for arg in args {
+        // Entries are matched as written and as resolved against the cwd
+        let resolved = resolve(arg, env);
+        let before = entries.len();
+        entries.retain(|entry| !same_entry(entry, Path::new(arg)) && !same_entry(entry, &resolved));
+        if entries.len() == before {
+            missing.push_str(&format!("path-remove: {}: not in PATH\n", arg));
+        }
+    }
+
+    let result = store(entries, env, "path-remove");
+    if missing.is_empty() || !result.status.is_success() {
+        result
+    } else {
+        BuiltinResult::failure(1, missing)
+    }
+}
+
+/// Export `entries` as the new PATH
+fn store(entries: Vec<PathBuf>, env: &mut Environment, name: &str) -> BuiltinResult {
+    match env::join_paths(entries) {
+        Ok(value) => {
+            env.export("PATH", Some(&value.to_string_lossy()));
+            BuiltinResult::success()
+        }
+        // A directory containing the separator itself
+        Err(e) => BuiltinResult::failure(1, format!("{}: {}\n", name, e)),
+    }
+}
+
+/// Whether two PATH entries name the same directory
+///
+/// Trailing separators are ignored, and case too on Windows.
+fn same_entry(a: &Path, b: &Path) -> bool {
+    fn normalize(path: &Path) -> String {
+        let text = path.to_string_lossy();
+        let text = text.trim_end_matches(['/', '\\']);
+        if cfg!(windows) {
+            text.to_lowercase()
+        } else {
+            text.to_string()
+        }
+    }
+    normalize(a) == normalize(b)
+}
+
+/// Resolve a directory argument relative to the shell's cwd
+fn resolve(arg: &str, env: &Environment) -> PathBuf {
+    let path = if let Some(rest) = arg.strip_prefix("~/") {
+        match env.get_value("HOME") {
+            Some(home) => PathBuf::from(home).join(rest),
+            None => PathBuf::from(arg),
+        }
+    } else {
+        PathBuf::from(arg)
+    };
+
+    if path.is_absolute() {
+        path
+    } else {
+        env.cwd().join(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_remove_keep_order_without_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b, c) = (dir.path().join("a"), dir.path().join("b"), dir.path().join("c"));
+        for d in [&a, &b, &c] {
+            std::fs::create_dir(d).unwrap();
+        }
+        let mut env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        env.set("PATH", &env::join_paths([&a, &b]).unwrap().to_string_lossy());
+
+        let path = |env: &Environment| env.path_entries();
+
+        // Prepending an existing entry moves it instead of duplicating it
+        assert!(execute_add(&["c".to_string()], &mut env).status.is_success());
+        assert!(execute_add(&[b.to_string_lossy().to_string()], &mut env).status.is_success());
+        assert_eq!(path(&env), vec![b.clone(), c.clone(), a.clone()]);
+
+        assert!(execute_add(&["-a".to_string(), "b".to_string()], &mut env).status.is_success());
+        assert_eq!(path(&env), vec![c.clone(), a.clone(), b.clone()]);
+
+        assert!(!execute_add(&["missing".to_string()], &mut env).status.is_success());
+
+        assert!(execute_remove(&["a".to_string()], &mut env).status.is_success());
+        assert_eq!(path(&env), vec![c.clone(), b.clone()]);
+        assert!(!execute_remove(&["a".to_string()], &mut env).status.is_success());
+        assert!(env.get_exports().contains_key("PATH"));
+    }
+}
@@ -0,0 +1,145 @@
+//! realpath - Print canonical absolute paths
+
+use crate::csh::ast::ExitStatus;
+use crate::csh::builtins::cd::normalize_path;
+use crate::csh::builtins::BuiltinResult;
+use crate::csh::environment::Environment;
+use std::path::{Component, Path, PathBuf};
+
+/// How much of a path has to exist
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    /// Every component but the last (the default)
+    AllButLast,
+    /// Every component (`-e`)
+    Existing,
+    /// None (`-m`)
+    Missing,
+}
+
+/// realpath [-e|-m] PATH... - resolve `.`, `..` and symlinks
+pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
+    let mut mode = Mode::AllButLast;
+    let mut paths: Vec<&String> = Vec::new();
+    let mut options_done = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--" if !options_done => options_done = true,
+            "-e" | "--canonicalize-existing" if !options_done => mode = Mode::Existing,
+            "-m" | "--canonicalize-missing" if !options_done => mode = Mode::Missing,
+            _ if !options_done && arg.starts_with('-') && arg.len() > 1 => {
+                return BuiltinResult::failure(2, format!("realpath: invalid option '{}'\n", arg));
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return BuiltinResult::failure(1, "realpath: missing operand\n".to_string());
+    }
+
+    let mut output = String::new();
+    let mut errors = String::new();
+    for arg in paths {
+        match resolve(&expand_tilde(arg, env), env.cwd(), mode) {
+            Ok(path) => output.push_str(&format!("{}\n", path.display())),
+            Err(e) => errors.push_str(&format!("realpath: {}: {}\n", arg, e)),
+        }
+    }
+
+    if errors.is_empty() {
+        return BuiltinResult::success_with_output(output);
+    }
+    BuiltinResult {
+        status: ExitStatus::failure(1),
+        output: Some(output).filter(|o| !o.is_empty()),
+        error: Some(errors),
+        should_exit: false,
+        exit_code: None,
+    }
+}
+
+/// `~` and `~/...` relative to HOME
+pub fn expand_tilde(arg: &str, env: &Environment) -> PathBuf {
+    match (arg, env.get_value("HOME")) {
+        ("~", Some(home)) => PathBuf::from(home),
+        (_, Some(home)) if arg.starts_with("~/") => PathBuf::from(home).join(&arg[2..]),
+        _ => PathBuf::from(arg),
+    }
+}
+
+/// Resolve `path` against `cwd` one component at a time
+///
+/// Existing components are canonicalized (following symlinks); missing
+/// ones are appended as written, with `..` removing the previous one.
+fn resolve(path: &Path, cwd: &Path, mode: Mode) -> Result<PathBuf, &'static str> {
+    let full = cwd.join(path);
+    let components: Vec<Component> = full.components().collect();
+    let mut resolved = PathBuf::new();
+    let mut missing = false;
+
+    for (i, component) in components.iter().enumerate() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => resolved.push(component.as_os_str()),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => {
+                let is_last = components[i + 1..]
+                    .iter()
+                    .all(|c| matches!(c, Component::CurDir));
+                let candidate = resolved.join(name);
+                if !missing {
+                    if let Ok(canonical) = candidate.canonicalize() {
+                        resolved = canonical;
+                        continue;
+                    }
+                }
+                match mode {
+                    Mode::Existing => return Err("No such file or directory"),
+                    Mode::AllButLast if !is_last => return Err("No such file or directory"),
+                    _ => {}
+                }
+                missing = true;
+                resolved = candidate;
+            }
+        }
+    }
+
+    Ok(normalize_path(resolved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn run(args: &[&str], env: &Environment) -> BuiltinResult {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        execute(&args, env)
+    }
+
+    #[test]
+    fn test_resolves_relative_dotdot_and_missing_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = normalize_path(dir.path().canonicalize().unwrap());
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub").join("file.txt"), "").unwrap();
+        let env = Environment::with_vars(root.clone(), std::env::vars());
+        let file = root.join("sub").join("file.txt");
+
+        let result = run(&["sub/file.txt"], &env);
+        assert_eq!(result.output, Some(format!("{}\n", file.display())));
+
+        let result = run(&["sub/../sub/./file.txt", "."], &env);
+        assert_eq!(result.output, Some(format!("{}\n{}\n", file.display(), root.display())));
+
+        // Only the last component may be missing by default; -m allows any
+        assert!(run(&["sub/new.txt"], &env).status.is_success());
+        assert!(!run(&["-e", "sub/new.txt"], &env).status.is_success());
+        assert!(!run(&["nope/deeper/x"], &env).status.is_success());
+        let result = run(&["-m", "nope/deeper/../x"], &env);
+        assert_eq!(result.output, Some(format!("{}\n", root.join("nope").join("x").display())));
+    }
+}
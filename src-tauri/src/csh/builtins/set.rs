@@ -4,10 +4,12 @@ use crate::csh::builtins::BuiltinResult;
 use crate::csh::environment::Environment;
 
 pub fn execute(args: &[String], env: &mut Environment) -> BuiltinResult {
-    if args.is_empty() {
-        // Show all variables
+    let local_only = args.len() == 1 && matches!(args[0].as_str(), "-l" | "--local");
+    if args.is_empty() || local_only {
+        // Show all variables (only the shell's own with --local)
         let mut output = String::new();
-        let mut vars: Vec<_> = env.get_all().into_iter().collect();
+        let vars = if local_only { env.get_local() } else { env.get_all() };
+        let mut vars: Vec<_> = vars.into_iter().collect();
         vars.sort_by(|a, b| a.0.cmp(&b.0));
 
         for (key, value) in vars {
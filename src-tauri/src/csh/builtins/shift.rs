@@ -0,0 +1,59 @@
+//! shift - Drop positional parameters
+
+use crate::csh::builtins::BuiltinResult;
+use crate::csh::environment::Environment;
+
+/// shift [N] - drop `$1`..`$N` and renumber the rest (N defaults to 1)
+///
+/// Shifting more parameters than there are fails and changes nothing.
+pub fn execute(args: &[String], env: &mut Environment) -> BuiltinResult {
+    let count = match args {
+        [] => 1,
+        [count] => match count.parse::<usize>() {
+            Ok(count) => count,
+            Err(_) => {
+                return BuiltinResult::failure(
+                    2,
+                    format!("shift: {}: numeric argument required\n", count),
+                )
+            }
+        },
+        _ => return BuiltinResult::failure(2, "shift: too many arguments\n".to_string()),
+    };
+
+    if env.shift_positional(count) {
+        BuiltinResult::success()
+    } else {
+        BuiltinResult::failure(1, format!("shift: {}: shift count out of range\n", count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shift_renumbers_parameters() {
+        let mut env = Environment::new();
+        let params = ["script", "a", "b", "c"];
+        env.set_positional(params.iter().map(|p| p.to_string()).collect());
+        assert_eq!(env.expand_variables("${#} ${1}"), "3 a");
+
+        assert!(execute(&[], &mut env).status.is_success());
+        assert_eq!(env.expand_variables("${#} ${0} ${1} ${2} [${3}]"), "2 script b c []");
+
+        // Too far: nothing changes
+        let result = execute(&["3".to_string()], &mut env);
+        assert_eq!(result.status.code, 1);
+        assert_eq!(env.expand_variables("${#} ${1}"), "2 b");
+        assert_eq!(execute(&["x".to_string()], &mut env).status.code, 2);
+
+        assert!(execute(&["2".to_string()], &mut env).status.is_success());
+        assert_eq!(env.expand_variables("${#} [${1}]"), "0 []");
+
+        // Nothing to shift, even without a `$0`
+        env.set_positional(Vec::new());
+        assert!(execute(&["0".to_string()], &mut env).status.is_success());
+        assert_eq!(execute(&[], &mut env).status.code, 1);
+    }
+}
@@ -0,0 +1,218 @@
+//! timeout - Run a command with a time limit
+//!
+//! The executor runs the command; this module parses the arguments and
+//! provides the watchdog that signals the command once its time is up.
+
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Exit status when the command ran out of time (as in GNU timeout)
+pub const TIMED_OUT: i32 = 124;
+/// Exit status when timeout itself fails, e.g. on bad arguments
+pub const FAILED: i32 = 125;
+
+const SIGKILL: i32 = 9;
+const SIGTERM: i32 = 15;
+
+/// Signals that have the same number everywhere
+const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", 1),
+    ("INT", 2),
+    ("QUIT", 3),
+    ("ABRT", 6),
+    ("KILL", SIGKILL),
+    ("ALRM", 14),
+    ("TERM", SIGTERM),
+];
+
+/// How long a command may run and how it is stopped
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeLimit {
+    pub duration: Duration,
+    /// Signal sent when the time is up
+    pub signal: i32,
+    /// Grace period after which KILL follows the first signal
+    pub kill_after: Option<Duration>,
+}
+
+/// Parse `[-s SIGNAL] [-k DURATION] DURATION COMMAND [ARG]...`
+///
+/// Returns the limit (`None` for a duration of 0, meaning no limit) and
+/// the command with its arguments, or an error message.
+pub fn parse(args: &[String]) -> Result<(Option<TimeLimit>, &[String]), String> {
+    let mut signal = SIGTERM;
+    let mut kill_after = None;
+    let mut i = 0;
+
+    while let Some(arg) = args.get(i) {
+        let value = || args.get(i + 1).ok_or(format!("timeout: option requires an argument -- '{}'\n", arg));
+        match arg.as_str() {
+            "-s" | "--signal" => {
+                let name = value()?;
+                signal = parse_signal(name).ok_or(format!("timeout: {}: invalid signal\n", name))?;
+                i += 2;
+            }
+            "-k" | "--kill-after" => {
+                let text = value()?;
+                kill_after = Some(parse_duration(text).ok_or(format!("timeout: invalid time interval '{}'\n", text))?);
+                i += 2;
+            }
+            "--" => {
+                i += 1;
+                break;
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 && parse_duration(arg).is_none() => {
+                return Err(format!("timeout: invalid option '{}'\n", arg));
+            }
+            _ => break,
+        }
+    }
+
+    let Some(text) = args.get(i) else {
+        return Err("timeout: missing operand\n".to_string());
+    };
+    let duration = parse_duration(text).ok_or(format!("timeout: invalid time interval '{}'\n", text))?;
+    let command = &args[i + 1..];
+    if command.is_empty() {
+        return Err(format!("timeout: missing operand after '{}'\n", text));
+    }
+
+    let limit = (!duration.is_zero()).then_some(TimeLimit {
+        duration,
+        signal,
+        kill_after,
+    });
+    Ok((limit, command))
+}
+
+/// `1.5`, `30s`, `2m`, `1h` or `1d`; plain numbers are seconds
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let (number, unit) = match text.char_indices().last()? {
+        (i, 's') => (&text[..i], 1.0),
+        (i, 'm') => (&text[..i], 60.0),
+        (i, 'h') => (&text[..i], 3600.0),
+        (i, 'd') => (&text[..i], 86400.0),
+        _ => (text, 1.0),
+    };
+    let seconds: f64 = number.parse().ok()?;
+    Duration::try_from_secs_f64(seconds * unit).ok()
+}
+
+/// A signal name (`TERM`, `SIGTERM`) or number
+pub fn parse_signal(text: &str) -> Option<i32> {
+    if let Ok(number) = text.parse() {
+        return Some(number);
+    }
+    let name = text.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    SIGNALS.iter().find(|(n, _)| *n == name).map(|(_, number)| *number)
+}
+
+/// What the watchdog had to do
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// The command finished in time
+    Finished,
+    /// The command was sent the signal
+    TimedOut,
+    /// The command had to be killed
+    Killed,
+}
+
+/// Thread that signals a process group once its time limit is up
+pub struct Watchdog {
+    done: mpsc::Sender<()>,
+    handle: JoinHandle<Outcome>,
+}
+
+impl Watchdog {
+    /// Start timing `pid`, which must lead its own process group
+    pub fn start(pid: u32, limit: TimeLimit) -> Self {
+        let (done, finished) = mpsc::channel::<()>();
+        let handle = thread::spawn(move || {
+            if finished.recv_timeout(limit.duration) != Err(RecvTimeoutError::Timeout) {
+                return Outcome::Finished;
+            }
+            signal_group(pid, limit.signal);
+            if limit.signal == SIGKILL {
+                return Outcome::Killed;
+            }
+
+            let Some(grace) = limit.kill_after else {
+                return Outcome::TimedOut;
+            };
+            if finished.recv_timeout(grace) != Err(RecvTimeoutError::Timeout) {
+                return Outcome::TimedOut;
+            }
+            signal_group(pid, SIGKILL);
+            Outcome::Killed
+        });
+        Self { done, handle }
+    }
+
+    /// Call once the process has exited
+    pub fn finish(self) -> Outcome {
+        let _ = self.done.send(());
+        self.handle.join().unwrap_or(Outcome::Finished)
+    }
+}
+
+/// Send `signal` to the process group led by `pid`
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: i32) {
+    use nix::sys::signal::{killpg, Signal};
+    use nix::unistd::Pid;
+
+    let signal = Signal::try_from(signal).unwrap_or(Signal::SIGTERM);
+    if let Err(e) = killpg(Pid::from_raw(pid as i32), signal) {
+        log::warn!("timeout: failed to signal process group {}: {}", pid, e);
+    }
+}
+
+/// End `pid` and its child processes (Windows has no signals to send)
+#[cfg(windows)]
+fn signal_group(pid: u32, _signal: i32) {
+    let status = std::process::Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status();
+    if let Err(e) = status {
+        log::warn!("timeout: failed to end process {}: {}", pid, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_options_and_durations() {
+        let line = args("-s KILL -k 2m 1.5 sleep 10");
+        let (limit, command) = parse(&line).unwrap();
+        assert_eq!(
+            limit,
+            Some(TimeLimit {
+                duration: Duration::from_millis(1500),
+                signal: SIGKILL,
+                kill_after: Some(Duration::from_secs(120)),
+            })
+        );
+        assert_eq!(command, &args("sleep 10")[..]);
+
+        assert_eq!(parse_duration("1h"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("0.5d"), Some(Duration::from_secs(43200)));
+        assert_eq!(parse_signal("sigint"), Some(2));
+        assert_eq!(parse(&args("0 sleep 10")).unwrap().0, None);
+
+        assert!(parse(&args("1 ")).unwrap_err().contains("missing operand after '1'"));
+        assert!(parse(&args("soon ls")).unwrap_err().contains("invalid time interval 'soon'"));
+        assert!(parse(&args("-s NOPE 1 ls")).unwrap_err().contains("NOPE: invalid signal"));
+        assert!(parse(&args("-x 1 ls")).unwrap_err().contains("invalid option '-x'"));
+    }
+}
@@ -0,0 +1,70 @@
+//! wait - Wait for background jobs to finish
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::csh::ast::ExitStatus;
+use crate::csh::builtins::BuiltinResult;
+use crate::csh::jobs::JobTable;
+
+/// How often running jobs are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// wait [%N|PID...] - block until the jobs finish (all of them by default)
+///
+/// The status is that of the last job waited for, or 127 if the last
+/// argument named no job. Ctrl+C stops waiting with status 130 and leaves
+/// the jobs running.
+pub fn execute(args: &[String], jobs: &mut JobTable, interrupted: &AtomicBool) -> BuiltinResult {
+    let mut targets: Vec<Result<usize, String>> = Vec::new();
+    if args.is_empty() {
+        targets.extend(jobs.list().iter().map(|job| Ok(job.id)));
+    }
+    for arg in args {
+        let target = if let Some(number) = arg.strip_prefix('%') {
+            number
+                .parse()
+                .ok()
+                .filter(|id| jobs.list().iter().any(|job| job.id == *id))
+                .ok_or_else(|| format!("wait: {}: no such job\n", arg))
+        } else if let Ok(pid) = arg.parse() {
+            jobs.find_pid(pid)
+                .ok_or_else(|| format!("wait: pid {} is not a child of this shell\n", pid))
+        } else {
+            return BuiltinResult::failure(2, format!("wait: '{}': not a pid or valid job spec\n", arg));
+        };
+        targets.push(target);
+    }
+
+    let mut status = ExitStatus::success();
+    let mut errors = String::new();
+    for target in targets {
+        let id = match target {
+            Ok(id) => id,
+            Err(message) => {
+                errors.push_str(&message);
+                status = ExitStatus::failure(127);
+                continue;
+            }
+        };
+        status = loop {
+            jobs.poll();
+            if let Some(status) = jobs.take_finished(id) {
+                break status;
+            }
+            if interrupted.load(Ordering::SeqCst) {
+                return BuiltinResult::failure(130, String::new());
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+    }
+
+    BuiltinResult {
+        status,
+        output: None,
+        error: Some(errors).filter(|e| !e.is_empty()),
+        should_exit: false,
+        exit_code: None,
+    }
+}
@@ -2,7 +2,6 @@
 
 use crate::csh::builtins::BuiltinResult;
 use crate::csh::environment::Environment;
-use std::path::PathBuf;
 
 pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
     if args.is_empty() {
@@ -10,14 +9,15 @@ pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
     }
 
     let mut output = String::new();
-    let path_var = env.get_value("PATH").unwrap_or_default();
-    let paths: Vec<&str> = path_var.split(';').collect(); // Windows uses ;
+    let paths = env.path_entries();
 
     // Built-in commands
     let builtins = vec![
         "cd", "pwd", "echo", "exit", "clear", "cls", "ls", "dir", "cat", "type",
         "env", "set", "unset", "export", "alias", "unalias", "history",
-        "which", "where", "help", "true", "false",
+        "which", "where", "help", "true", "false", "ln", "path-add", "path-remove",
+        "yes", "realpath", "diag", "wait", "open", "start", "timeout", "shift",
+        "break", "continue", "return",
     ];
 
     for cmd in args {
@@ -35,9 +35,7 @@ pub fn execute(args: &[String], env: &Environment) -> BuiltinResult {
 
         // Search in PATH
         let mut found = false;
-        for path_dir in &paths {
-            let dir = PathBuf::from(path_dir);
-
+        for dir in &paths {
             // Try with common extensions on Windows
             let extensions = ["", ".exe", ".bat", ".cmd", ".ps1", ".com"];
 
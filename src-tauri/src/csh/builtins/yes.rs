@@ -0,0 +1,115 @@
+//! yes - Print a line over and over
+
+use crate::csh::builtins::BuiltinResult;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Bytes written per call, so short lines don't cost a write each
+const CHUNK_SIZE: usize = 8192;
+
+/// The line `yes` repeats: its arguments joined by spaces, or `y`
+pub fn line(args: &[String]) -> String {
+    if args.is_empty() {
+        "y".to_string()
+    } else {
+        args.join(" ")
+    }
+}
+
+/// yes [STRING...] - write STRING (default `y`) until interrupted
+///
+/// Stops quietly when the reader goes away (e.g. `yes | head`).
+pub fn execute(args: &[String], out: &mut dyn Write, interrupted: &AtomicBool) -> BuiltinResult {
+    match write_lines(out, &line(args), interrupted, None) {
+        Ok(()) => BuiltinResult::success(),
+        Err(e) => BuiltinResult::failure(1, format!("yes: {}\n", e)),
+    }
+}
+
+/// Write `line` repeatedly until `interrupted` is set, the reader closes
+/// the pipe, or `limit` lines have been written
+///
+/// Writes block while the reader is behind, so a slow consumer slows
+/// `yes` down instead of it buffering without bound.
+pub fn write_lines(
+    out: &mut dyn Write,
+    line: &str,
+    interrupted: &AtomicBool,
+    limit: Option<usize>,
+) -> io::Result<()> {
+    let line = format!("{}\n", line);
+    let per_chunk = (CHUNK_SIZE / line.len()).max(1);
+    let chunk = line.repeat(per_chunk);
+    let mut remaining = limit;
+
+    while !interrupted.load(Ordering::SeqCst) {
+        let lines = match remaining {
+            Some(0) => break,
+            Some(n) => n.min(per_chunk),
+            None => per_chunk,
+        };
+        match out.write_all(&chunk.as_bytes()[..lines * line.len()]).and_then(|_| out.flush()) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => break,
+            Err(e) => return Err(e),
+        }
+        if let Some(n) = remaining.as_mut() {
+            *n -= lines;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Accepts `capacity` bytes, then reports the reader as gone
+    struct ClosingPipe {
+        written: Vec<u8>,
+        capacity: usize,
+    }
+
+    impl Write for ClosingPipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let room = self.capacity - self.written.len();
+            if room == 0 {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            let n = buf.len().min(room);
+            self.written.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_limit_interrupt_and_closed_reader() {
+        let running = AtomicBool::new(false);
+
+        let mut out = Vec::new();
+        write_lines(&mut out, &line(&[]), &running, Some(3)).unwrap();
+        assert_eq!(out, b"y\ny\ny\n");
+
+        let mut out = Vec::new();
+        let args = vec!["a".to_string(), "b".to_string()];
+        write_lines(&mut out, &line(&args), &running, Some(CHUNK_SIZE)).unwrap();
+        assert_eq!(out.len(), CHUNK_SIZE * 4);
+        assert!(out.starts_with(b"a b\na b\n"));
+
+        // An interrupt before the first write produces nothing
+        let interrupted = AtomicBool::new(true);
+        let mut out = Vec::new();
+        write_lines(&mut out, "y", &interrupted, None).unwrap();
+        assert!(out.is_empty());
+
+        // Without a limit, a closed pipe is what ends it, without an error
+        let mut pipe = ClosingPipe { written: Vec::new(), capacity: 10 };
+        let result = execute(&[], &mut pipe, &running);
+        assert!(result.status.is_success());
+        assert_eq!(pipe.written, b"y\ny\ny\ny\ny\n");
+    }
+}
@@ -1,13 +1,26 @@
 //! Tab completion for CSH
 
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::csh::builtins::help;
+use crate::csh::builtins::ls::format_size;
 use crate::csh::builtins::Builtins;
 use crate::csh::environment::Environment;
+use crate::csh::git;
+use crate::csh::options::ShellOptions;
+use crate::csh::processes::{self, Process};
+use crate::csh::users;
+use serde::Serialize;
+use unicode_width::UnicodeWidthStr;
 
 /// Completion result
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Completion {
     /// The completed text
     pub text: String,
@@ -15,21 +28,201 @@ pub struct Completion {
     pub display: String,
     /// Whether this is a directory (for adding /)
     pub is_dir: bool,
+    /// What kind of thing was matched (for icons and grouping)
+    pub kind: CompletionKind,
+    /// Details for verbose listings (files only)
+    pub meta: Option<CompletionMeta>,
 }
 
+/// Where a completion came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletionKind {
+    /// A builtin command (also `help` topics)
+    Builtin,
+    Alias,
+    /// An executable found on `PATH`
+    Command,
+    File,
+    Directory,
+    /// A variable or shell option name
+    Variable,
+    /// A line from the command history
+    History,
+    /// A command's flag, or a value after `--flag=`
+    Flag,
+    /// A git branch name
+    Branch,
+    /// A process ID, or a job spec like `%1`
+    Process,
+}
+
+/// Extra information about a file candidate, never inserted into the line
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CompletionMeta {
+    /// Size in bytes (regular files only)
+    pub size: Option<u64>,
+    /// Short type hint: "dir", "link" or "file"
+    pub kind: &'static str,
+}
+
+/// Per-command completion behaviour for arguments
+#[derive(Debug, Clone, Default)]
+pub struct CompletionSpec {
+    /// Only offer files with these extensions (lowercase, without dot).
+    /// Directories are always offered so the user can navigate.
+    pub extensions: Vec<String>,
+    /// Values offered after `--flag=`, keyed by flag (including dashes)
+    pub flag_values: HashMap<String, Vec<String>>,
+}
+
+impl CompletionSpec {
+    /// Spec restricting file arguments to the given extensions
+    pub fn extensions(extensions: &[&str]) -> Self {
+        Self {
+            extensions: extensions.iter().map(|e| e.to_lowercase()).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Add the values completed after `flag=` (e.g. `--color`)
+    pub fn flag_values(mut self, flag: &str, values: &[&str]) -> Self {
+        self.flag_values
+            .insert(flag.to_string(), values.iter().map(|v| v.to_string()).collect());
+        self
+    }
+}
+
+/// A directory entry as read for path completion
+#[derive(Debug)]
+struct ListedEntry {
+    name: String,
+    is_dir: bool,
+    meta: CompletionMeta,
+}
+
+/// Listing of the working directory kept between completions
+#[derive(Debug)]
+struct CachedDir {
+    dir: PathBuf,
+    modified: Option<SystemTime>,
+    entries: Rc<Vec<ListedEntry>>,
+}
+
+/// Branch names of the last repository completed in
+#[derive(Debug)]
+struct CachedBranches {
+    git_dir: PathBuf,
+    /// Modification times of `HEAD`, `refs/heads` and `packed-refs`
+    stamp: [Option<SystemTime>; 3],
+    names: Rc<Vec<String>>,
+}
+
+/// Process list from the last completion that needed one
+#[derive(Debug)]
+struct CachedProcesses {
+    fetched: Instant,
+    processes: Rc<Vec<Process>>,
+}
+
+/// git subcommands whose arguments are usually a branch
+const GIT_BRANCH_SUBCOMMANDS: &[&str] = &["checkout", "switch", "merge", "rebase"];
+
+/// Commands whose arguments are process IDs
+const PROCESS_COMMANDS: &[&str] = &["kill", "ps"];
+
+/// How long a process list is reused; pressing Tab repeatedly shouldn't
+/// enumerate every process each time
+const PROCESS_CACHE_TTL: Duration = Duration::from_secs(2);
+
 /// Tab completion handler
 pub struct Completer {
     builtins: Vec<String>,
+    /// Custom completion specs keyed by command name
+    specs: HashMap<String, CompletionSpec>,
+    /// Commands that run the command given as their arguments (`sudo ls`)
+    wrappers: Vec<String>,
+    /// Listing of the cwd (see `invalidate_cwd_cache`)
+    cwd_cache: RefCell<Option<CachedDir>>,
+    /// Branches of the current repository (see `git_branches`)
+    branch_cache: RefCell<Option<CachedBranches>>,
+    /// Running processes (see `processes`)
+    process_cache: RefCell<Option<CachedProcesses>>,
+    /// Where process lists come from (replaced in tests)
+    list_processes: fn() -> Vec<Process>,
+    /// Background jobs as (number, command), kept up to date by the shell
+    jobs: RefCell<Vec<(usize, String)>>,
 }
 
 impl Completer {
     pub fn new() -> Self {
         let builtins = Builtins::new();
-        Self {
+        let mut completer = Self {
             builtins: builtins.list().iter().map(|s| s.to_string()).collect(),
+            specs: HashMap::new(),
+            wrappers: Vec::new(),
+            cwd_cache: RefCell::new(None),
+            branch_cache: RefCell::new(None),
+            process_cache: RefCell::new(None),
+            list_processes: processes::list,
+            jobs: RefCell::new(Vec::new()),
+        };
+
+        for wrapper in ["sudo", "doas", "time", "env", "nohup", "nice"] {
+            completer.register_wrapper(wrapper);
+        }
+
+        completer.register("python", CompletionSpec::extensions(&["py", "pyw"]));
+        completer.register("python3", CompletionSpec::extensions(&["py", "pyw"]));
+        completer.register("node", CompletionSpec::extensions(&["js", "mjs", "cjs"]));
+
+        let icons = ["emoji", "nerd", "none"];
+        completer.register_flag_values("ls", "--icons", &icons);
+        completer.register_flag_values("dir", "--icons", &icons);
+        let color = ["auto", "always", "never"];
+        for command in ["grep", "git", "diff"] {
+            completer.register_flag_values(command, "--color", &color);
+        }
+
+        completer
+    }
+
+    /// Register a completion spec for a command's arguments
+    pub fn register(&mut self, command: &str, spec: CompletionSpec) {
+        self.specs.insert(command.to_string(), spec);
+    }
+
+    /// Register the values completed after `command ... flag=`, keeping the
+    /// rest of any spec already registered for `command`
+    pub fn register_flag_values(&mut self, command: &str, flag: &str, values: &[&str]) {
+        let spec = self.specs.remove(command).unwrap_or_default();
+        self.specs.insert(command.to_string(), spec.flag_values(flag, values));
+    }
+
+    /// Treat `command` as a wrapper whose arguments form another command
+    pub fn register_wrapper(&mut self, command: &str) {
+        if !self.wrappers.iter().any(|w| w == command) {
+            self.wrappers.push(command.to_string());
         }
     }
 
+    /// Index of the word where the command being completed starts
+    ///
+    /// Skips wrappers along with any flags and `NAME=value` assignments
+    /// between them and the command (`sudo -E env FOO=1 ls`).
+    fn command_start(&self, words: &[Word]) -> usize {
+        let mut start = 0;
+        while start + 1 < words.len() && self.wrappers.contains(&words[start].value) {
+            start += 1;
+            while start + 1 < words.len()
+                && (words[start].value.starts_with('-') || words[start].value.contains('='))
+            {
+                start += 1;
+            }
+        }
+        start
+    }
+
     /// Get completions for the given input
     pub fn complete(&self, input: &str, env: &Environment) -> Vec<Completion> {
         let trimmed = input.trim();
@@ -38,27 +231,60 @@ impl Completer {
             return Vec::new();
         }
 
-        // Split into words
-        let words: Vec<&str> = trimmed.split_whitespace().collect();
+        // Split into words (quotes may keep spaces inside a word); only the
+        // command after the last `|`, `&&`, `||` or `;` matters
+        let words = split_words(input);
+        let segment = words.iter().rposition(|w| w.is_separator).map_or(0, |i| i + 1);
+        let words = &words[segment..];
+        let words = &words[self.command_start(words)..];
 
-        if words.is_empty() {
+        let Some(current) = words.last() else {
             return Vec::new();
-        }
+        };
 
-        // Check if we're completing the first word (command) or arguments
-        let is_first_word = words.len() == 1 && !input.ends_with(' ');
+        let after_redirect = words.len() > 1 && words[words.len() - 2].is_redirect;
 
-        if is_first_word {
+        // Check if we're completing the first word (command) or arguments
+        if after_redirect {
+            // Redirect targets are always files, whatever the command
+            self.complete_path(&current.value, env, None)
+        } else if words.len() == 1 && (current.open_quote.is_some() || current.value.contains(['/', '\\'])) {
+            // A quoted or path-like command is a file to run
+            self.complete_path(&current.value, env, None)
+        } else if words.len() == 1 {
             // Complete command name
-            self.complete_command(&words[0], env)
+            self.complete_command(&current.value, env)
+        } else if words.len() == 2 && words[0].value == "help" {
+            // Help topics are builtin names, not files
+            self.complete_help_topic(&current.value)
+        } else if let Some(completions) = self.complete_variable(words, env) {
+            completions
+        } else if let Some(completions) = self.complete_flag_value(&words[0].value, &current.value) {
+            completions
+        } else if let Some(completions) = self.complete_builtin_flag(&words[0].value, &current.value) {
+            completions
+        } else if let Some(completions) = self.complete_process(words) {
+            completions
+        } else if let Some(completions) = self.complete_git_branch(words, env) {
+            completions
         } else {
-            // Complete file path
-            let last_word = if input.ends_with(' ') {
-                ""
-            } else {
-                words.last().unwrap_or(&"")
+            // Complete `~user`, or else a file path
+            let extensions = self
+                .specs
+                .get(&words[0].value)
+                .map(|spec| spec.extensions.as_slice())
+                .filter(|exts| !exts.is_empty());
+            let mut completions = match self.complete_user(&current.value) {
+                Some(completions) => completions,
+                None => self.complete_path(&current.value, env, extensions),
             };
-            self.complete_path(last_word, env)
+            if words.len() == 2 && words[0].value == "cd" {
+                let mut shortcuts = self.complete_cd_shortcut(&current.value, env);
+                shortcuts.append(&mut completions);
+                completions = shortcuts;
+                completions.extend(self.complete_visited_dir(&current.value, env));
+            }
+            completions
         }
     }
 
@@ -73,6 +299,8 @@ impl Completer {
                     text: builtin.clone(),
                     display: format!("{} (builtin)", builtin),
                     is_dir: false,
+                    kind: CompletionKind::Builtin,
+                    meta: None,
                 });
             }
         }
@@ -84,69 +312,253 @@ impl Completer {
                     text: name.clone(),
                     display: format!("{} (alias: {})", name, value),
                     is_dir: false,
+                    kind: CompletionKind::Alias,
+                    meta: None,
                 });
             }
         }
 
         // Add matching commands from PATH
-        if let Some(path_var) = env.get_value("PATH") {
-            for dir in path_var.split(';') {
-                if let Ok(entries) = fs::read_dir(dir) {
-                    for entry in entries.filter_map(|e| e.ok()) {
-                        let name = entry.file_name().to_string_lossy().to_string();
-
-                        // Remove extension for display on Windows
-                        let display_name = name
-                            .strip_suffix(".exe")
-                            .or_else(|| name.strip_suffix(".bat"))
-                            .or_else(|| name.strip_suffix(".cmd"))
-                            .or_else(|| name.strip_suffix(".ps1"))
-                            .unwrap_or(&name);
-
-                        if display_name.to_lowercase().starts_with(&prefix.to_lowercase()) {
-                            // Avoid duplicates
-                            if !completions.iter().any(|c| c.text == display_name) {
-                                completions.push(Completion {
-                                    text: display_name.to_string(),
-                                    display: display_name.to_string(),
-                                    is_dir: false,
-                                });
-                            }
-                        }
+        for dir in env.path_entries() {
+            if let Ok(entries) = fs::read_dir(dir) {
+                for entry in entries.filter_map(|e| e.ok()) {
+                    let name = entry.file_name().to_string_lossy().to_string();
+
+                    // Remove extension for display on Windows
+                    let display_name = name
+                        .strip_suffix(".exe")
+                        .or_else(|| name.strip_suffix(".bat"))
+                        .or_else(|| name.strip_suffix(".cmd"))
+                        .or_else(|| name.strip_suffix(".ps1"))
+                        .unwrap_or(&name);
+
+                    if display_name.to_lowercase().starts_with(&prefix.to_lowercase()) {
+                        completions.push(Completion {
+                            text: display_name.to_string(),
+                            display: display_name.to_string(),
+                            is_dir: false,
+                            kind: CompletionKind::Command,
+                            meta: None,
+                        });
                     }
                 }
             }
         }
 
-        // Sort and deduplicate
+        // Deduplicate (keeping the first, highest-priority entry), then sort
+        dedup_completions(&mut completions, COMMANDS_IGNORE_CASE);
         completions.sort_by(|a, b| a.text.cmp(&b.text));
-        completions.dedup_by(|a, b| a.text == b.text);
 
         completions
     }
 
-    /// Complete a file path
-    fn complete_path(&self, prefix: &str, env: &Environment) -> Vec<Completion> {
+    /// Complete a `help` topic (builtin command names)
+    fn complete_help_topic(&self, prefix: &str) -> Vec<Completion> {
+        let mut completions: Vec<Completion> = self
+            .builtins
+            .iter()
+            .filter(|name| name.starts_with(prefix))
+            .map(|name| Completion {
+                text: name.clone(),
+                display: name.clone(),
+                is_dir: false,
+                kind: CompletionKind::Builtin,
+                meta: None,
+            })
+            .collect();
+
+        completions.sort_by(|a, b| a.text.cmp(&b.text));
+        completions
+    }
+
+    /// Complete the value in `--flag=value` from the command's registered values
+    ///
+    /// Completions keep the `--flag=` part so they replace the whole word.
+    /// Returns `None` when the flag has no registered values.
+    fn complete_flag_value(&self, command: &str, word: &str) -> Option<Vec<Completion>> {
+        let (flag, prefix) = word.split_once('=')?;
+        if !flag.starts_with('-') {
+            return None;
+        }
+        let values = self.specs.get(command)?.flag_values.get(flag)?;
+
+        Some(
+            values
+                .iter()
+                .filter(|value| value.starts_with(prefix))
+                .map(|value| Completion {
+                    text: format!("{}={}", flag, value),
+                    display: value.clone(),
+                    is_dir: false,
+                    kind: CompletionKind::Flag,
+                    meta: None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Complete a flag of a builtin from the options on its help page
+    ///
+    /// Returns `None` unless the word starts with `-` and the builtin
+    /// documents options, so such words still complete as paths.
+    fn complete_builtin_flag(&self, command: &str, word: &str) -> Option<Vec<Completion>> {
+        if !word.starts_with('-') || !self.builtins.iter().any(|b| b == command) {
+            return None;
+        }
+        let flags = help::option_flags(command);
+        if flags.is_empty() {
+            return None;
+        }
+
+        Some(
+            flags
+                .into_iter()
+                .filter(|(flag, _)| flag.starts_with(word))
+                .map(|(flag, description)| Completion {
+                    text: flag.to_string(),
+                    display: format!("{} ({})", flag, description),
+                    is_dir: false,
+                    kind: CompletionKind::Flag,
+                    meta: None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Complete a branch name after `git checkout`, `switch`, `merge` or `rebase`
+    ///
+    /// Returns `None` outside a repository, for other subcommands and for
+    /// flags, and when no branch matches, so paths are completed instead.
+    fn complete_git_branch(&self, words: &[Word], env: &Environment) -> Option<Vec<Completion>> {
+        let prefix = &words[words.len() - 1].value;
+        if words[0].value != "git"
+            || words.len() < 3
+            || !GIT_BRANCH_SUBCOMMANDS.contains(&words[1].value.as_str())
+            || prefix.starts_with('-')
+        {
+            return None;
+        }
+
+        // Like paths, only the part after the last `/` is replaced
+        let dir_len = prefix.rfind('/').map(|i| i + 1).unwrap_or(0);
+        let completions: Vec<Completion> = self
+            .git_branches(env.cwd())?
+            .iter()
+            .filter(|name| name.starts_with(prefix.as_str()))
+            .map(|name| Completion {
+                text: name[dir_len..].to_string(),
+                display: name.clone(),
+                is_dir: false,
+                kind: CompletionKind::Branch,
+                meta: None,
+            })
+            .collect();
+        (!completions.is_empty()).then_some(completions)
+    }
+
+    /// Branch names of the repository containing `cwd`, if any
+    ///
+    /// Cached until `HEAD`, `refs/heads` or `packed-refs` changes. Checking
+    /// out or creating a branch rewrites one of them; a branch created in an
+    /// existing `refs/heads` subfolder without checking it out shows up
+    /// once one of them next changes.
+    fn git_branches(&self, cwd: &Path) -> Option<Rc<Vec<String>>> {
+        let git_dir = git::git_dir(cwd)?;
+        let common = git::common_dir(&git_dir);
+        let modified = |path: PathBuf| fs::metadata(path).and_then(|m| m.modified()).ok();
+        let stamp = [
+            modified(git_dir.join("HEAD")),
+            modified(common.join("refs").join("heads")),
+            modified(common.join("packed-refs")),
+        ];
+
+        if let Some(cached) = self.branch_cache.borrow().as_ref() {
+            if cached.git_dir == git_dir && cached.stamp == stamp {
+                return Some(Rc::clone(&cached.names));
+            }
+        }
+
+        let names = Rc::new(git::branches(&git_dir));
+        *self.branch_cache.borrow_mut() = Some(CachedBranches {
+            git_dir,
+            stamp,
+            names: Rc::clone(&names),
+        });
+        Some(names)
+    }
+
+    /// Complete variable (or option) names for `unset`, `set` and `export`
+    ///
+    /// Returns `None` when the word should be completed as a path instead.
+    fn complete_variable(&self, words: &[Word], env: &Environment) -> Option<Vec<Completion>> {
+        let prefix = &words[words.len() - 1].value;
+        if prefix.contains('=') {
+            return None;
+        }
+
+        let previous = words[words.len() - 2].value.as_str();
+        let mut names: Vec<String> = match (words[0].value.as_str(), previous) {
+            ("set", "-o") | ("set", "+o") => {
+                ShellOptions::NAMES.iter().map(|s| s.to_string()).collect()
+            }
+            ("export", "-n") => env.get_exports().into_keys().collect(),
+            ("unset", _) | ("set", _) | ("export", _) => env.get_all().into_keys().collect(),
+            _ => return None,
+        };
+
+        names.retain(|name| name.starts_with(prefix.as_str()));
+        names.sort();
+        names.dedup();
+
+        Some(
+            names
+                .into_iter()
+                .map(|name| Completion {
+                    display: name.clone(),
+                    text: name,
+                    is_dir: false,
+                    kind: CompletionKind::Variable,
+                    meta: None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Complete a file path, optionally keeping only files with `extensions`
+    ///
+    /// Variables in the directory part (`$HOME/Doc`, `${APPDATA}\Co`) are
+    /// expanded to find the directory; completions only replace the file
+    /// name, so the variable stays as typed.
+    fn complete_path(
+        &self,
+        prefix: &str,
+        env: &Environment,
+        extensions: Option<&[String]>,
+    ) -> Vec<Completion> {
         let mut completions = Vec::new();
 
-        // Handle tilde expansion
-        let (dir, file_prefix) = if prefix.starts_with("~/") {
-            if let Some(home) = env.get_value("HOME") {
-                let expanded = prefix.replacen("~", &home, 1);
-                let path = Path::new(&expanded);
-                if let Some(parent) = path.parent() {
-                    (
-                        parent.to_path_buf(),
-                        path.file_name()
-                            .map(|s| s.to_string_lossy().to_string())
-                            .unwrap_or_default(),
-                    )
-                } else {
-                    (PathBuf::from(&home), String::new())
-                }
+        let expanded;
+        let prefix = match prefix.rfind(['/', '\\']) {
+            Some(i) if prefix[..i].contains('$') => {
+                expanded = format!("{}{}", env.expand_variables(&prefix[..i]), &prefix[i..]);
+                expanded.as_str()
+            }
+            _ => prefix,
+        };
+
+        // Handle tilde expansion: `~/` is $HOME, `~name/` that user's home
+        let tilde = prefix.strip_prefix('~').and_then(|rest| rest.split_once('/'));
+        let (dir, file_prefix) = if let Some((user, rest)) = tilde {
+            let home = if user.is_empty() {
+                env.get_value("HOME").map(PathBuf::from)
             } else {
+                users::home_dir(user)
+            };
+            let Some(home) = home else {
                 return completions;
-            }
+            };
+            let (subdir, file_prefix) = rest.rsplit_once('/').unwrap_or(("", rest));
+            (home.join(subdir), file_prefix.to_string())
         } else if prefix.contains('/') || prefix.contains('\\') {
             let path = Path::new(prefix);
             if let Some(parent) = path.parent() {
@@ -171,25 +583,33 @@ impl Completer {
         };
 
         // Read directory entries
-        if let Ok(entries) = fs::read_dir(&dir) {
-            for entry in entries.filter_map(|e| e.ok()) {
-                let name = entry.file_name().to_string_lossy().to_string();
-
-                if name.to_lowercase().starts_with(&file_prefix.to_lowercase()) {
-                    let is_dir = entry.path().is_dir();
-                    let display = if is_dir {
-                        format!("{}/", name)
-                    } else {
-                        name.clone()
-                    };
-
-                    completions.push(Completion {
-                        text: name,
-                        display,
-                        is_dir,
-                    });
+        for listed in self.list_dir(&dir, env.cwd()).iter() {
+            if !listed.name.to_lowercase().starts_with(&file_prefix.to_lowercase()) {
+                continue;
+            }
+
+            if let (false, Some(extensions)) = (listed.is_dir, extensions) {
+                let matches = Path::new(&listed.name)
+                    .extension()
+                    .map(|ext| extensions.contains(&ext.to_string_lossy().to_lowercase()))
+                    .unwrap_or(false);
+                if !matches {
+                    continue;
                 }
             }
+            let display = if listed.is_dir {
+                format!("{}/", listed.name)
+            } else {
+                listed.name.clone()
+            };
+
+            completions.push(Completion {
+                text: listed.name.clone(),
+                display,
+                is_dir: listed.is_dir,
+                kind: if listed.is_dir { CompletionKind::Directory } else { CompletionKind::File },
+                meta: Some(listed.meta.clone()),
+            });
         }
 
         // Sort: directories first, then alphabetically
@@ -204,6 +624,220 @@ impl Completer {
         completions
     }
 
+    /// Entries of `dir`, reusing the cached listing when `dir` is the cwd
+    ///
+    /// The cache is also dropped when the directory's modification time
+    /// changes, so files created or removed there show up straight away.
+    fn list_dir(&self, dir: &Path, cwd: &Path) -> Rc<Vec<ListedEntry>> {
+        let modified = fs::metadata(dir).and_then(|m| m.modified()).ok();
+        let cacheable = dir == cwd && modified.is_some();
+
+        if cacheable {
+            if let Some(cached) = self.cwd_cache.borrow().as_ref() {
+                if cached.dir == dir && cached.modified == modified {
+                    return Rc::clone(&cached.entries);
+                }
+            }
+        }
+
+        let mut entries = Vec::new();
+        if let Ok(read) = fs::read_dir(dir) {
+            for entry in read.filter_map(|e| e.ok()) {
+                let path = entry.path();
+                let is_dir = path.is_dir();
+                let is_link = entry.file_type().map(|t| t.is_symlink()).unwrap_or(false);
+                let meta = CompletionMeta {
+                    size: fs::metadata(&path).ok().filter(|m| m.is_file()).map(|m| m.len()),
+                    kind: if is_link {
+                        "link"
+                    } else if is_dir {
+                        "dir"
+                    } else {
+                        "file"
+                    },
+                };
+                entries.push(ListedEntry {
+                    name: entry.file_name().to_string_lossy().to_string(),
+                    is_dir,
+                    meta,
+                });
+            }
+        }
+
+        let entries = Rc::new(entries);
+        if cacheable {
+            *self.cwd_cache.borrow_mut() = Some(CachedDir {
+                dir: dir.to_path_buf(),
+                modified,
+                entries: Rc::clone(&entries),
+            });
+        }
+        entries
+    }
+
+    /// Complete a PID for `kill` and `ps`, or a job spec for `kill %`
+    ///
+    /// A numeric word matches the start of PIDs and any other word the
+    /// start of process names; either way the PID is inserted, with the
+    /// name shown beside it. Signal options (`-9`) are left alone.
+    fn complete_process(&self, words: &[Word]) -> Option<Vec<Completion>> {
+        let command = words[0].value.as_str();
+        let word = words.last()?.value.as_str();
+        if !PROCESS_COMMANDS.contains(&command) || word.starts_with('-') {
+            return None;
+        }
+
+        let entry = |text: String, display: String| Completion {
+            text,
+            display,
+            is_dir: false,
+            kind: CompletionKind::Process,
+            meta: None,
+        };
+
+        if let Some(number) = word.strip_prefix('%') {
+            if command != "kill" {
+                return None;
+            }
+            let jobs = self.jobs.borrow();
+            let completions = jobs
+                .iter()
+                .filter(|(id, _)| id.to_string().starts_with(number))
+                .map(|(id, job)| entry(format!("%{}", id), format!("%{}  {}", id, job)))
+                .collect();
+            return Some(completions);
+        }
+
+        let by_pid = word.chars().all(|c| c.is_ascii_digit());
+        let name_prefix = word.to_lowercase();
+        let completions = self
+            .processes()
+            .iter()
+            .filter(|process| {
+                if by_pid {
+                    process.pid.to_string().starts_with(word)
+                } else {
+                    process.name.to_lowercase().starts_with(&name_prefix)
+                }
+            })
+            .map(|process| {
+                entry(process.pid.to_string(), format!("{}  {}", process.pid, process.name))
+            })
+            .collect();
+        Some(completions)
+    }
+
+    /// Running processes, enumerated at most every `PROCESS_CACHE_TTL`
+    fn processes(&self) -> Rc<Vec<Process>> {
+        let mut cache = self.process_cache.borrow_mut();
+        match cache.as_ref() {
+            Some(cached) if cached.fetched.elapsed() < PROCESS_CACHE_TTL => {
+                Rc::clone(&cached.processes)
+            }
+            _ => {
+                let processes = Rc::new((self.list_processes)());
+                *cache = Some(CachedProcesses {
+                    fetched: Instant::now(),
+                    processes: Rc::clone(&processes),
+                });
+                processes
+            }
+        }
+    }
+
+    /// Replace the background jobs offered for `kill %`
+    pub fn set_jobs(&self, jobs: Vec<(usize, String)>) {
+        *self.jobs.borrow_mut() = jobs;
+    }
+
+    /// Forget the cached listing of the working directory
+    ///
+    /// Called after the shell changes directory, so completion never
+    /// offers the previous directory's files.
+    pub fn invalidate_cwd_cache(&self) {
+        self.cwd_cache.borrow_mut().take();
+    }
+
+    /// Users for a `~name` word (without a `/` yet), completing to `~name/`
+    fn complete_user(&self, prefix: &str) -> Option<Vec<Completion>> {
+        let name = prefix.strip_prefix('~').filter(|name| !name.contains(['/', '\\']))?;
+        let completions = users::list()
+            .into_iter()
+            .filter(|user| user.name.starts_with(name))
+            .map(|user| Completion {
+                text: format!("~{}", user.name),
+                display: format!("~{}/", user.name),
+                is_dir: true,
+                kind: CompletionKind::Directory,
+                meta: None,
+            })
+            .collect();
+        Some(completions)
+    }
+
+    /// Navigation shortcuts for `cd`: `..`, `-` (when `OLDPWD` is set) and `~`
+    ///
+    /// Only offered for an empty word or one they start with, so `cd .`
+    /// suggests `..` while `cd src` completes normally.
+    fn complete_cd_shortcut(&self, prefix: &str, env: &Environment) -> Vec<Completion> {
+        let mut shortcuts = Vec::new();
+        if env.cwd().parent().is_some() {
+            shortcuts.push(("..", "../".to_string(), true));
+        }
+        if let Some(oldpwd) = env.get_value("OLDPWD") {
+            shortcuts.push(("-", format!("-  ({})", oldpwd), false));
+        }
+        if env.get_value("HOME").is_some() {
+            shortcuts.push(("~", "~/".to_string(), true));
+        }
+
+        shortcuts
+            .into_iter()
+            .filter(|(text, _, _)| text.starts_with(prefix))
+            .map(|(text, display, is_dir)| Completion {
+                text: text.to_string(),
+                display,
+                is_dir,
+                kind: CompletionKind::Directory,
+                meta: None,
+            })
+            .collect()
+    }
+
+    /// Recently visited directories for `cd`, most recent first
+    ///
+    /// Offered by name like `z`: a word without a path separator matches
+    /// the start of a directory's last component and completes to its full
+    /// path, and an empty word lists them all. Directories inside the cwd
+    /// are already offered by name.
+    fn complete_visited_dir(&self, prefix: &str, env: &Environment) -> Vec<Completion> {
+        if prefix.contains(['/', '\\']) {
+            return Vec::new();
+        }
+        let prefix = prefix.to_lowercase();
+
+        env.visited_dirs()
+            .iter()
+            .filter(|dir| *dir != env.cwd() && dir.parent() != Some(env.cwd().as_path()))
+            .filter(|dir| {
+                dir.file_name()
+                    .map(|name| name.to_string_lossy().to_lowercase().starts_with(&prefix))
+                    .unwrap_or(false)
+            })
+            .filter(|dir| dir.is_dir())
+            .map(|dir| {
+                let text = dir.to_string_lossy().to_string();
+                Completion {
+                    display: format!("{}/", text),
+                    text,
+                    is_dir: true,
+                    kind: CompletionKind::Directory,
+                    meta: Some(CompletionMeta { size: None, kind: "dir" }),
+                }
+            })
+            .collect()
+    }
+
     /// Get the common prefix of all completions
     pub fn common_prefix(completions: &[Completion]) -> String {
         if completions.is_empty() {
@@ -230,6 +864,259 @@ impl Completer {
     }
 }
 
+/// Whether command names are case-insensitive (`git` and `Git.exe` are
+/// the same program on Windows)
+const COMMANDS_IGNORE_CASE: bool = cfg!(windows);
+
+/// Remove repeated candidates, keeping the first occurrence of each
+fn dedup_completions(completions: &mut Vec<Completion>, ignore_case: bool) {
+    let mut seen = HashSet::new();
+    completions.retain(|c| {
+        let key = if ignore_case {
+            c.text.to_lowercase()
+        } else {
+            c.text.clone()
+        };
+        seen.insert(key)
+    });
+}
+
+/// Most candidates kept from one completion unless `CSH_COMPLETION_LIMIT`
+/// says otherwise
+pub const DEFAULT_COMPLETION_LIMIT: usize = 500;
+
+/// The cap on candidates from `CSH_COMPLETION_LIMIT` (0 for none)
+pub fn completion_limit(env: &Environment) -> usize {
+    env.get_value("CSH_COMPLETION_LIMIT")
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(DEFAULT_COMPLETION_LIMIT)
+}
+
+/// Keep the first `limit` candidates (all of them for 0), returning how
+/// many were dropped
+pub fn truncate_completions(completions: &mut Vec<Completion>, limit: usize) -> usize {
+    if limit == 0 || completions.len() <= limit {
+        return 0;
+    }
+    let omitted = completions.len() - limit;
+    completions.truncate(limit);
+    omitted
+}
+
+/// Lay out candidates for display below the prompt
+///
+/// Normally a grid of names fitted to `width` columns. With `verbose`,
+/// one candidate per line with its size and type where known. `omitted`
+/// candidates that were cut off are counted on a last line.
+pub fn format_listing(
+    completions: &[Completion],
+    omitted: usize,
+    verbose: bool,
+    width: usize,
+) -> String {
+    let name_width = completions
+        .iter()
+        .map(|c| UnicodeWidthStr::width(c.display.as_str()))
+        .max()
+        .unwrap_or(0);
+    let pad = |c: &Completion| " ".repeat(name_width - UnicodeWidthStr::width(c.display.as_str()));
+
+    let mut output = String::new();
+    if verbose {
+        for completion in completions {
+            match &completion.meta {
+                Some(meta) => {
+                    let size = meta.size.map(format_size).unwrap_or_default();
+                    output.push_str(&format!(
+                        "{}{}  {:>7}  {}\n",
+                        completion.display,
+                        pad(completion),
+                        size,
+                        meta.kind
+                    ));
+                }
+                None => {
+                    output.push_str(&completion.display);
+                    output.push('\n');
+                }
+            }
+        }
+    } else {
+        let columns = (width / (name_width + 2)).max(1);
+        for row in completions.chunks(columns) {
+            let line: Vec<String> = row
+                .iter()
+                .map(|c| format!("{}{}", c.display, pad(c)))
+                .collect();
+            output.push_str(line.join("  ").trim_end());
+            output.push('\n');
+        }
+    }
+
+    if omitted > 0 {
+        output.push_str(&format!("... and {} more\n", omitted));
+    }
+    output
+}
+
+/// A word on the command line, as seen by the completer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    /// Byte offset of the word's first character in the input
+    pub start: usize,
+    /// The word with quotes and escaped spaces removed
+    pub value: String,
+    /// Quote character still open at the end of the input
+    pub open_quote: Option<char>,
+    /// An unquoted redirection operator (`>`, `>>`, `2>`, `<`, ...)
+    pub is_redirect: bool,
+    /// An unquoted operator starting a new command (`|`, `&&`, `||`, `;`)
+    pub is_separator: bool,
+}
+
+/// Split a command line into words, respecting quotes and `\ `
+///
+/// Unquoted redirection operators and command separators are words of
+/// their own, even when written without spaces (`echo hi>out`). If the
+/// input ends with unquoted whitespace or an operator, an empty word is
+/// appended so the caller sees that a new argument has begun. Other
+/// backslashes are kept literally since they are path separators on
+/// Windows.
+pub fn split_words(input: &str) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut current: Option<Word> = None;
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let in_quote = current.as_ref().and_then(|w| w.open_quote);
+
+        if in_quote.is_none() && c.is_whitespace() {
+            if let Some(word) = current.take() {
+                words.push(word);
+            }
+            continue;
+        }
+
+        if in_quote.is_none() && (c == '>' || c == '<') {
+            // A lone fd number or `&` right before it belongs to the operator
+            let mut operator = Word {
+                start: i,
+                value: String::new(),
+                open_quote: None,
+                is_redirect: true,
+                is_separator: false,
+            };
+            if let Some(word) = current.take() {
+                if matches!(word.value.as_str(), "1" | "2" | "&") && word.start + word.value.len() == i {
+                    operator.start = word.start;
+                    operator.value = word.value;
+                } else {
+                    words.push(word);
+                }
+            }
+            operator.value.push(c);
+            while let Some((_, next)) = chars.next_if(|(_, n)| c == '>' && (*n == '>' || *n == '|')) {
+                operator.value.push(next);
+            }
+            words.push(operator);
+            continue;
+        }
+
+        let is_and = c == '&' && chars.peek().is_some_and(|(_, n)| *n == '&');
+        if in_quote.is_none() && (c == '|' || c == ';' || is_and) {
+            if let Some(word) = current.take() {
+                words.push(word);
+            }
+            let mut operator = c.to_string();
+            if let Some((_, next)) = chars.next_if(|(_, n)| c != ';' && *n == c) {
+                operator.push(next);
+            }
+            words.push(Word {
+                start: i,
+                value: operator,
+                open_quote: None,
+                is_redirect: false,
+                is_separator: true,
+            });
+            continue;
+        }
+
+        let word = current.get_or_insert_with(|| Word {
+            start: i,
+            value: String::new(),
+            open_quote: None,
+            is_redirect: false,
+            is_separator: false,
+        });
+
+        match (in_quote, c) {
+            (Some(q), c) if c == q => word.open_quote = None,
+            (None, '"') | (None, '\'') => word.open_quote = Some(c),
+            (None, '\\') if chars.peek().map(|(_, n)| *n == ' ').unwrap_or(false) => {
+                word.value.push(' ');
+                chars.next();
+            }
+            _ => word.value.push(c),
+        }
+    }
+
+    match current {
+        Some(word) => words.push(word),
+        None if !words.is_empty() => words.push(Word {
+            start: input.len(),
+            value: String::new(),
+            open_quote: None,
+            is_redirect: false,
+            is_separator: false,
+        }),
+        None => {}
+    }
+
+    words
+}
+
+/// Replace the word being completed in `input` with `text`
+///
+/// `text` replaces the file name part of the word; any directory prefix
+/// already typed is kept. Directories get a trailing `/`. The word is
+/// quoted if it needs to be (or was already); `finished` closes the
+/// quote, except for directories so completion can continue inside them.
+/// `$` and braces in the typed prefix are variables and don't need quoting.
+pub fn insert_completion(input: &str, text: &str, is_dir: bool, finished: bool) -> String {
+    let Some(word) = split_words(input).pop() else {
+        return input.to_string();
+    };
+
+    let dir_len = word.value.rfind(['/', '\\']).map(|i| i + 1).unwrap_or(0);
+    let mut value = format!("{}{}", &word.value[..dir_len], text);
+    if is_dir && !value.ends_with('/') && !value.ends_with('\\') {
+        value.push('/');
+    }
+
+    let special = |c: char| c.is_whitespace() || "|&;<>()'\"#".contains(c);
+    let needs_quotes = value[..dir_len].chars().any(special)
+        || text.chars().any(|c| special(c) || "${}".contains(c));
+    let quote = match word.open_quote {
+        Some(q) => Some(q),
+        None if needs_quotes && (value.contains('"') || text.contains('$')) => Some('\''),
+        None if needs_quotes => Some('"'),
+        None => None,
+    };
+
+    let mut result = input[..word.start].to_string();
+    match quote {
+        Some(q) => {
+            result.push(q);
+            result.push_str(&value);
+            if finished && !is_dir {
+                result.push(q);
+            }
+        }
+        None => result.push_str(&value),
+    }
+    result
+}
+
 impl Default for Completer {
     fn default() -> Self {
         Self::new()
@@ -247,14 +1134,553 @@ mod tests {
                 text: "hello".to_string(),
                 display: "hello".to_string(),
                 is_dir: false,
+                kind: CompletionKind::Builtin,
+                meta: None,
             },
             Completion {
                 text: "help".to_string(),
                 display: "help".to_string(),
                 is_dir: false,
+                kind: CompletionKind::Builtin,
+                meta: None,
             },
         ];
 
         assert_eq!(Completer::common_prefix(&completions), "hel");
     }
+
+    #[test]
+    fn test_each_source_sets_its_kind() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("zzdir")).unwrap();
+        fs::write(dir.path().join("zzfile.txt"), "").unwrap();
+        fs::write(dir.path().join("zzcmd"), "").unwrap();
+        let mut env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        env.set("PATH", &dir.path().to_string_lossy());
+        env.set_alias("zzalias", "ls");
+        env.set("ZZVAR", "1");
+        let completer = Completer::new();
+        let kinds = |input: &str| -> Vec<(String, CompletionKind)> {
+            completer.complete(input, &env).into_iter().map(|c| (c.text, c.kind)).collect()
+        };
+        let one = |text: &str, kind| vec![(text.to_string(), kind)];
+
+        assert_eq!(kinds("ech"), one("echo", CompletionKind::Builtin));
+        assert_eq!(kinds("help ech"), one("echo", CompletionKind::Builtin));
+        assert_eq!(kinds("zzal"), one("zzalias", CompletionKind::Alias));
+        assert_eq!(kinds("zzc"), one("zzcmd", CompletionKind::Command));
+        assert_eq!(kinds("cat zzf"), one("zzfile.txt", CompletionKind::File));
+        assert_eq!(kinds("cat zzd"), one("zzdir", CompletionKind::Directory));
+        assert_eq!(kinds("unset ZZV"), one("ZZVAR", CompletionKind::Variable));
+        assert_eq!(kinds("ls --icons=ne"), one("--icons=nerd", CompletionKind::Flag));
+
+        let json = serde_json::to_value(&completer.complete("cat zzd", &env)[0]).unwrap();
+        assert_eq!(json["kind"], "directory");
+        assert_eq!(json["isDir"], true);
+    }
+
+    #[test]
+    fn test_verbose_listing_shows_sizes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("big.log"), vec![b'x'; 2048]).unwrap();
+        fs::write(dir.path().join("bin.log"), "abc").unwrap();
+        fs::create_dir(dir.path().join("build")).unwrap();
+
+        let env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        let completions = Completer::new().complete("cat b", &env);
+
+        let verbose = format_listing(&completions, 0, true, 80);
+        assert!(verbose.contains("build/  "));
+        assert!(verbose.contains("   2.0K  file\n"));
+        assert!(verbose.contains("     3B  file\n"));
+        assert!(verbose.lines().any(|l| l.starts_with("build/") && l.ends_with("dir")));
+
+        let plain = format_listing(&completions, 0, false, 80);
+        assert_eq!(plain, "build/   big.log  bin.log\n");
+
+        // Metadata never leaks into the inserted text
+        assert!(completions.iter().all(|c| !c.text.contains(' ')));
+    }
+
+    #[test]
+    fn test_large_listings_are_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..600 {
+            fs::write(dir.path().join(format!("file{:03}.txt", i)), "").unwrap();
+        }
+        fs::write(dir.path().join("other.txt"), "").unwrap();
+
+        let mut env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        let mut completions = Completer::new().complete("cat f", &env);
+        assert_eq!(completions.len(), 600);
+        // The prefix comes from the full set, before it is cut down
+        let common = Completer::common_prefix(&completions);
+        assert_eq!(truncate_completions(&mut completions, completion_limit(&env)), 100);
+        assert_eq!(completions.len(), DEFAULT_COMPLETION_LIMIT);
+        assert_eq!(common, "file");
+        assert_eq!(completions.last().unwrap().text, "file499.txt");
+
+        let listing = format_listing(&completions, 100, false, 80);
+        assert!(listing.ends_with("file499.txt\n... and 100 more\n"), "{}", listing);
+        let listing = format_listing(&completions[..2], 5, true, 80);
+        assert!(listing.ends_with("... and 5 more\n"));
+
+        env.set("CSH_COMPLETION_LIMIT", "0");
+        let mut completions = Completer::new().complete("cat f", &env);
+        assert_eq!(truncate_completions(&mut completions, completion_limit(&env)), 0);
+        assert_eq!(completions.len(), 600);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_case_variants_merged_on_windows() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        fs::write(first.path().join("zzgit.exe"), "").unwrap();
+        fs::write(second.path().join("ZZGit.exe"), "").unwrap();
+
+        let mut env = Environment::new();
+        let path = format!("{};{}", first.path().display(), second.path().display());
+        env.set("PATH", &path);
+
+        let texts: Vec<String> = Completer::new()
+            .complete("zzg", &env)
+            .into_iter()
+            .map(|c| c.text)
+            .collect();
+        assert_eq!(texts, vec!["zzgit"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_case_variants_kept_on_unix() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("zztool"), "").unwrap();
+        // Default macOS volumes ignore case, so there is only one file
+        if dir.path().join("ZZtool").exists() {
+            return;
+        }
+        fs::write(dir.path().join("ZZtool"), "").unwrap();
+
+        let mut env = Environment::new();
+        env.set("PATH", &dir.path().to_string_lossy());
+
+        let texts: Vec<String> = Completer::new()
+            .complete("zz", &env)
+            .into_iter()
+            .map(|c| c.text)
+            .collect();
+        assert_eq!(texts, vec!["ZZtool", "zztool"]);
+    }
+
+    #[test]
+    fn test_split_words_respects_quotes() {
+        let words = split_words("cd \"My Doc");
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[1].value, "My Doc");
+        assert_eq!(words[1].start, 3);
+        assert_eq!(words[1].open_quote, Some('"'));
+
+        assert_eq!(split_words("cat My\\ File")[1].value, "My File");
+        assert_eq!(split_words("ls ").last().unwrap().value, "");
+    }
+
+    #[test]
+    fn test_redirect_targets_and_open_quotes_complete_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("out file.txt"), "").unwrap();
+        fs::write(dir.path().join("output.log"), "").unwrap();
+        let env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+
+        let words = split_words("echo hi 2>>ou");
+        let values: Vec<&str> = words.iter().map(|w| w.value.as_str()).collect();
+        assert_eq!(values, vec!["echo", "hi", "2>>", "ou"]);
+        assert!(words[2].is_redirect);
+
+        let mut completer = Completer::new();
+        // Even a command restricted to other extensions gets every file
+        completer.register("runpy", CompletionSpec::extensions(&["py"]));
+        let texts = |input: &str| -> Vec<String> {
+            completer.complete(input, &env).into_iter().map(|c| c.text).collect()
+        };
+        assert_eq!(texts("runpy x > ou"), vec!["out file.txt", "output.log"]);
+        assert_eq!(texts("echo hi>ou"), vec!["out file.txt", "output.log"]);
+        assert_eq!(texts("help >"), vec!["out file.txt", "output.log"]);
+        assert_eq!(insert_completion("echo hi>ou", "output.log", false, true), "echo hi>output.log");
+        assert_eq!(insert_completion("echo hi >", "output.log", false, true), "echo hi >output.log");
+
+        // Inside an open quote the quote isn't part of the name
+        assert_eq!(texts("cat \"out f"), vec!["out file.txt"]);
+        assert_eq!(texts("echo > 'out"), vec!["out file.txt", "output.log"]);
+        assert_eq!(
+            insert_completion("echo > 'out", "out file.txt", false, true),
+            "echo > 'out file.txt'"
+        );
+        // `>` inside quotes is just a character
+        assert_eq!(split_words("echo 'a>b").len(), 2);
+    }
+
+    #[test]
+    fn test_command_completion_after_pipes_and_operators() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("echo-notes.txt"), "").unwrap();
+        let env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        let completer = Completer::new();
+        let texts = |input: &str| -> Vec<String> {
+            completer.complete(input, &env).into_iter().map(|c| c.text).collect()
+        };
+
+        let words = split_words("ls|ech");
+        let values: Vec<&str> = words.iter().map(|w| w.value.as_str()).collect();
+        assert_eq!(values, vec!["ls", "|", "ech"]);
+        assert!(words[1].is_separator);
+
+        for input in ["ls | ech", "ls|ech", "cd x && ech", "false || ech", "pwd; ech"] {
+            assert_eq!(texts(input), vec!["echo"], "{}", input);
+        }
+        assert_eq!(insert_completion("ls|ech", "echo", false, false), "ls|echo");
+        // Arguments after the new command are still files
+        assert_eq!(texts("ls | echo ech"), vec!["echo-notes.txt"]);
+        // Quoted operators don't start a command
+        assert_eq!(texts("echo 'a | ech"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_complete_directory_with_space() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("My Documents")).unwrap();
+        fs::write(dir.path().join("My Notes.txt"), "").unwrap();
+
+        let env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        let completer = Completer::new();
+
+        let completions = completer.complete("cd \"My Doc", &env);
+        assert_eq!(completions.len(), 1);
+        assert_eq!(completions[0].text, "My Documents");
+
+        let line = insert_completion("cd \"My Doc", &completions[0].text, true, true);
+        assert_eq!(line, "cd \"My Documents/");
+
+        // Unquoted input gets quoted; finished files close the quote
+        let line = insert_completion("cat My\\ N", "My Notes.txt", false, true);
+        assert_eq!(line, "cat \"My Notes.txt\"");
+        let line = insert_completion("cat sub/M", "My ", false, false);
+        assert_eq!(line, "cat \"sub/My ");
+        assert_eq!(insert_completion("cat ma", "main.rs", false, true), "cat main.rs");
+    }
+
+    #[test]
+    fn test_variable_in_directory_part() {
+        let home = tempfile::tempdir().unwrap();
+        fs::create_dir(home.path().join("Documents")).unwrap();
+        fs::write(home.path().join("Documents").join("notes.txt"), "").unwrap();
+
+        let mut env = Environment::with_vars(std::env::temp_dir(), std::env::vars());
+        env.set("HOME", &home.path().to_string_lossy());
+        let completer = Completer::new();
+        let texts = |input: &str| -> Vec<String> {
+            completer.complete(input, &env).into_iter().map(|c| c.text).collect()
+        };
+
+        assert_eq!(texts("ls $HOME/Doc"), vec!["Documents"]);
+        assert_eq!(texts("cat ${HOME}/Documents/no"), vec!["notes.txt"]);
+        assert_eq!(insert_completion("ls $HOME/Doc", "Documents", true, true), "ls $HOME/Documents/");
+        assert_eq!(
+            insert_completion("cat ${HOME}/Documents/n", "notes.txt", false, true),
+            "cat ${HOME}/Documents/notes.txt"
+        );
+    }
+
+    #[test]
+    fn test_git_branches_after_branch_subcommands() {
+        let repo = tempfile::tempdir().unwrap();
+        let git_dir = repo.path().join(".git");
+        fs::create_dir_all(git_dir.join("refs").join("heads").join("feature")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(git_dir.join("refs").join("heads").join("main"), "").unwrap();
+        fs::write(git_dir.join("refs").join("heads").join("feature").join("login"), "").unwrap();
+        fs::write(
+            git_dir.join("packed-refs"),
+            "# pack-refs with: peeled\nabc123 refs/heads/old-release\nfff000 refs/tags/v1\n^abc123\n",
+        )
+        .unwrap();
+        fs::create_dir(repo.path().join("src")).unwrap();
+        fs::write(repo.path().join("src").join("main.rs"), "").unwrap();
+
+        let env = Environment::with_vars(repo.path().join("src"), std::env::vars());
+        let completer = Completer::new();
+        let texts = |input: &str| -> Vec<String> {
+            completer.complete(input, &env).into_iter().map(|c| c.text).collect()
+        };
+
+        assert_eq!(texts("git checkout "), vec!["feature/login", "main", "old-release"]);
+        assert_eq!(texts("git switch o"), vec!["old-release"]);
+        assert_eq!(texts("git rebase feature/l"), vec!["login"]);
+        assert_eq!(
+            insert_completion("git rebase feature/l", "login", false, true),
+            "git rebase feature/login"
+        );
+
+        // Other subcommands, flags and non-branches complete paths
+        assert_eq!(texts("git add m"), vec!["main.rs"]);
+        assert_eq!(texts("git checkout ../src/m"), vec!["main.rs"]);
+        assert_eq!(texts("git merge x"), Vec::<String>::new());
+        let outside = Environment::with_vars(std::env::temp_dir(), std::env::vars());
+        assert!(completer.complete_git_branch(&split_words("git checkout "), &outside).is_none());
+    }
+
+    #[test]
+    fn test_help_completes_builtins() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("cargo.toml"), "").unwrap();
+        let env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        let completer = Completer::new();
+
+        let texts: Vec<String> = completer
+            .complete("help ca", &env)
+            .into_iter()
+            .map(|c| c.text)
+            .collect();
+        assert_eq!(texts, vec!["cat"]);
+
+        // Only the first argument is a topic
+        assert_eq!(completer.complete("help cat ca", &env)[0].text, "cargo.toml");
+    }
+
+    #[test]
+    fn test_variable_names_for_unset() {
+        let mut env = Environment::new();
+        env.set("CSH_COMPLETE_ALPHA", "1");
+        env.set("CSH_COMPLETE_BETA", "2");
+        let completer = Completer::new();
+
+        let texts: Vec<String> = completer
+            .complete("unset CSH_COMPLETE_", &env)
+            .into_iter()
+            .map(|c| c.text)
+            .collect();
+        assert_eq!(texts, vec!["CSH_COMPLETE_ALPHA", "CSH_COMPLETE_BETA"]);
+
+        // `set -o` offers option names instead
+        let texts: Vec<String> = completer
+            .complete("set -o noc", &env)
+            .into_iter()
+            .map(|c| c.text)
+            .collect();
+        assert_eq!(texts, vec!["noclobber"]);
+    }
+
+    #[test]
+    fn test_wrapper_shifts_command_position() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("log.txt"), "").unwrap();
+        let env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        let completer = Completer::new();
+
+        // The word after `sudo` is a command
+        let completions = completer.complete("sudo l", &env);
+        assert!(completions.iter().any(|c| c.text == "ls"));
+        assert!(!completions.iter().any(|c| c.text == "log.txt"));
+
+        // Arguments of the inner command are files
+        let texts: Vec<String> = completer
+            .complete("sudo ls ", &env)
+            .into_iter()
+            .map(|c| c.text)
+            .collect();
+        assert_eq!(texts, vec!["log.txt"]);
+
+        // Flags and assignments before the command are skipped
+        let completions = completer.complete("env -i FOO=1 l", &env);
+        assert!(completions.iter().any(|c| c.text == "ls"));
+    }
+
+    #[test]
+    fn test_extension_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("script.py"), "").unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+
+        let env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+
+        let mut completer = Completer::new();
+        completer.register("runpy", CompletionSpec::extensions(&["PY"]));
+
+        let texts: Vec<String> = completer
+            .complete("runpy ", &env)
+            .into_iter()
+            .map(|c| c.text)
+            .collect();
+        assert_eq!(texts, vec!["src", "script.py"]);
+
+        // Commands without a spec still see every file
+        assert_eq!(completer.complete("cat ", &env).len(), 3);
+    }
+
+    #[test]
+    fn test_flag_value_completion() {
+        let env = Environment::new();
+        let mut completer = Completer::new();
+        completer.register("mytool", CompletionSpec::extensions(&["txt"]));
+        completer.register_flag_values("mytool", "--color", &["auto", "always", "never"]);
+
+        let texts = |input: &str| -> Vec<String> {
+            completer.complete(input, &env).into_iter().map(|c| c.text).collect()
+        };
+        assert_eq!(texts("mytool --color="), vec!["--color=auto", "--color=always", "--color=never"]);
+        assert_eq!(texts("mytool -v --color=al"), vec!["--color=always"]);
+        assert_eq!(insert_completion("mytool --color=al", "--color=always", false, true), "mytool --color=always");
+
+        // The earlier spec survives, and unregistered flags aren't values
+        assert_eq!(completer.specs["mytool"].extensions, vec!["txt"]);
+        assert!(completer.complete_flag_value("mytool", "--width=").is_none());
+        assert!(completer.complete_flag_value("cat", "--color=").is_none());
+    }
+
+    #[test]
+    fn test_builtin_flags_from_help() {
+        let env = Environment::new();
+        let completer = Completer::new();
+        let texts = |input: &str| -> Vec<String> {
+            completer.complete(input, &env).into_iter().map(|c| c.text).collect()
+        };
+
+        let ls = texts("ls -");
+        for flag in ["-a", "-l", "--tree", "--icons", "--icons=", "--max-depth"] {
+            assert!(ls.contains(&flag.to_string()), "{} missing from {:?}", flag, ls);
+        }
+        assert_eq!(texts("dir --tr"), vec!["--tree"]);
+        assert_eq!(texts("cat --"), vec!["--color", "--keep-bom"]);
+        assert_eq!(texts("echo -"), vec!["-n", "-e", "-E"]);
+        assert!(completer.complete("cat --", &env)[0].display.contains("Syntax highlight"));
+
+        // Builtins without documented options, and other commands, complete paths
+        assert!(completer.complete_builtin_flag("cd", "-").is_none());
+        assert!(completer.complete_builtin_flag("git", "-").is_none());
+        assert!(completer.complete_builtin_flag("ls", "a").is_none());
+    }
+
+    #[test]
+    fn test_cd_offers_visited_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let alpha = dir.path().join("work").join("alpha");
+        let home = dir.path().join("home");
+        fs::create_dir_all(&alpha).unwrap();
+        fs::create_dir_all(home.join("almanac")).unwrap();
+
+        let mut env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        for target in [&alpha, &home] {
+            let args = [target.to_string_lossy().to_string()];
+            assert!(crate::csh::builtins::cd::execute(&args, &mut env).status.is_success());
+        }
+
+        // The local directory comes first, then the visited one by full path
+        let completions = Completer::new().complete("cd al", &env);
+        let texts: Vec<&str> = completions.iter().map(|c| c.text.as_str()).collect();
+        let visited = env.visited_dirs()[1].to_string_lossy().to_string();
+        assert_eq!(texts, vec!["almanac", visited.as_str()]);
+        assert!(visited.ends_with("alpha"));
+
+        // Other commands don't get them
+        assert_eq!(Completer::new().complete("ls al", &env).len(), 1);
+    }
+
+    #[test]
+    fn test_kill_completes_pids_names_and_jobs() {
+        fn fake_processes() -> Vec<Process> {
+            CALLS.with(|calls| calls.set(calls.get() + 1));
+            vec![
+                Process { pid: 1, name: "init".to_string() },
+                Process { pid: 120, name: "Firefox".to_string() },
+                Process { pid: 1234, name: "sshd".to_string() },
+            ]
+        }
+        thread_local! {
+            static CALLS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+        }
+
+        let env = Environment::with_vars(std::env::temp_dir(), std::env::vars());
+        let mut completer = Completer::new();
+        completer.list_processes = fake_processes;
+        completer.set_jobs(vec![(1, "sleep 100".to_string()), (2, "make".to_string())]);
+        let complete = |input: &str| -> Vec<(String, String)> {
+            completer.complete(input, &env).into_iter().map(|c| (c.text, c.display)).collect()
+        };
+        let pair = |text: &str, display: &str| (text.to_string(), display.to_string());
+
+        let twelve = vec![pair("120", "120  Firefox"), pair("1234", "1234  sshd")];
+        assert_eq!(complete("kill 12"), twelve);
+        assert_eq!(complete("kill -9 fire"), vec![pair("120", "120  Firefox")]);
+        assert_eq!(complete("ps ss"), vec![pair("1234", "1234  sshd")]);
+        assert_eq!(complete("kill ").len(), 3);
+        assert_eq!(complete("kill %"), vec![pair("%1", "%1  sleep 100"), pair("%2", "%2  make")]);
+        assert_eq!(complete("kill %2"), vec![pair("%2", "%2  make")]);
+        let signal = "-s (Signal to send, by name or number (default TERM))";
+        assert_eq!(complete("kill -"), vec![pair("-s", signal)]);
+
+        // The process list was read once for all of those
+        assert_eq!(CALLS.with(|calls| calls.get()), 1);
+        assert_eq!(completer.complete("kill 1", &env)[0].kind, CompletionKind::Process);
+    }
+
+    #[test]
+    fn test_cd_offers_navigation_shortcuts() {
+        let dir = tempfile::tempdir().unwrap();
+        let work = dir.path().join("work");
+        fs::create_dir_all(work.join("src")).unwrap();
+        fs::create_dir_all(dir.path().join("elsewhere")).unwrap();
+
+        let mut env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        env.set("HOME", &dir.path().to_string_lossy());
+        for target in [dir.path().join("elsewhere"), work] {
+            let args = [target.to_string_lossy().to_string()];
+            assert!(crate::csh::builtins::cd::execute(&args, &mut env).status.is_success());
+        }
+        assert!(env.get_value("OLDPWD").is_some());
+
+        let completer = Completer::new();
+        let texts = |input: &str| -> Vec<String> {
+            completer.complete(input, &env).into_iter().map(|c| c.text).collect()
+        };
+        let previous = env.visited_dirs()[1].to_string_lossy().to_string();
+        assert_eq!(texts("cd "), vec!["..", "-", "~", "src", previous.as_str()]);
+        assert_eq!(texts("cd ."), vec![".."]);
+        assert_eq!(texts("cd -"), vec!["-"]);
+        // `~` comes before the users `~name` also offers
+        assert_eq!(texts("cd ~")[0], "~");
+        assert_eq!(texts("cd s"), vec!["src"]);
+
+        let line = insert_completion("cd ~", "~", true, true);
+        assert_eq!(line, "cd ~/");
+        assert!(completer.complete("ls ", &env).iter().all(|c| c.text != ".."));
+    }
+
+    #[test]
+    fn test_tilde_completes_users() {
+        // Any account will do; the current user may not be in the list
+        let Some(user) = users::list().into_iter().next() else {
+            return;
+        };
+        let (me, home) = (user.name, user.home);
+        let mut env = Environment::new();
+        env.set("HOME", &home.to_string_lossy());
+
+        let completer = Completer::new();
+        let completions = completer.complete("ls ~", &env);
+        assert!(completions.iter().any(|c| c.text == format!("~{}", me)));
+        assert!(completions.iter().all(|c| c.is_dir && c.text.starts_with('~')));
+
+        let partial = format!("ls ~{}", &me[..me.len() - 1]);
+        let completions = completer.complete(&partial, &env);
+        let mine = completions.iter().find(|c| c.text == format!("~{}", me)).unwrap();
+        assert_eq!(insert_completion(&partial, &mine.text, true, true), format!("ls ~{}/", me));
+
+        // After the slash, files in that user's home
+        let texts = |input: &str| -> Vec<String> {
+            completer.complete(input, &env).into_iter().map(|c| c.text).collect()
+        };
+        assert_eq!(texts(&format!("ls ~{}/", me)), texts("ls ~/"));
+        assert!(texts("ls ~no-such-user/").is_empty());
+    }
 }
@@ -1,9 +1,17 @@
 //! Environment variable management for CSH
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::path::PathBuf;
 
+use crate::csh::ast::posix_exit_code;
+use crate::csh::lexer;
+use crate::csh::shell::VERSION;
+use crate::csh::users;
+
+/// Most directories remembered for `cd` completion
+pub const MAX_VISITED_DIRS: usize = 100;
+
 /// Manages environment variables for the shell
 #[derive(Debug, Clone)]
 pub struct Environment {
@@ -11,6 +19,10 @@ pub struct Environment {
     local_vars: HashMap<String, String>,
     /// Exported variables (inherited by child processes)
     exported_vars: HashMap<String, String>,
+    /// Names marked for export that have no value yet (`export VAR`)
+    exported_unset: HashSet<String>,
+    /// Exported names taken unchanged from the process environment
+    inherited: HashSet<String>,
     /// Aliases
     aliases: HashMap<String, String>,
     /// Current working directory
@@ -19,29 +31,61 @@ pub struct Environment {
     last_exit_code: i32,
     /// Shell PID
     shell_pid: u32,
+    /// Directories changed into with `cd`, most recent first
+    visited_dirs: Vec<PathBuf>,
+    /// Positional parameters `$0`, `$1`, ...
+    positional: Vec<String>,
+    /// Keep `cd` and `export` from changing the process (see `with_vars`)
+    detached: bool,
 }
 
 impl Environment {
     pub fn new() -> Self {
         let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        Self::build(cwd, env::vars(), false)
+    }
+
+    /// Environment with its own working directory and variables
+    ///
+    /// `cd`, `export` and `unset` only change this environment, never the
+    /// process's directory or variables, so several shells can share one
+    /// process (as the tests do).
+    pub fn with_vars(cwd: PathBuf, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self::build(cwd, vars, true)
+    }
+
+    fn build(
+        cwd: PathBuf,
+        vars: impl IntoIterator<Item = (String, String)>,
+        detached: bool,
+    ) -> Self {
         let shell_pid = std::process::id();
 
         let mut env = Self {
             local_vars: HashMap::new(),
             exported_vars: HashMap::new(),
+            exported_unset: HashSet::new(),
+            inherited: HashSet::new(),
             aliases: HashMap::new(),
             cwd,
             last_exit_code: 0,
             shell_pid,
+            visited_dirs: Vec::new(),
+            positional: Vec::new(),
+            detached,
         };
 
-        // Initialize with system environment
-        for (key, value) in env::vars() {
+        // Initialize with the given (usually the system) environment
+        for (key, value) in vars {
+            env.inherited.insert(key.clone());
             env.exported_vars.insert(key, value);
         }
 
         // Set shell-specific variables
         env.set_special_vars();
+        for name in ["PWD", "HOME", "USER", "HOSTNAME"] {
+            env.inherited.remove(name);
+        }
 
         env
     }
@@ -51,7 +95,7 @@ impl Environment {
         self.local_vars
             .insert("SHELL_NAME".to_string(), "csh".to_string());
         self.local_vars
-            .insert("SHELL_VERSION".to_string(), "0.1.0".to_string());
+            .insert("SHELL_VERSION".to_string(), VERSION.to_string());
 
         // Current directory
         if let Some(cwd_str) = self.cwd.to_str() {
@@ -87,9 +131,13 @@ impl Environment {
     /// Get a variable value as owned string
     pub fn get_value(&self, name: &str) -> Option<String> {
         match name {
-            "?" => Some(self.last_exit_code.to_string()),
+            "?" => Some(posix_exit_code(self.last_exit_code).to_string()),
             "$" => Some(self.shell_pid.to_string()),
+            "#" => Some(self.positional.len().saturating_sub(1).to_string()),
             "PWD" => self.cwd.to_str().map(|s| s.to_string()),
+            _ if !name.is_empty() && name.bytes().all(|b| b.is_ascii_digit()) => {
+                name.parse().ok().and_then(|n: usize| self.positional.get(n)).cloned()
+            }
             _ => self
                 .local_vars
                 .get(name)
@@ -98,30 +146,62 @@ impl Environment {
         }
     }
 
-    /// Set a local variable
+    /// Set a local variable (exported instead if it was marked for export)
     pub fn set(&mut self, name: &str, value: &str) {
+        if self.exported_unset.contains(name) {
+            self.export(name, Some(value));
+            return;
+        }
         self.local_vars.insert(name.to_string(), value.to_string());
     }
 
     /// Export a variable (make it available to child processes)
+    ///
+    /// Without a value, an existing variable keeps its value; a name with
+    /// no value at all is only marked for export until it is assigned.
     pub fn export(&mut self, name: &str, value: Option<&str>) {
-        let val = value
+        let val = match value
             .map(|v| v.to_string())
             .or_else(|| self.local_vars.get(name).cloned())
-            .unwrap_or_default();
+            .or_else(|| self.exported_vars.get(name).cloned())
+        {
+            Some(val) => val,
+            None => {
+                self.exported_unset.insert(name.to_string());
+                return;
+            }
+        };
 
+        self.exported_unset.remove(name);
+        self.inherited.remove(name);
         self.exported_vars.insert(name.to_string(), val.clone());
-        env::set_var(name, &val);
+        if !self.detached {
+            env::set_var(name, &val);
+        }
 
         // Remove from local vars since it's now exported
         self.local_vars.remove(name);
     }
 
+    /// Stop exporting a variable, keeping it as a shell-local variable
+    pub fn unexport(&mut self, name: &str) {
+        self.exported_unset.remove(name);
+        if let Some(value) = self.exported_vars.remove(name) {
+            self.local_vars.insert(name.to_string(), value);
+            if !self.detached {
+                env::remove_var(name);
+            }
+        }
+    }
+
     /// Unset a variable
     pub fn unset(&mut self, name: &str) {
         self.local_vars.remove(name);
+        self.exported_unset.remove(name);
         self.exported_vars.remove(name);
-        env::remove_var(name);
+        if !self.detached {
+            env::remove_var(name);
+        }
     }
 
     /// Get all exported variables for child processes
@@ -129,6 +209,11 @@ impl Environment {
         self.exported_vars.clone()
     }
 
+    /// Get names marked for export that have no value yet
+    pub fn get_exported_unset(&self) -> Vec<String> {
+        self.exported_unset.iter().cloned().collect()
+    }
+
     /// Get all variables (local + exported)
     pub fn get_all(&self) -> HashMap<String, String> {
         let mut all = self.exported_vars.clone();
@@ -138,12 +223,37 @@ impl Environment {
         all
     }
 
+    /// Variables the shell itself set: its own special variables, shell
+    /// variables and exports, but not those inherited unchanged from the
+    /// process environment (`env --local`)
+    pub fn get_local(&self) -> HashMap<String, String> {
+        let mut local: HashMap<String, String> = self
+            .exported_vars
+            .iter()
+            .filter(|(name, _)| !self.inherited.contains(*name))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        for (k, v) in &self.local_vars {
+            local.insert(k.clone(), v.clone());
+        }
+        local
+    }
+
+    /// The directories in `PATH`, split on the platform separator (`:`, or
+    /// `;` on Windows), without empty entries
+    pub fn path_entries(&self) -> Vec<PathBuf> {
+        let value = self.get_value("PATH").unwrap_or_default();
+        env::split_paths(&value)
+            .filter(|entry| !entry.as_os_str().is_empty())
+            .collect()
+    }
+
     /// Set the last exit code
     pub fn set_last_exit_code(&mut self, code: i32) {
         self.last_exit_code = code;
     }
 
-    /// Get the last exit code
+    /// Get the last exit code, untruncated (`$?` gives the 0-255 form)
     pub fn last_exit_code(&self) -> i32 {
         self.last_exit_code
     }
@@ -155,19 +265,67 @@ impl Environment {
 
     /// Set current working directory
     pub fn set_cwd(&mut self, path: PathBuf) -> std::io::Result<()> {
-        env::set_current_dir(&path)?;
+        if !self.detached {
+            env::set_current_dir(&path)?;
+        } else if !path.is_dir() {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "not a directory"));
+        }
         self.cwd = path;
 
         // Update PWD
         if let Some(cwd_str) = self.cwd.to_str() {
             self.exported_vars
                 .insert("PWD".to_string(), cwd_str.to_string());
-            env::set_var("PWD", cwd_str);
+            if !self.detached {
+                env::set_var("PWD", cwd_str);
+            }
         }
 
         Ok(())
     }
 
+    /// Remember `dir` as the most recently visited directory
+    pub fn record_visited_dir(&mut self, dir: PathBuf) {
+        self.visited_dirs.retain(|d| *d != dir);
+        self.visited_dirs.insert(0, dir);
+        self.visited_dirs.truncate(MAX_VISITED_DIRS);
+    }
+
+    /// Directories changed into with `cd`, most recent first
+    pub fn visited_dirs(&self) -> &[PathBuf] {
+        &self.visited_dirs
+    }
+
+    /// Replace the visited directories (e.g. with those saved last session)
+    pub fn set_visited_dirs(&mut self, mut dirs: Vec<PathBuf>) {
+        dirs.truncate(MAX_VISITED_DIRS);
+        self.visited_dirs = dirs;
+    }
+
+    /// Set the positional parameters, starting with `$0`
+    pub fn set_positional(&mut self, params: Vec<String>) {
+        self.positional = params;
+    }
+
+    /// Set the positional parameters, returning the previous ones
+    pub fn replace_positional(&mut self, params: Vec<String>) -> Vec<String> {
+        std::mem::replace(&mut self.positional, params)
+    }
+
+    /// Drop `$1`..`$count`, keeping `$0`
+    ///
+    /// Returns false, leaving the parameters alone, if there are fewer
+    /// than `count`.
+    pub fn shift_positional(&mut self, count: usize) -> bool {
+        if count > self.positional.len().saturating_sub(1) {
+            return false;
+        }
+        if count > 0 {
+            self.positional.drain(1..=count);
+        }
+        true
+    }
+
     /// Add an alias
     pub fn set_alias(&mut self, name: &str, value: &str) {
         self.aliases.insert(name.to_string(), value.to_string());
@@ -189,35 +347,51 @@ impl Environment {
     }
 
     /// Expand variables in a string
+    ///
+    /// Besides `$VAR` and `${VAR}` this handles `${VAR:-word}`,
+    /// `${VAR:=word}`, `${VAR:+word}` (and their colon-less forms, which
+    /// treat an empty value as set), `${#VAR}` and `${VAR:offset:length}`.
+    /// `${VAR:=word}` only substitutes here; see `expand_assigning`.
     pub fn expand_variables(&self, input: &str) -> String {
+        self.expand_collecting(input, &mut Vec::new())
+    }
+
+    /// Expand variables like `expand_variables`, also making the
+    /// assignments `${VAR:=word}` asks for
+    pub fn expand_assigning(&mut self, input: &str) -> String {
+        let mut assigned = Vec::new();
+        let result = self.expand_collecting(input, &mut assigned);
+        for (name, value) in assigned {
+            self.set(&name, &value);
+        }
+        result
+    }
+
+    /// Expand variables, adding `${VAR:=word}` assignments to `assigned`
+    fn expand_collecting(&self, input: &str, assigned: &mut Vec<(String, String)>) -> String {
         let mut result = String::new();
         let mut chars = input.chars().peekable();
 
         while let Some(c) = chars.next() {
             if c == '$' {
                 if chars.peek() == Some(&'{') {
-                    // ${VAR} syntax
+                    // ${VAR} syntax, where the word of `${VAR:-word}` may
+                    // hold more braces; an unclosed one runs to the end
                     chars.next(); // consume '{'
-                    let mut var_name = String::new();
-                    while let Some(&ch) = chars.peek() {
-                        if ch == '}' {
-                            chars.next();
-                            break;
-                        }
-                        var_name.push(ch);
+                    let rest: String = chars.clone().collect();
+                    let inner = lexer::parameter_body(&rest).unwrap_or(&rest);
+                    for _ in 0..=inner.chars().count() {
                         chars.next();
                     }
-                    if let Some(value) = self.get_value(&var_name) {
-                        result.push_str(&value);
-                    }
-                } else if chars.peek().map(|c| c.is_alphabetic() || *c == '_' || *c == '?') == Some(true) {
+                    result.push_str(&self.expand_parameter(inner, assigned));
+                } else if chars.peek().is_some_and(|c| c.is_alphabetic() || "_?#".contains(*c)) {
                     // $VAR syntax
                     let mut var_name = String::new();
                     while let Some(&ch) = chars.peek() {
                         if ch.is_alphanumeric() || ch == '_' {
                             var_name.push(ch);
                             chars.next();
-                        } else if ch == '?' && var_name.is_empty() {
+                        } else if (ch == '?' || ch == '#') && var_name.is_empty() {
                             var_name.push(ch);
                             chars.next();
                             break;
@@ -225,18 +399,30 @@ impl Environment {
                             break;
                         }
                     }
-                    if let Some(value) = self.get_value(&var_name) {
+                    if let Some(value) = self.lookup(&var_name, assigned) {
                         result.push_str(&value);
                     }
                 } else {
                     result.push(c);
                 }
             } else if c == '~' && result.is_empty() {
-                // Tilde expansion at start
-                if let Some(home) = self.get_value("HOME") {
-                    result.push_str(&home);
+                // Tilde expansion at start: `~` is $HOME, `~name` that
+                // user's home; an unknown name is left alone
+                let name: String =
+                    chars.clone().take_while(|ch| !matches!(ch, '/' | '\\')).collect();
+                let home = if name.is_empty() {
+                    self.get_value("HOME")
                 } else {
-                    result.push(c);
+                    users::home_dir(&name).map(|home| home.to_string_lossy().to_string())
+                };
+                match home {
+                    Some(home) => {
+                        result.push_str(&home);
+                        for _ in name.chars() {
+                            chars.next();
+                        }
+                    }
+                    None => result.push(c),
                 }
             } else {
                 result.push(c);
@@ -246,9 +432,104 @@ impl Environment {
         result
     }
 
+    /// Expand the inside of a `${...}`
+    fn expand_parameter(&self, inner: &str, assigned: &mut Vec<(String, String)>) -> String {
+        match self.resolve_parameter(inner, assigned) {
+            Parameter::Value(value) => value,
+            Parameter::Word(word) => self.expand_collecting(word, assigned),
+            Parameter::Assign(name, word) => {
+                let word = self.expand_collecting(word, assigned);
+                assigned.push((name.to_string(), word.clone()));
+                word
+            }
+        }
+    }
+
+    /// What the inside of a `${...}` expands to, leaving its word (if it
+    /// is used) for the caller to expand, as when it runs commands
+    pub fn parameter<'a>(&self, inner: &'a str) -> Parameter<'a> {
+        self.resolve_parameter(inner, &[])
+    }
+
+    fn resolve_parameter<'a>(
+        &self,
+        inner: &'a str,
+        assigned: &[(String, String)],
+    ) -> Parameter<'a> {
+        // `${#VAR}` is the value's length (`${#}` alone is the parameter count)
+        if let Some(name) = inner.strip_prefix('#').filter(|name| !name.is_empty()) {
+            let value = self.lookup(name, assigned);
+            return Parameter::Value(value.map_or(0, |value| value.chars().count()).to_string());
+        }
+
+        let name_len = match inner.chars().next() {
+            Some('?' | '#' | '$') => 1,
+            _ => inner
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(inner.len()),
+        };
+        let (name, operation) = inner.split_at(name_len);
+        let value = self.lookup(name, assigned);
+
+        // With a colon, an empty value counts as unset
+        let (colon, operation) = match operation.strip_prefix(':') {
+            Some(operation) => (true, operation),
+            None => (false, operation),
+        };
+        let missing = value.as_deref().is_none_or(|value| colon && value.is_empty());
+
+        let mut op = operation.chars();
+        match (op.next(), op.as_str()) {
+            (None, _) if !colon => Parameter::Value(value.unwrap_or_default()),
+            (Some('-'), word) if missing => Parameter::Word(word),
+            (Some('='), word) if missing => Parameter::Assign(name, word),
+            (Some('+'), word) if !missing => Parameter::Word(word),
+            (Some('+'), _) => Parameter::Value(String::new()),
+            (Some('-' | '='), _) => Parameter::Value(value.unwrap_or_default()),
+            _ if colon => Parameter::Value(substring(&value.unwrap_or_default(), operation)),
+            // Not a form we know
+            _ => Parameter::Value(String::new()),
+        }
+    }
+
+    /// Look up a variable, seeing assignments made earlier in the same
+    /// expansion
+    fn lookup(&self, name: &str, assigned: &[(String, String)]) -> Option<String> {
+        match assigned.iter().rev().find(|(assigned, _)| assigned == name) {
+            Some((_, value)) => Some(value.clone()),
+            None => self.get_value(name),
+        }
+    }
+
     /// Expand an alias if it exists
+    ///
+    /// Aliases whose first word is another alias are expanded in turn
+    /// (`alias ll='ls -l'` then `alias l=ll` gives `ls -l` for `l`). A
+    /// name is never expanded twice, so `alias ls='ls -F'` and cycles stop.
     pub fn expand_alias(&self, command: &str) -> Option<String> {
-        self.aliases.get(command).cloned()
+        let mut expansion = self.aliases.get(command)?.clone();
+        let mut seen = vec![command.to_string()];
+
+        loop {
+            let (first, rest) = match expansion.split_once(char::is_whitespace) {
+                Some((first, rest)) => (first, rest),
+                None => (expansion.as_str(), ""),
+            };
+            if seen.iter().any(|name| name == first) {
+                break;
+            }
+            let Some(next) = self.aliases.get(first) else {
+                break;
+            };
+            seen.push(first.to_string());
+            expansion = if rest.is_empty() {
+                next.clone()
+            } else {
+                format!("{} {}", next, rest)
+            };
+        }
+
+        Some(expansion)
     }
 }
 
@@ -258,6 +539,52 @@ impl Default for Environment {
     }
 }
 
+/// What a `${...}` expands to (see `Environment::parameter`)
+#[derive(Debug, PartialEq)]
+pub enum Parameter<'a> {
+    /// A value, used as it is
+    Value(String),
+    /// A word to expand (`${VAR:-word}` with `VAR` unset, say)
+    Word(&'a str),
+    /// A word to expand and assign to a variable (`${VAR:=word}`)
+    Assign(&'a str, &'a str),
+}
+
+/// `${VAR:offset:length}` of `value`
+///
+/// A negative offset counts from the end (written `${VAR: -2}` or
+/// `${VAR:(-2)}`, as `:-` means something else), and a negative length
+/// leaves that many characters off the end.
+fn substring(value: &str, spec: &str) -> String {
+    let parse = |number: &str| {
+        let number = number.trim();
+        let number = number.strip_prefix('(').and_then(|n| n.strip_suffix(')')).unwrap_or(number);
+        number.trim().parse::<i64>().ok()
+    };
+    let chars: Vec<char> = value.chars().collect();
+    let len = chars.len() as i64;
+
+    let (offset, length) = match spec.split_once(':') {
+        Some((offset, length)) => (offset, Some(length)),
+        None => (spec, None),
+    };
+    let start = match parse(offset) {
+        Some(offset) if offset < 0 => len + offset,
+        Some(offset) => offset.min(len),
+        None => return String::new(),
+    };
+    let end = match length.map(parse) {
+        None => len,
+        Some(None) => return String::new(),
+        Some(Some(length)) if length < 0 => len + length,
+        Some(Some(length)) => start.saturating_add(length).min(len),
+    };
+    if start < 0 || end < start {
+        return String::new();
+    }
+    chars[start as usize..end as usize].iter().collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -277,6 +604,20 @@ mod tests {
         assert!(env.exported_vars.contains_key("FOO"));
     }
 
+    #[test]
+    fn test_path_entries() {
+        let mut env = Environment::new();
+        #[cfg(unix)]
+        let (path, expected) = ("/a:/b", ["/a", "/b"]);
+        #[cfg(windows)]
+        let (path, expected) = (r"C:\a;C:\b", [r"C:\a", r"C:\b"]);
+        env.set("PATH", path);
+        assert_eq!(env.path_entries(), expected.map(PathBuf::from));
+
+        env.set("PATH", "");
+        assert!(env.path_entries().is_empty());
+    }
+
     #[test]
     fn test_expand_variables() {
         let mut env = Environment::new();
@@ -293,10 +634,115 @@ mod tests {
         assert_eq!(result, "Hello World!");
     }
 
+    #[test]
+    fn test_parameter_expansion_forms() {
+        let mut env = Environment::new();
+        env.set("EMPTY", "");
+        env.set("NAME", "World");
+
+        // Colon forms treat empty like unset; the others only unset
+        let output = env.expand_variables("${UNSET:-dflt} ${EMPTY:-dflt} ${NAME:-dflt}");
+        assert_eq!(output, "dflt dflt World");
+        assert_eq!(env.expand_variables("[${UNSET-dflt}] [${EMPTY-dflt}]"), "[dflt] []");
+        let output = env.expand_variables("[${UNSET:+alt}] [${EMPTY:+alt}] [${NAME:+alt}]");
+        assert_eq!(output, "[] [] [alt]");
+        assert_eq!(env.expand_variables("[${UNSET+alt}] [${EMPTY+alt}]"), "[] [alt]");
+        // The word is expanded itself, only when used
+        assert_eq!(env.expand_variables("${UNSET:-Hello ${NAME}}"), "Hello World");
+        assert_eq!(env.parameter("NAME:-$(echo x)"), Parameter::Value("World".to_string()));
+        assert_eq!(env.parameter("UNSET:-$(echo x)"), Parameter::Word("$(echo x)"));
+        assert_eq!(env.parameter("UNSET:=$(echo x)"), Parameter::Assign("UNSET", "$(echo x)"));
+
+        // `:=` assigns, and later references see the new value
+        assert_eq!(env.expand_variables("${UNSET:=first}"), "first");
+        assert_eq!(env.get_value("UNSET"), None);
+        assert_eq!(env.expand_assigning("${UNSET:=first} ${UNSET}"), "first first");
+        assert_eq!(env.expand_assigning("${UNSET:=second} ${EMPTY=kept}"), "first ");
+        assert_eq!(env.get_value("UNSET").as_deref(), Some("first"));
+        assert_eq!(env.get_value("EMPTY").as_deref(), Some(""));
+
+        assert_eq!(env.expand_variables("${#NAME} ${#EMPTY} ${#UNSET2}"), "5 0 0");
+
+        env.set("WORD", "abcdef");
+        assert_eq!(env.expand_variables("${WORD:2} ${WORD:1:3} ${WORD:4:10}"), "cdef bcd ef");
+        assert_eq!(env.expand_variables("${WORD: -2} ${WORD:(-4):2} ${WORD:1:-2}"), "ef cd bcd");
+        assert_eq!(env.expand_variables("[${WORD:10}] [${WORD: -10}] [${UNSET2:1}]"), "[] [] []");
+    }
+
+    #[test]
+    fn test_detached_environment_leaves_the_process_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let vars = [("CSH_TEST_GIVEN".to_string(), "given".to_string())];
+        let mut env = Environment::with_vars(dir.path().to_path_buf(), vars);
+        assert_eq!(env.get_value("CSH_TEST_GIVEN").as_deref(), Some("given"));
+        assert_eq!(env.get_value("PWD"), dir.path().to_str().map(String::from));
+        assert_eq!(env.get_value("PATH"), None);
+
+        env.export("CSH_TEST_DETACHED", Some("1"));
+        env.set_cwd(std::env::temp_dir()).unwrap();
+        assert!(env.set_cwd(dir.path().join("missing")).is_err());
+        assert_eq!(env.cwd(), &std::env::temp_dir());
+        assert!(std::env::var_os("CSH_TEST_DETACHED").is_none());
+        assert_ne!(std::env::current_dir().unwrap(), std::env::temp_dir());
+    }
+
+    #[test]
+    fn test_tilde_expansion() {
+        let mut env = Environment::new();
+        env.set("HOME", "/home/dir");
+
+        assert_eq!(env.expand_variables("~/notes"), "/home/dir/notes");
+        if let Some(user) = users::list().into_iter().next() {
+            let home = user.home.to_string_lossy();
+            let expanded = env.expand_variables(&format!("~{}/notes", user.name));
+            assert_eq!(expanded, format!("{}/notes", home));
+        }
+        assert_eq!(env.expand_variables("~no-such-user/x"), "~no-such-user/x");
+        assert_eq!(env.expand_variables("a~"), "a~");
+    }
+
+    #[test]
+    fn test_large_exit_codes() {
+        use crate::csh::ast::ExitStatus;
+
+        // STATUS_ACCESS_VIOLATION as returned by a Windows process
+        let access_violation = 0xC000_0005_u32 as i32;
+        let status = ExitStatus::failure(access_violation);
+        assert!(!status.is_success());
+        assert_eq!(status.posix_code(), 5);
+
+        // Low byte of zero must not look like success
+        assert_eq!(ExitStatus::failure(256).posix_code(), 1);
+        assert_eq!(ExitStatus::failure(-1).posix_code(), 255);
+        assert_eq!(ExitStatus::failure(42).posix_code(), 42);
+
+        let mut env = Environment::new();
+        env.set_last_exit_code(access_violation);
+        assert_eq!(env.get_value("?").as_deref(), Some("5"));
+        assert_eq!(env.expand_variables("status $?"), "status 5");
+        assert_eq!(env.last_exit_code(), access_violation);
+    }
+
     #[test]
     fn test_alias() {
         let mut env = Environment::new();
         env.set_alias("ll", "ls -la");
         assert_eq!(env.get_alias("ll"), Some(&"ls -la".to_string()));
     }
+
+    #[test]
+    fn test_alias_chains_expand_fully() {
+        let mut env = Environment::new();
+        env.set_alias("ls", "ls --icons");
+        env.set_alias("ll", "ls -l");
+        env.set_alias("lt", "ll --tree");
+        assert_eq!(env.expand_alias("lt").as_deref(), Some("ls --icons -l --tree"));
+        assert_eq!(env.expand_alias("ls").as_deref(), Some("ls --icons"));
+        assert_eq!(env.expand_alias("cat"), None);
+
+        // A cycle stops at the first name seen again
+        env.set_alias("a", "b 1");
+        env.set_alias("b", "a 2");
+        assert_eq!(env.expand_alias("a").as_deref(), Some("a 2 1"));
+    }
 }
@@ -3,14 +3,29 @@
 //! Handles execution of parsed commands, including built-in commands,
 //! external processes, pipes, and redirections.
 
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command as ProcessCommand, Stdio};
+use std::sync::atomic::Ordering;
 
-use crate::csh::ast::{Command, CommandLine, ExitStatus, LogicalOp, Pipeline, RedirectType};
-use crate::csh::builtins::Builtins;
-use crate::csh::environment::Environment;
+use crate::csh::arithmetic;
+use crate::csh::ast::{
+    Command, CommandLine, ExitStatus, LogicalOp, Pipeline, Quoting, RedirectType, Statement,
+};
+use crate::csh::braces;
+use crate::csh::builtins::timeout::{self, Outcome, TimeLimit, Watchdog};
+use crate::csh::builtins::{kill, wait, yes, BuiltinResult, Builtins, OutputContext};
+use crate::csh::environment::{Environment, Parameter};
 use crate::csh::history::History;
+use crate::csh::interrupt;
+use crate::csh::jobs::JobTable;
+use crate::csh::lexer;
+use crate::csh::options::ShellOptions;
+use crate::csh::parser;
+use crate::csh::suggest;
+use crate::csh::wildcard;
 
 /// Command executor
 pub struct Executor {
@@ -20,21 +35,69 @@ pub struct Executor {
     pub history: History,
     /// Built-in commands handler
     builtins: Builtins,
-    /// Output buffer for captured output
-    output_buffer: Vec<u8>,
-    /// Whether to capture output instead of printing
-    capture_output: bool,
+    /// Where command output goes (stdout, capture buffer, or callback)
+    sink: OutputSink,
+    /// Options toggled with `set -o` / `set +o`
+    pub options: ShellOptions,
+    /// Set once the `exit` builtin has run
+    pub exit_requested: bool,
+    /// Commands started with `&`
+    pub jobs: JobTable,
+    /// Limit for the next external command, set by `timeout`
+    time_limit: Option<TimeLimit>,
+    /// Number of loops currently running
+    loop_depth: usize,
+    /// Set by `break`, `continue` or `return` until the innermost loop or
+    /// function handles it
+    flow_control: Option<FlowControl>,
+    /// Functions defined with `name() { ... }`
+    functions: HashMap<String, Vec<Statement>>,
+    /// Number of function calls currently running
+    function_depth: usize,
+    /// Input for commands without a `<` of their own, while a function
+    /// reads from a file or a pipe
+    stdin: Option<File>,
+    /// Number of conditions running (`if`, `while`, and the operands of
+    /// `&&`/`||` but the last); failures in them don't trigger `errexit`
+    condition_depth: usize,
 }
 
+/// How `break`, `continue` and `return` leave the current loop iteration
+/// or function
+///
+/// `Break` and `Continue` hold the number of loops they still apply to
+/// (`break 2` leaves the innermost loop and the one around it).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FlowControl {
+    Break(usize),
+    Continue(usize),
+    Return,
+}
+
+/// Calls nested deeper than this fail, rather than overflow the stack
+const MAX_FUNCTION_DEPTH: usize = 100;
+
 impl Executor {
     pub fn new() -> Self {
         let history_path = History::get_default_path();
+        let history = History::with_file(10000, history_path);
+        let mut env = Environment::new();
+        env.set_visited_dirs(history.load_dirs());
         Self {
-            env: Environment::new(),
-            history: History::with_file(10000, history_path),
+            env,
+            history,
             builtins: Builtins::new(),
-            output_buffer: Vec::new(),
-            capture_output: false,
+            sink: OutputSink::Stdout,
+            options: ShellOptions::new(),
+            exit_requested: false,
+            jobs: JobTable::new(),
+            time_limit: None,
+            loop_depth: 0,
+            flow_control: None,
+            functions: HashMap::new(),
+            function_depth: 0,
+            stdin: None,
+            condition_depth: 0,
         }
     }
 
@@ -43,6 +106,7 @@ impl Executor {
         if cmd_line.is_empty() {
             return ExitStatus::success();
         }
+        self.jobs.poll();
 
         let mut last_status = ExitStatus::success();
         let mut skip_next = false;
@@ -75,11 +139,277 @@ impl Executor {
 
             last_status = self.execute_pipeline(pipeline);
             self.env.set_last_exit_code(last_status.code);
+            let tested = matches!(cmd_line.operators.get(i), Some(LogicalOp::And | LogicalOp::Or));
+            if !tested {
+                self.check_errexit(&last_status);
+            }
+            if self.exit_requested || self.flow_control.is_some() {
+                break;
+            }
         }
 
         last_status
     }
 
+    /// Execute statements in order, stopping early once `exit`, `break`,
+    /// `continue` or `return` has run
+    pub fn execute_statements(&mut self, statements: &[Statement]) -> ExitStatus {
+        let mut last_status = ExitStatus::success();
+        for statement in statements {
+            last_status = self.execute_statement(statement);
+            if self.exit_requested || self.flow_control.is_some() {
+                break;
+            }
+        }
+        last_status
+    }
+
+    /// Execute a single statement, leaving its status in `$?`
+    pub fn execute_statement(&mut self, statement: &Statement) -> ExitStatus {
+        let status = self.run_statement(statement);
+        self.env.set_last_exit_code(status.code);
+        status
+    }
+
+    fn run_statement(&mut self, statement: &Statement) -> ExitStatus {
+        match statement {
+            Statement::CommandLine(cmd_line) => self.execute(cmd_line),
+            Statement::If {
+                condition,
+                then_branch,
+                elif_branches,
+                else_branch,
+            } => {
+                // The first condition that succeeds picks the branch; if none
+                // does and there's no `else`, the statement succeeds
+                let branches = std::iter::once((condition, then_branch))
+                    .chain(elif_branches.iter().map(|(condition, body)| (condition, body)));
+                for (condition, body) in branches {
+                    let status = self.tested(|this| this.execute(condition));
+                    if self.exit_requested {
+                        return status;
+                    }
+                    if status.is_success() {
+                        return self.execute_statements(body);
+                    }
+                }
+                match else_branch {
+                    Some(body) => self.execute_statements(body),
+                    None => ExitStatus::success(),
+                }
+            }
+            Statement::For {
+                variable,
+                items,
+                quoted_items,
+                body,
+            } => {
+                let values = self.expand_words(items, quoted_items, true);
+                let mut status = ExitStatus::success();
+                self.enter_loop();
+                for value in values {
+                    self.env.set(variable, &value);
+                    status = self.execute_statements(body);
+                    if !self.next_iteration(&mut status) {
+                        break;
+                    }
+                }
+                self.loop_depth -= 1;
+                status
+            }
+            Statement::While { condition, body } => {
+                // Runs until the condition fails, `break` or Ctrl+C; the
+                // status is the body's last, or 0 if it never ran
+                let mut status = ExitStatus::success();
+                self.enter_loop();
+                loop {
+                    let tested = self.tested(|this| this.execute(condition));
+                    if self.exit_requested {
+                        status = tested;
+                        break;
+                    }
+                    if !tested.is_success() {
+                        break;
+                    }
+                    status = self.execute_statements(body);
+                    if !self.next_iteration(&mut status) {
+                        break;
+                    }
+                }
+                self.loop_depth -= 1;
+                status
+            }
+            Statement::Function { name, body } => {
+                self.functions.insert(name.clone(), body.clone());
+                ExitStatus::success()
+            }
+            Statement::AndOr { first, rest } => {
+                // Operands before `&&` or `||` are tested, as conditions are
+                let operators: Vec<_> = rest.iter().map(|(operator, _)| operator).collect();
+                let tested = |i: usize| {
+                    matches!(operators.get(i), Some(LogicalOp::And | LogicalOp::Or))
+                };
+                let mut status = if tested(0) {
+                    self.tested(|this| this.execute_statement(first))
+                } else {
+                    self.execute_statement(first)
+                };
+                for (i, (operator, statement)) in rest.iter().enumerate() {
+                    if self.exit_requested || self.flow_control.is_some() {
+                        break;
+                    }
+                    let run = match operator {
+                        LogicalOp::And => status.is_success(),
+                        LogicalOp::Or => !status.is_success(),
+                        LogicalOp::Sequence => true,
+                    };
+                    if run {
+                        status = if tested(i + 1) {
+                            self.tested(|this| this.execute_statement(statement))
+                        } else {
+                            self.execute_statement(statement)
+                        };
+                    }
+                }
+                status
+            }
+            Statement::Return(code) => self.leave_function(*code),
+            Statement::Break => self.leave_iteration(FlowControl::Break(1)),
+            Statement::Continue => self.leave_iteration(FlowControl::Continue(1)),
+        }
+    }
+
+    /// Run `run` as a condition, where a failure doesn't trigger `errexit`
+    fn tested<T>(&mut self, run: impl FnOnce(&mut Self) -> T) -> T {
+        self.condition_depth += 1;
+        let result = run(self);
+        self.condition_depth -= 1;
+        result
+    }
+
+    /// Under `errexit`, end the shell after an untested failure
+    fn check_errexit(&mut self, status: &ExitStatus) {
+        if self.options.errexit() && self.condition_depth == 0 && !status.is_success() {
+            self.exit_requested = true;
+        }
+    }
+
+    /// Note that a loop has started; the outermost one forgets any earlier
+    /// Ctrl+C
+    fn enter_loop(&mut self) {
+        if self.loop_depth == 0 {
+            interrupt::clear();
+        }
+        self.loop_depth += 1;
+    }
+
+    /// Whether a loop should go on after an iteration
+    ///
+    /// Consumes a pending `break` or `continue`. Ctrl+C ends every running
+    /// loop with status 130 (builtins and loops over builtins would
+    /// otherwise never notice it).
+    fn next_iteration(&mut self, status: &mut ExitStatus) -> bool {
+        if interrupt::flag().load(Ordering::SeqCst) {
+            *status = ExitStatus::failure(130);
+            self.flow_control = None;
+            return false;
+        }
+        match self.flow_control {
+            // Left for the function call to handle
+            Some(FlowControl::Return) => false,
+            // Loops further out see what's left of `break N`/`continue N`
+            Some(FlowControl::Break(levels)) => {
+                self.flow_control = (levels > 1).then(|| FlowControl::Break(levels - 1));
+                false
+            }
+            Some(FlowControl::Continue(levels)) if levels > 1 => {
+                self.flow_control = Some(FlowControl::Continue(levels - 1));
+                false
+            }
+            Some(FlowControl::Continue(_)) | None => {
+                self.flow_control = None;
+                !self.exit_requested
+            }
+        }
+    }
+
+    /// Stop the current loop iteration, for `break` and `continue`
+    ///
+    /// A count beyond the loops running applies to all of them.
+    fn leave_iteration(&mut self, control: FlowControl) -> ExitStatus {
+        if self.loop_depth == 0 {
+            let name = if matches!(control, FlowControl::Break(_)) { "break" } else { "continue" };
+            self.write_error(&format!("csh: {}: only meaningful in a loop\n", name));
+            return ExitStatus::failure(1);
+        }
+        self.flow_control = Some(match control {
+            FlowControl::Break(levels) => FlowControl::Break(levels.min(self.loop_depth)),
+            FlowControl::Continue(levels) => FlowControl::Continue(levels.min(self.loop_depth)),
+            FlowControl::Return => FlowControl::Return,
+        });
+        ExitStatus::success()
+    }
+
+    /// Leave the running function with `code`, or `$?` if none is given
+    fn leave_function(&mut self, code: Option<i32>) -> ExitStatus {
+        if self.function_depth == 0 {
+            self.write_error("csh: return: can only return from a function\n");
+            return ExitStatus::failure(1);
+        }
+        self.flow_control = Some(FlowControl::Return);
+        ExitStatus::failure(code.unwrap_or_else(|| self.env.last_exit_code()))
+    }
+
+    /// Run function `name` with `args` as `$1`, `$2`, ...
+    ///
+    /// Output redirects apply to everything the body writes, and `input`
+    /// to everything it reads; errors still go to stderr. The call's status
+    /// is that of `return` or else the body's last command.
+    fn call_function(
+        &mut self,
+        name: &str,
+        args: Vec<String>,
+        input: Option<File>,
+        redirects: &[crate::csh::ast::Redirect],
+    ) -> ExitStatus {
+        if self.function_depth >= MAX_FUNCTION_DEPTH {
+            self.write_error(&format!("csh: {}: functions nested too deeply\n", name));
+            return ExitStatus::failure(1);
+        }
+        let body = self.functions[name].clone();
+
+        let output_file = match self.open_redirects(redirects) {
+            Ok(files) => files.output,
+            Err(status) => return status,
+        };
+        let previous_sink =
+            output_file.map(|file| std::mem::replace(&mut self.sink, OutputSink::File(file)));
+        let previous_stdin = input.map(|file| self.stdin.replace(file));
+
+        // `$0` stays the shell's; loops outside don't see `break` from inside
+        let mut params = vec![self.env.get_value("0").unwrap_or_default()];
+        params.extend(args);
+        let previous_params = self.env.replace_positional(params);
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.function_depth += 1;
+
+        let status = self.execute_statements(&body);
+
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+        self.env.set_positional(previous_params);
+        if let Some(sink) = previous_sink {
+            self.sink = sink;
+        }
+        if let Some(stdin) = previous_stdin {
+            self.stdin = stdin;
+        }
+        if self.flow_control == Some(FlowControl::Return) {
+            self.flow_control = None;
+        }
+        status
+    }
+
     /// Execute a pipeline
     pub fn execute_pipeline(&mut self, pipeline: &Pipeline) -> ExitStatus {
         if pipeline.commands.is_empty() {
@@ -108,13 +438,9 @@ impl Executor {
         stdout_redirects: &[crate::csh::ast::Redirect],
         background: bool,
     ) -> ExitStatus {
-        // Expand variables in command name and args
-        let expanded_name = self.env.expand_variables(&cmd.name);
-        let expanded_args: Vec<String> = cmd
-            .args
-            .iter()
-            .map(|arg| self.env.expand_variables(arg))
-            .collect();
+        // Expand variables, command substitutions and wildcards
+        let expanded_name = self.expand_word(&cmd.name);
+        let expanded_args = self.expand_args(cmd);
 
         // Check for alias
         let (final_name, final_args) = if let Some(alias_expansion) = self.env.expand_alias(&expanded_name) {
@@ -127,11 +453,45 @@ impl Executor {
             (expanded_name, expanded_args)
         };
 
+        self.trace(&final_name, &final_args);
+        self.run_command(final_name, final_args, stdin_redirect, stdout_redirects, background)
+    }
+
+    /// Under `xtrace`, print a command about to run to stderr
+    fn trace(&self, name: &str, args: &[String]) {
+        if self.options.xtrace() {
+            self.write_error(&trace_line(name, args));
+        }
+    }
+
+    /// Run an expanded command: a builtin, an autocd directory or a program
+    fn run_command(
+        &mut self,
+        final_name: String,
+        final_args: Vec<String>,
+        stdin_redirect: Option<&crate::csh::ast::Redirect>,
+        stdout_redirects: &[crate::csh::ast::Redirect],
+        background: bool,
+    ) -> ExitStatus {
+        // Functions take precedence over builtins and programs
+        if self.functions.contains_key(&final_name) {
+            let input = match stdin_redirect.map(|r| self.open_input(r)).transpose() {
+                Ok(input) => input,
+                Err(status) => return status,
+            };
+            return self.call_function(&final_name, final_args, input, stdout_redirects);
+        }
+
         // Check if it's a built-in command
         if self.builtins.is_builtin(&final_name) {
             return self.execute_builtin(&final_name, &final_args, stdout_redirects);
         }
 
+        // autocd: a directory name on its own changes into it
+        if self.options.autocd() && final_args.is_empty() && self.is_autocd_target(&final_name) {
+            return self.execute_builtin("cd", &[final_name], stdout_redirects);
+        }
+
         // External command
         self.execute_external(&final_name, &final_args, stdin_redirect, stdout_redirects, background)
     }
@@ -143,36 +503,90 @@ impl Executor {
         args: &[String],
         redirects: &[crate::csh::ast::Redirect],
     ) -> ExitStatus {
-        // Setup output redirection if needed
-        let mut output_file: Option<File> = None;
-        for redirect in redirects {
-            match redirect.redirect_type {
-                RedirectType::StdoutOverwrite | RedirectType::BothOverwrite => {
-                    let path = self.env.expand_variables(&redirect.target);
-                    match File::create(&path) {
-                        Ok(f) => output_file = Some(f),
-                        Err(e) => {
-                            self.write_error(&format!("csh: cannot create {}: {}\n", path, e));
-                            return ExitStatus::failure(1);
-                        }
+        // The command run by `timeout` handles the redirects itself
+        if name == "timeout" {
+            return self.execute_timeout(args, redirects);
+        }
+        if name == "break" || name == "continue" {
+            let levels = match args {
+                [] => 1,
+                [count] => match count.parse::<i64>() {
+                    Ok(levels) if levels >= 1 => levels as usize,
+                    Ok(_) => {
+                        let message = format!("{}: {}: loop count out of range", name, count);
+                        self.write_error(&format!("csh: {}\n", message));
+                        return ExitStatus::failure(1);
                     }
+                    Err(_) => {
+                        let message = format!("{}: {}: numeric argument required", name, count);
+                        self.write_error(&format!("csh: {}\n", message));
+                        return ExitStatus::failure(2);
+                    }
+                },
+                _ => {
+                    self.write_error(&format!("csh: {}: too many arguments\n", name));
+                    return ExitStatus::failure(1);
                 }
-                RedirectType::StdoutAppend | RedirectType::BothAppend => {
-                    let path = self.env.expand_variables(&redirect.target);
-                    match OpenOptions::new().create(true).append(true).open(&path) {
-                        Ok(f) => output_file = Some(f),
-                        Err(e) => {
-                            self.write_error(&format!("csh: cannot open {}: {}\n", path, e));
-                            return ExitStatus::failure(1);
-                        }
+            };
+            let control = if name == "break" {
+                FlowControl::Break(levels)
+            } else {
+                FlowControl::Continue(levels)
+            };
+            return self.leave_iteration(control);
+        }
+        if name == "return" {
+            return match args {
+                [] => self.leave_function(None),
+                [code] => match code.parse() {
+                    Ok(code) => self.leave_function(Some(code)),
+                    Err(_) => {
+                        let message = format!("csh: return: {}: numeric argument required\n", code);
+                        self.write_error(&message);
+                        ExitStatus::failure(2)
                     }
+                },
+                _ => {
+                    self.write_error("csh: return: too many arguments\n");
+                    ExitStatus::failure(1)
                 }
-                _ => {}
-            }
+            };
+        }
+
+        // Setup output redirection if needed
+        let RedirectFiles {
+            output: mut output_file,
+            error: mut error_file,
+            errors_to_sink,
+        } = match self.open_redirects(redirects) {
+            Ok(files) => files,
+            Err(status) => return status,
+        };
+
+        // Builtins only see a terminal when nothing is redirected or captured
+        let ctx = if output_file.is_none() && self.sink.is_stdout() && atty::is(atty::Stream::Stdout) {
+            OutputContext::terminal()
+        } else {
+            OutputContext::redirected()
+        };
+
+        // `yes` streams instead of returning its output all at once
+        if name == "yes" {
+            return self.execute_yes(args, output_file, error_file);
         }
 
-        // Execute the built-in
-        let result = self.builtins.execute(name, args, &mut self.env, &mut self.history);
+        // Execute the built-in (`set -o`/`set +o` toggle executor options)
+        let result = if name == "set" && matches!(args.first().map(String::as_str), Some("-o" | "+o")) {
+            self.set_option(args)
+        } else if name == "wait" {
+            interrupt::clear();
+            wait::execute(args, &mut self.jobs, interrupt::flag())
+        } else if name == "kill" {
+            kill::execute(args, &self.jobs)
+        } else {
+            self.builtins.execute(name, args, &mut self.env, &mut self.history, ctx)
+        };
+        self.exit_requested |= result.should_exit;
 
         // Write output
         if let Some(ref output) = result.output {
@@ -185,12 +599,121 @@ impl Executor {
 
         // Handle errors
         if let Some(ref error) = result.error {
-            self.write_error(error);
+            if let Some(ref mut file) = error_file {
+                let _ = file.write_all(error.as_bytes());
+            } else if errors_to_sink {
+                self.write_output(error);
+            } else {
+                self.write_error(error);
+            }
         }
 
         result.status
     }
 
+    /// Open the output files of a builtin's `redirects`
+    fn open_redirects(
+        &mut self,
+        redirects: &[crate::csh::ast::Redirect],
+    ) -> Result<RedirectFiles, ExitStatus> {
+        let mut output_file: Option<File> = None;
+        let mut error_file: Option<File> = None;
+        let mut errors_to_sink = false;
+        for redirect in redirects {
+            match redirect.redirect_type {
+                RedirectType::StdinRead => continue,
+                // Errors go where output goes so far; a later `> file` doesn't move them
+                RedirectType::StderrToStdout => {
+                    error_file = output_file.as_ref().and_then(|file| file.try_clone().ok());
+                    errors_to_sink = output_file.is_none();
+                    continue;
+                }
+                _ => {}
+            }
+
+            let path = self.expand_word(&redirect.target);
+            let file = match self.open_output_redirect(&path, &redirect.redirect_type) {
+                Ok(f) => f,
+                Err(e) => {
+                    let action = match redirect.redirect_type {
+                        RedirectType::StdoutAppend
+                        | RedirectType::StderrAppend
+                        | RedirectType::BothAppend => "open",
+                        _ => "create",
+                    };
+                    self.write_error(&format!("csh: cannot {} {}: {}\n", action, path, e));
+                    return Err(ExitStatus::failure(1));
+                }
+            };
+            match redirect.redirect_type {
+                RedirectType::StderrOverwrite | RedirectType::StderrAppend => {
+                    error_file = Some(file);
+                    errors_to_sink = false;
+                }
+                RedirectType::BothOverwrite | RedirectType::BothAppend => {
+                    // Both streams share one file position, like `&>` for external commands
+                    error_file = file.try_clone().ok();
+                    errors_to_sink = false;
+                    output_file = Some(file);
+                }
+                _ => output_file = Some(file),
+            }
+        }
+        Ok(RedirectFiles {
+            output: output_file,
+            error: error_file,
+            errors_to_sink,
+        })
+    }
+
+    /// Run a command under `timeout`
+    ///
+    /// Only external commands are timed; a builtin just runs.
+    fn execute_timeout(&mut self, args: &[String], redirects: &[crate::csh::ast::Redirect]) -> ExitStatus {
+        // `timeout /t 5` is Windows' own pause command
+        if cfg!(windows) && args.first().is_some_and(|arg| arg.starts_with('/')) {
+            return self.execute_external("timeout", args, None, redirects, false);
+        }
+
+        let (limit, command) = match timeout::parse(args) {
+            Ok(parsed) => parsed,
+            Err(message) => {
+                self.write_error(&message);
+                return ExitStatus::failure(timeout::FAILED);
+            }
+        };
+
+        self.time_limit = limit;
+        let status = self.run_command(command[0].clone(), command[1..].to_vec(), None, redirects, false);
+        self.time_limit = None;
+        status
+    }
+
+    /// Run `yes` into a redirect file or the output sink
+    fn execute_yes(&mut self, args: &[String], output_file: Option<File>, error_file: Option<File>) -> ExitStatus {
+        interrupt::clear();
+        let line = yes::line(args);
+        let result = if let Some(mut file) = output_file {
+            yes::write_lines(&mut file, &line, interrupt::flag(), None)
+        } else {
+            yes::write_lines(&mut self.sink, &line, interrupt::flag(), None)
+        };
+
+        match result {
+            Ok(()) => ExitStatus::success(),
+            Err(e) => {
+                let message = format!("yes: {}\n", e);
+                match error_file {
+                    Some(mut file) => {
+                        let _ = file.write_all(message.as_bytes());
+                    }
+                    None => self.write_error(&message),
+                }
+                ExitStatus::failure(1)
+            }
+        }
+    }
+
     /// Execute an external command
     fn execute_external(
         &mut self,
@@ -200,6 +723,11 @@ impl Executor {
         stdout_redirects: &[crate::csh::ast::Redirect],
         background: bool,
     ) -> ExitStatus {
+        if !self.command_exists(name) {
+            self.write_error(&self.command_not_found(name));
+            return ExitStatus::failure(127);
+        }
+
         // On Windows, run ALL external commands through cmd.exe /c
         // This lets Windows handle PATH resolution, PATHEXT, and script execution
         #[cfg(windows)]
@@ -221,48 +749,52 @@ impl Executor {
 
         // Setup stdin
         if let Some(redirect) = stdin_redirect {
-            let path = self.env.expand_variables(&redirect.target);
-            match File::open(&path) {
+            match self.open_input(redirect) {
                 Ok(file) => {
                     cmd.stdin(Stdio::from(file));
                 }
-                Err(e) => {
-                    self.write_error(&format!("csh: cannot open {}: {}\n", path, e));
-                    return ExitStatus::failure(1);
-                }
+                Err(status) => return status,
             }
         } else {
-            cmd.stdin(Stdio::inherit());
+            cmd.stdin(self.inherited_stdin());
         }
 
         // Setup stdout/stderr
         let mut stdout_file: Option<File> = None;
         let mut stderr_file: Option<File> = None;
+        // `2>&1` while stdout still went to the sink
+        let mut stderr_to_sink = false;
 
         for redirect in stdout_redirects {
-            let path = self.env.expand_variables(&redirect.target);
-            let file = match redirect.redirect_type {
-                RedirectType::StdoutOverwrite => File::create(&path),
-                RedirectType::StdoutAppend => OpenOptions::new().create(true).append(true).open(&path),
-                RedirectType::StderrOverwrite => File::create(&path),
-                RedirectType::StderrAppend => OpenOptions::new().create(true).append(true).open(&path),
-                RedirectType::BothOverwrite => File::create(&path),
-                RedirectType::BothAppend => OpenOptions::new().create(true).append(true).open(&path),
-                _ => continue,
-            };
+            match redirect.redirect_type {
+                RedirectType::StdinRead => continue,
+                // Stderr goes where stdout goes so far; a later `> file`
+                // doesn't move it
+                RedirectType::StderrToStdout => {
+                    stderr_file = stdout_file.as_ref().and_then(|file| file.try_clone().ok());
+                    stderr_to_sink = stdout_file.is_none();
+                    continue;
+                }
+                _ => {}
+            }
+            let path = self.expand_word(&redirect.target);
 
-            match file {
+            match self.open_output_redirect(&path, &redirect.redirect_type) {
                 Ok(f) => {
                     match redirect.redirect_type {
-                        RedirectType::StdoutOverwrite | RedirectType::StdoutAppend => {
+                        RedirectType::StdoutOverwrite
+                        | RedirectType::StdoutClobber
+                        | RedirectType::StdoutAppend => {
                             stdout_file = Some(f);
                         }
                         RedirectType::StderrOverwrite | RedirectType::StderrAppend => {
                             stderr_file = Some(f);
+                            stderr_to_sink = false;
                         }
                         RedirectType::BothOverwrite | RedirectType::BothAppend => {
                             stdout_file = Some(f.try_clone().unwrap());
                             stderr_file = Some(f);
+                            stderr_to_sink = false;
                         }
                         _ => {}
                     }
@@ -274,46 +806,86 @@ impl Executor {
             }
         }
 
+        // Output for a capturing sink is read from a pipe; with `2>&1` both
+        // streams share it, so they interleave as the command wrote them
+        let capture = !self.sink.is_stdout() && (stdout_file.is_none() || stderr_to_sink);
+        let (capture_reader, capture_writer) = if capture {
+            match io::pipe() {
+                Ok((reader, writer)) => (Some(reader), Some(writer)),
+                Err(e) => {
+                    self.write_error(&format!("csh: cannot create pipe: {}\n", e));
+                    return ExitStatus::failure(1);
+                }
+            }
+        } else {
+            (None, None)
+        };
+        let capture_stdio = || capture_writer.as_ref().and_then(|w| w.try_clone().ok());
+
         if let Some(file) = stdout_file {
             cmd.stdout(Stdio::from(file));
-        } else if self.capture_output {
-            cmd.stdout(Stdio::piped());
+        } else if let Some(writer) = capture_stdio() {
+            cmd.stdout(writer);
         } else {
             cmd.stdout(Stdio::inherit());
         }
 
         if let Some(file) = stderr_file {
             cmd.stderr(Stdio::from(file));
+        } else if let (true, Some(writer)) = (stderr_to_sink, capture_stdio()) {
+            cmd.stderr(writer);
+        } else if let (true, Some(stdout)) = (stderr_to_sink, shell_stdout()) {
+            cmd.stderr(stdout);
         } else {
             cmd.stderr(Stdio::inherit());
         }
 
-        // Spawn the process
-        match cmd.spawn() {
+        // A timed command gets its own process group, so that it can be
+        // stopped along with everything it started
+        let time_limit = if background { None } else { self.time_limit.take() };
+        #[cfg(unix)]
+        if time_limit.is_some() {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+
+        // Spawn the process; the pipe only reaches EOF once every copy of
+        // its write end, including those held by `cmd`, is closed
+        let spawned = cmd.spawn();
+        drop(cmd);
+        drop(capture_writer);
+        match spawned {
             Ok(mut child) => {
                 if background {
-                    self.write_output(&format!("[{}] {}\n", child.id(), name));
+                    let pid = child.id();
+                    let id = self.jobs.add(child, name);
+                    self.write_output(&format!("[{}] {}\n", id, pid));
                     ExitStatus::success()
                 } else {
-                    // Wait for completion
-                    match child.wait() {
-                        Ok(status) => {
-                            if self.capture_output {
-                                if let Some(mut stdout) = child.stdout.take() {
-                                    let mut output = Vec::new();
-                                    let _ = stdout.read_to_end(&mut output);
-                                    self.output_buffer.extend(output);
-                                }
-                            }
-                            ExitStatus::failure(status.code().unwrap_or(1))
-                        }
+                    let watchdog = time_limit.map(|limit| Watchdog::start(child.id(), limit));
+
+                    // Forward piped output as it arrives, then wait for completion
+                    if let Some(reader) = capture_reader {
+                        self.forward_output(reader);
+                    }
+                    let status = match child.wait() {
+                        Ok(status) => ExitStatus::from(status),
                         Err(e) => {
                             self.write_error(&format!("csh: error waiting for {}: {}\n", name, e));
                             ExitStatus::failure(1)
                         }
+                    };
+                    match watchdog.map(Watchdog::finish) {
+                        Some(Outcome::TimedOut) => ExitStatus::failure(timeout::TIMED_OUT),
+                        Some(Outcome::Killed) => ExitStatus::failure(128 + 9),
+                        _ => status,
                     }
                 }
             }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.write_error(&self.command_not_found(name));
+                ExitStatus::failure(127)
+            }
             Err(e) => {
                 self.write_error(&format!("csh: {}: {}\n", name, e));
                 ExitStatus::failure(127)
@@ -325,14 +897,82 @@ impl Executor {
     fn execute_pipe_chain(&mut self, pipeline: &Pipeline) -> ExitStatus {
         let mut children: Vec<Child> = Vec::new();
         let cmd_count = pipeline.commands.len();
+        // Each stage's status, and the stage each child runs
+        let mut statuses = vec![ExitStatus::success(); cmd_count];
+        let mut child_stages: Vec<usize> = Vec::new();
+        // A leading `yes` and functions run in-process; a thread feeds what
+        // they write to the next stage
+        let mut fed: Option<File> = None;
+        let mut feeders: Vec<std::thread::JoinHandle<()>> = Vec::new();
 
         for (i, cmd) in pipeline.commands.iter().enumerate() {
-            let expanded_name = self.env.expand_variables(&cmd.name);
-            let expanded_args: Vec<String> = cmd
-                .args
-                .iter()
-                .map(|arg| self.env.expand_variables(arg))
-                .collect();
+            let expanded_name = self.expand_word(&cmd.name);
+            let expanded_args = self.expand_args(cmd);
+            self.trace(&expanded_name, &expanded_args);
+
+            // The pipeline's `<` file, or what the stage before wrote
+            let input = if i == 0 {
+                match pipeline.stdin_redirect.as_ref().map(|r| self.open_input(r)).transpose() {
+                    Ok(input) => input,
+                    Err(status) => return status,
+                }
+            } else {
+                fed.take().or_else(|| {
+                    let previous = children.last_mut().and_then(|child| child.stdout.take());
+                    previous.map(into_file)
+                })
+            };
+
+            if i == 0 && expanded_name == "yes" && input.is_none() {
+                // Ends when the reader exits (broken pipe) or on Ctrl+C
+                let line = yes::line(&expanded_args);
+                interrupt::clear();
+                let started = start_feeder(move |pipe| {
+                    let _ = yes::write_lines(pipe, &line, interrupt::flag(), None);
+                });
+                match started {
+                    Ok((reader, feeder)) => {
+                        fed = Some(reader);
+                        feeders.push(feeder);
+                    }
+                    Err(e) => {
+                        self.write_error(&format!("csh: cannot create pipe: {}\n", e));
+                        return ExitStatus::failure(1);
+                    }
+                }
+                continue;
+            }
+
+            if self.functions.contains_key(&expanded_name) {
+                if i == cmd_count - 1 {
+                    let redirects = &pipeline.stdout_redirects;
+                    let name = &expanded_name;
+                    statuses[i] = self.call_function(name, expanded_args, input, redirects);
+                    continue;
+                }
+
+                // Collect the output, then hand it on once the call is done
+                let outer = std::mem::replace(&mut self.sink, OutputSink::Buffer(Vec::new()));
+                statuses[i] = self.call_function(&expanded_name, expanded_args, input, &[]);
+                let output = match std::mem::replace(&mut self.sink, outer) {
+                    OutputSink::Buffer(output) => output,
+                    _ => Vec::new(),
+                };
+                match start_feeder(move |pipe| {
+                    let _ = pipe.write_all(&output);
+                }) {
+                    Ok((reader, feeder)) => {
+                        fed = Some(reader);
+                        feeders.push(feeder);
+                    }
+                    Err(e) => {
+                        self.write_error(&format!("csh: cannot create pipe: {}\n", e));
+                        abort_children(children);
+                        return ExitStatus::failure(1);
+                    }
+                }
+                continue;
+            }
 
             // On Windows, run ALL external commands through cmd.exe /c
             #[cfg(windows)]
@@ -351,41 +991,24 @@ impl Executor {
             process.current_dir(self.env.cwd());
 
             // Setup stdin
-            if i == 0 {
-                // First command - check for input redirect
-                if let Some(redirect) = &pipeline.stdin_redirect {
-                    let path = self.env.expand_variables(&redirect.target);
-                    match File::open(&path) {
-                        Ok(file) => process.stdin(Stdio::from(file)),
-                        Err(e) => {
-                            self.write_error(&format!("csh: cannot open {}: {}\n", path, e));
-                            return ExitStatus::failure(1);
-                        }
-                    };
-                } else {
-                    process.stdin(Stdio::inherit());
-                }
-            } else {
-                // Take stdout from previous command
-                if let Some(prev_child) = children.last_mut() {
-                    if let Some(stdout) = prev_child.stdout.take() {
-                        process.stdin(Stdio::from(stdout));
-                    }
-                }
-            }
+            match input {
+                Some(file) => process.stdin(Stdio::from(file)),
+                None => process.stdin(self.inherited_stdin()),
+            };
 
             // Setup stdout
             if i == cmd_count - 1 {
                 // Last command - check for output redirect
                 let mut has_redirect = false;
                 for redirect in &pipeline.stdout_redirects {
-                    let path = self.env.expand_variables(&redirect.target);
+                    let path = self.expand_word(&redirect.target);
                     let file = match redirect.redirect_type {
-                        RedirectType::StdoutOverwrite | RedirectType::BothOverwrite => {
-                            File::create(&path)
-                        }
-                        RedirectType::StdoutAppend | RedirectType::BothAppend => {
-                            OpenOptions::new().create(true).append(true).open(&path)
+                        RedirectType::StdoutOverwrite
+                        | RedirectType::StdoutClobber
+                        | RedirectType::StdoutAppend
+                        | RedirectType::BothOverwrite
+                        | RedirectType::BothAppend => {
+                            self.open_output_redirect(&path, &redirect.redirect_type)
                         }
                         _ => continue,
                     };
@@ -397,13 +1020,18 @@ impl Executor {
                         }
                         Err(e) => {
                             self.write_error(&format!("csh: cannot create {}: {}\n", path, e));
+                            abort_children(children);
                             return ExitStatus::failure(1);
                         }
                     }
                 }
 
                 if !has_redirect {
-                    process.stdout(Stdio::inherit());
+                    if self.sink.is_stdout() {
+                        process.stdout(Stdio::inherit());
+                    } else {
+                        process.stdout(Stdio::piped());
+                    }
                 }
             } else {
                 // Not last - pipe to next command
@@ -413,38 +1041,358 @@ impl Executor {
             process.stderr(Stdio::inherit());
 
             match process.spawn() {
-                Ok(child) => children.push(child),
+                Ok(child) => {
+                    children.push(child);
+                    child_stages.push(i);
+                }
                 Err(e) => {
-                    self.write_error(&format!("csh: {}: {}\n", expanded_name, e));
+                    if e.kind() == io::ErrorKind::NotFound {
+                        self.write_error(&self.command_not_found(&expanded_name));
+                    } else {
+                        self.write_error(&format!("csh: {}: {}\n", expanded_name, e));
+                    }
+                    abort_children(children);
                     return ExitStatus::failure(127);
                 }
             }
         }
 
-        // Wait for all children
-        let mut last_status = ExitStatus::success();
-        for mut child in children {
-            match child.wait() {
-                Ok(status) => {
-                    last_status = ExitStatus::failure(status.code().unwrap_or(1));
-                }
+        // Wait from the consuming end backwards: every stage's output is
+        // already wired into the next stage, so nothing waits on a full pipe
+        if let Some(stdout) = children.last_mut().and_then(|child| child.stdout.take()) {
+            self.forward_output(stdout);
+        }
+        for (mut child, stage) in children.into_iter().zip(child_stages).rev() {
+            statuses[stage] = match child.wait() {
+                Ok(status) => ExitStatus::from(status),
                 Err(e) => {
                     self.write_error(&format!("csh: error waiting for process: {}\n", e));
-                    last_status = ExitStatus::failure(1);
+                    ExitStatus::failure(1)
+                }
+            };
+        }
+        for feeder in feeders {
+            let _ = feeder.join();
+        }
+
+        // The pipeline's status is that of its last command, or under
+        // `pipefail` of its last failing one
+        let failed = statuses.iter().rposition(|status| !status.is_success());
+        match failed {
+            Some(stage) if self.options.pipefail() => statuses.swap_remove(stage),
+            _ => statuses.pop().unwrap_or_else(ExitStatus::success),
+        }
+    }
+
+    /// Handle `set -o NAME` (enable) and `set +o NAME` (disable)
+    fn set_option(&mut self, args: &[String]) -> BuiltinResult {
+        let enable = args[0] == "-o";
+        let Some(option) = args.get(1) else {
+            // No name: list options (`+o` in re-inputtable form)
+            let listing = if enable {
+                self.options.listing()
+            } else {
+                self.options.reinput()
+            };
+            return BuiltinResult::success_with_output(listing);
+        };
+
+        if !self.options.set(option, enable) {
+            return BuiltinResult::failure(1, format!("set: {}: invalid option name\n", option));
+        }
+        self.history.set_record_details(self.options.extendedhistory());
+
+        BuiltinResult::success()
+    }
+
+    /// Whether `name` should be treated as `cd name` under `autocd`
+    ///
+    /// It must be an existing directory, and a bare name must not also be
+    /// a command on PATH (the command wins, as in zsh).
+    fn is_autocd_target(&self, name: &str) -> bool {
+        let path = match (name.strip_prefix('~'), self.env.get_value("HOME")) {
+            (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+                PathBuf::from(format!("{}{}", home, rest))
+            }
+            _ => PathBuf::from(name),
+        };
+        if !self.env.cwd().join(&path).is_dir() {
+            return false;
+        }
+
+        if name.contains(['/', '\\']) || name.starts_with('~') {
+            return true;
+        }
+        !self.is_on_path(name)
+    }
+
+    /// Whether a bare command name resolves to a file on PATH
+    fn is_on_path(&self, name: &str) -> bool {
+        let extensions = self.executable_extensions();
+        self.env.path_entries().into_iter().any(|dir| {
+            extensions
+                .iter()
+                .any(|ext| is_command_file(&dir.join(format!("{}{}", name, ext))))
+        })
+    }
+
+    /// Suffixes tried when looking a command up: none, then on Windows
+    /// those in the shell's `PATHEXT`
+    fn executable_extensions(&self) -> Vec<String> {
+        if cfg!(windows) {
+            executable_extensions(self.env.get_value("PATHEXT").as_deref())
+        } else {
+            vec![String::new()]
+        }
+    }
+
+    /// Whether an external command name can be run at all
+    ///
+    /// Names with a path separator are left for the OS to judge. On Windows,
+    /// cmd.exe also runs its internal commands and files in the cwd.
+    fn command_exists(&self, name: &str) -> bool {
+        if name.contains(['/', '\\']) || self.is_on_path(name) {
+            return true;
+        }
+        cfg!(windows)
+            && (CMD_INTERNALS.contains(&name.to_lowercase().as_str())
+                || self
+                    .executable_extensions()
+                    .iter()
+                    .any(|ext| is_command_file(&self.env.cwd().join(format!("{}{}", name, ext)))))
+    }
+
+    /// "command not found" message, with the closest builtin, alias or
+    /// PATH command as a suggestion
+    fn command_not_found(&self, name: &str) -> String {
+        let mut candidates: Vec<String> = self.builtins.list().iter().map(|s| s.to_string()).collect();
+        candidates.extend(self.env.get_aliases().keys().cloned());
+
+        let extensions = self.executable_extensions();
+        for dir in self.env.path_entries() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let lowercase = file_name.to_lowercase();
+                let command = extensions[1..]
+                    .iter()
+                    .filter(|ext| lowercase.ends_with(ext.as_str()))
+                    .find_map(|ext| file_name.get(..file_name.len() - ext.len()))
+                    .unwrap_or(&file_name);
+                candidates.push(command.to_string());
+            }
+        }
+
+        match suggest::closest(name, candidates.iter().map(String::as_str)) {
+            Some(suggestion) => format!("csh: {}: command not found. Did you mean {}?\n", name, suggestion),
+            None => format!("csh: {}: command not found\n", name),
+        }
+    }
+
+    /// Open the target of an output redirect
+    ///
+    /// Append redirects always append. Plain overwrites refuse to replace an
+    /// existing regular file while `noclobber` is set; `>|` always overwrites.
+    fn open_output_redirect(&self, path: &str, redirect_type: &RedirectType) -> io::Result<File> {
+        match redirect_type {
+            RedirectType::StdoutAppend | RedirectType::StderrAppend | RedirectType::BothAppend => {
+                OpenOptions::new().create(true).append(true).open(path)
+            }
+            RedirectType::StdoutClobber => File::create(path),
+            _ => {
+                if self.options.noclobber() && Path::new(path).is_file() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        "cannot overwrite existing file",
+                    ));
                 }
+                File::create(path)
             }
         }
+    }
 
-        last_status
+    /// Expand `$(...)` command substitutions, `$((...))` arithmetic and
+    /// variables in a word
+    ///
+    /// Variables inside a substitution are left for the command itself,
+    /// and its output is not expanded again. An expression that can't be
+    /// evaluated is left as written.
+    fn expand_word(&mut self, word: &str) -> String {
+        let mut result = String::new();
+        let mut rest = word;
+        // Length of the start of `rest` known to hold no `$(...)`
+        let mut scanned = 0;
+        while let Some(found) = rest[scanned..].find('$') {
+            let start = scanned + found;
+            let after = &rest[start + 1..];
+
+            // `${VAR:-$(cmd)}` runs `cmd` only if the word is used
+            if let Some(parameter) = after.strip_prefix('{') {
+                let Some(body) = lexer::parameter_body(parameter) else {
+                    break;
+                };
+                let end = start + 2 + body.len() + 1;
+                if body.contains("$(") {
+                    result.push_str(&self.env.expand_assigning(&rest[..start]));
+                    let value = self.expand_parameter(body);
+                    result.push_str(&value);
+                    rest = &rest[end..];
+                    scanned = 0;
+                } else {
+                    scanned = end;
+                }
+                continue;
+            }
+            if !after.starts_with('(') {
+                scanned = start + 1;
+                continue;
+            }
+
+            let Some(command) = lexer::command_sub_body(&rest[start + 2..]) else {
+                break;
+            };
+            result.push_str(&self.env.expand_assigning(&rest[..start]));
+            let end = start + 2 + command.len() + 1;
+            match arithmetic::expression(command) {
+                Some(expr) => {
+                    let expr = self.env.expand_assigning(expr);
+                    match arithmetic::evaluate(&expr, |name| self.env.get_value(name)) {
+                        Ok(value) => result.push_str(&value.to_string()),
+                        Err(_) => result.push_str(&rest[start..end]),
+                    }
+                }
+                None => result.push_str(&self.command_output(command)),
+            }
+            rest = &rest[end..];
+            scanned = 0;
+        }
+        result.push_str(&self.env.expand_assigning(rest));
+        result
+    }
+
+    /// Expand the inside of a `${...}` whose word runs commands
+    fn expand_parameter(&mut self, body: &str) -> String {
+        match self.env.parameter(body) {
+            Parameter::Value(value) => value,
+            Parameter::Word(word) => self.expand_word(word),
+            Parameter::Assign(name, word) => {
+                let value = self.expand_word(word);
+                self.env.set(name, &value);
+                value
+            }
+        }
+    }
+
+    /// Expand a command's arguments (see `expand_words`)
+    fn expand_args(&mut self, cmd: &Command) -> Vec<String> {
+        self.expand_words(&cmd.args, &cmd.quoted_args, false)
+    }
+
+    /// Expand a list of words, such as arguments or `for` loop items
+    ///
+    /// Unquoted words go through brace expansion first, then each word
+    /// is expanded and wildcard patterns are replaced with the files they
+    /// match (patterns matching nothing are kept). Double-quoted words are
+    /// only expanded; single-quoted ones are kept as they are.
+    ///
+    /// With `split`, as for `for` items, unquoted words are also split on
+    /// whitespace once expanded. Arguments aren't, so that `cd $DIR` works
+    /// for a path with spaces.
+    fn expand_words(&mut self, words: &[String], quoting: &[Quoting], split: bool) -> Vec<String> {
+        let mut args = Vec::with_capacity(words.len());
+        for (i, arg) in words.iter().enumerate() {
+            match quoting.get(i).copied().unwrap_or_default() {
+                Quoting::Single => {
+                    args.push(arg.clone());
+                    continue;
+                }
+                Quoting::Double => {
+                    args.push(self.expand_word(arg));
+                    continue;
+                }
+                Quoting::Unquoted => {}
+            }
+            for word in braces::expand(arg) {
+                let word = self.expand_word(&word);
+                let fields = if split {
+                    word.split_whitespace().map(str::to_string).collect()
+                } else {
+                    vec![word]
+                };
+                for field in fields {
+                    match wildcard::expand(&field, self.env.cwd()) {
+                        Some(paths) => args.extend(paths),
+                        None => args.push(field),
+                    }
+                }
+            }
+        }
+        args
+    }
+
+    /// Run `source` with its output captured, for `$(...)`
+    ///
+    /// Trailing newlines are removed, as in other shells. Errors still go
+    /// to stderr and the status is left in `$?`.
+    fn command_output(&mut self, source: &str) -> String {
+        let cmd_line = match parser::parse(source) {
+            Ok(cmd_line) => cmd_line,
+            Err(e) => {
+                self.write_error(&format!("csh: {}\n", e));
+                self.env.set_last_exit_code(2);
+                return String::new();
+            }
+        };
+
+        // A failure inside is the enclosing command's to report under `errexit`
+        let previous = std::mem::replace(&mut self.sink, OutputSink::Buffer(Vec::new()));
+        let status = self.tested(|this| this.execute(&cmd_line));
+        self.env.set_last_exit_code(status.code);
+        let output = match std::mem::replace(&mut self.sink, previous) {
+            OutputSink::Buffer(buffer) => buffer,
+            _ => Vec::new(),
+        };
+        String::from_utf8_lossy(&output).trim_end_matches(['\n', '\r']).to_string()
     }
 
     /// Write to stdout
     pub fn write_output(&mut self, text: &str) {
-        if self.capture_output {
-            self.output_buffer.extend(text.as_bytes());
-        } else {
-            print!("{}", text);
-            let _ = io::stdout().flush();
+        let _ = self.sink.write_all(text.as_bytes());
+        let _ = self.sink.flush();
+    }
+
+    /// Pass a child's piped stdout to the sink chunk by chunk until EOF
+    fn forward_output(&mut self, mut stdout: impl Read) {
+        let mut buf = [0u8; 8192];
+        loop {
+            match stdout.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let _ = self.sink.write_all(&buf[..n]);
+                    let _ = self.sink.flush();
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Open the file a `<` redirect reads from
+    fn open_input(&mut self, redirect: &crate::csh::ast::Redirect) -> Result<File, ExitStatus> {
+        let path = self.expand_word(&redirect.target);
+        File::open(&path).map_err(|e| {
+            self.write_error(&format!("csh: cannot open {}: {}\n", path, e));
+            ExitStatus::failure(1)
+        })
+    }
+
+    /// Stdin for a command without a `<` of its own
+    fn inherited_stdin(&self) -> Stdio {
+        match self.stdin.as_ref().and_then(|file| file.try_clone().ok()) {
+            Some(file) => Stdio::from(file),
+            None => Stdio::inherit(),
         }
     }
 
@@ -456,13 +1404,177 @@ impl Executor {
 
     /// Set capture mode and return captured output
     pub fn capture(&mut self, capture: bool) -> Vec<u8> {
-        self.capture_output = capture;
-        if !capture {
-            std::mem::take(&mut self.output_buffer)
+        let sink = if capture {
+            OutputSink::Buffer(Vec::new())
         } else {
-            Vec::new()
+            OutputSink::Stdout
+        };
+        match std::mem::replace(&mut self.sink, sink) {
+            OutputSink::Buffer(buffer) if !capture => buffer,
+            _ => Vec::new(),
         }
     }
+
+    /// Execute a command line, handing output to `on_output` as it is produced
+    ///
+    /// Builtins deliver their output in one chunk; external commands
+    /// deliver it as they write it. Errors still go to stderr.
+    pub fn execute_streaming<F>(&mut self, cmd_line: &CommandLine, on_output: F) -> ExitStatus
+    where
+        F: FnMut(&[u8]) + 'static,
+    {
+        let previous = std::mem::replace(&mut self.sink, OutputSink::Callback(Box::new(on_output)));
+        let status = self.execute(cmd_line);
+        self.sink = previous;
+        status
+    }
+}
+
+/// Files a builtin's redirects opened (see `open_redirects`)
+struct RedirectFiles {
+    /// Where output goes instead of the sink
+    output: Option<File>,
+    /// Where errors go instead of stderr
+    error: Option<File>,
+    /// `2>&1` while output goes to the sink: errors follow it there
+    errors_to_sink: bool,
+}
+
+/// Receives output chunks in `execute_streaming`
+type OutputCallback = Box<dyn FnMut(&[u8])>;
+
+/// Where command output is written
+enum OutputSink {
+    /// The shell's own stdout
+    Stdout,
+    /// Collected until `capture(false)`
+    Buffer(Vec<u8>),
+    /// Handed to an `execute_streaming` callback
+    Callback(OutputCallback),
+    /// A file a function call's output is redirected to
+    File(File),
+}
+
+impl OutputSink {
+    fn is_stdout(&self) -> bool {
+        matches!(self, OutputSink::Stdout)
+    }
+}
+
+impl Write for OutputSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputSink::Stdout => io::stdout().write(buf),
+            OutputSink::Buffer(buffer) => {
+                buffer.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            OutputSink::Callback(callback) => {
+                callback(buf);
+                Ok(buf.len())
+            }
+            OutputSink::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputSink::Stdout => io::stdout().flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What Windows uses when `PATHEXT` is unset
+const DEFAULT_PATHEXT: &str = ".COM;.EXE;.BAT;.CMD";
+
+/// No suffix, then the lowercased suffixes listed in `pathext`
+fn executable_extensions(pathext: Option<&str>) -> Vec<String> {
+    let pathext = pathext.filter(|value| !value.is_empty()).unwrap_or(DEFAULT_PATHEXT);
+    std::iter::once(String::new())
+        .chain(
+            pathext
+                .split(';')
+                .filter(|ext| !ext.is_empty())
+                .map(|ext| ext.to_lowercase()),
+        )
+        .collect()
+}
+
+/// Whether `path` is something that can be run: not a directory, and
+/// not necessarily a regular file (the App Execution Aliases in
+/// WindowsApps are reparse points that can't be opened like one)
+fn is_command_file(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok_and(|metadata| !metadata.is_dir())
+}
+
+/// Commands built into cmd.exe, which exist without a file on PATH
+const CMD_INTERNALS: &[&str] = &[
+    "assoc", "break", "call", "cd", "chdir", "cls", "color", "copy", "date", "del", "dir",
+    "echo", "endlocal", "erase", "exit", "for", "ftype", "goto", "if", "md", "mkdir", "mklink",
+    "move", "path", "pause", "popd", "prompt", "pushd", "rd", "rem", "ren", "rename", "rmdir",
+    "set", "setlocal", "shift", "start", "time", "title", "type", "ver", "verify", "vol",
+];
+
+/// A copy of the shell's own stdout for a child's stderr (`2>&1`)
+fn shell_stdout() -> Option<Stdio> {
+    #[cfg(unix)]
+    let handle = {
+        use std::os::fd::AsFd;
+        io::stdout().as_fd().try_clone_to_owned()
+    };
+    #[cfg(windows)]
+    let handle = {
+        use std::os::windows::io::AsHandle;
+        io::stdout().as_handle().try_clone_to_owned()
+    };
+    handle.ok().map(Stdio::from)
+}
+
+/// `+ name args` as `xtrace` prints it, quoting words that are empty or
+/// have spaces
+fn trace_line(name: &str, args: &[String]) -> String {
+    let mut line = String::from("+");
+    for word in std::iter::once(name).chain(args.iter().map(String::as_str)) {
+        line.push(' ');
+        if word.is_empty() || word.contains(char::is_whitespace) {
+            line.push_str(&format!("'{}'", word));
+        } else {
+            line.push_str(word);
+        }
+    }
+    line.push('\n');
+    line
+}
+
+/// Kill and reap already-started pipeline stages after a setup failure
+fn abort_children(children: Vec<Child>) {
+    for mut child in children.into_iter().rev() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Start a thread that writes to a new pipe with `write`, returning the
+/// pipe's read end
+fn start_feeder(
+    write: impl FnOnce(&mut io::PipeWriter) + Send + 'static,
+) -> io::Result<(File, std::thread::JoinHandle<()>)> {
+    let (reader, mut writer) = io::pipe()?;
+    let feeder = std::thread::spawn(move || write(&mut writer));
+    Ok((into_file(reader), feeder))
+}
+
+/// A pipe's read end as a file, so every command a function runs can share it
+#[cfg(unix)]
+fn into_file(pipe: impl Into<std::os::fd::OwnedFd>) -> File {
+    File::from(pipe.into())
+}
+
+/// A pipe's read end as a file, so every command a function runs can share it
+#[cfg(windows)]
+fn into_file(pipe: impl Into<std::os::windows::io::OwnedHandle>) -> File {
+    File::from(pipe.into())
 }
 
 impl Default for Executor {
@@ -470,3 +1582,501 @@ impl Default for Executor {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csh::parser;
+    use std::fs;
+
+    fn run(executor: &mut Executor, line: &str) -> ExitStatus {
+        executor.execute(&parser::parse(line).unwrap())
+    }
+
+    #[test]
+    fn test_noclobber_refuses_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("out.txt");
+        fs::write(&target, "original\n").unwrap();
+        let target = target.to_string_lossy().to_string();
+
+        let mut executor = Executor::new();
+        assert!(run(&mut executor, "set -o noclobber").is_success());
+        assert!(executor.options.noclobber());
+
+        let status = run(&mut executor, &format!("echo replaced > '{}'", target));
+        assert!(!status.is_success());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "original\n");
+
+        // Appending is always allowed
+        assert!(run(&mut executor, &format!("echo more >> '{}'", target)).is_success());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "original\nmore\n");
+    }
+
+    #[test]
+    fn test_builtin_stderr_redirects() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("nonexistent").to_string_lossy().to_string();
+        let err = dir.path().join("err.txt").to_string_lossy().to_string();
+        let both = dir.path().join("both.txt").to_string_lossy().to_string();
+
+        let mut executor = Executor::new();
+        executor.capture(true);
+        let status = run(&mut executor, &format!("ls '{}' 2>'{}'", missing, err));
+        assert!(!status.is_success());
+        assert!(executor.capture(true).is_empty());
+        let error = fs::read_to_string(&err).unwrap();
+        assert!(error.contains("nonexistent"), "{}", error);
+
+        // 2>> appends; &> takes output and errors in order
+        run(&mut executor, &format!("ls '{}' 2>>'{}'", missing, err));
+        assert_eq!(fs::read_to_string(&err).unwrap(), error.repeat(2));
+        run(&mut executor, &format!("echo out &>'{}'", both));
+        run(&mut executor, &format!("ls '{}' &>>'{}'", missing, both));
+        assert_eq!(fs::read_to_string(&both).unwrap(), format!("out\n{}", error));
+        assert!(executor.capture(false).is_empty());
+    }
+
+    #[test]
+    fn test_clobber_redirect_overrides_noclobber() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("out.txt");
+        fs::write(&target, "original\n").unwrap();
+        let target = target.to_string_lossy().to_string();
+
+        let mut executor = Executor::new();
+        executor.options.set("noclobber", true);
+
+        assert!(run(&mut executor, &format!("echo replaced >| '{}'", target)).is_success());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "replaced\n");
+
+        // Turning the option off restores plain overwrites
+        assert!(run(&mut executor, "set +o noclobber").is_success());
+        assert!(run(&mut executor, &format!("echo again > '{}'", target)).is_success());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "again\n");
+    }
+
+    #[test]
+    fn test_autocd_changes_into_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("projects")).unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        let mut executor = Executor::new();
+        executor.env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+
+        // Off by default
+        assert!(!run(&mut executor, "projects").is_success());
+
+        run(&mut executor, "set -o autocd");
+        assert!(run(&mut executor, "projects").is_success());
+        let expected = dir.path().join("projects").canonicalize().unwrap();
+        assert_eq!(executor.env.cwd().canonicalize().unwrap(), expected);
+
+        // Files are still run (and fail) as commands
+        executor.env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        assert!(!run(&mut executor, "notes.txt").is_success());
+        assert_eq!(executor.env.cwd(), &dir.path().to_path_buf());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal_exit_status() {
+        let mut executor = Executor::new();
+        let status = run(&mut executor, "sh -c 'kill -9 $$'");
+        assert_eq!(status.code, 137);
+        assert_eq!(status.signal, Some(9));
+        assert_eq!(executor.env.get_value("?").as_deref(), Some("137"));
+
+        // Only 0 counts as success for && and ||
+        let status = run(&mut executor, "sh -c 'exit 3' && true");
+        assert_eq!(status.code, 3);
+        assert!(run(&mut executor, "sh -c 'exit 3' || true").is_success());
+    }
+
+    #[test]
+    fn test_quoted_assignment_values_keep_spaces() {
+        let mut executor = Executor::new();
+
+        assert!(run(&mut executor, "export GREETING=\"hi there\"").is_success());
+        assert_eq!(executor.env.get_value("GREETING").as_deref(), Some("hi there"));
+        assert_eq!(
+            executor.env.get_exports().get("GREETING").map(String::as_str),
+            Some("hi there")
+        );
+
+        assert!(run(&mut executor, "set Y='c d'").is_success());
+        assert_eq!(executor.env.get_value("Y").as_deref(), Some("c d"));
+
+        // Quotes may also cover just part of the word, or the whole of it
+        run(&mut executor, "set PART=a\"b c\"d");
+        assert_eq!(executor.env.get_value("PART").as_deref(), Some("ab cd"));
+        run(&mut executor, "export 'WHOLE=x  y'");
+        assert_eq!(executor.env.get_value("WHOLE").as_deref(), Some("x  y"));
+    }
+
+    #[test]
+    fn test_redirected_clear_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("out.txt").to_string_lossy().to_string();
+
+        let mut executor = Executor::new();
+        assert!(run(&mut executor, &format!("clear > '{}'", target)).is_success());
+        assert_eq!(fs::read(&target).unwrap(), b"");
+
+        assert_eq!(run_captured(&mut executor, "cls"), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pipeline_large_output_into_slow_consumer() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("count.txt").to_string_lossy().to_string();
+        let line = format!("seq 1 200000 | sh -c 'sleep 0.2; wc -l' > '{}'", out);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut executor = Executor::new();
+            tx.send(run(&mut executor, &line)).unwrap();
+        });
+
+        let status = rx
+            .recv_timeout(std::time::Duration::from_secs(20))
+            .expect("pipeline did not finish");
+        assert!(status.is_success());
+        assert_eq!(fs::read_to_string(dir.path().join("count.txt")).unwrap().trim(), "200000");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_yes_stops_when_the_reader_exits() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.txt").to_string_lossy().to_string();
+        let line = format!("yes ok | head -n 3 > '{}'", out);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut executor = Executor::new();
+            tx.send(run(&mut executor, &line)).unwrap();
+        });
+
+        let status = rx
+            .recv_timeout(std::time::Duration::from_secs(20))
+            .expect("yes kept running after head exited");
+        assert!(status.is_success());
+        assert_eq!(fs::read_to_string(dir.path().join("out.txt")).unwrap(), "ok\nok\nok\n");
+    }
+
+    #[test]
+    fn test_command_not_found_suggests_near_miss() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("mytool"), "").unwrap();
+        let mut executor = Executor::new();
+        executor.env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        executor.env.set("PATH", &dir.path().to_string_lossy());
+
+        assert_eq!(run(&mut executor, "mytoll --version").code, 127);
+        assert_eq!(executor.env.get_value("?").as_deref(), Some("127"));
+        assert_eq!(
+            executor.command_not_found("mytoll"),
+            "csh: mytoll: command not found. Did you mean mytool?\n"
+        );
+        // Builtins are candidates too
+        assert!(executor.command_not_found("ecoh").ends_with("Did you mean echo?\n"));
+        assert_eq!(executor.command_not_found("qqqqqqqq"), "csh: qqqqqqqq: command not found\n");
+    }
+
+    #[test]
+    fn test_command_lookup_uses_pathext_and_links() {
+        assert_eq!(executable_extensions(Some(".EXE;.Py;")), ["", ".exe", ".py"]);
+        assert_eq!(executable_extensions(None), ["", ".com", ".exe", ".bat", ".cmd"]);
+        assert_eq!(executable_extensions(Some("")).len(), 5);
+
+        // A link that can't be followed still names a command, as App
+        // Execution Aliases do on Windows
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("folder")).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.path().join("missing"), dir.path().join("alias")).unwrap();
+        #[cfg(unix)]
+        assert!(is_command_file(&dir.path().join("alias")));
+        assert!(!is_command_file(&dir.path().join("folder")));
+        assert!(!is_command_file(&dir.path().join("nothing")));
+    }
+
+    #[test]
+    fn test_streaming_delivers_chunks_as_produced() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let chunks: Rc<RefCell<Vec<String>>> = Rc::default();
+        let stream = |executor: &mut Executor, line: &str| {
+            chunks.borrow_mut().clear();
+            let sink = Rc::clone(&chunks);
+            executor.execute_streaming(&parser::parse(line).unwrap(), move |chunk| {
+                sink.borrow_mut().push(String::from_utf8_lossy(chunk).to_string());
+            })
+        };
+        let mut executor = Executor::new();
+
+        assert!(stream(&mut executor, "echo one; echo two").is_success());
+        assert_eq!(*chunks.borrow(), vec!["one\n", "two\n"]);
+
+        // An external command's lines arrive separately, not all at the end
+        #[cfg(unix)]
+        {
+            assert!(stream(&mut executor, "sh -c 'echo a; sleep 0.2; echo b; sleep 0.2; echo c'").is_success());
+            assert!(chunks.borrow().len() >= 3);
+            assert_eq!(chunks.borrow().concat(), "a\nb\nc\n");
+        }
+
+        // The previous sink is back afterwards
+        assert!(executor.sink.is_stdout());
+    }
+
+    fn run_captured(executor: &mut Executor, line: &str) -> String {
+        executor.capture(true);
+        run(executor, line);
+        String::from_utf8(executor.capture(false)).unwrap()
+    }
+
+    #[test]
+    fn test_command_substitution() {
+        let mut executor = Executor::new();
+        assert_eq!(run_captured(&mut executor, "echo $(echo hi)"), "hi\n");
+        assert_eq!(run_captured(&mut executor, "echo [$(echo $(echo hi))]"), "[hi]\n");
+        assert_eq!(run_captured(&mut executor, "echo \"[$(echo a b)]\" x$(echo y)z"), "[a b] xyz\n");
+
+        assert_eq!(run_captured(&mut executor, "echo `echo hi`"), "hi\n");
+
+        // The command's own variables are expanded when it runs
+        executor.env.set("NAME", "csh");
+        assert_eq!(run_captured(&mut executor, "echo $(echo $NAME)"), "csh\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_substitution_captures_stderr() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut executor = Executor::new();
+        executor.env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        let script = "'echo one; echo two >&2; echo three'";
+        let out = dir.path().join("out.txt");
+        let err = dir.path().join("err.txt");
+
+        // Both streams share one pipe, so they stay in order
+        run(&mut executor, &format!("X=$(sh -c {} 2>&1)", script));
+        assert_eq!(executor.env.get_value("X").as_deref(), Some("one\ntwo\nthree"));
+        run(&mut executor, &format!("X=$(sh -c {} 2> {})", script, err.display()));
+        assert_eq!(executor.env.get_value("X").as_deref(), Some("one\nthree"));
+        assert_eq!(fs::read_to_string(&err).unwrap(), "two\n");
+
+        // Stderr joins stdout where it goes at that point
+        run(&mut executor, &format!("X=$(sh -c {} > {} 2>&1)", script, out.display()));
+        assert_eq!(executor.env.get_value("X").as_deref(), Some(""));
+        assert_eq!(fs::read_to_string(&out).unwrap(), "one\ntwo\nthree\n");
+        run(&mut executor, &format!("X=$(sh -c {} 2>&1 > {})", script, out.display()));
+        assert_eq!(executor.env.get_value("X").as_deref(), Some("two"));
+
+        // Builtin errors too
+        run(&mut executor, "X=$(cd missing 2>&1)");
+        assert!(executor.env.get_value("X").unwrap().contains("missing"));
+    }
+
+    #[test]
+    fn test_single_quotes_are_literal() {
+        let mut executor = Executor::new();
+        let line = "echo '$(echo x)' '`echo y`' '$((1 + 1))' '$HOME'";
+        let output = run_captured(&mut executor, line);
+        assert_eq!(output, "$(echo x) `echo y` $((1 + 1)) $HOME\n");
+        assert_eq!(run_captured(&mut executor, "echo \"$(echo x)\""), "x\n");
+    }
+
+    #[test]
+    fn test_wildcards_expand_unless_quoted() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("b.rs"), "").unwrap();
+        fs::write(dir.path().join("a.rs"), "").unwrap();
+        let mut executor = Executor::new();
+        executor.env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+
+        assert_eq!(run_captured(&mut executor, "echo *.rs"), "a.rs b.rs\n");
+        assert_eq!(run_captured(&mut executor, "echo '*.rs' \"*.rs\""), "*.rs *.rs\n");
+        assert_eq!(run_captured(&mut executor, "echo *.md"), "*.md\n");
+        assert_eq!(run_captured(&mut executor, "echo \\*.rs a\\{1,2}"), "*.rs a{1,2}\n");
+    }
+
+    #[test]
+    fn test_brace_expansion() {
+        let mut executor = Executor::new();
+        executor.env.set("N", "x");
+        let output = run_captured(&mut executor, "echo file{1,2}.txt {3..1}");
+        assert_eq!(output, "file1.txt file2.txt 3 2 1\n");
+        assert_eq!(run_captured(&mut executor, "echo $N{a,b} '{a,b}' {}"), "xa xb {a,b} {}\n");
+    }
+
+    #[test]
+    fn test_arithmetic_expansion() {
+        let mut executor = Executor::new();
+        assert_eq!(run_captured(&mut executor, "echo $((2 + 3 * 4))"), "14\n");
+        let output = run_captured(&mut executor, "x=5; echo $((x*2)) \"$(($x - 1))\" $(( (x) ))");
+        assert_eq!(output, "10 4 5\n");
+        // Bad expressions are left as written
+        let output = run_captured(&mut executor, "echo $((1 / 0)) $((2 +))");
+        assert_eq!(output, "$((1 / 0)) $((2 +))\n");
+        assert_eq!(run_captured(&mut executor, "echo $(echo 2+3)"), "2+3\n");
+    }
+
+    #[test]
+    fn test_parameter_expansion() {
+        let mut executor = Executor::new();
+        let output = run_captured(&mut executor, "echo ${X:=a,b} \"${U:-${X}!}\" ${#X}");
+        assert_eq!(output, "a,b a,b! 3\n");
+        assert_eq!(executor.env.get_value("X").as_deref(), Some("a,b"));
+
+        // Substitutions in the word run only when it is used
+        let line = "echo ${U:-$(echo sub)} ${X:-$(echo no)} \"${V:=$(echo {a})}\" $V";
+        assert_eq!(run_captured(&mut executor, line), "sub a,b {a} {a}\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wait_returns_job_status() {
+        let mut executor = Executor::new();
+        let output = run_captured(&mut executor, "sh -c 'sleep 0.1; exit 3' &");
+        assert_eq!(output, format!("[1] {}\n", executor.jobs.list()[0].pid));
+        assert_eq!(run(&mut executor, "wait").code, 3);
+        assert!(executor.jobs.list().is_empty());
+
+        // By job number or PID, even after the job has finished
+        run_captured(&mut executor, "sh -c 'exit 4' &");
+        run_captured(&mut executor, "sh -c 'exit 5' &");
+        let pid = executor.jobs.list()[1].pid;
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(run(&mut executor, "wait %1").code, 4);
+        assert_eq!(run(&mut executor, &format!("wait {}", pid)).code, 5);
+        assert_eq!(run(&mut executor, "wait %1").code, 127);
+
+        // Ctrl+C stops waiting and leaves the job running
+        run_captured(&mut executor, "sleep 5 &");
+        let interrupted = std::sync::atomic::AtomicBool::new(true);
+        let result = wait::execute(&[], &mut executor.jobs, &interrupted);
+        assert_eq!(result.status.code, 130);
+        assert_eq!(executor.jobs.list().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kill_signals_jobs() {
+        let mut executor = Executor::new();
+        run_captured(&mut executor, "sleep 5 &");
+        run_captured(&mut executor, "sleep 5 &");
+        assert_eq!(run(&mut executor, "kill %1").code, 0);
+        assert_eq!(run(&mut executor, "wait %1").code, 128 + 15);
+        assert_eq!(run(&mut executor, "kill -s KILL %2").code, 0);
+        assert_eq!(run(&mut executor, "wait %2").code, 128 + 9);
+
+        assert_eq!(run(&mut executor, "kill %1").code, 1);
+        assert_eq!(run(&mut executor, "kill -NOPE 1").code, 1);
+        assert_eq!(run(&mut executor, "kill").code, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_timeout_stops_slow_commands() {
+        let mut executor = Executor::new();
+        let started = std::time::Instant::now();
+        assert_eq!(run(&mut executor, "timeout 5 sh -c 'exit 3'").code, 3);
+        assert_eq!(run_captured(&mut executor, "timeout 5 echo fast"), "fast\n");
+
+        // Processes the command started are stopped with it
+        assert_eq!(run(&mut executor, "timeout 0.2 sh -c 'sleep 5 & sleep 5; wait'").code, 124);
+        assert_eq!(run(&mut executor, "timeout -s KILL 0.1 sleep 5").code, 137);
+        let ignores_term = "timeout -k 0.1 0.1 sh -c 'trap \"\" TERM; sleep 5; sleep 5'";
+        assert_eq!(run(&mut executor, ignores_term).code, 137);
+        assert!(started.elapsed() < std::time::Duration::from_secs(4));
+
+        assert_eq!(run(&mut executor, "timeout soon sleep 1").code, 125);
+    }
+
+    #[test]
+    fn test_set_o_lists_options() {
+        let mut executor = Executor::new();
+        assert!(run(&mut executor, "set -o pipefail").is_success());
+
+        let listing = run_captured(&mut executor, "set -o");
+        assert!(listing.contains("pipefail       \ton\n"));
+        assert!(listing.contains("noclobber      \toff\n"));
+    }
+
+    #[test]
+    fn test_set_plus_o_round_trips() {
+        let mut original = Executor::new();
+        run(&mut original, "set -o noclobber");
+        run(&mut original, "set -o xtrace");
+        let script = run_captured(&mut original, "set +o");
+        assert!(script.contains("set -o noclobber\n"));
+        assert!(script.contains("set +o errexit\n"));
+
+        let mut restored = Executor::new();
+        restored.options.set("errexit", true);
+        for line in script.lines() {
+            assert!(run(&mut restored, line).is_success());
+        }
+        assert_eq!(restored.options, original.options);
+    }
+
+    #[test]
+    fn test_errexit_stops_at_untested_failures() {
+        let mut executor = Executor::new();
+        run(&mut executor, "set -o errexit");
+        executor.capture(true);
+        let script = "\
+if false; then echo no; fi
+while false; do echo no; done
+false && echo no
+false || echo a
+echo $(false)b
+false
+echo no
+";
+        let status = executor.execute_statements(&parser::parse_script(script).unwrap());
+        assert_eq!(status.code, 1);
+        assert!(executor.exit_requested);
+        assert_eq!(String::from_utf8(executor.capture(false)).unwrap(), "a\nb\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_equals_in_conditions() {
+        let mut executor = Executor::new();
+        executor.capture(true);
+        let script = "\
+if [ a = a ]; then echo y; fi
+if test a = b; then echo no; fi
+x=2
+while test $x != 0; do echo $x; x=$((x - 1)); done
+";
+        let status = executor.execute_statements(&parser::parse_script(script).unwrap());
+        assert!(status.is_success());
+        assert_eq!(String::from_utf8(executor.capture(false)).unwrap(), "y\n2\n1\n");
+    }
+
+    #[test]
+    fn test_pipefail_uses_the_last_failure() {
+        let mut executor = Executor::new();
+        assert_eq!(run(&mut executor, "false | true").code, 0);
+
+        run(&mut executor, "set -o pipefail");
+        assert_eq!(run(&mut executor, "false | true").code, 1);
+        assert_eq!(run(&mut executor, "sh -c 'exit 3' | sh -c 'exit 4' | true").code, 4);
+        assert_eq!(run(&mut executor, "true | true").code, 0);
+    }
+
+    #[test]
+    fn test_xtrace_lines() {
+        let args = ["a b".to_string(), String::new(), "c".to_string()];
+        assert_eq!(trace_line("echo", &args), "+ echo 'a b' '' c\n");
+        assert_eq!(trace_line("ls", &[]), "+ ls\n");
+    }
+}
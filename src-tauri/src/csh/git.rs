@@ -0,0 +1,70 @@
+//! Reading git repository metadata without running git
+//!
+//! Used for the prompt's branch name and for completing branch names.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The `.git` directory of the repository containing `dir`
+///
+/// Worktrees and submodules have a `.git` file pointing elsewhere, which
+/// is followed.
+pub fn git_dir(dir: &Path) -> Option<PathBuf> {
+    for dir in dir.ancestors() {
+        let git = dir.join(".git");
+        if git.is_file() {
+            let pointer = fs::read_to_string(&git).ok()?;
+            let target = pointer.trim().strip_prefix("gitdir:")?.trim();
+            return Some(dir.join(target));
+        } else if git.is_dir() {
+            return Some(git);
+        }
+    }
+    None
+}
+
+/// Directory holding the refs shared by all worktrees of `git_dir`
+pub fn common_dir(git_dir: &Path) -> PathBuf {
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common) => git_dir.join(common.trim()),
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+/// Local branch names, sorted: loose refs under `refs/heads` plus
+/// `packed-refs`
+pub fn branches(git_dir: &Path) -> Vec<String> {
+    let common = common_dir(git_dir);
+    let mut names = Vec::new();
+    collect_loose_refs(&common.join("refs").join("heads"), "", &mut names);
+
+    if let Ok(packed) = fs::read_to_string(common.join("packed-refs")) {
+        // "<sha> refs/heads/<name>"; `#` headers and `^` peeled tags are skipped
+        names.extend(
+            packed
+                .lines()
+                .filter_map(|line| line.split_once(' '))
+                .filter_map(|(_, name)| name.strip_prefix("refs/heads/"))
+                .map(str::to_string),
+        );
+    }
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Add the ref files below `dir` (branch names may contain `/`)
+fn collect_loose_refs(dir: &Path, prefix: &str, names: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => collect_loose_refs(&entry.path(), &format!("{}/", name), names),
+            Ok(_) => names.push(name),
+            Err(_) => {}
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Basic syntax highlighting for CSH
+//!
+//! Applies ANSI colors to keywords, strings, comments and numbers.
+//! Languages are described by `SyntaxRules` entries in `LANGUAGES`;
+//! supporting a new language only requires adding a table entry.
+
+const KEYWORD_COLOR: &str = "\x1b[1;35m";
+const STRING_COLOR: &str = "\x1b[32m";
+const COMMENT_COLOR: &str = "\x1b[90m";
+const NUMBER_COLOR: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Highlighting rules for a single language
+#[derive(Debug)]
+pub struct SyntaxRules {
+    /// File extensions (lowercase, without dot) this language applies to
+    pub extensions: &'static [&'static str],
+    /// Reserved words to highlight
+    pub keywords: &'static [&'static str],
+    /// Marker that starts a comment running to end of line
+    pub line_comment: Option<&'static str>,
+    /// Start and end markers of a block comment
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// Characters that delimit string literals
+    pub string_delimiters: &'static [char],
+}
+
+const C_LIKE_STRINGS: &[char] = &['"', '\''];
+
+/// All known languages
+pub static LANGUAGES: &[SyntaxRules] = &[
+    SyntaxRules {
+        extensions: &["rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else",
+            "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop",
+            "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static",
+            "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: &['"'],
+    },
+    SyntaxRules {
+        extensions: &["py", "pyw"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def",
+            "del", "elif", "else", "except", "False", "finally", "for", "from", "global",
+            "if", "import", "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass",
+            "raise", "return", "True", "try", "while", "with", "yield",
+        ],
+        line_comment: Some("#"),
+        block_comment: None,
+        string_delimiters: C_LIKE_STRINGS,
+    },
+    SyntaxRules {
+        extensions: &["js", "mjs", "cjs", "jsx", "ts", "tsx", "mts"],
+        keywords: &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue",
+            "default", "delete", "do", "else", "export", "extends", "false", "finally",
+            "for", "function", "if", "import", "in", "instanceof", "interface", "let",
+            "new", "null", "return", "switch", "this", "throw", "true", "try", "type",
+            "typeof", "undefined", "var", "void", "while", "yield",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: &['"', '\'', '`'],
+    },
+    SyntaxRules {
+        extensions: &["c", "h", "cpp", "cc", "cxx", "hpp", "cs", "java", "go"],
+        keywords: &[
+            "break", "case", "char", "class", "const", "continue", "default", "do",
+            "double", "else", "enum", "false", "float", "for", "func", "if", "import",
+            "int", "interface", "long", "namespace", "new", "package", "private",
+            "protected", "public", "return", "static", "struct", "switch", "this",
+            "true", "typedef", "var", "void", "while",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: C_LIKE_STRINGS,
+    },
+    SyntaxRules {
+        extensions: &["sh", "bash", "zsh", "csh"],
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for",
+            "function", "if", "in", "local", "return", "then", "while",
+        ],
+        line_comment: Some("#"),
+        block_comment: None,
+        string_delimiters: C_LIKE_STRINGS,
+    },
+    SyntaxRules {
+        extensions: &["toml", "ini", "cfg", "conf", "yaml", "yml"],
+        keywords: &["true", "false", "null"],
+        line_comment: Some("#"),
+        block_comment: None,
+        string_delimiters: C_LIKE_STRINGS,
+    },
+    SyntaxRules {
+        extensions: &["json"],
+        keywords: &["true", "false", "null"],
+        line_comment: None,
+        block_comment: None,
+        string_delimiters: &['"'],
+    },
+];
+
+/// Look up the rules for a file extension (case-insensitive)
+pub fn rules_for_extension(ext: &str) -> Option<&'static SyntaxRules> {
+    let ext = ext.to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|rules| rules.extensions.contains(&ext.as_str()))
+}
+
+/// Stateful highlighter (tracks block comments across lines)
+pub struct Highlighter {
+    rules: &'static SyntaxRules,
+    in_block_comment: bool,
+}
+
+impl Highlighter {
+    pub fn new(rules: &'static SyntaxRules) -> Self {
+        Self {
+            rules,
+            in_block_comment: false,
+        }
+    }
+
+    /// Highlight a single line (without its line terminator)
+    pub fn highlight_line(&mut self, line: &str) -> String {
+        let mut output = String::with_capacity(line.len() * 2);
+        let mut rest = line;
+
+        while !rest.is_empty() {
+            if self.in_block_comment {
+                let end = self.rules.block_comment.map(|(_, end)| end).unwrap_or("");
+                match rest.find(end) {
+                    Some(pos) => {
+                        let comment_end = pos + end.len();
+                        push_colored(&mut output, COMMENT_COLOR, &rest[..comment_end]);
+                        rest = &rest[comment_end..];
+                        self.in_block_comment = false;
+                    }
+                    None => {
+                        push_colored(&mut output, COMMENT_COLOR, rest);
+                        rest = "";
+                    }
+                }
+                continue;
+            }
+
+            if let Some(marker) = self.rules.line_comment {
+                if rest.starts_with(marker) {
+                    push_colored(&mut output, COMMENT_COLOR, rest);
+                    break;
+                }
+            }
+
+            if let Some((start, _)) = self.rules.block_comment {
+                if rest.starts_with(start) {
+                    self.in_block_comment = true;
+                    continue;
+                }
+            }
+
+            let c = rest.chars().next().unwrap();
+
+            if self.rules.string_delimiters.contains(&c) {
+                let len = string_literal_len(rest, c);
+                push_colored(&mut output, STRING_COLOR, &rest[..len]);
+                rest = &rest[len..];
+            } else if c.is_ascii_digit() {
+                let len = rest
+                    .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '.' || ch == '_'))
+                    .unwrap_or(rest.len());
+                push_colored(&mut output, NUMBER_COLOR, &rest[..len]);
+                rest = &rest[len..];
+            } else if c.is_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+                    .unwrap_or(rest.len());
+                let word = &rest[..len];
+                if self.rules.keywords.contains(&word) {
+                    push_colored(&mut output, KEYWORD_COLOR, word);
+                } else {
+                    output.push_str(word);
+                }
+                rest = &rest[len..];
+            } else {
+                output.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+
+        output
+    }
+}
+
+/// Byte length of the string literal at the start of `text`, including
+/// quotes. Unterminated literals run to the end of the line.
+fn string_literal_len(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return i + c.len_utf8();
+        }
+    }
+    text.len()
+}
+
+fn push_colored(output: &mut String, color: &str, text: &str) {
+    output.push_str(color);
+    output.push_str(text);
+    output.push_str(RESET);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_tokens() {
+        let rules = rules_for_extension("RS").unwrap();
+        let mut highlighter = Highlighter::new(rules);
+        let line = highlighter.highlight_line("let s = \"fn\"; // done");
+
+        assert!(line.starts_with("\x1b[1;35mlet\x1b[0m"));
+        assert!(line.contains("\x1b[32m\"fn\"\x1b[0m"));
+        assert!(line.contains("\x1b[90m// done\x1b[0m"));
+    }
+
+    #[test]
+    fn test_block_comment_spans_lines() {
+        let rules = rules_for_extension("rs").unwrap();
+        let mut highlighter = Highlighter::new(rules);
+
+        highlighter.highlight_line("/* start");
+        let line = highlighter.highlight_line("still */ fn");
+        assert!(line.starts_with("\x1b[90mstill */\x1b[0m"));
+        assert!(line.ends_with("\x1b[1;35mfn\x1b[0m"));
+    }
+
+    #[test]
+    fn test_unknown_extension() {
+        assert!(rules_for_extension("xyz").is_none());
+    }
+}
@@ -1,15 +1,71 @@
 //! Command history for CSH
+//!
+//! The history file has one command per line. With `set -o
+//! extendedhistory`, a command may be preceded by a `#csh:STATUS:CWD` line
+//! recording how it exited and where it ran; files without such lines
+//! load as before.
 
 use std::collections::VecDeque;
 use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::csh::environment::MAX_VISITED_DIRS;
+
+/// Start of the line holding an entry's details in the history file
+const DETAILS_PREFIX: &str = "#csh:";
+
+/// How a history entry ran
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryDetails {
+    pub status: i32,
+    pub cwd: PathBuf,
+}
+
+impl EntryDetails {
+    /// Parse a `#csh:STATUS:CWD` line
+    fn parse(line: &str) -> Option<Self> {
+        let (status, cwd) = line.strip_prefix(DETAILS_PREFIX)?.split_once(':')?;
+        Some(Self {
+            status: status.parse().ok()?,
+            cwd: PathBuf::from(cwd),
+        })
+    }
+
+    fn to_line(&self) -> String {
+        format!("{}{}:{}", DETAILS_PREFIX, self.status, self.cwd.display())
+    }
+}
+
+/// A command with its details, as kept in the history file
+type Record = (String, Option<EntryDetails>);
+
+/// Commands that look like they carry a password or token, which are
+/// kept out of the history unless turned off (see `set_ignore`)
+///
+/// Matched like `HISTIGNORE` patterns against the whole line, ignoring case.
+const SECRET_PATTERNS: &[&str] = &[
+    "*password=*",
+    "*passwd=*",
+    "*token=*",
+    "*secret=*",
+    "*api_key=*",
+    "*apikey=*",
+    "*--password *",
+    "mysql* -p[! ]*",
+    "*authorization:*",
+    "*://*:*@*",
+];
 
 /// Manages command history
 #[derive(Debug)]
 pub struct History {
     /// History entries
     entries: VecDeque<String>,
+    /// Details of each entry, in step with `entries`
+    details: VecDeque<Option<EntryDetails>>,
+    /// Event number of the oldest entry (numbers never shift within a session)
+    first_number: usize,
     /// Maximum history size
     max_size: usize,
     /// Current position when navigating history
@@ -18,16 +74,30 @@ pub struct History {
     file_path: Option<PathBuf>,
     /// Whether to save on each command
     autosave: bool,
+    /// Commands added since the history file was last written
+    unsaved: Vec<Record>,
+    /// Hold new entries back from the file until `annotate_last`
+    record_details: bool,
+    /// `HISTIGNORE` patterns: matching commands are never recorded
+    ignore_patterns: Vec<glob::Pattern>,
+    /// Also leave out commands matching `SECRET_PATTERNS`
+    ignore_secrets: bool,
 }
 
 impl History {
     pub fn new(max_size: usize) -> Self {
         Self {
             entries: VecDeque::with_capacity(max_size),
+            details: VecDeque::with_capacity(max_size),
+            first_number: 1,
             max_size,
             position: 0,
             file_path: None,
             autosave: true,
+            unsaved: Vec::new(),
+            record_details: false,
+            ignore_patterns: Vec::new(),
+            ignore_secrets: true,
         }
     }
 
@@ -39,38 +109,114 @@ impl History {
         history
     }
 
+    /// File the history is saved to, if any
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+
+    /// Wait for each new entry's details before saving it
+    ///
+    /// Set while `extendedhistory` is on; `annotate_last` then saves.
+    pub fn set_record_details(&mut self, enabled: bool) {
+        self.record_details = enabled;
+    }
+
+    /// Choose which commands are left out of the history
+    ///
+    /// `histignore` is a `HISTIGNORE` value: glob patterns separated by
+    /// `:`, each matched against the whole command. `secrets` adds the
+    /// built-in patterns for commands that look like they hold a password.
+    pub fn set_ignore(&mut self, histignore: &str, secrets: bool) {
+        self.ignore_patterns = histignore
+            .split(':')
+            .filter(|pattern| !pattern.is_empty())
+            .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+            .collect();
+        self.ignore_secrets = secrets;
+    }
+
+    /// Whether `command` is kept out of the history: it starts with a
+    /// space (privacy feature) or matches an ignore pattern
+    pub fn is_ignored(&self, command: &str) -> bool {
+        if command.starts_with(' ') {
+            return true;
+        }
+        if self.ignore_patterns.iter().any(|pattern| pattern.matches(command)) {
+            return true;
+        }
+        let any_case = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        self.ignore_secrets
+            && SECRET_PATTERNS.iter().any(|pattern| {
+                glob::Pattern::new(pattern).is_ok_and(|p| p.matches_with(command, any_case))
+            })
+    }
+
     /// Add a command to history
-    pub fn add(&mut self, command: String) {
-        // Don't add empty commands or duplicates of the last command
-        if command.is_empty() {
-            return;
+    ///
+    /// Returns whether the command is now the latest entry (it may already
+    /// have been), so `annotate_last` describes it.
+    pub fn add(&mut self, command: String) -> bool {
+        // Don't add empty or ignored commands, or duplicates of the last one
+        if command.is_empty() || self.is_ignored(&command) {
+            return false;
         }
 
         if let Some(last) = self.entries.back() {
             if last == &command {
-                return;
+                return true;
             }
         }
 
-        // Don't add commands that start with space (privacy feature)
-        if command.starts_with(' ') {
-            return;
-        }
-
         // Remove oldest if at capacity
         if self.entries.len() >= self.max_size {
             self.entries.pop_front();
+            self.details.pop_front();
+            self.first_number += 1;
         }
 
-        self.entries.push_back(command);
+        self.entries.push_back(command.clone());
+        self.details.push_back(None);
         self.position = self.entries.len();
+        if self.file_path.is_some() {
+            self.unsaved.push((command, None));
+        }
 
         // Autosave if enabled
+        if self.autosave && !self.record_details {
+            self.save();
+        }
+        true
+    }
+
+    /// Record how the most recent entry ran
+    pub fn annotate_last(&mut self, status: i32, cwd: PathBuf) {
+        let Some(last) = self.details.back_mut() else {
+            return;
+        };
+        let details = EntryDetails { status, cwd };
+        *last = Some(details.clone());
+        if let Some((command, unsaved)) = self.unsaved.last_mut() {
+            if Some(&*command) == self.entries.back() {
+                *unsaved = Some(details);
+            }
+        }
+
         if self.autosave {
             self.save();
         }
     }
 
+    /// Details of the entry with event number `number`, if recorded
+    pub fn details_by_number(&self, number: usize) -> Option<&EntryDetails> {
+        number
+            .checked_sub(self.first_number)
+            .and_then(|index| self.details.get(index))
+            .and_then(Option::as_ref)
+    }
+
     /// Get previous command (for up arrow)
     pub fn previous(&mut self) -> Option<&String> {
         if self.entries.is_empty() || self.position == 0 {
@@ -103,6 +249,106 @@ impl History {
         self.entries.get(index)
     }
 
+    /// Get the event number of the entry at `index`
+    pub fn number_at(&self, index: usize) -> usize {
+        self.first_number + index
+    }
+
+    /// Get command by event number (as shown by `history` and used by `!n`)
+    pub fn get_by_number(&self, number: usize) -> Option<&String> {
+        number
+            .checked_sub(self.first_number)
+            .and_then(|index| self.entries.get(index))
+    }
+
+    /// Expand history references (`!!`, `!n`, `!-n`, `!word`) in a command line
+    ///
+    /// Returns `Ok(None)` when the line contains no references.
+    /// References inside single quotes are left alone, as is a `!` followed
+    /// by anything else (`!-x`, `! cmd`, `!=`).
+    pub fn expand(&self, line: &str) -> Result<Option<String>, String> {
+        let mut result = String::with_capacity(line.len());
+        let mut chars = line.chars().peekable();
+        let mut in_single_quote = false;
+        let mut expanded = false;
+
+        while let Some(c) = chars.next() {
+            if c == '\'' {
+                in_single_quote = !in_single_quote;
+            }
+            if c != '!' || in_single_quote {
+                result.push(c);
+                continue;
+            }
+
+            let reference = match chars.peek().copied() {
+                Some('!') => {
+                    chars.next();
+                    "!".to_string()
+                }
+                Some(d) if d.is_ascii_digit() || d == '-' => {
+                    let mut rest = chars.clone();
+                    rest.next();
+                    if d == '-' && !rest.peek().is_some_and(char::is_ascii_digit) {
+                        result.push(c);
+                        continue;
+                    }
+                    let mut spec = String::new();
+                    if d == '-' {
+                        spec.push(chars.next().unwrap());
+                    }
+                    while let Some(&d) = chars.peek() {
+                        if !d.is_ascii_digit() {
+                            break;
+                        }
+                        spec.push(d);
+                        chars.next();
+                    }
+                    spec
+                }
+                Some(w) if w.is_alphabetic() || w == '_' => {
+                    let mut word = String::new();
+                    while let Some(&w) = chars.peek() {
+                        if w.is_whitespace() || ";&|()<>'\"".contains(w) {
+                            break;
+                        }
+                        word.push(w);
+                        chars.next();
+                    }
+                    word
+                }
+                _ => {
+                    result.push(c);
+                    continue;
+                }
+            };
+
+            let command = match reference.as_str() {
+                "!" => self.entries.back(),
+                word if !word.starts_with(|d: char| d == '-' || d.is_ascii_digit()) => {
+                    self.entries.iter().rev().find(|command| command.starts_with(word))
+                }
+                spec => match spec.strip_prefix('-') {
+                    Some(offset) => offset
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .and_then(|n| self.entries.len().checked_sub(n))
+                        .and_then(|index| self.entries.get(index)),
+                    None => spec.parse().ok().and_then(|n| self.get_by_number(n)),
+                },
+            };
+
+            match command {
+                Some(command) => result.push_str(command),
+                None => return Err(format!("!{}: event not found", reference)),
+            }
+            expanded = true;
+        }
+
+        Ok(if expanded { Some(result) } else { None })
+    }
+
     /// Get last N commands
     pub fn last_n(&self, n: usize) -> Vec<&String> {
         self.entries
@@ -130,9 +376,12 @@ impl History {
         self.entries.is_empty()
     }
 
-    /// Clear history
+    /// Clear history (in memory; the history file is left alone)
     pub fn clear(&mut self) {
+        self.unsaved.clear();
+        self.first_number += self.entries.len();
         self.entries.clear();
+        self.details.clear();
         self.position = 0;
     }
 
@@ -154,36 +403,109 @@ impl History {
     }
 
     /// Load history from file
-    fn load_from_file(&mut self, path: &PathBuf) {
-        if let Ok(file) = File::open(path) {
-            let reader = BufReader::new(file);
-            for line in reader.lines().flatten() {
-                if !line.is_empty() && self.entries.len() < self.max_size {
-                    self.entries.push_back(line);
-                }
+    fn load_from_file(&mut self, path: &Path) {
+        for (command, details) in read_records(path) {
+            if self.entries.len() < self.max_size {
+                self.entries.push_back(command);
+                self.details.push_back(details);
             }
-            self.position = self.entries.len();
         }
+        self.position = self.entries.len();
     }
 
     /// Save history to file
-    pub fn save(&self) {
-        if let Some(ref path) = self.file_path {
-            if let Some(parent) = path.parent() {
-                let _ = std::fs::create_dir_all(parent);
-            }
+    ///
+    /// Like bash's `histappend`: the file is re-read and only the commands
+    /// added since the last save are appended, so concurrent sessions
+    /// sharing the file don't overwrite each other's history.
+    pub fn save(&mut self) {
+        let Some(path) = self.file_path.clone() else {
+            return;
+        };
+        if self.unsaved.is_empty() {
+            return;
+        }
+
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
 
-            if let Ok(mut file) = OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(path)
-            {
-                for entry in &self.entries {
-                    let _ = writeln!(file, "{}", entry);
+        let mut merged = read_records(&path);
+        for entry in &self.unsaved {
+            if merged.last().map(|(command, _)| command) != Some(&entry.0) {
+                merged.push(entry.clone());
+            }
+        }
+        let excess = merged.len().saturating_sub(self.max_size);
+
+        if let Ok(mut file) = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+        {
+            let written = merged[excess..].iter().try_for_each(|(command, details)| {
+                if let Some(details) = details {
+                    writeln!(file, "{}", details.to_line())?;
                 }
+                writeln!(file, "{}", command)
+            });
+            if written.is_ok() {
+                self.unsaved.clear();
+            }
+        }
+    }
+
+    /// File the `cd` directory history is kept in, next to the history file
+    fn dirs_file(&self) -> Option<PathBuf> {
+        self.file_path.as_ref().map(|path| path.with_file_name("csh_dirs"))
+    }
+
+    /// Load the directories saved by `save_dirs`, most recent first
+    pub fn load_dirs(&self) -> Vec<PathBuf> {
+        let Some(path) = self.dirs_file() else {
+            return Vec::new();
+        };
+        match File::open(path) {
+            Ok(file) => BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .take(MAX_VISITED_DIRS)
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Save visited directories, most recent first
+    ///
+    /// Directories already in the file that `dirs` doesn't mention are
+    /// kept after them, so other sessions' entries survive.
+    pub fn save_dirs(&self, dirs: &[PathBuf]) {
+        let Some(path) = self.dirs_file() else {
+            return;
+        };
+        if dirs.is_empty() {
+            return;
+        }
+
+        let mut merged = dirs.to_vec();
+        for dir in self.load_dirs() {
+            if !merged.contains(&dir) {
+                merged.push(dir);
             }
         }
+        merged.truncate(MAX_VISITED_DIRS);
+
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(mut file) = File::create(&path) {
+            let _ = merged
+                .iter()
+                .try_for_each(|dir| writeln!(file, "{}", dir.display()));
+        }
     }
 
     /// Get history file path
@@ -195,6 +517,25 @@ impl History {
     }
 }
 
+/// Commands in a history file, each with the details line before it
+fn read_records(path: &Path) -> Vec<Record> {
+    let Ok(file) = File::open(path) else {
+        return Vec::new();
+    };
+    let mut records = Vec::new();
+    let mut details = None;
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        if line.is_empty() {
+            continue;
+        }
+        match EntryDetails::parse(&line) {
+            Some(parsed) => details = Some(parsed),
+            None => records.push((line, details.take())),
+        }
+    }
+    records
+}
+
 impl Default for History {
     fn default() -> Self {
         Self::new(1000)
@@ -234,6 +575,62 @@ mod tests {
         assert_eq!(history.len(), 1);
     }
 
+    #[test]
+    fn test_numbers_stay_stable_when_trimmed() {
+        let mut history = History::new(2);
+        history.autosave = false;
+        history.add("cmd1".to_string());
+        history.add("cmd2".to_string());
+        history.add("cmd3".to_string());
+
+        assert_eq!(history.number_at(0), 2);
+        assert_eq!(history.get_by_number(1), None);
+        assert_eq!(history.get_by_number(3), Some(&"cmd3".to_string()));
+    }
+
+    #[test]
+    fn test_expand() {
+        let mut history = History::new(100);
+        history.autosave = false;
+        history.add("ls -la".to_string());
+        history.add("cd /tmp".to_string());
+
+        assert_eq!(history.expand("echo hi").unwrap(), None);
+        assert_eq!(history.expand("!1").unwrap(), Some("ls -la".to_string()));
+        assert_eq!(history.expand("!-1").unwrap(), Some("cd /tmp".to_string()));
+        assert_eq!(history.expand("sudo !!").unwrap(), Some("sudo cd /tmp".to_string()));
+        assert_eq!(history.expand("echo '!1'").unwrap(), None);
+        assert!(history.expand("!9").is_err());
+
+        // The most recent command starting with a word
+        assert_eq!(history.expand("!ls").unwrap(), Some("ls -la".to_string()));
+        assert_eq!(history.expand("!c; pwd").unwrap(), Some("cd /tmp; pwd".to_string()));
+        assert!(history.expand("!git").is_err());
+
+        // Anything else after `!` is not a reference
+        assert_eq!(history.expand("echo !-x ! != hi!").unwrap(), None);
+    }
+
+    #[test]
+    fn test_concurrent_sessions_merge_on_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("csh_history");
+        std::fs::write(&path, "old\n").unwrap();
+
+        let mut first = History::with_file(100, path.clone());
+        let mut second = History::with_file(100, path.clone());
+        first.add("first-1".to_string());
+        second.add("second-1".to_string());
+        first.add("first-2".to_string());
+        second.autosave = false;
+        second.add("second-2".to_string());
+        second.save();
+
+        let reloaded = History::with_file(100, path);
+        let entries: Vec<&str> = reloaded.all().into_iter().map(String::as_str).collect();
+        assert_eq!(entries, vec!["old", "first-1", "second-1", "first-2", "second-2"]);
+    }
+
     #[test]
     fn test_search() {
         let mut history = History::new(100);
@@ -244,4 +641,58 @@ mod tests {
         let results = history.search("ls");
         assert_eq!(results.len(), 2);
     }
+
+    #[test]
+    fn test_details_round_trip_through_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("csh_history");
+        std::fs::write(&path, "plain\n").unwrap();
+
+        let mut history = History::with_file(100, path.clone());
+        history.set_record_details(true);
+        history.add("make".to_string());
+        history.annotate_last(2, PathBuf::from("C:\\src:x"));
+        history.set_record_details(false);
+        history.add("ls".to_string());
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text, "plain\n#csh:2:C:\\src:x\nmake\nls\n");
+
+        let reloaded = History::with_file(100, path);
+        let entries: Vec<&str> = reloaded.all().into_iter().map(String::as_str).collect();
+        assert_eq!(entries, vec!["plain", "make", "ls"]);
+        assert_eq!(reloaded.details_by_number(1), None);
+        let details = reloaded.details_by_number(2).unwrap();
+        assert_eq!((details.status, details.cwd.clone()), (2, PathBuf::from("C:\\src:x")));
+        assert_eq!(reloaded.details_by_number(3), None);
+    }
+
+    #[test]
+    fn test_secrets_and_histignore_are_not_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("csh_history");
+        let mut history = History::with_file(100, path.clone());
+
+        assert!(history.add("ls".to_string()));
+        assert!(!history.add("export DB_PASSWORD=hunter2".to_string()));
+        assert!(!history.add("mysql -u root -phunter2".to_string()));
+        assert!(!history.add("curl -H 'Authorization: Bearer abc' https://x".to_string()));
+        assert!(!history.add(" echo private".to_string()));
+        assert!(history.add("mkdir -p build".to_string()));
+
+        history.set_ignore("cd *:pwd", true);
+        assert!(!history.add("cd /tmp".to_string()));
+        assert!(!history.add("pwd".to_string()));
+        assert!(history.add("pwd -P".to_string()));
+
+        // Neither in memory nor in the file
+        let entries: Vec<&str> = history.all().into_iter().map(String::as_str).collect();
+        assert_eq!(entries, vec!["ls", "mkdir -p build", "pwd -P"]);
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text, "ls\nmkdir -p build\npwd -P\n");
+
+        // The built-in patterns can be turned off
+        history.set_ignore("", false);
+        assert!(history.add("export TOKEN=abc".to_string()));
+    }
 }
@@ -0,0 +1,252 @@
+//! File type icons for `ls`
+//!
+//! Each `IconTheme` in `THEMES` maps file kinds and extensions to glyphs.
+//! Supporting another icon font only requires adding a table entry.
+//!
+//! Terminals disagree on how wide emoji are, so interactive shells measure
+//! it once at startup (`measure_emoji_width`) and `ls` lays out its grid
+//! with the measured width.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crossterm::cursor::{self, MoveToColumn};
+use crossterm::terminal::{Clear, ClearType};
+use crossterm::queue;
+
+/// Icons for one glyph set
+#[derive(Debug)]
+pub struct IconTheme {
+    /// Name used by `ls --icons=<name>` and `CSH_LS_ICONS`
+    pub name: &'static str,
+    pub directory: &'static str,
+    pub symlink: &'static str,
+    /// Files with no icon of their own that are executable
+    pub executable: &'static str,
+    /// Everything else
+    pub file: &'static str,
+    /// Icons by lowercase extension (or whole file name, e.g. `dockerfile`)
+    pub extensions: &'static [(&'static [&'static str], &'static str)],
+    /// Terminal columns each icon takes up
+    pub width: usize,
+}
+
+impl IconTheme {
+    /// Terminal columns each icon takes up on this terminal
+    ///
+    /// For emoji that is the width measured at startup, else `configured`
+    /// (`CSH_EMOJI_WIDTH`), else the theme's own assumption.
+    pub fn columns(&self, configured: Option<usize>) -> usize {
+        if !std::ptr::eq(self, &EMOJI) {
+            return self.width;
+        }
+        measured_emoji_width().or(configured).unwrap_or(self.width)
+    }
+
+    /// Icon for a file with the given name and kind
+    pub fn icon(&self, name: &str, is_dir: bool, is_symlink: bool, is_executable: bool) -> &'static str {
+        if is_dir {
+            return self.directory;
+        }
+        if is_symlink {
+            return self.symlink;
+        }
+
+        let ext = name.rsplit('.').next().unwrap_or("").to_lowercase();
+        self.extensions
+            .iter()
+            .find(|(exts, _)| exts.contains(&ext.as_str()))
+            .map(|(_, icon)| *icon)
+            .unwrap_or(if is_executable { self.executable } else { self.file })
+    }
+}
+
+/// Unicode emoji, readable without any special font
+pub static EMOJI: IconTheme = IconTheme {
+    name: "emoji",
+    directory: "📁",
+    symlink: "🔗",
+    executable: "⚡",
+    file: "📄",
+    extensions: &[
+        // Programming languages
+        (&["rs"], "🦀"),
+        (&["py"], "🐍"),
+        (&["js", "mjs", "cjs"], "📜"),
+        (&["ts", "tsx", "mts"], "📘"),
+        (&["jsx"], "⚛️"),
+        (&["go"], "🔷"),
+        (&["rb"], "💎"),
+        (&["php"], "🐘"),
+        (&["java", "class", "jar"], "☕"),
+        (&["c", "h"], "🔧"),
+        (&["cpp", "cc", "cxx", "hpp"], "⚙️"),
+        (&["cs"], "🎯"),
+        (&["swift"], "🦅"),
+        (&["kt", "kts"], "🎨"),
+        (&["lua"], "🌙"),
+        (&["sh", "bash", "zsh", "fish"], "🐚"),
+        (&["ps1", "psm1"], "💠"),
+        // Config & Data
+        (&["json"], "📋"),
+        (&["yaml", "yml"], "⚙️"),
+        (&["toml"], "📦"),
+        (&["xml"], "📰"),
+        (&["csv"], "📊"),
+        (&["sql"], "🗃️"),
+        (&["env"], "🔐"),
+        (&["ini", "cfg", "conf"], "🔩"),
+        // Documents
+        (&["md", "markdown"], "📝"),
+        (&["txt"], "📄"),
+        (&["pdf"], "📕"),
+        (&["doc", "docx"], "📘"),
+        (&["xls", "xlsx"], "📗"),
+        (&["ppt", "pptx"], "📙"),
+        (&["rtf"], "📃"),
+        // Media
+        (&["png", "jpg", "jpeg", "gif", "bmp", "ico", "webp"], "🖼️"),
+        (&["svg"], "🎨"),
+        (&["mp3", "wav", "flac", "ogg", "m4a"], "🎵"),
+        (&["mp4", "mkv", "avi", "mov", "webm"], "🎬"),
+        // Archives and packages
+        (&["zip", "rar", "7z", "tar", "gz", "bz2", "xz", "deb", "rpm"], "📦"),
+        // Executables
+        (&["exe", "msi"], "⚡"),
+        (&["bat", "cmd"], "🖥️"),
+        (&["app", "dmg"], "📱"),
+        // Web
+        (&["html", "htm"], "🌐"),
+        (&["css", "scss", "sass", "less"], "🎨"),
+        (&["vue"], "💚"),
+        (&["svelte"], "🧡"),
+        // Special files
+        (&["lock"], "🔒"),
+        (&["gitignore", "gitattributes", "gitmodules"], "🔀"),
+        (&["dockerfile"], "🐳"),
+        (&["license", "licence"], "📜"),
+    ],
+    width: 2,
+};
+
+/// Nerd Font glyphs (private use area codepoints)
+pub static NERD: IconTheme = IconTheme {
+    name: "nerd",
+    directory: "\u{f07b}",
+    symlink: "\u{f0c1}",
+    executable: "\u{f489}",
+    file: "\u{f15b}",
+    extensions: &[
+        // Programming languages
+        (&["rs"], "\u{e7a8}"),
+        (&["py"], "\u{e73c}"),
+        (&["js", "mjs", "cjs"], "\u{e74e}"),
+        (&["ts", "mts"], "\u{e628}"),
+        (&["jsx", "tsx"], "\u{e7ba}"),
+        (&["go"], "\u{e627}"),
+        (&["rb"], "\u{e739}"),
+        (&["php"], "\u{e73d}"),
+        (&["java", "class", "jar"], "\u{e738}"),
+        (&["c", "h"], "\u{e61e}"),
+        (&["cpp", "cc", "cxx", "hpp"], "\u{e61d}"),
+        (&["cs"], "\u{e648}"),
+        (&["swift"], "\u{e755}"),
+        (&["kt", "kts"], "\u{e634}"),
+        (&["lua"], "\u{e620}"),
+        (&["sh", "bash", "zsh", "fish", "ps1", "psm1", "bat", "cmd"], "\u{f489}"),
+        // Config & Data
+        (&["json"], "\u{e60b}"),
+        (&["yaml", "yml", "toml", "ini", "cfg", "conf"], "\u{e615}"),
+        (&["xml"], "\u{e619}"),
+        (&["csv", "xls", "xlsx"], "\u{f1c3}"),
+        (&["sql"], "\u{f1c0}"),
+        (&["env", "lock"], "\u{f023}"),
+        // Documents
+        (&["md", "markdown"], "\u{e609}"),
+        (&["txt", "rtf", "license", "licence"], "\u{f15c}"),
+        (&["pdf"], "\u{f1c1}"),
+        (&["doc", "docx"], "\u{f1c2}"),
+        (&["ppt", "pptx"], "\u{f1c4}"),
+        // Media
+        (&["png", "jpg", "jpeg", "gif", "bmp", "ico", "webp", "svg"], "\u{f1c5}"),
+        (&["mp3", "wav", "flac", "ogg", "m4a"], "\u{f1c7}"),
+        (&["mp4", "mkv", "avi", "mov", "webm"], "\u{f1c8}"),
+        // Archives and packages
+        (&["zip", "rar", "7z", "tar", "gz", "bz2", "xz", "deb", "rpm", "dmg"], "\u{f1c6}"),
+        // Web
+        (&["html", "htm"], "\u{e736}"),
+        (&["css", "scss", "sass", "less"], "\u{e749}"),
+        (&["vue"], "\u{e6a0}"),
+        (&["svelte"], "\u{e697}"),
+        // Special files
+        (&["gitignore", "gitattributes", "gitmodules"], "\u{e702}"),
+        (&["dockerfile"], "\u{e7b0}"),
+    ],
+    width: 1,
+};
+
+/// Printed to find out how wide the terminal draws emoji
+const EMOJI_SAMPLE: &str = "\u{1f4c1}";
+
+/// Emoji width learned by `measure_emoji_width`, 0 until then
+static MEASURED_EMOJI_WIDTH: AtomicUsize = AtomicUsize::new(0);
+
+/// The emoji width measured at startup, if any
+pub fn measured_emoji_width() -> Option<usize> {
+    match MEASURED_EMOJI_WIDTH.load(Ordering::Relaxed) {
+        0 => None,
+        width => Some(width),
+    }
+}
+
+/// Learn how many columns the terminal gives an emoji
+///
+/// Prints a sample emoji between two cursor position queries (`CSI 6n`)
+/// and erases it again. Only call this on an interactive terminal: one
+/// that never answers costs the query's timeout. Returns the width, which
+/// `columns` uses from then on, or `None` if the terminal didn't answer.
+pub fn measure_emoji_width() -> Option<usize> {
+    let mut stdout = io::stdout();
+    let (start, _) = cursor::position().ok()?;
+    write!(stdout, "{}", EMOJI_SAMPLE).ok()?;
+    stdout.flush().ok()?;
+    let end = cursor::position().map(|(column, _)| column);
+
+    let _ = queue!(stdout, MoveToColumn(start), Clear(ClearType::UntilNewLine));
+    let _ = stdout.flush();
+
+    let width = end.ok()?.checked_sub(start)? as usize;
+    if !(1..=2).contains(&width) {
+        return None;
+    }
+    MEASURED_EMOJI_WIDTH.store(width, Ordering::Relaxed);
+    Some(width)
+}
+
+/// All known themes; the first is the default
+pub static THEMES: &[&IconTheme] = &[&EMOJI, &NERD];
+
+/// Look up a theme by name; `nerdfont` is accepted for `nerd`
+pub fn theme_by_name(name: &str) -> Option<&'static IconTheme> {
+    let name = match name {
+        "nerdfont" => "nerd",
+        other => other,
+    };
+    THEMES.iter().copied().find(|theme| theme.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_and_kind_lookup() {
+        assert_eq!(EMOJI.icon("main.RS", false, false, false), "🦀");
+        assert_eq!(NERD.icon("main.rs", false, false, false), "\u{e7a8}");
+        assert_eq!(NERD.icon("src", true, false, false), NERD.directory);
+        assert_eq!(EMOJI.icon("run", false, false, true), EMOJI.executable);
+        assert_eq!(EMOJI.icon("notes", false, false, false), EMOJI.file);
+        assert!(std::ptr::eq(theme_by_name("nerdfont").unwrap(), &NERD));
+        assert!(theme_by_name("bogus").is_none());
+    }
+}
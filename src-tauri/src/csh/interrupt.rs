@@ -0,0 +1,78 @@
+//! Ctrl+C handling for long-running builtins
+//!
+//! Builtins run inside the shell process, so an interrupt must not take
+//! the shell down with them. `install` registers a console handler (a
+//! SIGINT handler on Unix) that only raises a flag; builtins that loop
+//! (such as `yes`) poll it and stop. External commands still receive the
+//! console event or signal themselves.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The shared interrupt flag
+pub fn flag() -> &'static AtomicBool {
+    &INTERRUPTED
+}
+
+/// Forget an earlier interrupt (call before starting a new command)
+pub fn clear() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+/// Register the Ctrl+C / Ctrl+Break handler
+#[cfg(windows)]
+pub fn install() {
+    use windows::Win32::Foundation::BOOL;
+    use windows::Win32::System::Console::{SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_C_EVENT};
+
+    unsafe extern "system" fn handler(ctrl_type: u32) -> BOOL {
+        if ctrl_type == CTRL_C_EVENT || ctrl_type == CTRL_BREAK_EVENT {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+            BOOL(1)
+        } else {
+            // Close / logoff / shutdown keep their default behavior
+            BOOL(0)
+        }
+    }
+
+    unsafe {
+        if let Err(e) = SetConsoleCtrlHandler(Some(handler), true) {
+            log::warn!("Failed to install Ctrl+C handler: {}", e);
+        }
+    }
+}
+
+/// Register the SIGINT handler
+///
+/// Programs the shell starts get the default handling back when they
+/// exec, so Ctrl+C still stops them.
+#[cfg(unix)]
+pub fn install() {
+    use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+
+    extern "C" fn handler(_: nix::libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+
+    // Restart interrupted reads and waits rather than failing them
+    let action = SigAction::new(SigHandler::Handler(handler), SaFlags::SA_RESTART, SigSet::empty());
+    if let Err(e) = unsafe { sigaction(Signal::SIGINT, &action) } {
+        log::warn!("Failed to install SIGINT handler: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sigint_sets_the_flag() {
+        install();
+        clear();
+        nix::sys::signal::raise(nix::sys::signal::Signal::SIGINT).unwrap();
+        // Still running: the handler only noted the interrupt
+        assert!(flag().load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,121 @@
+//! Background jobs started with `cmd &`
+
+use std::process::Child;
+
+use crate::csh::ast::ExitStatus;
+
+/// A command running (or finished) in the background
+pub struct Job {
+    /// Job number, as in `%1`
+    pub id: usize,
+    pub pid: u32,
+    /// The command as typed, for messages
+    pub command: String,
+    child: Child,
+    /// Exit status once the process has finished and been reaped
+    status: Option<ExitStatus>,
+}
+
+/// The shell's background jobs
+///
+/// Finished processes are reaped as soon as the table is polled, but
+/// their status is kept until `wait` collects it or the shell reports it
+/// before the next prompt.
+#[derive(Default)]
+pub struct JobTable {
+    jobs: Vec<Job>,
+}
+
+impl JobTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track a background process, returning its job number
+    ///
+    /// Numbers continue from the highest one in use, so they start at 1
+    /// again once every job has been waited for.
+    pub fn add(&mut self, child: Child, command: &str) -> usize {
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        self.jobs.push(Job {
+            id,
+            pid: child.id(),
+            command: command.to_string(),
+            child,
+            status: None,
+        });
+        id
+    }
+
+    /// Reap every job that has finished since the last poll
+    pub fn poll(&mut self) {
+        for job in self.jobs.iter_mut().filter(|job| job.status.is_none()) {
+            if let Ok(Some(status)) = job.child.try_wait() {
+                job.status = Some(ExitStatus::from(status));
+            }
+        }
+    }
+
+    /// Jobs in the order they were started
+    pub fn list(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    /// Number of the job with process ID `pid`
+    pub fn find_pid(&self, pid: u32) -> Option<usize> {
+        self.jobs.iter().find(|job| job.pid == pid).map(|job| job.id)
+    }
+
+    /// Remove job `id` if it has finished, returning its status
+    pub fn take_finished(&mut self, id: usize) -> Option<ExitStatus> {
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+        let status = self.jobs[index].status.clone()?;
+        self.jobs.remove(index);
+        Some(status)
+    }
+
+    /// Remove every finished job, returning a line about each, like
+    /// `[1]  Done    make` or `[2]  Exit 2  make test`
+    pub fn report_finished(&mut self) -> String {
+        self.poll();
+        let mut report = String::new();
+        self.jobs.retain(|job| {
+            let Some(status) = &job.status else {
+                return true;
+            };
+            let state = if status.is_success() {
+                "Done".to_string()
+            } else {
+                format!("Exit {}", status.posix_code())
+            };
+            report.push_str(&format!("[{}]  {:<8}{}\n", job.id, state, job.command));
+            false
+        });
+        report
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_finished_jobs_are_reported_once() {
+        let mut jobs = JobTable::new();
+        let spawn = |script: &str| Command::new("sh").args(["-c", script]).spawn().unwrap();
+        let mut done = spawn("exit 0");
+        let mut failed = spawn("exit 3");
+        done.wait().unwrap();
+        failed.wait().unwrap();
+        jobs.add(done, "true");
+        jobs.add(failed, "false");
+        jobs.add(spawn("sleep 5"), "sleep 5");
+
+        assert_eq!(jobs.report_finished(), "[1]  Done    true\n[2]  Exit 3  false\n");
+        assert_eq!(jobs.report_finished(), "");
+        assert_eq!(jobs.list().len(), 1);
+        assert_eq!(jobs.list()[0].command, "sleep 5");
+        jobs.jobs[0].child.kill().unwrap();
+    }
+}
@@ -10,10 +10,17 @@ use std::str::Chars;
 pub enum Token {
     /// A word (command name, argument, etc.)
     Word(String),
-    /// A quoted string (preserves spaces)
+    /// A word with backslash-escaped characters (`\*.rs`), which isn't
+    /// expanded as a pattern
+    EscapedWord(String),
+    /// A double-quoted string (preserves spaces)
     QuotedString(String),
+    /// A single-quoted string, taken literally
+    SingleQuoted(String),
     /// Environment variable reference ($VAR or ${VAR})
     Variable(String),
+    /// Command substitution $(...) or `...`, holding the command
+    CommandSub(String),
     /// Pipe operator |
     Pipe,
     /// And operator &&
@@ -28,12 +35,16 @@ pub enum Token {
     RedirectOut,
     /// Append redirect >>
     AppendOut,
+    /// Forced output redirect >| (ignores noclobber)
+    ClobberOut,
     /// Input redirect <
     RedirectIn,
     /// Stderr redirect 2>
     RedirectErr,
     /// Stderr append 2>>
     AppendErr,
+    /// Stderr to wherever stdout goes 2>&1
+    ErrToOut,
     /// Both redirect &>
     RedirectBoth,
     /// Both append &>>
@@ -61,6 +72,7 @@ pub enum Token {
 pub enum LexerError {
     UnterminatedString(char),
     UnterminatedVariable,
+    UnterminatedCommandSub,
     InvalidEscape(char),
     UnexpectedChar(char),
 }
@@ -72,6 +84,7 @@ impl std::fmt::Display for LexerError {
                 write!(f, "Unterminated string starting with {}", quote)
             }
             LexerError::UnterminatedVariable => write!(f, "Unterminated variable reference"),
+            LexerError::UnterminatedCommandSub => write!(f, "Unterminated command substitution"),
             LexerError::InvalidEscape(c) => write!(f, "Invalid escape sequence: \\{}", c),
             LexerError::UnexpectedChar(c) => write!(f, "Unexpected character: {}", c),
         }
@@ -159,11 +172,16 @@ impl<'a> Lexer<'a> {
             }
             Some('>') => {
                 self.advance();
-                if self.input.peek() == Some(&'>') {
-                    self.advance();
-                    Ok(Token::AppendOut)
-                } else {
-                    Ok(Token::RedirectOut)
+                match self.input.peek() {
+                    Some(&'>') => {
+                        self.advance();
+                        Ok(Token::AppendOut)
+                    }
+                    Some(&'|') => {
+                        self.advance();
+                        Ok(Token::ClobberOut)
+                    }
+                    _ => Ok(Token::RedirectOut),
                 }
             }
             Some('<') => {
@@ -171,15 +189,20 @@ impl<'a> Lexer<'a> {
                 Ok(Token::RedirectIn)
             }
             Some('2') => {
-                // Check for 2> or 2>>
+                // Check for 2>, 2>> or 2>&1
                 let mut chars = self.input.clone();
                 chars.next(); // consume '2'
                 if chars.peek() == Some(&'>') {
                     self.advance(); // consume '2'
                     self.advance(); // consume '>'
+                    let mut rest = self.input.clone();
                     if self.input.peek() == Some(&'>') {
                         self.advance();
                         Ok(Token::AppendErr)
+                    } else if rest.next() == Some('&') && rest.next() == Some('1') {
+                        self.advance();
+                        self.advance();
+                        Ok(Token::ErrToOut)
                     } else {
                         Ok(Token::RedirectErr)
                     }
@@ -187,9 +210,13 @@ impl<'a> Lexer<'a> {
                     self.read_word()
                 }
             }
+            // A lone `=` is a token; `=x` and `==` are words
             Some('=') => {
                 self.advance();
-                Ok(Token::Equals)
+                match self.input.peek() {
+                    Some(&next) if !is_word_end(next) => self.read_word_from("=".to_string()),
+                    _ => Ok(Token::Equals),
+                }
             }
             Some('(') => {
                 self.advance();
@@ -199,17 +226,37 @@ impl<'a> Lexer<'a> {
                 self.advance();
                 Ok(Token::RightParen)
             }
-            Some('{') => {
-                self.advance();
-                Ok(Token::LeftBrace)
-            }
-            Some('}') => {
+            // A brace on its own is a token; `{a,b}` is a word for brace expansion
+            Some(c @ ('{' | '}')) => {
                 self.advance();
-                Ok(Token::RightBrace)
+                match self.input.peek() {
+                    Some(&next) if !is_word_end(next) => self.read_word_from(c.to_string()),
+                    _ if c == '{' => Ok(Token::LeftBrace),
+                    _ => Ok(Token::RightBrace),
+                }
             }
             Some('\'') => self.read_single_quoted_string(),
             Some('"') => self.read_double_quoted_string(),
-            Some('$') => self.read_variable(),
+            Some(c @ ('$' | '`')) => {
+                let token = if c == '$' {
+                    self.read_variable()?
+                } else {
+                    self.read_backticks()?
+                };
+                // `$HOME/bin` or `$(pwd)/src` carries on as one word
+                match self.input.peek() {
+                    Some(&c) if !is_word_end(c) => {
+                        let start = match token {
+                            Token::Variable(v) => format!("${{{}}}", v),
+                            Token::CommandSub(c) => format!("$({})", c),
+                            Token::Word(w) => w,
+                            _ => String::new(),
+                        };
+                        self.read_word_from(start)
+                    }
+                    _ => Ok(token),
+                }
+            }
             Some(_) => self.read_word(),
         }
     }
@@ -272,7 +319,7 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        Ok(Token::QuotedString(result))
+        Ok(Token::SingleQuoted(result))
     }
 
     fn read_double_quoted_string(&mut self) -> Result<Token, LexerError> {
@@ -322,11 +369,17 @@ impl<'a> Lexer<'a> {
                         }
                     }
                 }
+                Some('`') => {
+                    if let Token::CommandSub(command) = self.read_backticks()? {
+                        result.push_str(&format!("$({})", command));
+                    }
+                }
                 Some('$') => {
                     // Variable expansion in double quotes
-                    let var_token = self.read_variable()?;
-                    if let Token::Variable(var) = var_token {
-                        result.push_str(&format!("${{{}}}", var));
+                    match self.read_variable()? {
+                        Token::Variable(var) => result.push_str(&format!("${{{}}}", var)),
+                        Token::CommandSub(command) => result.push_str(&format!("$({})", command)),
+                        _ => {}
                     }
                 }
                 Some(c) => {
@@ -345,17 +398,36 @@ impl<'a> Lexer<'a> {
         let mut var_name = String::new();
 
         match self.input.peek().copied() {
+            Some('(') => {
+                // $(command) syntax
+                self.advance();
+                let rest: String = self.input.clone().collect();
+                let command = command_sub_body(&rest)
+                    .ok_or(LexerError::UnterminatedCommandSub)?
+                    .to_string();
+                // The command and its closing paren
+                for _ in 0..=command.chars().count() {
+                    self.advance();
+                }
+                return Ok(Token::CommandSub(command));
+            }
             Some('{') => {
-                // ${VAR} syntax
+                // ${VAR} syntax; `${VAR:-${OTHER}}` nests
                 self.advance();
+                let mut depth = 0;
                 loop {
                     match self.input.peek().copied() {
                         None => return Err(LexerError::UnterminatedVariable),
-                        Some('}') => {
+                        Some('}') if depth == 0 => {
                             self.advance();
                             break;
                         }
                         Some(c) => {
+                            match c {
+                                '{' => depth += 1,
+                                '}' => depth -= 1,
+                                _ => {}
+                            }
                             var_name.push(c);
                             self.advance();
                         }
@@ -383,6 +455,11 @@ impl<'a> Lexer<'a> {
                 self.advance();
                 var_name.push('$');
             }
+            Some('#') => {
+                // $# - number of positional parameters
+                self.advance();
+                var_name.push('#');
+            }
             Some('0'..='9') => {
                 // $0-$9 - positional parameters
                 var_name.push(self.advance().unwrap());
@@ -396,16 +473,45 @@ impl<'a> Lexer<'a> {
         Ok(Token::Variable(var_name))
     }
 
+    /// Read a `command` substitution
+    ///
+    /// Backslash escapes a backtick, a backslash or `$`; other backslashes
+    /// are kept for the command.
+    fn read_backticks(&mut self) -> Result<Token, LexerError> {
+        self.advance(); // consume opening backtick
+        let mut command = String::new();
+
+        loop {
+            match self.advance() {
+                None => return Err(LexerError::UnterminatedCommandSub),
+                Some('`') => break,
+                Some('\\') => match self.input.peek().copied() {
+                    Some(c @ ('`' | '\\' | '$')) => {
+                        command.push(c);
+                        self.advance();
+                    }
+                    _ => command.push('\\'),
+                },
+                Some(c) => command.push(c),
+            }
+        }
+
+        Ok(Token::CommandSub(command))
+    }
+
     fn read_word(&mut self) -> Result<Token, LexerError> {
-        let mut word = String::new();
+        self.read_word_from(String::new())
+    }
 
+    /// Read the rest of a word that starts with `word`
+    fn read_word_from(&mut self, mut word: String) -> Result<Token, LexerError> {
+        let mut escaped = false;
         while let Some(&c) = self.input.peek() {
             match c {
-                // Word terminators
-                ' ' | '\t' | '\r' | '\n' | '|' | '&' | ';' | '>' | '<' | '(' | ')' | '{' | '}'
-                | '#' => break,
+                c if is_word_end(c) => break,
                 // Handle escape sequences
                 '\\' => {
+                    escaped = true;
                     self.advance();
                     if let Some(&next) = self.input.peek() {
                         word.push(next);
@@ -419,15 +525,20 @@ impl<'a> Lexer<'a> {
                     } else {
                         self.read_double_quoted_string()?
                     };
-                    if let Token::QuotedString(s) = quoted {
+                    if let Token::QuotedString(s) | Token::SingleQuoted(s) = quoted {
                         word.push_str(&s);
                     }
                 }
-                // Handle variables within words
-                '$' => {
-                    let var = self.read_variable()?;
+                // Handle variables and command substitutions within words
+                '$' | '`' => {
+                    let var = if c == '$' {
+                        self.read_variable()?
+                    } else {
+                        self.read_backticks()?
+                    };
                     match var {
                         Token::Variable(v) => word.push_str(&format!("${{{}}}", v)),
+                        Token::CommandSub(c) => word.push_str(&format!("$({})", c)),
                         Token::Word(w) => word.push_str(&w),
                         _ => {}
                     }
@@ -439,10 +550,78 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        Ok(Token::Word(word))
+        Ok(if escaped {
+            Token::EscapedWord(word)
+        } else {
+            Token::Word(word)
+        })
     }
 }
 
+/// Characters that end a word
+fn is_word_end(c: char) -> bool {
+    matches!(
+        c,
+        ' ' | '\t' | '\r' | '\n' | '|' | '&' | ';' | '>' | '<' | '(' | ')' | '#'
+    )
+}
+
+/// The command of a `$(...)`, given the text following its `$(`
+///
+/// Stops at the matching `)`: nested parens, quoted text and escaped
+/// characters don't end it. `None` if the parens are never closed.
+pub fn command_sub_body(text: &str) -> Option<&str> {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut chars = text.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                chars.next();
+            }
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') if depth == 0 => return Some(&text[..i]),
+            (None, ')') => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The inside of a `${...}`, given the text following its `${`
+///
+/// Stops at the matching `}`: nested braces and `$(...)` (which may hold
+/// braces of its own) don't end it. `None` if the brace is never closed.
+pub fn parameter_body(text: &str) -> Option<&str> {
+    let mut depth = 0;
+    let mut chars = text.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '$' if text[i + 1..].starts_with('(') => {
+                let command = command_sub_body(&text[i + 2..])?;
+                // The paren, the command and its closing paren
+                for _ in 0..command.chars().count() + 2 {
+                    chars.next();
+                }
+            }
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(&text[..i]),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -493,6 +672,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_clobber_redirect() {
+        let mut lexer = Lexer::new("echo hi >| out.txt");
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("echo".to_string()),
+                Token::Word("hi".to_string()),
+                Token::ClobberOut,
+                Token::Word("out.txt".to_string()),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stderr_to_stdout() {
+        let mut lexer = Lexer::new("make 2>&1 2>x");
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("make".to_string()),
+                Token::ErrToOut,
+                Token::RedirectErr,
+                Token::Word("x".to_string()),
+                Token::Eof,
+            ]
+        );
+    }
+
     #[test]
     fn test_quoted_string() {
         let mut lexer = Lexer::new("echo 'hello world'");
@@ -501,7 +712,23 @@ mod tests {
             tokens,
             vec![
                 Token::Word("echo".to_string()),
-                Token::QuotedString("hello world".to_string()),
+                Token::SingleQuoted("hello world".to_string()),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_escaped_word() {
+        let mut lexer = Lexer::new("echo \\*.toml my\\ file $HOME/\\?");
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("echo".to_string()),
+                Token::EscapedWord("*.toml".to_string()),
+                Token::EscapedWord("my file".to_string()),
+                Token::EscapedWord("${HOME}/?".to_string()),
                 Token::Eof,
             ]
         );
@@ -537,4 +764,81 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_braces_inside_words() {
+        let mut lexer = Lexer::new("echo file{1,2}.txt {a,b} {} { }");
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("echo".to_string()),
+                Token::Word("file{1,2}.txt".to_string()),
+                Token::Word("{a,b}".to_string()),
+                Token::Word("{}".to_string()),
+                Token::LeftBrace,
+                Token::RightBrace,
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_substitution() {
+        let mut lexer = Lexer::new("echo $(echo $(echo hi)) x$(pwd)/y $(pwd)/z");
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("echo".to_string()),
+                Token::CommandSub("echo $(echo hi)".to_string()),
+                Token::Word("x$(pwd)/y".to_string()),
+                Token::Word("$(pwd)/z".to_string()),
+                Token::Eof,
+            ]
+        );
+
+        // Quoted parens don't close it; inside double quotes it is kept
+        let mut lexer = Lexer::new("echo $(echo ')' \")\") \"[$(date)]\"");
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(tokens[1], Token::CommandSub("echo ')' \")\"".to_string()));
+        assert_eq!(tokens[2], Token::QuotedString("[$(date)]".to_string()));
+
+        let mut lexer = Lexer::new("echo $(echo (hi)");
+        assert_eq!(lexer.tokenize(), Err(LexerError::UnterminatedCommandSub));
+    }
+
+    #[test]
+    fn test_backtick_substitution() {
+        let mut lexer = Lexer::new("echo `echo hi` x`pwd`/y \"[`date`]\" '`literal`'");
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("echo".to_string()),
+                Token::CommandSub("echo hi".to_string()),
+                Token::Word("x$(pwd)/y".to_string()),
+                Token::QuotedString("[$(date)]".to_string()),
+                Token::SingleQuoted("`literal`".to_string()),
+                Token::Eof,
+            ]
+        );
+
+        let mut lexer = Lexer::new("echo `echo \\`echo in\\`` `echo a\\b`");
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(tokens[1], Token::CommandSub("echo `echo in`".to_string()));
+        assert_eq!(tokens[2], Token::CommandSub("echo a\\b".to_string()));
+
+        let mut lexer = Lexer::new("echo `date");
+        assert_eq!(lexer.tokenize(), Err(LexerError::UnterminatedCommandSub));
+    }
+
+    #[test]
+    fn test_parameter_body() {
+        assert_eq!(parameter_body("U:-$(echo sub)} rest"), Some("U:-$(echo sub)"));
+        assert_eq!(parameter_body("U:-${V:-x}}"), Some("U:-${V:-x}"));
+        assert_eq!(parameter_body("U:-$(echo })}"), Some("U:-$(echo })"));
+        assert_eq!(parameter_body("U:-$(echo"), None);
+        assert_eq!(parameter_body("U"), None);
+    }
 }
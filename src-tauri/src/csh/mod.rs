@@ -10,19 +10,33 @@
 //! - Tab completion with readline support
 //! - Scripting support
 
+pub mod arithmetic;
 pub mod ast;
+pub mod braces;
 pub mod builtins;
 pub mod completion;
 pub mod environment;
 pub mod executor;
+pub mod git;
+pub mod highlight;
 pub mod history;
+pub mod icons;
+pub mod interrupt;
+pub mod jobs;
 pub mod lexer;
+pub mod options;
 pub mod parser;
 pub mod pipes;
+pub mod processes;
 pub mod readline;
 pub mod redirect;
 pub mod script;
 pub mod shell;
+pub mod suggest;
+pub mod text;
+pub mod users;
+pub mod walk;
+pub mod wildcard;
 
 // Re-exports
 pub use environment::Environment;
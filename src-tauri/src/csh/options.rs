@@ -0,0 +1,123 @@
+//! Shell options (`set -o` / `set +o`)
+//!
+//! All toggleable options live in `ShellOptions`. `extendedhistory` is
+//! enforced by the shell and the others by the executor.
+
+/// Toggleable shell options, all off by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShellOptions {
+    autocd: bool,
+    errexit: bool,
+    extendedhistory: bool,
+    noclobber: bool,
+    pipefail: bool,
+    xtrace: bool,
+}
+
+impl ShellOptions {
+    /// Option names in listing order
+    pub const NAMES: &'static [&'static str] =
+        &["autocd", "errexit", "extendedhistory", "noclobber", "pipefail", "xtrace"];
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Typing a directory name on its own changes into it
+    pub fn autocd(&self) -> bool {
+        self.autocd
+    }
+
+    /// Exit on the first failing command
+    pub fn errexit(&self) -> bool {
+        self.errexit
+    }
+
+    /// Record each command's exit status and directory in the history
+    pub fn extendedhistory(&self) -> bool {
+        self.extendedhistory
+    }
+
+    /// Refuse to overwrite existing files with `>`
+    pub fn noclobber(&self) -> bool {
+        self.noclobber
+    }
+
+    /// A pipeline's status is that of its last failing command
+    pub fn pipefail(&self) -> bool {
+        self.pipefail
+    }
+
+    /// Print commands before executing them
+    pub fn xtrace(&self) -> bool {
+        self.xtrace
+    }
+
+    /// Get an option by name
+    pub fn get(&self, name: &str) -> Option<bool> {
+        Some(match name {
+            "autocd" => self.autocd,
+            "errexit" => self.errexit,
+            "extendedhistory" => self.extendedhistory,
+            "noclobber" => self.noclobber,
+            "pipefail" => self.pipefail,
+            "xtrace" => self.xtrace,
+            _ => return None,
+        })
+    }
+
+    /// Set an option by name; returns false for unknown names
+    pub fn set(&mut self, name: &str, enabled: bool) -> bool {
+        let option = match name {
+            "autocd" => &mut self.autocd,
+            "errexit" => &mut self.errexit,
+            "extendedhistory" => &mut self.extendedhistory,
+            "noclobber" => &mut self.noclobber,
+            "pipefail" => &mut self.pipefail,
+            "xtrace" => &mut self.xtrace,
+            _ => return false,
+        };
+        *option = enabled;
+        true
+    }
+
+    /// Human-readable listing (`set -o`)
+    pub fn listing(&self) -> String {
+        Self::NAMES
+            .iter()
+            .map(|name| {
+                let state = if self.get(name) == Some(true) { "on" } else { "off" };
+                format!("{:<15}\t{}\n", name, state)
+            })
+            .collect()
+    }
+
+    /// Listing as commands that restore the current state (`set +o`)
+    pub fn reinput(&self) -> String {
+        Self::NAMES
+            .iter()
+            .map(|name| {
+                let flag = if self.get(name) == Some(true) { "-o" } else { "+o" };
+                format!("set {} {}\n", flag, name)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_get() {
+        let mut options = ShellOptions::new();
+        assert!(options.set("pipefail", true));
+        assert!(options.pipefail());
+        assert_eq!(options.get("noclobber"), Some(false));
+        assert!(!options.set("bogus", true));
+        assert_eq!(options.get("bogus"), None);
+        // Nothing would read these
+        assert!(!options.set("posix", true));
+        assert!(!options.set("vi", true));
+    }
+}
@@ -2,7 +2,9 @@
 //!
 //! Converts tokens into an Abstract Syntax Tree (AST).
 
-use crate::csh::ast::{Command, CommandLine, LogicalOp, Pipeline, Redirect, RedirectType};
+use crate::csh::ast::{
+    Command, CommandLine, LogicalOp, Pipeline, Quoting, Redirect, RedirectType, Statement,
+};
 use crate::csh::lexer::{Lexer, LexerError, Token};
 
 /// Parser error types
@@ -37,24 +39,362 @@ impl From<LexerError> for ParseError {
     }
 }
 
+/// Words that end a block or a condition, so they are never run as commands
+const CLOSING_KEYWORDS: &[&str] = &["then", "elif", "else", "fi", "do", "done"];
+
+/// Words that start a compound command, which ends the command line
+/// before it
+const COMPOUND_KEYWORDS: &[&str] = &["if", "for", "while", "function"];
+
 /// The parser struct
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
+    /// `&&` or `||` already read when a command line stopped in front of
+    /// a compound command (see `parse_and_or`)
+    pending_operator: Option<LogicalOp>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(input: &'a str) -> Self {
         Self {
             lexer: Lexer::new(input),
+            pending_operator: None,
         }
     }
 
     /// Parse a complete command line
     pub fn parse(&mut self) -> Result<CommandLine, ParseError> {
-        self.parse_command_line()
+        let command_line = self.parse_command_line()?;
+        self.reject_pending_operator()?;
+        Ok(command_line)
+    }
+
+    /// Parse a whole script into statements
+    ///
+    /// Statements are separated by newlines or `;`. A construct left open
+    /// at the end, such as an `if` without its `fi`, is `UnexpectedEof`.
+    pub fn parse_script(&mut self) -> Result<Vec<Statement>, ParseError> {
+        let statements = self.parse_statements(&[])?;
+        match self.lexer.next_token()? {
+            Token::Eof => Ok(statements),
+            token => Err(ParseError::UnexpectedToken(format!("{:?}", token))),
+        }
+    }
+
+    /// Parse statements until one of `terminators` (left unconsumed) or
+    /// the end of input; `"}"` stands for the closing brace of a function
+    fn parse_statements(&mut self, terminators: &[&str]) -> Result<Vec<Statement>, ParseError> {
+        let mut statements = Vec::new();
+        loop {
+            self.skip_separators()?;
+            let token = self.lexer.peek()?.clone();
+            match token {
+                Token::Eof if terminators.is_empty() => return Ok(statements),
+                Token::Eof => return Err(ParseError::UnexpectedEof),
+                Token::Word(word) if terminators.contains(&word.as_str()) => {
+                    return Ok(statements)
+                }
+                Token::Word(word) if CLOSING_KEYWORDS.contains(&word.as_str()) => {
+                    return Err(ParseError::UnexpectedToken(word));
+                }
+                Token::RightBrace if terminators.contains(&"}") => return Ok(statements),
+                Token::RightBrace => return Err(ParseError::UnexpectedToken("}".to_string())),
+                _ => {
+                    let statement = self.parse_statement()?;
+                    let statement = self.parse_and_or(statement)?;
+                    statements.push(statement);
+                }
+            }
+        }
+    }
+
+    /// Parse one statement: a compound command, or a command line up to a
+    /// separator
+    fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+        let token = self.lexer.peek()?.clone();
+        match token {
+            Token::Word(word) if word == "if" => self.parse_if(),
+            Token::Word(word) if word == "for" => self.parse_for(),
+            Token::Word(word) if word == "while" => self.parse_while(),
+            Token::Word(word) if word == "function" => {
+                self.lexer.next_token()?;
+                let name = match self.lexer.next_token()? {
+                    Token::Word(name) if is_variable_name(&name) => name,
+                    Token::Eof => return Err(ParseError::UnexpectedEof),
+                    token => {
+                        return Err(ParseError::InvalidSyntax(format!(
+                            "bad function name: {:?}",
+                            token
+                        )))
+                    }
+                };
+                // `function name() { ... }` is accepted too
+                if matches!(self.lexer.peek()?, Token::LeftParen) {
+                    self.lexer.next_token()?;
+                    self.expect_token(Token::RightParen, ")")?;
+                }
+                self.parse_function(name)
+            }
+            _ => {
+                let command_line = self.parse_command_line()?;
+                let bare = bare_command(&command_line);
+
+                // `name() { ... }` defines a function
+                if matches!(self.lexer.peek()?, Token::LeftParen) {
+                    return match bare {
+                        Some(cmd) if cmd.args.is_empty() && is_variable_name(&cmd.name) => {
+                            let name = cmd.name.clone();
+                            self.lexer.next_token()?;
+                            self.expect_token(Token::RightParen, ")")?;
+                            self.parse_function(name)
+                        }
+                        _ => Err(ParseError::UnexpectedToken("(".to_string())),
+                    };
+                }
+
+                // `cmd &` ends a statement like `;` does
+                let backgrounded = command_line.pipelines.last().is_some_and(|p| p.background);
+                let statement = match bare {
+                    Some(cmd) if cmd.name == "return" => match cmd.args.as_slice() {
+                        [] => Statement::Return(None),
+                        [code] => match code.parse() {
+                            Ok(code) => Statement::Return(Some(code)),
+                            // `return $x` and the like run the builtin instead
+                            Err(_) => Statement::CommandLine(command_line),
+                        },
+                        _ => Statement::CommandLine(command_line),
+                    },
+                    // `break 2` and the like run the builtin
+                    Some(cmd) if cmd.name == "break" && cmd.args.is_empty() => Statement::Break,
+                    Some(cmd) if cmd.name == "continue" && cmd.args.is_empty() => {
+                        Statement::Continue
+                    }
+                    _ => Statement::CommandLine(command_line),
+                };
+                if self.pending_operator.is_some() {
+                    return Ok(statement);
+                }
+                let next = self.lexer.peek()?.clone();
+                match next {
+                    Token::Newline | Token::Semicolon | Token::Comment(_) | Token::Eof => {}
+                    Token::Word(word) if CLOSING_KEYWORDS.contains(&word.as_str()) => {}
+                    Token::Word(word) if COMPOUND_KEYWORDS.contains(&word.as_str()) => {}
+                    Token::RightBrace => {}
+                    _ if backgrounded => {}
+                    token => return Err(ParseError::UnexpectedToken(format!("{:?}", token))),
+                }
+                Ok(statement)
+            }
+        }
+    }
+
+    /// Join `first` with any statements that follow it after `&&` or `||`
+    ///
+    /// Command lines handle these operators themselves; this is for
+    /// chains where a compound command comes before or after one.
+    fn parse_and_or(&mut self, first: Statement) -> Result<Statement, ParseError> {
+        let mut rest = Vec::new();
+        loop {
+            let operator = match self.pending_operator.take() {
+                Some(operator) => operator,
+                None => match self.lexer.peek()? {
+                    Token::And => LogicalOp::And,
+                    Token::Or => LogicalOp::Or,
+                    _ => break,
+                },
+            };
+            if matches!(self.lexer.peek()?, Token::And | Token::Or) {
+                self.lexer.next_token()?;
+            }
+            self.skip_newlines()?;
+
+            // A compound command, or one pipeline so that the operators
+            // after it stay in this chain
+            let statement = if self.at_compound_keyword()? {
+                self.parse_statement()?
+            } else {
+                Statement::CommandLine(CommandLine::single(self.parse_pipeline()?))
+            };
+            rest.push((operator, statement));
+        }
+
+        if rest.is_empty() {
+            return Ok(first);
+        }
+        Ok(Statement::AndOr {
+            first: Box::new(first),
+            rest,
+        })
+    }
+
+    /// Parse `if ...; then ...; [elif ...; then ...;] [else ...;] fi`
+    fn parse_if(&mut self) -> Result<Statement, ParseError> {
+        self.expect_keyword("if")?;
+        let condition = Box::new(self.parse_condition()?);
+        self.expect_keyword("then")?;
+        let then_branch = self.parse_statements(&["elif", "else", "fi"])?;
+
+        let mut elif_branches = Vec::new();
+        let mut else_branch = None;
+        loop {
+            match self.lexer.next_token()? {
+                Token::Word(word) if word == "elif" => {
+                    let condition = Box::new(self.parse_condition()?);
+                    self.expect_keyword("then")?;
+                    let body = self.parse_statements(&["elif", "else", "fi"])?;
+                    elif_branches.push((condition, body));
+                }
+                Token::Word(word) if word == "else" => {
+                    else_branch = Some(self.parse_statements(&["fi"])?);
+                    self.expect_keyword("fi")?;
+                    break;
+                }
+                // `parse_statements` only stops at one of the terminators
+                _ => break,
+            }
+        }
+
+        Ok(Statement::If {
+            condition,
+            then_branch,
+            elif_branches,
+            else_branch,
+        })
+    }
+
+    /// Parse `for VAR in items...; do ...; done`
+    fn parse_for(&mut self) -> Result<Statement, ParseError> {
+        self.expect_keyword("for")?;
+        let variable = match self.lexer.next_token()? {
+            Token::Word(word) if is_variable_name(&word) => word,
+            Token::Eof => return Err(ParseError::UnexpectedEof),
+            token => {
+                return Err(ParseError::InvalidSyntax(format!(
+                    "bad for loop variable: {:?}",
+                    token
+                )))
+            }
+        };
+        self.expect_keyword("in")?;
+
+        let mut items = Vec::new();
+        let mut quoted_items = Vec::new();
+        loop {
+            let (item, quoted) = match self.lexer.peek()?.clone() {
+                Token::Word(word) => (word, Quoting::Unquoted),
+                Token::QuotedString(s) | Token::EscapedWord(s) => (s, Quoting::Double),
+                Token::SingleQuoted(s) => (s, Quoting::Single),
+                Token::Variable(var) => (format!("${{{}}}", var), Quoting::Unquoted),
+                Token::CommandSub(command) => (format!("$({})", command), Quoting::Unquoted),
+                _ => break,
+            };
+            self.lexer.next_token()?;
+            items.push(item);
+            quoted_items.push(quoted);
+        }
+
+        self.expect_keyword("do")?;
+        let body = self.parse_statements(&["done"])?;
+        self.expect_keyword("done")?;
+
+        Ok(Statement::For {
+            variable,
+            items,
+            quoted_items,
+            body,
+        })
+    }
+
+    /// Parse `while ...; do ...; done`
+    fn parse_while(&mut self) -> Result<Statement, ParseError> {
+        self.expect_keyword("while")?;
+        let condition = Box::new(self.parse_condition()?);
+        self.expect_keyword("do")?;
+        let body = self.parse_statements(&["done"])?;
+        self.expect_keyword("done")?;
+        Ok(Statement::While { condition, body })
+    }
+
+    /// Parse the `{ ... }` body of function `name`
+    fn parse_function(&mut self, name: String) -> Result<Statement, ParseError> {
+        self.skip_newlines()?;
+        self.expect_token(Token::LeftBrace, "{")?;
+        let body = self.parse_statements(&["}"])?;
+        self.expect_token(Token::RightBrace, "}")?;
+        Ok(Statement::Function { name, body })
+    }
+
+    /// Parse the command line an `if`, `elif` or `while` tests
+    fn parse_condition(&mut self) -> Result<CommandLine, ParseError> {
+        let condition = self.parse_command_line()?;
+        self.reject_pending_operator()?;
+        if condition.is_empty() {
+            return match self.lexer.peek()? {
+                Token::Eof => Err(ParseError::UnexpectedEof),
+                token => Err(ParseError::UnexpectedToken(format!("{:?}", token))),
+            };
+        }
+        Ok(condition)
+    }
+
+    /// Consume `keyword`, after any separators before it
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        self.skip_separators()?;
+        match self.lexer.next_token()? {
+            Token::Word(word) if word == keyword => Ok(()),
+            Token::Eof => Err(ParseError::UnexpectedEof),
+            token => Err(ParseError::InvalidSyntax(format!(
+                "expected '{}', found {:?}",
+                keyword, token
+            ))),
+        }
+    }
+
+    /// Consume `expected`, shown as `text` in errors
+    fn expect_token(&mut self, expected: Token, text: &str) -> Result<(), ParseError> {
+        match self.lexer.next_token()? {
+            token if token == expected => Ok(()),
+            Token::Eof => Err(ParseError::UnexpectedEof),
+            token => Err(ParseError::InvalidSyntax(format!(
+                "expected '{}', found {:?}",
+                text, token
+            ))),
+        }
+    }
+
+    /// Whether the next token is a keyword or `}` that ends the current
+    /// command list
+    fn at_closing_keyword(&mut self) -> Result<bool, ParseError> {
+        let token = self.lexer.peek()?;
+        Ok(match token {
+            Token::Word(word) => CLOSING_KEYWORDS.contains(&word.as_str()),
+            token => *token == Token::RightBrace,
+        })
+    }
+
+    /// Whether the next token starts a compound command
+    fn at_compound_keyword(&mut self) -> Result<bool, ParseError> {
+        let token = self.lexer.peek()?;
+        Ok(matches!(token, Token::Word(word) if COMPOUND_KEYWORDS.contains(&word.as_str())))
+    }
+
+    /// Fail where a compound command follows `&&` or `||` but only a
+    /// command line can be used
+    fn reject_pending_operator(&mut self) -> Result<(), ParseError> {
+        match self.pending_operator.take() {
+            Some(_) => match self.lexer.peek()? {
+                Token::Word(word) => Err(ParseError::UnexpectedToken(word.clone())),
+                token => Err(ParseError::UnexpectedToken(format!("{:?}", token))),
+            },
+            None => Ok(()),
+        }
     }
 
     /// Parse a command line (may contain multiple pipelines with && || ;)
+    ///
+    /// Stops in front of a compound command, which `parse_statements`
+    /// handles; after `&&` or `||` the operator is left in
+    /// `pending_operator`.
     fn parse_command_line(&mut self) -> Result<CommandLine, ParseError> {
         let mut command_line = CommandLine::new();
 
@@ -77,6 +417,10 @@ impl<'a> Parser<'a> {
                 Token::And => {
                     self.lexer.next_token()?;
                     self.skip_newlines()?;
+                    if self.at_compound_keyword()? {
+                        self.pending_operator = Some(LogicalOp::And);
+                        break;
+                    }
                     let pipeline = self.parse_pipeline()?;
                     command_line.pipelines.push(pipeline);
                     command_line.operators.push(LogicalOp::And);
@@ -84,6 +428,10 @@ impl<'a> Parser<'a> {
                 Token::Or => {
                     self.lexer.next_token()?;
                     self.skip_newlines()?;
+                    if self.at_compound_keyword()? {
+                        self.pending_operator = Some(LogicalOp::Or);
+                        break;
+                    }
                     let pipeline = self.parse_pipeline()?;
                     command_line.pipelines.push(pipeline);
                     command_line.operators.push(LogicalOp::Or);
@@ -94,8 +442,10 @@ impl<'a> Parser<'a> {
                     // Check if there's another pipeline after the semicolon
                     if !matches!(
                         self.lexer.peek()?,
-                        Token::Eof | Token::Newline | Token::Semicolon
-                    ) {
+                        Token::Eof | Token::Newline | Token::Semicolon | Token::Comment(_)
+                    ) && !self.at_closing_keyword()?
+                        && !self.at_compound_keyword()?
+                    {
                         let pipeline = self.parse_pipeline()?;
                         command_line.pipelines.push(pipeline);
                         command_line.operators.push(LogicalOp::Sequence);
@@ -136,6 +486,15 @@ impl<'a> Parser<'a> {
                 Token::Pipe => {
                     self.lexer.next_token()?;
                     self.skip_newlines()?;
+                    if self.at_compound_keyword()? {
+                        let Token::Word(keyword) = self.lexer.next_token()? else {
+                            unreachable!("checked by at_compound_keyword");
+                        };
+                        return Err(ParseError::InvalidSyntax(format!(
+                            "'{}' can't be piped into yet",
+                            keyword
+                        )));
+                    }
 
                     // Move stdout redirects to the last command
                     stdout_redirects.clear();
@@ -175,6 +534,7 @@ impl<'a> Parser<'a> {
     fn parse_command(&mut self) -> Result<(Command, Vec<Redirect>), ParseError> {
         let mut name = String::new();
         let mut args = Vec::new();
+        let mut quoted_args = Vec::new();
         let mut redirects = Vec::new();
         let mut env_assignments = Vec::new();
 
@@ -182,7 +542,9 @@ impl<'a> Parser<'a> {
         loop {
             let token = self.lexer.peek()?.clone();
             match token {
-                Token::Word(word) if word.contains('=') && name.is_empty() => {
+                Token::Word(word) | Token::EscapedWord(word)
+                    if word.contains('=') && name.is_empty() =>
+                {
                     self.lexer.next_token()?;
                     if let Some((var, val)) = word.split_once('=') {
                         env_assignments.push((var.to_string(), val.to_string()));
@@ -202,14 +564,30 @@ impl<'a> Parser<'a> {
                         name = word;
                     } else {
                         args.push(word);
+                        quoted_args.push(Quoting::Unquoted);
                     }
                 }
-                Token::QuotedString(s) => {
-                    self.lexer.next_token()?;
+                // Escaped characters are taken literally, as in a quoted word
+                Token::QuotedString(s) | Token::SingleQuoted(s) | Token::EscapedWord(s) => {
+                    let quoting = match self.lexer.next_token()? {
+                        Token::SingleQuoted(_) => Quoting::Single,
+                        _ => Quoting::Double,
+                    };
                     if name.is_empty() {
                         name = s;
                     } else {
                         args.push(s);
+                        quoted_args.push(quoting);
+                    }
+                }
+                // A lone `=`, as in `test a = b`
+                Token::Equals => {
+                    self.lexer.next_token()?;
+                    if name.is_empty() {
+                        name = "=".to_string();
+                    } else {
+                        args.push("=".to_string());
+                        quoted_args.push(Quoting::Unquoted);
                     }
                 }
                 Token::Variable(var) => {
@@ -219,10 +597,22 @@ impl<'a> Parser<'a> {
                         name = var_ref;
                     } else {
                         args.push(var_ref);
+                        quoted_args.push(Quoting::Unquoted);
+                    }
+                }
+                Token::CommandSub(command) => {
+                    self.lexer.next_token()?;
+                    let sub = format!("$({})", command);
+                    if name.is_empty() {
+                        name = sub;
+                    } else {
+                        args.push(sub);
+                        quoted_args.push(Quoting::Unquoted);
                     }
                 }
                 Token::RedirectOut
                 | Token::AppendOut
+                | Token::ClobberOut
                 | Token::RedirectIn
                 | Token::RedirectErr
                 | Token::AppendErr
@@ -231,6 +621,10 @@ impl<'a> Parser<'a> {
                     let redirect = self.parse_redirect()?;
                     redirects.push(redirect);
                 }
+                Token::ErrToOut => {
+                    self.lexer.next_token()?;
+                    redirects.push(Redirect::new(RedirectType::StderrToStdout, String::new()));
+                }
                 _ => break,
             }
         }
@@ -250,6 +644,7 @@ impl<'a> Parser<'a> {
         }
 
         let mut cmd = Command::with_args(name, args);
+        cmd.quoted_args = quoted_args;
         cmd.env_assignments = env_assignments;
 
         Ok((cmd, redirects))
@@ -261,6 +656,7 @@ impl<'a> Parser<'a> {
         let redirect_type = match token {
             Token::RedirectOut => RedirectType::StdoutOverwrite,
             Token::AppendOut => RedirectType::StdoutAppend,
+            Token::ClobberOut => RedirectType::StdoutClobber,
             Token::RedirectIn => RedirectType::StdinRead,
             Token::RedirectErr => RedirectType::StderrOverwrite,
             Token::AppendErr => RedirectType::StderrAppend,
@@ -273,8 +669,9 @@ impl<'a> Parser<'a> {
         let target_token = self.lexer.next_token()?;
         let target = match target_token {
             Token::Word(w) => w,
-            Token::QuotedString(s) => s,
+            Token::QuotedString(s) | Token::SingleQuoted(s) | Token::EscapedWord(s) => s,
             Token::Variable(v) => format!("${{{}}}", v),
+            Token::CommandSub(c) => format!("$({})", c),
             Token::Eof => return Err(ParseError::MissingRedirectTarget),
             _ => return Err(ParseError::UnexpectedToken(format!("{:?}", target_token))),
         };
@@ -288,6 +685,40 @@ impl<'a> Parser<'a> {
         }
         Ok(())
     }
+
+    /// Skip newlines, `;` and comments between statements
+    fn skip_separators(&mut self) -> Result<(), ParseError> {
+        while matches!(
+            self.lexer.peek()?,
+            Token::Newline | Token::Semicolon | Token::Comment(_)
+        ) {
+            self.lexer.next_token()?;
+        }
+        Ok(())
+    }
+}
+
+/// The command of a command line that is just one command, with no
+/// redirects, pipes or operators
+fn bare_command(command_line: &CommandLine) -> Option<&Command> {
+    match command_line.pipelines.as_slice() {
+        [pipeline]
+            if pipeline.commands.len() == 1
+                && pipeline.stdin_redirect.is_none()
+                && pipeline.stdout_redirects.is_empty()
+                && !pipeline.background =>
+        {
+            pipeline.commands.first().filter(|cmd| cmd.env_assignments.is_empty())
+        }
+        _ => None,
+    }
+}
+
+/// Whether `name` can be assigned to, like `for` loop variables
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
 }
 
 /// Convenience function to parse a command line
@@ -296,6 +727,12 @@ pub fn parse(input: &str) -> Result<CommandLine, ParseError> {
     parser.parse()
 }
 
+/// Convenience function to parse a script, which may span several lines
+pub fn parse_script(input: &str) -> Result<Vec<Statement>, ParseError> {
+    let mut parser = Parser::new(input);
+    parser.parse_script()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -333,6 +770,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_stderr_to_stdout_redirect() {
+        let cmd_line = parse("make 2>&1 2>x").unwrap();
+        let types: Vec<_> =
+            cmd_line.pipelines[0].stdout_redirects.iter().map(|r| &r.redirect_type).collect();
+        assert_eq!(types, [&RedirectType::StderrToStdout, &RedirectType::StderrOverwrite]);
+    }
+
     #[test]
     fn test_logical_operators() {
         let cmd_line = parse("cmd1 && cmd2 || cmd3").unwrap();
@@ -342,6 +787,155 @@ mod tests {
         assert_eq!(cmd_line.operators[1], LogicalOp::Or);
     }
 
+    #[test]
+    fn test_quoted_args_are_marked() {
+        let cmd_line = parse("ls *.rs '*.md' \"$HOME\" x\"*\" '$(x)'").unwrap();
+        let cmd = &cmd_line.pipelines[0].commands[0];
+        assert_eq!(cmd.args, vec!["*.rs", "*.md", "${HOME}", "x*", "$(x)"]);
+        use Quoting::*;
+        assert_eq!(cmd.quoted_args, vec![Unquoted, Single, Double, Unquoted, Single]);
+    }
+
+    #[test]
+    fn test_if_elif_else() {
+        let script = "if false; then echo a\nelif true\nthen\n  echo b\n  echo c\n\
+                      else echo d; fi; echo e";
+        let statements = parse_script(script).unwrap();
+        assert_eq!(statements.len(), 2);
+        let Statement::If {
+            condition,
+            then_branch,
+            elif_branches,
+            else_branch,
+        } = &statements[0]
+        else {
+            panic!("expected an if statement");
+        };
+        assert_eq!(condition.pipelines[0].commands[0].name, "false");
+        assert_eq!(then_branch.len(), 1);
+        assert_eq!(elif_branches.len(), 1);
+        assert_eq!(elif_branches[0].1.len(), 2);
+        assert_eq!(else_branch.as_ref().map(Vec::len), Some(1));
+
+        // Keywords only count as the first word of a command
+        assert_eq!(parse_script("echo if then fi").unwrap().len(), 1);
+
+        let statements = parse_script("if [ a = a ]; then echo y; fi").unwrap();
+        let Statement::If { condition, .. } = &statements[0] else {
+            panic!("expected an if statement");
+        };
+        assert_eq!(condition.pipelines[0].commands[0].args, vec!["a", "=", "a", "]"]);
+        let cmd_line = parse("echo = =x ==").unwrap();
+        assert_eq!(cmd_line.pipelines[0].commands[0].args, vec!["=", "=x", "=="]);
+
+        assert!(matches!(parse_script("if true; then echo a"), Err(ParseError::UnexpectedEof)));
+        assert!(matches!(parse_script("if true\n"), Err(ParseError::UnexpectedEof)));
+        assert!(parse_script("if true; echo a; fi").is_err());
+        assert!(parse_script("fi").is_err());
+    }
+
+    #[test]
+    fn test_compound_commands_after_other_commands() {
+        let statements = parse_script("echo a; if true; then echo b; fi").unwrap();
+        assert!(matches!(statements[..], [Statement::CommandLine(_), Statement::If { .. }]));
+        let statements = parse_script("echo a; for i in 1; do echo $i; done; echo c").unwrap();
+        assert!(matches!(
+            statements[..],
+            [Statement::CommandLine(_), Statement::For { .. }, Statement::CommandLine(_)]
+        ));
+
+        let statements = parse_script("a && b && while c; do d; done || e | f; g").unwrap();
+        assert_eq!(statements.len(), 2);
+        let Statement::AndOr { first, rest } = &statements[0] else {
+            panic!("expected an && / || chain");
+        };
+        let Statement::CommandLine(first) = first.as_ref() else {
+            panic!("expected a command line first");
+        };
+        assert_eq!(first.pipelines.len(), 2);
+        assert!(matches!(
+            rest[..],
+            [
+                (LogicalOp::And, Statement::While { .. }),
+                (LogicalOp::Or, Statement::CommandLine(_))
+            ]
+        ));
+
+        assert!(parse_script("if true; then :; fi && echo x").is_ok());
+        assert!(parse_script("true && if true; then :; fi").is_ok());
+        assert!(parse_script("echo a | while true; do :; done").is_err());
+        assert!(parse("true && if true; then :; fi").is_err());
+    }
+
+    #[test]
+    fn test_function_definitions() {
+        let statements = parse_script("greet() {\n  echo $1\n  return 3\n}\ngreet world").unwrap();
+        assert_eq!(statements.len(), 2);
+        let Statement::Function { name, body } = &statements[0] else {
+            panic!("expected a function definition");
+        };
+        assert_eq!(name, "greet");
+        assert_eq!(body.len(), 2);
+        assert_eq!(body[1], Statement::Return(Some(3)));
+
+        let script = "function greet { echo hi; }; function bye() { return; }";
+        let statements = parse_script(script).unwrap();
+        assert!(matches!(&statements[0], Statement::Function { name, .. } if name == "greet"));
+        let Statement::Function { body, .. } = &statements[1] else {
+            panic!("expected a function definition");
+        };
+        assert_eq!(body[..], [Statement::Return(None)]);
+
+        assert!(matches!(parse_script("greet() {\n  echo hi\n"), Err(ParseError::UnexpectedEof)));
+        assert!(parse_script("echo a() { echo b; }").is_err());
+        assert!(parse_script("}").is_err());
+    }
+
+    #[test]
+    fn test_for_loop() {
+        let script = "for f in a \"*.rs\" $X; do\n  echo $f\n  break\ndone";
+        let statements = parse_script(script).unwrap();
+        let Statement::For {
+            variable,
+            items,
+            quoted_items,
+            body,
+        } = &statements[0]
+        else {
+            panic!("expected a for loop");
+        };
+        assert_eq!(variable, "f");
+        assert_eq!(items, &vec!["a", "*.rs", "${X}"]);
+        assert_eq!(quoted_items, &vec![Quoting::Unquoted, Quoting::Double, Quoting::Unquoted]);
+        assert!(matches!(body[..], [Statement::CommandLine(_), Statement::Break]));
+
+        assert!(parse_script("for x in; do done").is_ok());
+        assert!(matches!(parse_script("for x in a b; do"), Err(ParseError::UnexpectedEof)));
+        assert!(parse_script("for 1x in a; do echo; done").is_err());
+        assert!(parse_script("for x a; do echo; done").is_err());
+    }
+
+    #[test]
+    fn test_while_loop() {
+        let statements = parse_script("while shift\ndo echo $1; done; echo end").unwrap();
+        assert_eq!(statements.len(), 2);
+        let Statement::While { condition, body } = &statements[0] else {
+            panic!("expected a while loop");
+        };
+        assert_eq!(condition.pipelines[0].commands[0].name, "shift");
+        assert_eq!(body.len(), 1);
+
+        // `=` and `!=` are ordinary arguments in conditions
+        let statements = parse_script("while test $x != 0; do x=0; done").unwrap();
+        let Statement::While { condition, .. } = &statements[0] else {
+            panic!("expected a while loop");
+        };
+        assert_eq!(condition.pipelines[0].commands[0].args, vec!["${x}", "!=", "0"]);
+
+        assert!(matches!(parse_script("while true; do"), Err(ParseError::UnexpectedEof)));
+        assert!(parse_script("while true; echo; done").is_err());
+    }
+
     #[test]
     fn test_background() {
         let cmd_line = parse("sleep 10 &").unwrap();
@@ -0,0 +1,90 @@
+//! Running processes, for completing `kill` and `ps` arguments
+
+/// A running process
+#[derive(Debug, Clone, PartialEq)]
+pub struct Process {
+    pub pid: u32,
+    /// Executable name, without its directory
+    pub name: String,
+}
+
+/// Every process the system reports, sorted by PID
+///
+/// Errors give an empty list: this only feeds completion.
+pub fn list() -> Vec<Process> {
+    let mut processes = read_processes();
+    processes.sort_by_key(|process| process.pid);
+    processes
+}
+
+/// Linux: each numeric directory in /proc is a process, named in `comm`
+#[cfg(target_os = "linux")]
+fn read_processes() -> Vec<Process> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse().ok()?;
+            let name = std::fs::read_to_string(entry.path().join("comm")).ok()?;
+            Some(Process {
+                pid,
+                name: name.trim_end().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Other Unix systems: ask `ps`
+#[cfg(all(unix, not(target_os = "linux")))]
+fn read_processes() -> Vec<Process> {
+    let Ok(output) = std::process::Command::new("ps").args(["-axo", "pid=,comm="]).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (pid, command) = line.trim_start().split_once(' ')?;
+            let name = command.trim().rsplit('/').next()?;
+            Some(Process {
+                pid: pid.parse().ok()?,
+                name: name.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Windows: ask `tasklist` for CSV rows of "name","pid",...
+#[cfg(windows)]
+fn read_processes() -> Vec<Process> {
+    let Ok(output) = std::process::Command::new("tasklist").args(["/fo", "csv", "/nh"]).output()
+    else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split("\",\"").map(|field| field.trim_matches('"'));
+            let name = fields.next()?;
+            let pid = fields.next()?.parse().ok()?;
+            Some(Process {
+                pid,
+                name: name.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_list_includes_this_process() {
+        let processes = list();
+        assert!(processes.iter().any(|process| process.pid == std::process::id()));
+        assert!(processes.windows(2).all(|pair| pair[0].pid < pair[1].pid));
+    }
+}
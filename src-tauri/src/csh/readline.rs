@@ -3,15 +3,28 @@
 //! This module provides a readline-like interface for CSH with:
 //! - Tab completion for commands and paths
 //! - History navigation (Up/Down arrows)
+//! - History autosuggestions shown as ghost text (Right/End accepts,
+//!   Alt+Enter or Ctrl+Enter accepts and runs)
 //! - Cursor movement (Left/Right/Home/End)
 //! - Line editing (Backspace, Delete, Ctrl+U, Ctrl+K)
 //! - Ctrl+C to cancel, Ctrl+D for EOF
+//! - An optional right-aligned prompt (RPROMPT) that steps aside when the
+//!   input reaches it
+//!
+//! Raw mode is held by a `RawModeGuard` for the length of one line, so it
+//! is switched off again on every exit path, including errors and panics.
 //!
 //! Works both in standalone mode and inside PTY (ConPTY on Windows).
 
-use std::io::{self, Read, Stdout, Write};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use unicode_width::UnicodeWidthStr;
 
-use crate::csh::completion::{Completer, Completion};
+use crate::csh::completion::{
+    completion_limit, format_listing, insert_completion, split_words, truncate_completions, Completer,
+    Completion,
+};
 use crate::csh::environment::Environment;
 use crate::csh::history::History;
 
@@ -33,6 +46,8 @@ enum EscapeState {
     Escape,      // Got ESC
     Csi,         // Got ESC [
     CsiParam,    // Got ESC [ with parameters
+    Ss3,         // Got ESC O
+    LinuxFn,     // Got ESC [ [ (Linux console F1-F5)
 }
 
 /// Line editor state
@@ -47,6 +62,8 @@ pub struct LineEditor {
     saved_input: String,
     /// Completions cache
     completions: Vec<Completion>,
+    /// Completions left out of the cache by the limit
+    completions_omitted: usize,
     /// Current completion index (for cycling)
     completion_index: usize,
     /// Whether we're in completion mode
@@ -59,6 +76,12 @@ pub struct LineEditor {
     escape_params: String,
     /// Whether user has typed anything (to avoid redraw on initial resize)
     has_input: bool,
+    /// Rest of the most recent matching history entry, shown as ghost text
+    suggestion: Option<String>,
+    /// Text shown at the right edge of the input line (empty for none)
+    rprompt: String,
+    /// Whether the right prompt is currently on screen
+    rprompt_shown: bool,
 }
 
 impl LineEditor {
@@ -69,15 +92,24 @@ impl LineEditor {
             history_index: -1,
             saved_input: String::new(),
             completions: Vec::new(),
+            completions_omitted: 0,
             completion_index: 0,
             in_completion: false,
             prompt: String::new(),
             escape_state: EscapeState::Normal,
             escape_params: String::new(),
             has_input: false,
+            suggestion: None,
+            rprompt: String::new(),
+            rprompt_shown: false,
         }
     }
 
+    /// Set the right prompt used by the next `readline` call
+    pub fn set_rprompt(&mut self, rprompt: String) {
+        self.rprompt = rprompt;
+    }
+
     /// Read a line with the given prompt
     pub fn readline(
         &mut self,
@@ -99,29 +131,31 @@ impl LineEditor {
         self.escape_state = EscapeState::Normal;
         self.escape_params.clear();
         self.has_input = false;
+        self.suggestion = None;
+        self.rprompt_shown = false;
 
         // Print prompt
         let mut stdout = io::stdout();
         write!(stdout, "{}", prompt)?;
+        self.refresh_rprompt(&mut stdout)?;
         stdout.flush()?;
 
         // Enable raw mode using crossterm
         // This works for both real terminals and ConPTY
-        let raw_mode_result = crossterm::terminal::enable_raw_mode();
-        
-        let result = match raw_mode_result {
-            Ok(_) => {
+        let result = match RawModeGuard::enable() {
+            Some(_guard) => {
                 // Raw mode enabled successfully - use crossterm events
-                let res = self.read_loop_crossterm(&mut stdout, history, completer, env);
-                let _ = crossterm::terminal::disable_raw_mode();
-                res
+                self.read_loop_crossterm(&mut stdout, history, completer, env)
             }
-            Err(_) => {
-                // Raw mode failed - try raw byte reading
+            None => {
+                // Raw mode failed or didn't take effect - try raw byte reading
                 self.read_loop_raw(&mut stdout, history, completer, env)
             }
         };
 
+        // The right prompt doesn't stay behind above the command's output
+        let _ = self.clear_rprompt(&mut stdout);
+
         // Print newline after input
         println!();
 
@@ -131,12 +165,12 @@ impl LineEditor {
     /// Read loop using crossterm events
     fn read_loop_crossterm(
         &mut self,
-        stdout: &mut Stdout,
+        stdout: &mut dyn Write,
         history: &History,
         completer: &Completer,
         env: &Environment,
     ) -> io::Result<ReadlineResult> {
-        use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers, KeyEventKind};
+        use crossterm::event::{self, Event, KeyEvent, KeyEventKind};
 
         loop {
             // Poll for events with a timeout
@@ -150,86 +184,8 @@ impl LineEditor {
                         kind: KeyEventKind::Press, // Only handle Press events, ignore Release
                         ..
                     }) => {
-                        // Handle Ctrl combinations first
-                        if modifiers.contains(KeyModifiers::CONTROL) {
-                            match code {
-                                KeyCode::Char('c') => {
-                                    write!(stdout, "^C")?;
-                                    stdout.flush()?;
-                                    return Ok(ReadlineResult::Interrupted);
-                                }
-                                KeyCode::Char('d') => {
-                                    if self.buffer.is_empty() {
-                                        return Ok(ReadlineResult::Eof);
-                                    } else {
-                                        self.delete_char(stdout)?;
-                                    }
-                                }
-                                KeyCode::Char('l') => {
-                                    self.clear_screen(stdout)?;
-                                }
-                                KeyCode::Char('u') => {
-                                    self.delete_to_start(stdout)?;
-                                }
-                                KeyCode::Char('k') => {
-                                    self.delete_to_end(stdout)?;
-                                }
-                                KeyCode::Char('a') => {
-                                    self.move_to_start(stdout)?;
-                                }
-                                KeyCode::Char('e') => {
-                                    self.move_to_end(stdout)?;
-                                }
-                                KeyCode::Char('w') => {
-                                    self.delete_word_backward(stdout)?;
-                                }
-                                _ => {}
-                            }
-                            continue;
-                        }
-
-                        // Reset completion mode on non-Tab key
-                        if code != KeyCode::Tab {
-                            self.in_completion = false;
-                            self.completions.clear(); // Clear old completions
-                        }
-
-                        match code {
-                            KeyCode::Enter => {
-                                return Ok(ReadlineResult::Line(self.buffer.clone()));
-                            }
-                            KeyCode::Tab => {
-                                self.handle_tab(stdout, completer, env)?;
-                            }
-                            KeyCode::Backspace => {
-                                self.delete_char_backward(stdout)?;
-                            }
-                            KeyCode::Delete => {
-                                self.delete_char(stdout)?;
-                            }
-                            KeyCode::Left => {
-                                self.move_left(stdout)?;
-                            }
-                            KeyCode::Right => {
-                                self.move_right(stdout)?;
-                            }
-                            KeyCode::Up => {
-                                self.history_prev(stdout, history)?;
-                            }
-                            KeyCode::Down => {
-                                self.history_next(stdout, history)?;
-                            }
-                            KeyCode::Home => {
-                                self.move_to_start(stdout)?;
-                            }
-                            KeyCode::End => {
-                                self.move_to_end(stdout)?;
-                            }
-                            KeyCode::Char(c) => {
-                                self.has_input = true;
-                                self.insert_char(c, stdout)?;
-                            }
-                            _ => {}
+                        if let Some(result) = self.handle_key(code, modifiers, stdout, history, completer, env)? {
+                            return Ok(result);
                         }
                     }
                     Event::Key(_) => {
@@ -241,6 +197,9 @@ impl LineEditor {
                         if self.has_input {
                             self.redraw_line(stdout)?;
                         }
+                        // Realign the right prompt to the new width
+                        self.clear_rprompt(stdout)?;
+                        self.refresh_rprompt(stdout)?;
                     }
                     _ => {}
                 }
@@ -248,10 +207,120 @@ impl LineEditor {
         }
     }
 
+    /// Handle one key press; returns a result when the line is finished
+    fn handle_key(
+        &mut self,
+        code: crossterm::event::KeyCode,
+        modifiers: crossterm::event::KeyModifiers,
+        stdout: &mut dyn Write,
+        history: &History,
+        completer: &Completer,
+        env: &Environment,
+    ) -> io::Result<Option<ReadlineResult>> {
+        use crossterm::event::{KeyCode, KeyModifiers};
+
+        // Ctrl+Enter / Alt+Enter: take the suggestion and run it
+        if code == KeyCode::Enter && modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return self.accept_suggestion_and_run(stdout);
+        }
+
+        // Handle Ctrl combinations first
+        if modifiers.contains(KeyModifiers::CONTROL) {
+            match code {
+                KeyCode::Char('c') => {
+                    write!(stdout, "^C")?;
+                    stdout.flush()?;
+                    return Ok(Some(ReadlineResult::Interrupted));
+                }
+                KeyCode::Char('d') => {
+                    if self.buffer.is_empty() {
+                        return Ok(Some(ReadlineResult::Eof));
+                    } else {
+                        self.delete_char(stdout)?;
+                    }
+                }
+                KeyCode::Char('l') => {
+                    self.clear_screen(stdout)?;
+                }
+                KeyCode::Char('u') => {
+                    self.delete_to_start(stdout)?;
+                }
+                KeyCode::Char('k') => {
+                    self.delete_to_end(stdout)?;
+                }
+                KeyCode::Char('a') => {
+                    self.move_to_start(stdout)?;
+                }
+                KeyCode::Char('e') => {
+                    self.move_to_end(stdout)?;
+                }
+                KeyCode::Char('w') => {
+                    self.delete_word_backward(stdout)?;
+                }
+                _ => {}
+            }
+            self.refresh_suggestion(stdout, history)?;
+            self.refresh_rprompt(stdout)?;
+            return Ok(None);
+        }
+
+        // Reset completion mode on non-Tab key
+        if code != KeyCode::Tab {
+            self.in_completion = false;
+            self.completions.clear(); // Clear old completions
+        }
+
+        match code {
+            KeyCode::Enter => {
+                self.clear_suggestion(stdout)?;
+                return Ok(Some(ReadlineResult::Line(self.buffer.clone())));
+            }
+            KeyCode::Tab => {
+                self.handle_tab(stdout, completer, env)?;
+            }
+            KeyCode::Backspace => {
+                self.delete_char_backward(stdout)?;
+            }
+            KeyCode::Delete => {
+                self.delete_char(stdout)?;
+            }
+            KeyCode::Left => {
+                self.move_left(stdout)?;
+            }
+            KeyCode::Right | KeyCode::End if self.suggestion.is_some() => {
+                self.accept_suggestion(stdout)?;
+            }
+            KeyCode::Right => {
+                self.move_right(stdout)?;
+            }
+            KeyCode::Up => {
+                self.history_prev(stdout, history)?;
+            }
+            KeyCode::Down => {
+                self.history_next(stdout, history)?;
+            }
+            KeyCode::Home => {
+                self.move_to_start(stdout)?;
+            }
+            KeyCode::End => {
+                self.move_to_end(stdout)?;
+            }
+            KeyCode::Char(c) => {
+                self.has_input = true;
+                self.insert_char(c, stdout)?;
+            }
+            _ => {}
+        }
+
+        self.refresh_suggestion(stdout, history)?;
+        self.refresh_rprompt(stdout)?;
+        Ok(None)
+    }
+
     /// Read loop using raw byte reading (fallback)
     fn read_loop_raw(
         &mut self,
-        stdout: &mut Stdout,
+        stdout: &mut dyn Write,
         history: &History,
         completer: &Completer,
         env: &Environment,
@@ -265,117 +334,248 @@ impl LineEditor {
                 return Ok(ReadlineResult::Eof);
             }
 
-            let byte = buf[0];
+            if let Some(result) = self.handle_raw_byte(buf[0], &mut stdin, stdout, history, completer, env)? {
+                return Ok(result);
+            }
 
-            // Handle escape sequences
-            match self.escape_state {
-                EscapeState::Normal => {
-                    match byte {
-                        0x1B => {
-                            self.escape_state = EscapeState::Escape;
-                            self.escape_params.clear();
-                        }
-                        0x03 => {
-                            write!(stdout, "^C")?;
-                            stdout.flush()?;
-                            return Ok(ReadlineResult::Interrupted);
-                        }
-                        0x04 => {
-                            if self.buffer.is_empty() {
-                                return Ok(ReadlineResult::Eof);
-                            } else {
-                                self.delete_char(stdout)?;
-                            }
-                        }
-                        0x09 => {
-                            self.handle_tab(stdout, completer, env)?;
-                        }
-                        0x0D | 0x0A => {
-                            return Ok(ReadlineResult::Line(self.buffer.clone()));
-                        }
-                        0x7F | 0x08 => {
-                            self.in_completion = false;
-                            self.delete_char_backward(stdout)?;
+            if self.escape_state == EscapeState::Normal {
+                self.refresh_suggestion(stdout, history)?;
+                self.refresh_rprompt(stdout)?;
+            }
+        }
+    }
+
+    /// Handle one byte of raw input; `input` supplies the rest of a UTF-8 character
+    ///
+    /// Escape sequences are parsed per ECMA-48 so unknown ones are consumed
+    /// whole instead of leaking into the line. Covered key variants:
+    /// - Home: `ESC [ H`, `ESC O H`, `ESC [ 1 ~`, `ESC [ 7 ~`
+    /// - End: `ESC [ F`, `ESC O F`, `ESC [ 4 ~`, `ESC [ 8 ~`
+    /// - Delete: `ESC [ 3 ~`
+    /// - Page Up/Down, Insert: `ESC [ 5 ~`, `ESC [ 6 ~`, `ESC [ 2 ~` (ignored)
+    ///
+    /// plus xterm modifier parameters (`ESC [ 1 ; 5 H`, `ESC [ 3 ; 2 ~`) and
+    /// rxvt's modified forms ending in `^`, `$` or `@`.
+    fn handle_raw_byte<R: Read>(
+        &mut self,
+        byte: u8,
+        input: &mut R,
+        stdout: &mut dyn Write,
+        history: &History,
+        completer: &Completer,
+        env: &Environment,
+    ) -> io::Result<Option<ReadlineResult>> {
+        match self.escape_state {
+            EscapeState::Normal => {
+                match byte {
+                    0x1B => {
+                        self.escape_state = EscapeState::Escape;
+                        self.escape_params.clear();
+                    }
+                    0x03 => {
+                        write!(stdout, "^C")?;
+                        stdout.flush()?;
+                        return Ok(Some(ReadlineResult::Interrupted));
+                    }
+                    0x04 => {
+                        if self.buffer.is_empty() {
+                            return Ok(Some(ReadlineResult::Eof));
+                        } else {
+                            self.delete_char(stdout)?;
                         }
-                        0x01 => self.move_to_start(stdout)?,
-                        0x05 => self.move_to_end(stdout)?,
-                        0x0B => self.delete_to_end(stdout)?,
-                        0x15 => self.delete_to_start(stdout)?,
-                        0x17 => self.delete_word_backward(stdout)?,
-                        0x0C => self.clear_screen(stdout)?,
-                        0x20..=0x7E => {
+                    }
+                    0x09 => {
+                        self.handle_tab(stdout, completer, env)?;
+                    }
+                    0x0D | 0x0A => {
+                        self.clear_suggestion(stdout)?;
+                        return Ok(Some(ReadlineResult::Line(self.buffer.clone())));
+                    }
+                    0x7F | 0x08 => {
+                        self.in_completion = false;
+                        self.delete_char_backward(stdout)?;
+                    }
+                    0x01 => self.move_to_start(stdout)?,
+                    0x05 => self.move_to_end(stdout)?,
+                    0x0B => self.delete_to_end(stdout)?,
+                    0x15 => self.delete_to_start(stdout)?,
+                    0x17 => self.delete_word_backward(stdout)?,
+                    0x0C => self.clear_screen(stdout)?,
+                    0x20..=0x7E => {
+                        self.in_completion = false;
+                        self.insert_char(byte as char, stdout)?;
+                    }
+                    _ => {
+                        if byte >= 0xC0 {
                             self.in_completion = false;
-                            self.insert_char(byte as char, stdout)?;
-                        }
-                        _ => {
-                            if byte >= 0xC0 {
-                                self.in_completion = false;
-                                let char_result = self.read_utf8_char(&mut stdin, byte)?;
-                                if let Some(c) = char_result {
-                                    self.insert_char(c, stdout)?;
-                                }
+                            let char_result = self.read_utf8_char(input, byte)?;
+                            if let Some(c) = char_result {
+                                self.insert_char(c, stdout)?;
                             }
                         }
                     }
                 }
-                EscapeState::Escape => {
-                    match byte {
-                        b'[' | b'O' => {
-                            self.escape_state = EscapeState::Csi;
-                        }
-                        _ => {
-                            self.escape_state = EscapeState::Normal;
-                        }
+            }
+            EscapeState::Escape => {
+                match byte {
+                    b'[' => {
+                        self.escape_state = EscapeState::Csi;
+                    }
+                    b'O' => {
+                        self.escape_state = EscapeState::Ss3;
+                    }
+                    0x0D | 0x0A => {
+                        // Alt+Enter
+                        self.escape_state = EscapeState::Normal;
+                        return self.accept_suggestion_and_run(stdout);
+                    }
+                    _ => {
+                        self.escape_state = EscapeState::Normal;
                     }
                 }
-                EscapeState::Csi | EscapeState::CsiParam => {
-                    match byte {
-                        b'0'..=b'9' | b';' => {
-                            self.escape_params.push(byte as char);
-                            self.escape_state = EscapeState::CsiParam;
-                        }
-                        b'A' => {
-                            self.escape_state = EscapeState::Normal;
-                            self.in_completion = false;
-                            self.history_prev(stdout, history)?;
-                        }
-                        b'B' => {
-                            self.escape_state = EscapeState::Normal;
-                            self.in_completion = false;
-                            self.history_next(stdout, history)?;
-                        }
-                        b'C' => {
-                            self.escape_state = EscapeState::Normal;
-                            self.move_right(stdout)?;
-                        }
-                        b'D' => {
-                            self.escape_state = EscapeState::Normal;
-                            self.move_left(stdout)?;
-                        }
-                        b'H' => {
-                            self.escape_state = EscapeState::Normal;
-                            self.move_to_start(stdout)?;
-                        }
-                        b'F' => {
-                            self.escape_state = EscapeState::Normal;
-                            self.move_to_end(stdout)?;
-                        }
-                        b'~' => {
-                            self.escape_state = EscapeState::Normal;
-                            match self.escape_params.as_str() {
-                                "1" | "7" => self.move_to_start(stdout)?,
-                                "4" | "8" => self.move_to_end(stdout)?,
-                                "3" => self.delete_char(stdout)?,
-                                _ => {}
-                            }
-                        }
-                        _ => {
-                            self.escape_state = EscapeState::Normal;
+            }
+            EscapeState::Csi | EscapeState::CsiParam => {
+                match byte {
+                    b'[' if self.escape_state == EscapeState::Csi => {
+                        self.escape_state = EscapeState::LinuxFn;
+                    }
+                    b'~' | b'^' | b'$' | b'@' => {
+                        self.escape_state = EscapeState::Normal;
+                        let key = self.escape_params.split(';').next().unwrap_or("");
+                        match key {
+                            "1" | "7" => self.move_to_start(stdout)?,
+                            "4" | "8" => self.move_to_end(stdout)?,
+                            "3" => self.delete_char(stdout)?,
+                            // Insert, Page Up and Page Down have no editor action
+                            _ => {}
                         }
                     }
+                    // Parameter and intermediate bytes (rxvt's `$` final byte is matched above)
+                    0x20..=0x3F => {
+                        self.escape_params.push(byte as char);
+                        self.escape_state = EscapeState::CsiParam;
+                    }
+                    0x40..=0x7E => {
+                        self.escape_state = EscapeState::Normal;
+                        self.handle_cursor_key(byte, stdout, history)?;
+                    }
+                    // Not part of a sequence: drop it
+                    _ => {
+                        self.escape_state = EscapeState::Normal;
+                    }
+                }
+            }
+            EscapeState::Ss3 => {
+                match byte {
+                    // Some terminals put a modifier digit before the final byte
+                    b'0'..=b'9' | b';' => {
+                        self.escape_params.push(byte as char);
+                    }
+                    _ => {
+                        self.escape_state = EscapeState::Normal;
+                        self.handle_cursor_key(byte, stdout, history)?;
+                    }
                 }
             }
+            EscapeState::LinuxFn => {
+                // The function key letter ends the sequence
+                self.escape_state = EscapeState::Normal;
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Act on the final byte of a `ESC [` or `ESC O` cursor key sequence
+    fn handle_cursor_key(&mut self, key: u8, stdout: &mut dyn Write, history: &History) -> io::Result<()> {
+        match key {
+            b'A' => {
+                self.in_completion = false;
+                self.history_prev(stdout, history)?;
+            }
+            b'B' => {
+                self.in_completion = false;
+                self.history_next(stdout, history)?;
+            }
+            b'C' => {
+                if self.suggestion.is_some() {
+                    self.accept_suggestion(stdout)?;
+                } else {
+                    self.move_right(stdout)?;
+                }
+            }
+            b'D' => self.move_left(stdout)?,
+            b'H' => self.move_to_start(stdout)?,
+            b'F' => self.move_to_end(stdout)?,
+            // Function keys and anything else
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Show the rest of the latest history entry starting with the input
+    ///
+    /// Only offered while the cursor is at the end of a non-empty line.
+    fn refresh_suggestion(&mut self, stdout: &mut dyn Write, history: &History) -> io::Result<()> {
+        let at_end = self.cursor == self.buffer.chars().count();
+        let suggestion = if at_end && !self.buffer.is_empty() {
+            history
+                .search_prefix(&self.buffer)
+                .map(|entry| entry[self.buffer.len()..].to_string())
+                .filter(|rest| !rest.is_empty())
+        } else {
+            None
+        };
+
+        if suggestion.is_none() && self.suggestion.is_none() {
+            return Ok(());
+        }
+        self.suggestion = suggestion;
+
+        // Clear the old ghost text after the input, draw the new one in
+        // gray and put the cursor back where it was
+        let after = self.buffer.chars().count() - self.cursor;
+        if after > 0 {
+            write!(stdout, "\x1b[{}C", after)?;
         }
+        write!(stdout, "\x1b[K")?;
+        if let Some(ref ghost) = self.suggestion {
+            write!(stdout, "\x1b[90m{}\x1b[0m\x1b[{}D", ghost, ghost.chars().count())?;
+        }
+        if after > 0 {
+            write!(stdout, "\x1b[{}D", after)?;
+        }
+        stdout.flush()
+    }
+
+    /// Move the suggestion into the buffer
+    fn accept_suggestion(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
+        if let Some(ghost) = self.suggestion.take() {
+            self.buffer.push_str(&ghost);
+            self.cursor = self.buffer.chars().count();
+            self.has_input = true;
+            self.redraw_line(stdout)?;
+        }
+        Ok(())
+    }
+
+    /// Accept the suggestion and submit the line; does nothing without one
+    fn accept_suggestion_and_run(&mut self, stdout: &mut dyn Write) -> io::Result<Option<ReadlineResult>> {
+        if self.suggestion.is_none() {
+            return Ok(None);
+        }
+        self.accept_suggestion(stdout)?;
+        Ok(Some(ReadlineResult::Line(self.buffer.clone())))
+    }
+
+    /// Erase the ghost text before the line is submitted
+    fn clear_suggestion(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
+        // A suggestion is only shown with the cursor at the end of the line
+        if self.suggestion.take().is_some() {
+            write!(stdout, "\x1b[K")?;
+            stdout.flush()?;
+        }
+        Ok(())
     }
 
     /// Read a multi-byte UTF-8 character
@@ -398,7 +598,7 @@ impl LineEditor {
     }
 
     /// Insert a character at cursor position
-    fn insert_char(&mut self, c: char, stdout: &mut Stdout) -> io::Result<()> {
+    fn insert_char(&mut self, c: char, stdout: &mut dyn Write) -> io::Result<()> {
         let byte_pos = self.cursor_to_byte_pos();
         self.buffer.insert(byte_pos, c);
         self.cursor += 1;
@@ -417,7 +617,7 @@ impl LineEditor {
         self.buffer.char_indices().nth(self.cursor).map(|(i, _)| i).unwrap_or(self.buffer.len())
     }
 
-    fn delete_char_backward(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+    fn delete_char_backward(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
         if self.cursor == 0 { return Ok(()); }
 
         self.cursor -= 1;
@@ -429,7 +629,7 @@ impl LineEditor {
         Ok(())
     }
 
-    fn delete_char(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+    fn delete_char(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
         let char_count = self.buffer.chars().count();
         if self.cursor >= char_count { return Ok(()); }
 
@@ -439,7 +639,7 @@ impl LineEditor {
         Ok(())
     }
 
-    fn delete_to_start(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+    fn delete_to_start(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
         if self.cursor == 0 { return Ok(()); }
 
         let byte_pos = self.cursor_to_byte_pos();
@@ -449,7 +649,7 @@ impl LineEditor {
         Ok(())
     }
 
-    fn delete_to_end(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+    fn delete_to_end(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
         let char_count = self.buffer.chars().count();
         if self.cursor >= char_count { return Ok(()); }
 
@@ -460,7 +660,7 @@ impl LineEditor {
         Ok(())
     }
 
-    fn delete_word_backward(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+    fn delete_word_backward(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
         if self.cursor == 0 { return Ok(()); }
 
         let byte_pos = self.cursor_to_byte_pos();
@@ -475,7 +675,7 @@ impl LineEditor {
         Ok(())
     }
 
-    fn move_left(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+    fn move_left(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
         if self.cursor == 0 { return Ok(()); }
         self.cursor -= 1;
         write!(stdout, "\x1b[D")?;
@@ -483,7 +683,7 @@ impl LineEditor {
         Ok(())
     }
 
-    fn move_right(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+    fn move_right(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
         let char_count = self.buffer.chars().count();
         if self.cursor >= char_count { return Ok(()); }
         self.cursor += 1;
@@ -492,7 +692,7 @@ impl LineEditor {
         Ok(())
     }
 
-    fn move_to_start(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+    fn move_to_start(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
         if self.cursor > 0 {
             write!(stdout, "\x1b[{}D", self.cursor)?;
             self.cursor = 0;
@@ -501,7 +701,7 @@ impl LineEditor {
         Ok(())
     }
 
-    fn move_to_end(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+    fn move_to_end(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
         let char_count = self.buffer.chars().count();
         let move_by = char_count - self.cursor;
         if move_by > 0 {
@@ -512,7 +712,7 @@ impl LineEditor {
         Ok(())
     }
 
-    fn history_prev(&mut self, stdout: &mut Stdout, history: &History) -> io::Result<()> {
+    fn history_prev(&mut self, stdout: &mut dyn Write, history: &History) -> io::Result<()> {
         let history_len = history.len() as i32;
         if history_len == 0 { return Ok(()); }
 
@@ -531,7 +731,7 @@ impl LineEditor {
         Ok(())
     }
 
-    fn history_next(&mut self, stdout: &mut Stdout, history: &History) -> io::Result<()> {
+    fn history_next(&mut self, stdout: &mut dyn Write, history: &History) -> io::Result<()> {
         if self.history_index <= 0 {
             if self.history_index == 0 {
                 self.history_index = -1;
@@ -551,42 +751,22 @@ impl LineEditor {
         Ok(())
     }
 
-    fn handle_tab(&mut self, stdout: &mut Stdout, completer: &Completer, env: &Environment) -> io::Result<()> {
+    fn handle_tab(&mut self, stdout: &mut dyn Write, completer: &Completer, env: &Environment) -> io::Result<()> {
         // If already in completion mode with valid completions, cycle through them
         if self.in_completion && !self.completions.is_empty() {
             self.completion_index = (self.completion_index + 1) % self.completions.len();
-            
-            // Get current filename (not full path) and path prefix
-            let current_filename = self.get_completion_filename();
-            let path_prefix = self.get_completion_prefix();
-            
-            if !current_filename.is_empty() {
-                // Move back and clear just the filename part
-                write!(stdout, "\x1b[{}D\x1b[K", current_filename.chars().count())?;
-            }
-            
-            // Get the completion and write it
-            let completion = &self.completions[self.completion_index];
-            let mut text = completion.text.clone();
-            if completion.is_dir && !text.ends_with('/') && !text.ends_with('\\') {
-                text.push('/');
-            }
-            write!(stdout, "{}", text)?;
-            stdout.flush()?;
-            
-            // Update buffer with path prefix preserved
-            let current_word = self.get_completion_word();
-            let word_start = self.buffer.len() - current_word.len();
-            let full_completion = format!("{}{}", path_prefix, text);
-            self.buffer = format!("{}{}", &self.buffer[..word_start], full_completion);
-            self.cursor = self.buffer.chars().count();
-            
-            return Ok(());
+            let completion = self.completions[self.completion_index].clone();
+            return self.replace_completion_word(&completion.text, completion.is_dir, true, stdout);
         }
 
         // Get fresh completions
         self.completions = completer.complete(&self.buffer, env);
         self.completion_index = 0;
+        // Directories with thousands of entries keep only the first few for
+        // listing and cycling, but the common prefix comes from all of them
+        let common = Completer::common_prefix(&self.completions);
+        self.completions_omitted =
+            truncate_completions(&mut self.completions, completion_limit(env));
 
         if self.completions.is_empty() {
             // No completions - ring bell
@@ -596,19 +776,30 @@ impl LineEditor {
             return Ok(());
         }
 
-        if self.completions.len() == 1 {
+        if self.completions.len() == 1 && self.completions_omitted == 0 {
             // Single match: complete immediately
-            self.apply_completion_inline(stdout)?;
+            let completion = self.completions[0].clone();
+            let completed = insert_completion(&self.buffer, &completion.text, completion.is_dir, true);
+            if completed == self.buffer && !completion.is_dir {
+                // Already typed in full: end the word like bash does
+                self.buffer.push(' ');
+                self.cursor = self.buffer.chars().count();
+                self.redraw_line(stdout)?;
+            } else {
+                self.replace_completion_word(&completion.text, completion.is_dir, true, stdout)?;
+            }
             self.in_completion = false;
             self.completions.clear();
         } else {
             // Multiple matches
-            let common = Completer::common_prefix(&self.completions);
             let current_filename = self.get_completion_filename();
             
             if common.len() > current_filename.len() {
                 // Apply common prefix inline
-                self.apply_common_prefix_inline(&common, stdout)?;
+                self.replace_completion_word(&common, false, false, stdout)?;
+            } else {
+                // Nothing to add: show the candidates instead
+                self.show_completions(stdout, env)?;
             }
             
             // Enter completion mode for cycling
@@ -617,100 +808,43 @@ impl LineEditor {
         Ok(())
     }
 
-    fn get_completion_word(&self) -> String {
-        self.buffer.split_whitespace().last().unwrap_or("").to_string()
-    }
-
     /// Get just the filename part of the completion word (after last / or \)
     fn get_completion_filename(&self) -> String {
-        let word = self.get_completion_word();
+        let word = split_words(&self.buffer).pop().map(|w| w.value).unwrap_or_default();
         // Find last path separator
-        let last_sep = word.rfind(|c| c == '/' || c == '\\').map(|i| i + 1).unwrap_or(0);
+        let last_sep = word.rfind(['/', '\\']).map(|i| i + 1).unwrap_or(0);
         word[last_sep..].to_string()
     }
 
-    /// Get the directory prefix of the completion word (before the filename)
-    fn get_completion_prefix(&self) -> String {
-        let word = self.get_completion_word();
-        // Find last path separator
-        if let Some(last_sep) = word.rfind(|c| c == '/' || c == '\\') {
-            word[..=last_sep].to_string()
-        } else {
-            String::new()
-        }
-    }
-
-    /// Apply completion inline - just extend the current text without redrawing prompt
-    fn apply_completion_inline(&mut self, stdout: &mut Stdout) -> io::Result<()> {
-        if self.completions.is_empty() { return Ok(()); }
-
-        let completion = &self.completions[self.completion_index];
-        let current_filename = self.get_completion_filename();
-        let prefix = self.get_completion_prefix();
-        
-        // Build full completion with prefix
-        let mut new_text = completion.text.clone();
-        if completion.is_dir && !new_text.ends_with('/') && !new_text.ends_with('\\') {
-            new_text.push('/');
-        }
-        let full_completion = format!("{}{}", prefix, new_text);
-        
-        // Calculate what to append
-        let current_word = self.get_completion_word();
-        let append_text = if full_completion.len() > current_word.len() && full_completion.starts_with(&current_word) {
-            full_completion[current_word.len()..].to_string()
-        } else if new_text.len() > current_filename.len() && new_text.starts_with(&current_filename) {
-            // Just append the difference to filename
-            new_text[current_filename.len()..].to_string()
-        } else {
-            // Need to clear current filename and write new
-            if !current_filename.is_empty() {
-                write!(stdout, "\x1b[{}D\x1b[K", current_filename.chars().count())?;
-            }
-            new_text.clone()
-        };
-        
-        // Update buffer
-        let word_start = self.buffer.len() - current_word.len();
-        self.buffer = format!("{}{}", &self.buffer[..word_start], full_completion);
-        self.cursor = self.buffer.chars().count();
-        
-        // Print the appended/new text
-        write!(stdout, "{}", append_text)?;
-        stdout.flush()?;
-        
-        self.has_input = true;
-        Ok(())
+    /// List the current completions below the input line, then redraw it
+    ///
+    /// Setting `CSH_COMPLETION_VERBOSE` adds file sizes and types.
+    /// `CSH_COMPLETION_LIMIT` caps the candidates listed (0 for no cap).
+    fn show_completions(&self, stdout: &mut dyn Write, env: &Environment) -> io::Result<()> {
+        let verbose = env
+            .get_value("CSH_COMPLETION_VERBOSE")
+            .is_some_and(|v| !v.is_empty() && v != "0");
+        let listing =
+            format_listing(&self.completions, self.completions_omitted, verbose, terminal_width());
+        write!(stdout, "\r\n{}", listing.replace('\n', "\r\n"))?;
+        self.redraw_line(stdout)
     }
 
-    /// Apply common prefix inline - just extend the current text
-    fn apply_common_prefix_inline(&mut self, prefix: &str, stdout: &mut Stdout) -> io::Result<()> {
-        let current_filename = self.get_completion_filename();
-        let path_prefix = self.get_completion_prefix();
-        
-        // Calculate what to append (comparing with filename, not full word)
-        let append_text = if prefix.len() > current_filename.len() && prefix.starts_with(&current_filename) {
-            prefix[current_filename.len()..].to_string()
-        } else {
-            return Ok(()); // Nothing to append
-        };
-        
-        // Update buffer with path prefix + common prefix
-        let current_word = self.get_completion_word();
-        let word_start = self.buffer.len() - current_word.len();
-        let full_completion = format!("{}{}", path_prefix, prefix);
-        self.buffer = format!("{}{}", &self.buffer[..word_start], full_completion);
+    /// Replace the word under completion with `text` (quoted as needed)
+    fn replace_completion_word(
+        &mut self,
+        text: &str,
+        is_dir: bool,
+        finished: bool,
+        stdout: &mut dyn Write,
+    ) -> io::Result<()> {
+        self.buffer = insert_completion(&self.buffer, text, is_dir, finished);
         self.cursor = self.buffer.chars().count();
-        
-        // Print the appended text
-        write!(stdout, "{}", append_text)?;
-        stdout.flush()?;
-        
         self.has_input = true;
-        Ok(())
+        self.redraw_line(stdout)
     }
 
-    fn clear_screen(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+    fn clear_screen(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
         write!(stdout, "\x1b[2J\x1b[H")?;
         self.redraw_line(stdout)?;
         Ok(())
@@ -731,7 +865,7 @@ impl LineEditor {
         self.prompt.matches('\n').count() + 1
     }
 
-    fn redraw_line(&self, stdout: &mut Stdout) -> io::Result<()> {
+    fn redraw_line(&self, stdout: &mut dyn Write) -> io::Result<()> {
         // For history navigation, we only need to redraw the current input line
         // The first line of the prompt (status bar) should stay unchanged
         // 
@@ -755,7 +889,52 @@ impl LineEditor {
         Ok(())
     }
 
-    fn redraw_from_cursor(&self, stdout: &mut Stdout) -> io::Result<()> {
+    /// Draw the right prompt at the right edge of the input line
+    ///
+    /// Redraws erase everything after the input, so this runs after each
+    /// key. Once the input (with any ghost text) gets too close, the right
+    /// prompt is removed instead, as in zsh.
+    fn refresh_rprompt(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
+        self.refresh_rprompt_at(stdout, terminal_width())
+    }
+
+    fn refresh_rprompt_at(&mut self, stdout: &mut dyn Write, width: usize) -> io::Result<()> {
+        if self.rprompt.is_empty() {
+            return Ok(());
+        }
+        let used = visible_width(self.get_prompt_last_line())
+            + UnicodeWidthStr::width(self.buffer.as_str())
+            + self.suggestion.as_deref().map_or(0, UnicodeWidthStr::width);
+
+        match rprompt_column(&self.rprompt, used, width) {
+            Some(column) => {
+                // Save the cursor, jump to the column, draw, and come back
+                write!(stdout, "\x1b7\x1b[{}G{}\x1b8", column, self.rprompt)?;
+                self.rprompt_shown = true;
+                stdout.flush()
+            }
+            None => self.clear_rprompt(stdout),
+        }
+    }
+
+    /// Erase the right prompt, leaving the input and ghost text alone
+    fn clear_rprompt(&mut self, stdout: &mut dyn Write) -> io::Result<()> {
+        if !self.rprompt_shown {
+            return Ok(());
+        }
+        self.rprompt_shown = false;
+
+        let after = self.buffer.chars().count() - self.cursor
+            + self.suggestion.as_deref().map_or(0, |ghost| ghost.chars().count());
+        write!(stdout, "\x1b7")?;
+        if after > 0 {
+            write!(stdout, "\x1b[{}C", after)?;
+        }
+        write!(stdout, "\x1b[K\x1b8")?;
+        stdout.flush()
+    }
+
+    fn redraw_from_cursor(&self, stdout: &mut dyn Write) -> io::Result<()> {
         let byte_pos = self.cursor_to_byte_pos();
         let after_cursor = &self.buffer[byte_pos..];
         write!(stdout, "\x1b[K{}", after_cursor)?;
@@ -768,8 +947,369 @@ impl LineEditor {
     }
 }
 
+/// Keeps the terminal in raw mode until dropped
+struct RawModeGuard {
+    disable: Box<dyn Fn() -> io::Result<()>>,
+}
+
+impl RawModeGuard {
+    /// Switch the terminal to raw mode
+    ///
+    /// Returns `None` if that fails, or if it reports success but the
+    /// terminal still isn't raw (e.g. the TTY was swapped underneath us).
+    fn enable() -> Option<Self> {
+        Self::enable_with(
+            crossterm::terminal::enable_raw_mode,
+            is_raw_mode_enabled,
+            raw_mode_disabler(),
+        )
+    }
+
+    fn enable_with(
+        enable: fn() -> io::Result<()>,
+        is_enabled: fn() -> io::Result<bool>,
+        disable: impl Fn() -> io::Result<()> + 'static,
+    ) -> Option<Self> {
+        enable().ok()?;
+        // From here on, dropping the guard undoes whatever did take effect
+        let guard = Self {
+            disable: Box::new(disable),
+        };
+        is_enabled().unwrap_or(false).then_some(guard)
+    }
+}
+
+/// Whether the terminal is in raw mode
+///
+/// On Unix crossterm only reports what it last set, so the terminal's
+/// settings are read instead.
+#[cfg(unix)]
+fn is_raw_mode_enabled() -> io::Result<bool> {
+    on_tty(is_raw)
+}
+
+#[cfg(not(unix))]
+fn is_raw_mode_enabled() -> io::Result<bool> {
+    crossterm::terminal::is_raw_mode_enabled()
+}
+
+/// Whether terminal `fd` reads input a key at a time
+#[cfg(unix)]
+fn is_raw(fd: std::os::fd::RawFd) -> nix::Result<bool> {
+    use nix::sys::termios::{tcgetattr, LocalFlags};
+    Ok(!tcgetattr(fd)?.local_flags.contains(LocalFlags::ICANON))
+}
+
+/// What switches raw mode off again
+///
+/// On Unix, the terminal's settings from now are saved and put back
+/// exactly once crossterm has switched raw mode off.
+#[cfg(unix)]
+fn raw_mode_disabler() -> impl Fn() -> io::Result<()> {
+    use nix::sys::termios::{tcgetattr, tcsetattr, SetArg};
+    let saved = on_tty(tcgetattr).ok();
+    move || {
+        // Also lets crossterm switch raw mode on again next time
+        crossterm::terminal::disable_raw_mode()?;
+        match &saved {
+            Some(saved) => on_tty(|fd| tcsetattr(fd, SetArg::TCSANOW, saved)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn raw_mode_disabler() -> impl Fn() -> io::Result<()> {
+    crossterm::terminal::disable_raw_mode
+}
+
+/// Run `f` on the terminal crossterm uses: stdin, or `/dev/tty` when
+/// stdin isn't a terminal
+#[cfg(unix)]
+fn on_tty<T>(f: impl FnOnce(std::os::fd::RawFd) -> nix::Result<T>) -> io::Result<T> {
+    use std::io::IsTerminal;
+    use std::os::fd::AsRawFd;
+
+    if io::stdin().is_terminal() {
+        return Ok(f(io::stdin().as_raw_fd())?);
+    }
+    let tty = std::fs::File::open("/dev/tty")?;
+    Ok(f(tty.as_raw_fd())?)
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        // One retry: a backgrounded process can fail once while it settles
+        if (self.disable)().is_err() && (self.disable)().is_err() {
+            TERMINAL_NEEDS_RESET.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Set when raw mode could not be switched off (see `restore_terminal`)
+static TERMINAL_NEEDS_RESET: AtomicBool = AtomicBool::new(false);
+
+/// Leave the terminal usable for whatever runs after the shell
+///
+/// Retries switching off raw mode if an earlier attempt failed, then
+/// resets colors, shows the cursor and turns off mouse reporting.
+pub fn restore_terminal() {
+    if TERMINAL_NEEDS_RESET.swap(false, Ordering::SeqCst) {
+        if let Err(e) = crossterm::terminal::disable_raw_mode() {
+            eprintln!("csh: could not restore terminal mode: {}", e);
+        }
+    }
+    let mut stdout = io::stdout();
+    let _ = write!(stdout, "\x1b[0m\x1b[?25h\x1b[?1000l");
+    let _ = stdout.flush();
+}
+
+/// Terminal width in columns (`COLUMNS`, then 80, when it can't be queried)
+fn terminal_width() -> usize {
+    terminal_size::terminal_size()
+        .map(|(terminal_size::Width(w), _)| w as usize)
+        .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+        .unwrap_or(80)
+}
+
+/// Columns `text` takes on screen, ignoring ANSI escape sequences
+fn visible_width(text: &str) -> usize {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip ESC [ params final, or ESC and one character
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    UnicodeWidthStr::width(plain.as_str())
+}
+
+/// 1-based column where a right prompt starts, or `None` if it would come
+/// within a column of the `used` columns on the left
+///
+/// The last column is left empty so drawing never wraps the line.
+fn rprompt_column(rprompt: &str, used: usize, width: usize) -> Option<usize> {
+    let column = width.checked_sub(visible_width(rprompt))?;
+    (column > used + 1).then_some(column)
+}
+
 impl Default for LineEditor {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    fn type_text(editor: &mut LineEditor, text: &str, history: &History) {
+        let (completer, env) = (Completer::new(), Environment::new());
+        for c in text.chars() {
+            let result = editor
+                .handle_key(KeyCode::Char(c), KeyModifiers::NONE, &mut Vec::new(), history, &completer, &env)
+                .unwrap();
+            assert!(result.is_none());
+        }
+    }
+
+    fn press(editor: &mut LineEditor, code: KeyCode, modifiers: KeyModifiers, history: &History) -> Option<ReadlineResult> {
+        let (completer, env) = (Completer::new(), Environment::new());
+        editor
+            .handle_key(code, modifiers, &mut Vec::new(), history, &completer, &env)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_raw_mode_guard_restores_on_drop_and_panic() {
+        use std::sync::atomic::AtomicUsize;
+
+        static DISABLED: AtomicUsize = AtomicUsize::new(0);
+        fn ok() -> io::Result<()> {
+            Ok(())
+        }
+        fn raw() -> io::Result<bool> {
+            Ok(true)
+        }
+        fn not_raw() -> io::Result<bool> {
+            Ok(false)
+        }
+        fn disable() -> io::Result<()> {
+            DISABLED.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+        fn fail() -> io::Result<()> {
+            Err(io::Error::other("no tty"))
+        }
+
+        let guard = RawModeGuard::enable_with(ok, raw, disable).unwrap();
+        assert_eq!(DISABLED.load(Ordering::SeqCst), 0);
+        drop(guard);
+        assert_eq!(DISABLED.load(Ordering::SeqCst), 1);
+
+        let result = std::panic::catch_unwind(|| {
+            let _guard = RawModeGuard::enable_with(ok, raw, disable).unwrap();
+            panic!("key handler failed");
+        });
+        assert!(result.is_err());
+        assert_eq!(DISABLED.load(Ordering::SeqCst), 2);
+
+        // Raw mode that didn't take effect is undone and reported as failure
+        assert!(RawModeGuard::enable_with(ok, not_raw, disable).is_none());
+        assert_eq!(DISABLED.load(Ordering::SeqCst), 3);
+        assert!(RawModeGuard::enable_with(fail, raw, disable).is_none());
+        assert_eq!(DISABLED.load(Ordering::SeqCst), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_raw_reads_the_terminal() {
+        use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+        use std::fs::File;
+        use std::os::fd::{AsRawFd, FromRawFd};
+
+        let pty = nix::pty::openpty(None, None).unwrap();
+        // Owned, so both ends are closed at the end
+        let (_master, slave) =
+            unsafe { (File::from_raw_fd(pty.master), File::from_raw_fd(pty.slave)) };
+        let fd = slave.as_raw_fd();
+        assert!(!is_raw(fd).unwrap());
+
+        let cooked = tcgetattr(fd).unwrap();
+        let mut raw = cooked.clone();
+        cfmakeraw(&mut raw);
+        tcsetattr(fd, SetArg::TCSANOW, &raw).unwrap();
+        assert!(is_raw(fd).unwrap());
+
+        tcsetattr(fd, SetArg::TCSANOW, &cooked).unwrap();
+        assert!(!is_raw(fd).unwrap());
+    }
+
+    #[test]
+    fn test_accept_and_run_suggestion() {
+        let mut history = History::new(100);
+        history.add("cargo build --release".to_string());
+        history.add("ls -la".to_string());
+
+        let mut editor = LineEditor::new();
+        type_text(&mut editor, "car", &history);
+        assert_eq!(editor.suggestion.as_deref(), Some("go build --release"));
+
+        match press(&mut editor, KeyCode::Enter, KeyModifiers::ALT, &history) {
+            Some(ReadlineResult::Line(line)) => assert_eq!(line, "cargo build --release"),
+            other => panic!("expected a line, got {:?}", other),
+        }
+
+        // Without a suggestion the key does nothing
+        let mut editor = LineEditor::new();
+        type_text(&mut editor, "zzz", &history);
+        assert!(press(&mut editor, KeyCode::Enter, KeyModifiers::CONTROL, &history).is_none());
+        assert_eq!(editor.buffer, "zzz");
+    }
+
+    #[test]
+    fn test_rprompt_aligned_to_width() {
+        let mut editor = LineEditor::new();
+        editor.prompt = "user@host ~\r\n\x1b[1;32m❯\x1b[0m ".to_string();
+        editor.set_rprompt("\x1b[90m12:34:56\x1b[0m".to_string());
+
+        // 8 visible columns ending one short of the edge of an 80-column line
+        let mut out = Vec::new();
+        editor.refresh_rprompt_at(&mut out, 80).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, "\x1b7\x1b[72G\x1b[90m12:34:56\x1b[0m\x1b8");
+        assert!(editor.rprompt_shown);
+
+        // After a resize it moves with the edge
+        let mut out = Vec::new();
+        editor.refresh_rprompt_at(&mut out, 40).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("\x1b[32G"));
+
+        // Input reaching it takes it off the line
+        editor.buffer = "x".repeat(25);
+        editor.cursor = 25;
+        let mut out = Vec::new();
+        editor.refresh_rprompt_at(&mut out, 35).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\x1b7\x1b[K\x1b8");
+        assert!(!editor.rprompt_shown);
+    }
+
+    /// Feed raw terminal bytes through the fallback parser
+    fn feed(editor: &mut LineEditor, bytes: &[u8]) -> Option<ReadlineResult> {
+        let (completer, env, history) = (Completer::new(), Environment::new(), History::new(10));
+        let mut input = std::io::Cursor::new(bytes);
+        let mut byte = [0u8; 1];
+        while input.read(&mut byte).unwrap() == 1 {
+            let result = editor
+                .handle_raw_byte(byte[0], &mut input, &mut Vec::new(), &history, &completer, &env)
+                .unwrap();
+            if result.is_some() {
+                return result;
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_raw_home_end_delete_variants() {
+        let home: &[&[u8]] = &[b"\x1b[H", b"\x1bOH", b"\x1b[1~", b"\x1b[7~", b"\x1b[1;5H", b"\x1b[7^"];
+        let end: &[&[u8]] = &[b"\x1b[F", b"\x1bOF", b"\x1b[4~", b"\x1b[8~", b"\x1b[1;2F", b"\x1b[8$"];
+
+        for (home, end) in home.iter().zip(end) {
+            let mut editor = LineEditor::new();
+            feed(&mut editor, b"hello");
+            feed(&mut editor, home);
+            assert_eq!(editor.cursor, 0, "home {:?}", home);
+            assert_eq!(editor.escape_state, EscapeState::Normal);
+            feed(&mut editor, end);
+            assert_eq!(editor.cursor, 5, "end {:?}", end);
+            assert_eq!(editor.buffer, "hello");
+        }
+
+        for delete in [&b"\x1b[3~"[..], b"\x1b[3;5~", b"\x1b[3^"] {
+            let mut editor = LineEditor::new();
+            feed(&mut editor, b"hello\x1b[H");
+            feed(&mut editor, delete);
+            assert_eq!(editor.buffer, "ello", "delete {:?}", delete);
+        }
+
+        // Keys without an action, and the Linux console's F1, are swallowed whole
+        let mut editor = LineEditor::new();
+        feed(&mut editor, b"a\x1b[5~\x1b[6~\x1b[2~\x1b[[A\x1bOP\x1b[<0;3;4Mb");
+        assert_eq!(editor.buffer, "ab");
+        assert!(matches!(feed(&mut editor, b"\r"), Some(ReadlineResult::Line(line)) if line == "ab"));
+    }
+
+    #[test]
+    fn test_tab_on_complete_unique_word_adds_space() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+        let env = Environment::with_vars(dir.path().to_path_buf(), std::env::vars());
+        let (history, completer) = (History::new(10), Completer::new());
+
+        let mut editor = LineEditor::new();
+        editor.buffer = "cat no".to_string();
+        editor.cursor = editor.buffer.len();
+        let tab = |editor: &mut LineEditor| {
+            editor
+                .handle_key(KeyCode::Tab, KeyModifiers::NONE, &mut Vec::new(), &history, &completer, &env)
+                .unwrap();
+        };
+
+        tab(&mut editor);
+        assert_eq!(editor.buffer, "cat notes.txt");
+        tab(&mut editor);
+        assert_eq!(editor.buffer, "cat notes.txt ");
+        assert_eq!(editor.cursor, editor.buffer.len());
+    }
+}
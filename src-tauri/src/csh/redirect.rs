@@ -36,15 +36,19 @@ impl RedirectManager {
         let path = Path::new(&redirect.target);
 
         match redirect.redirect_type {
-            RedirectType::StdoutOverwrite | RedirectType::BothOverwrite => {
-                Self::open_output(path, false)
-            }
+            RedirectType::StdoutOverwrite
+            | RedirectType::StdoutClobber
+            | RedirectType::BothOverwrite => Self::open_output(path, false),
             RedirectType::StdoutAppend | RedirectType::BothAppend => {
                 Self::open_output(path, true)
             }
             RedirectType::StdinRead => Self::open_input(path),
             RedirectType::StderrOverwrite => Self::open_output(path, false),
             RedirectType::StderrAppend => Self::open_output(path, true),
+            RedirectType::StderrToStdout => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "2>&1 does not open a file",
+            )),
         }
     }
 }
@@ -7,7 +7,8 @@ use std::path::Path;
 
 use crate::csh::ast::ExitStatus;
 use crate::csh::executor::Executor;
-use crate::csh::parser;
+use crate::csh::parser::{self, ParseError};
+use crate::csh::text;
 
 /// Script executor
 pub struct ScriptRunner<'a> {
@@ -24,46 +25,59 @@ impl<'a> ScriptRunner<'a> {
         let content = fs::read_to_string(path)
             .map_err(|e| format!("Cannot read script {}: {}", path.display(), e))?;
 
-        self.run_script(&content)
+        self.run_script(&text::normalize_source(&content))
     }
 
     /// Execute a script string
+    ///
+    /// Lines are collected until they form complete statements, so an
+    /// `if` runs once its `fi` has been read. Each statement runs before
+    /// the next is parsed, as the lines run one by one otherwise.
     pub fn run_script(&mut self, script: &str) -> Result<ExitStatus, String> {
         let mut last_status = ExitStatus::success();
+        let mut pending = String::new();
+        let mut start_line = 0;
 
         for (line_num, line) in script.lines().enumerate() {
             let line = line.trim();
 
-            // Skip empty lines and comments
-            if line.is_empty() || line.starts_with('#') {
+            // Skip empty lines and comments between statements
+            if pending.is_empty() && (line.is_empty() || line.starts_with('#')) {
                 continue;
             }
+            if pending.is_empty() {
+                start_line = line_num + 1;
+            }
+            pending.push_str(line);
+            pending.push('\n');
 
-            // Parse and execute the line
-            match parser::parse(line) {
-                Ok(cmd_line) => {
-                    if !cmd_line.is_empty() {
-                        last_status = self.executor.execute(&cmd_line);
+            // Parse and execute once the statement is complete
+            match parser::parse_script(&pending) {
+                Ok(statements) => {
+                    pending.clear();
+                    last_status = self.executor.execute_statements(&statements);
 
-                        // Check for exit command
-                        if self.should_exit() {
-                            break;
-                        }
+                    // Check for exit command
+                    if self.should_exit() {
+                        break;
                     }
                 }
+                Err(ParseError::UnexpectedEof) => {}
                 Err(e) => {
                     return Err(format!("Line {}: {}", line_num + 1, e));
                 }
             }
         }
 
+        if !pending.is_empty() {
+            return Err(format!("Line {}: {}", start_line, ParseError::UnexpectedEof));
+        }
+
         Ok(last_status)
     }
 
     fn should_exit(&self) -> bool {
-        // Check if the last command was 'exit'
-        // This would be tracked in the executor
-        false
+        self.executor.exit_requested
     }
 }
 
@@ -75,7 +89,7 @@ pub fn is_csh_script(path: &Path) -> bool {
 
     // Check for shebang
     if let Ok(content) = fs::read_to_string(path) {
-        if let Some(first_line) = content.lines().next() {
+        if let Some(first_line) = text::strip_bom(&content).lines().next() {
             return first_line.starts_with("#!") && first_line.contains("csh");
         }
     }
@@ -87,6 +101,7 @@ pub fn is_csh_script(path: &Path) -> bool {
 pub fn load_script(path: &Path) -> Result<Vec<String>, String> {
     let content = fs::read_to_string(path)
         .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    let content = text::normalize_source(&content);
 
     let mut lines = Vec::new();
     let mut current_line = String::new();
@@ -113,3 +128,245 @@ pub fn load_script(path: &Path) -> Result<Vec<String>, String> {
 
     Ok(lines)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_file_with_bom_and_crlf() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("setup.csh");
+        fs::write(&path, "\u{feff}set FIRST=1\r\nset SECOND=2\r\n").unwrap();
+
+        let mut executor = Executor::new();
+        let status = ScriptRunner::new(&mut executor).run_file(&path).unwrap();
+        assert!(status.is_success());
+        assert_eq!(executor.env.get_value("FIRST").as_deref(), Some("1"));
+        assert_eq!(executor.env.get_value("SECOND").as_deref(), Some("2"));
+
+        assert_eq!(load_script(&path).unwrap(), vec!["set FIRST=1", "set SECOND=2"]);
+    }
+
+    #[test]
+    fn test_if_elif_else_chain() {
+        let script = "\
+set PICK=none
+if false; then
+  set PICK=first
+elif false
+then
+  set PICK=second
+elif true; then
+  # Only this branch runs
+  set PICK=third
+  set ALSO=yes
+else
+  set PICK=fourth
+fi
+if false; then set MISSED=1; else set FALLBACK=1; fi
+if true; then
+  if false; then set INNER=wrong; else set INNER=right; fi
+fi
+";
+        let mut executor = Executor::new();
+        let status = ScriptRunner::new(&mut executor).run_script(script).unwrap();
+        assert!(status.is_success());
+        assert_eq!(executor.env.get_value("PICK").as_deref(), Some("third"));
+        assert_eq!(executor.env.get_value("ALSO").as_deref(), Some("yes"));
+        assert_eq!(executor.env.get_value("MISSED"), None);
+        assert_eq!(executor.env.get_value("FALLBACK").as_deref(), Some("1"));
+        assert_eq!(executor.env.get_value("INNER").as_deref(), Some("right"));
+
+        let error = ScriptRunner::new(&mut executor).run_script("true\nif true; then\n  true\n");
+        assert_eq!(error.unwrap_err(), "Line 2: Unexpected end of input");
+    }
+
+    #[test]
+    fn test_while_loop_counts_down() {
+        let mut executor = Executor::new();
+        let params = ["script", "a", "b", "c"];
+        executor.env.set_positional(params.iter().map(|p| p.to_string()).collect());
+        executor.capture(true);
+
+        // `shift` fails once nothing is left, ending the loop
+        let script = "while shift; do\n  echo $#\ndone\nwhile false; do echo never; done\n";
+        let status = ScriptRunner::new(&mut executor).run_script(script).unwrap();
+        assert!(status.is_success());
+        assert_eq!(String::from_utf8(executor.capture(false)).unwrap(), "2\n1\n0\n");
+
+        executor.capture(true);
+        let script = "while true; do\n  echo once\n  break\n  echo never\ndone";
+        ScriptRunner::new(&mut executor).run_script(script).unwrap();
+        assert_eq!(String::from_utf8(executor.capture(false)).unwrap(), "once\n");
+    }
+
+    #[test]
+    fn test_for_loops() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("loops.csh");
+        fs::write(
+            &path,
+            "\
+set TOTAL=0
+for n in 1 2 {3..4}; do
+  set TOTAL=$((TOTAL + n))
+done
+for action in true continue echo break false; do
+  $action > $OUT && set ACTIONS=$ACTIONS,$action
+done
+for outer in x y; do
+  for inner in 1 2; do
+    set PAIRS=$PAIRS$outer$inner
+    break
+  done
+done
+for n in 1 2; do
+  false
+done
+",
+        )
+        .unwrap();
+
+        let mut executor = Executor::new();
+        executor.env.set("OUT", &dir.path().join("out.txt").to_string_lossy());
+        let status = ScriptRunner::new(&mut executor).run_file(&path).unwrap();
+        assert_eq!(executor.env.get_value("TOTAL").as_deref(), Some("10"));
+        // `continue` and `break` work as commands too
+        assert_eq!(executor.env.get_value("ACTIONS").as_deref(), Some(",true,echo"));
+        assert_eq!(executor.env.get_value("PAIRS").as_deref(), Some("x1y1"));
+        assert_eq!(executor.env.get_value("n").as_deref(), Some("2"));
+        // The script's status is that of the last command run
+        assert_eq!(status.code, 1);
+    }
+
+    #[test]
+    fn test_break_and_continue_counts() {
+        let mut executor = Executor::new();
+        executor.capture(true);
+        let script = "\
+for a in 1 2; do
+  for b in x y; do
+    echo $a$b
+    break 2
+  done
+done
+for a in 1 2; do
+  for b in x y; do
+    continue 2
+    echo no
+  done
+  echo no
+done
+for a in 1; do
+  while true; do break 5; done
+  echo no
+done
+for a in 1; do
+  break 0
+  break x
+done
+echo $?
+";
+        let status = ScriptRunner::new(&mut executor).run_script(script).unwrap();
+        assert!(status.is_success());
+        let output = String::from_utf8(executor.capture(false)).unwrap();
+        assert_eq!(output, "1x\n2\n");
+    }
+
+    #[test]
+    fn test_for_splits_expanded_items() {
+        let mut executor = Executor::new();
+        executor.env.set("X", "a b");
+        executor.env.set("EMPTY", "");
+        executor.capture(true);
+        let script = "\
+for v in $X; do echo [$v]; done
+for v in $(echo a b c); do echo [$v]; done
+for v in \"$X\" $EMPTY; do echo [$v]; done
+";
+        ScriptRunner::new(&mut executor).run_script(script).unwrap();
+        let output = String::from_utf8(executor.capture(false)).unwrap();
+        assert_eq!(output, "[a]\n[b]\n[a]\n[b]\n[c]\n[a b]\n");
+    }
+
+    #[test]
+    fn test_compound_commands_in_lists() {
+        let mut executor = Executor::new();
+        executor.capture(true);
+        let script = "\
+echo a; if true; then echo b; fi
+echo c; for i in 1 2; do echo $i; done
+true && if false; then echo no; else echo d; fi || echo no
+false && while true; do echo no; done || echo e
+if false; then echo no; fi
+echo $?
+while false; do echo no; done; echo $?
+for i in; do false; done; echo $?
+";
+        let status = ScriptRunner::new(&mut executor).run_script(script).unwrap();
+        assert!(status.is_success());
+        let output = String::from_utf8(executor.capture(false)).unwrap();
+        assert_eq!(output, "a\nb\nc\n1\n2\nd\ne\n0\n0\n0\n");
+    }
+
+    #[test]
+    fn test_functions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut executor = Executor::new();
+        executor.env.set("OUT", &dir.path().join("out.txt").to_string_lossy());
+        let params = ["script", "outer"];
+        executor.env.set_positional(params.iter().map(|p| p.to_string()).collect());
+        executor.capture(true);
+
+        let script = "\
+first() {
+  echo $1
+}
+function check {
+  for n in true false true; do
+    if $n; then continue; fi
+    return 7
+  done
+  echo unreachable
+}
+first hello world
+first > $OUT
+check
+set STATUS=$?
+echo $1
+";
+        let status = ScriptRunner::new(&mut executor).run_script(script).unwrap();
+        assert!(status.is_success());
+        assert_eq!(String::from_utf8(executor.capture(false)).unwrap(), "hello\nouter\n");
+        assert_eq!(executor.env.get_value("STATUS").as_deref(), Some("7"));
+        assert_eq!(fs::read_to_string(dir.path().join("out.txt")).unwrap(), "\n");
+
+        let status = ScriptRunner::new(&mut executor).run_script("return 1").unwrap();
+        assert_eq!(status.code, 1);
+    }
+
+    #[test]
+    fn test_functions_read_and_write_pipes() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.txt");
+        fs::write(&input, "from file\n").unwrap();
+        let mut executor = Executor::new();
+        executor.env.set("IN", &input.to_string_lossy());
+        executor.capture(true);
+
+        let script = "\
+say() { echo in; }
+upper() { tr a-z A-Z; }
+say | cat
+say | upper
+echo x | upper | cat
+upper < $IN
+say | false
+";
+        let status = ScriptRunner::new(&mut executor).run_script(script).unwrap();
+        assert_eq!(status.code, 1);
+        let output = String::from_utf8(executor.capture(false)).unwrap();
+        assert_eq!(output, "in\nIN\nX\nFROM FILE\n");
+    }
+}
@@ -3,18 +3,29 @@
 //! This module provides the main Shell struct that handles the REPL
 //! (Read-Eval-Print Loop) and integrates all other components.
 
-use std::io::{self, BufRead, Write};
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use chrono::Local;
 
 use crate::csh::ast::ExitStatus;
+use crate::csh::builtins::diag;
 use crate::csh::completion::{Completer, Completion};
 use crate::csh::environment::Environment;
 use crate::csh::executor::Executor;
+use crate::csh::git;
 use crate::csh::history::History;
+use crate::csh::icons;
+use crate::csh::interrupt;
 use crate::csh::parser;
-use crate::csh::readline::{LineEditor, ReadlineResult};
+use crate::csh::readline::{self, LineEditor, ReadlineResult};
 use crate::csh::script::ScriptRunner;
+use crate::csh::text;
+
+/// CSH version shown in the banner, `--version` and `diag`
+pub const VERSION: &str = "0.2.0";
 
 /// Shell configuration
 #[derive(Debug, Clone)]
@@ -29,6 +40,13 @@ pub struct ShellConfig {
     pub colors: bool,
     /// Use readline (interactive mode with completion)
     pub use_readline: bool,
+    /// Report how long startup took (`--timing` or `CSH_TIMING=1`)
+    pub timing: bool,
+    /// Right-aligned prompt template (`CSH_RPROMPT`), see `render_rprompt`
+    pub rprompt: Option<String>,
+    /// Interactive lines matching any of these ask before running
+    /// (`CSH_CONFIRM`, see `confirm_patterns`); empty turns the check off
+    pub confirm: Vec<glob::Pattern>,
 }
 
 impl Default for ShellConfig {
@@ -39,7 +57,57 @@ impl Default for ShellConfig {
             history_size: 10000,
             colors: true,
             use_readline: true, // Enable readline by default
+            timing: false,
+            rprompt: None,
+            confirm: Vec::new(),
+        }
+    }
+}
+
+/// Durations of the startup phases, for `--timing`
+#[derive(Debug, Default)]
+struct StartupTiming {
+    enabled: bool,
+    phases: Vec<(&'static str, Duration)>,
+    reported: bool,
+}
+
+impl StartupTiming {
+    fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    /// Run `f`, recording how long it took as `phase`
+    fn measure<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(phase, start.elapsed());
+        result
+    }
+
+    fn record(&mut self, phase: &'static str, elapsed: Duration) {
+        if self.enabled {
+            self.phases.push((phase, elapsed));
+        }
+    }
+
+    /// The report, the first time it's asked for and only when enabled
+    fn take_report(&mut self) -> Option<String> {
+        if !self.enabled || self.reported {
+            return None;
         }
+        self.reported = true;
+
+        let mut report = String::from("csh: startup timing\n");
+        for (phase, elapsed) in &self.phases {
+            report.push_str(&format!("  {:<14}{:>9.2}ms\n", phase, elapsed.as_secs_f64() * 1000.0));
+        }
+        let total: Duration = self.phases.iter().map(|(_, elapsed)| *elapsed).sum();
+        report.push_str(&format!("  {:<14}{:>9.2}ms\n", "total", total.as_secs_f64() * 1000.0));
+        Some(report)
     }
 }
 
@@ -57,30 +125,37 @@ pub struct Shell {
     should_exit: bool,
     /// Exit code to return
     exit_code: i32,
+    /// Startup phase durations (see `ShellConfig::timing`)
+    timing: StartupTiming,
 }
 
 impl Shell {
     /// Create a new shell instance
     pub fn new() -> Self {
-        Self {
-            executor: Executor::new(),
-            completer: Completer::new(),
-            line_editor: LineEditor::new(),
-            config: ShellConfig::default(),
-            should_exit: false,
-            exit_code: 0,
-        }
+        Self::with_config(ShellConfig::default())
     }
 
     /// Create a shell with custom configuration
     pub fn with_config(config: ShellConfig) -> Self {
+        let mut timing = StartupTiming::new(config.timing);
+        let executor = timing.measure("environment", Executor::new);
+        let completer = timing.measure("completion", Completer::new);
+
         Self {
-            executor: Executor::new(),
-            completer: Completer::new(),
+            executor,
+            completer,
             line_editor: LineEditor::new(),
             config,
             should_exit: false,
             exit_code: 0,
+            timing,
+        }
+    }
+
+    /// Print the startup timing report to stderr (once, if enabled)
+    pub fn report_startup_timing(&mut self) {
+        if let Some(report) = self.timing.take_report() {
+            eprint!("{}", report);
         }
     }
 
@@ -116,16 +191,44 @@ impl Shell {
 
         // Always use readline mode for interactive shells
         if self.config.use_readline {
-            self.run_readline_mode()
+            // Learn the emoji width for `ls` icons; terminals that don't
+            // answer leave CSH_EMOJI_WIDTH or the theme default in charge
+            if atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stdout) {
+                let start = Instant::now();
+                icons::measure_emoji_width();
+                self.timing.record("emoji width", start.elapsed());
+            }
+            let code = self.run_readline_mode();
+            readline::restore_terminal();
+            code
         } else {
             self.run_simple_mode()
         }
     }
 
+    /// Report background jobs that finished since the last prompt, and
+    /// forget them
+    fn report_jobs(&mut self) {
+        eprint!("{}", self.executor.jobs.report_finished());
+    }
+
     /// Run with readline support (Tab completion, history navigation, etc.)
     fn run_readline_mode(&mut self) -> i32 {
         loop {
+            let start = Instant::now();
             let prompt = self.get_prompt();
+            if !self.timing.reported {
+                self.timing.record("first prompt", start.elapsed());
+                self.report_startup_timing();
+            }
+
+            let rprompt = self.get_rprompt();
+            self.line_editor.set_rprompt(rprompt);
+
+            // Jobs for `kill %` completion
+            self.report_jobs();
+            let jobs = self.executor.jobs.list().iter();
+            self.completer.set_jobs(jobs.map(|job| (job.id, job.command.clone())).collect());
 
             // Use readline for input
             match self.line_editor.readline(
@@ -141,11 +244,22 @@ impl Shell {
                         continue;
                     }
 
-                    // Add to history
-                    self.executor.history.add(input.to_string());
+                    // Expand !n / !! references
+                    let input = match self.expand_history(input) {
+                        Some(input) => input,
+                        None => continue,
+                    };
 
-                    // Execute
-                    self.execute_line(input);
+                    // Add to history
+                    let recorded = self.add_to_history(&input);
+
+                    // Execute (unless the user backs out of a dangerous line)
+                    let cwd = self.executor.env.cwd().clone();
+                    let status =
+                        self.execute_confirmed(&input, &mut io::stdin().lock(), &mut io::stdout());
+                    if let Some(status) = status.filter(|_| recorded) {
+                        self.record_history_details(&status, cwd);
+                    }
 
                     if self.should_exit {
                         break;
@@ -169,6 +283,7 @@ impl Shell {
         }
 
         self.executor.history.save();
+        self.executor.history.save_dirs(self.executor.env.visited_dirs());
         self.exit_code
     }
 
@@ -176,12 +291,21 @@ impl Shell {
     fn run_simple_mode(&mut self) -> i32 {
         let stdin = io::stdin();
         let mut reader = stdin.lock();
+        // As in bash, `!` only refers to history when someone is typing
+        let interactive = atty::is(atty::Stream::Stdin);
 
         loop {
+            self.report_jobs();
+
             // Show prompt (only if terminal)
             if atty::is(atty::Stream::Stdout) {
+                let start = Instant::now();
                 self.show_prompt();
+                if !self.timing.reported {
+                    self.timing.record("first prompt", start.elapsed());
+                }
             }
+            self.report_startup_timing();
 
             // Read line
             let mut input = String::new();
@@ -200,11 +324,25 @@ impl Shell {
                         continue;
                     }
 
+                    // Expand !n / !! references
+                    let input = if interactive {
+                        match self.expand_history(input) {
+                            Some(input) => input,
+                            None => continue,
+                        }
+                    } else {
+                        input.to_string()
+                    };
+
                     // Add to history
-                    self.executor.history.add(input.to_string());
+                    let recorded = self.add_to_history(&input);
 
                     // Execute
-                    self.execute_line(input);
+                    let cwd = self.executor.env.cwd().clone();
+                    let status = self.execute_line(&input);
+                    if recorded {
+                        self.record_history_details(&status, cwd);
+                    }
 
                     if self.should_exit {
                         break;
@@ -218,24 +356,91 @@ impl Shell {
         }
 
         self.executor.history.save();
+        self.executor.history.save_dirs(self.executor.env.visited_dirs());
         self.exit_code
     }
 
+    /// Add `input` to the history unless `HISTIGNORE` or the built-in
+    /// secret patterns (off with `CSH_HISTIGNORE_SECRETS=0`) leave it out;
+    /// returns whether it was recorded
+    fn add_to_history(&mut self, input: &str) -> bool {
+        let env = &self.executor.env;
+        let histignore = env.get_value("HISTIGNORE").unwrap_or_default();
+        let secrets = env.get_value("CSH_HISTIGNORE_SECRETS").as_deref() != Some("0");
+        self.executor.history.set_ignore(&histignore, secrets);
+        self.executor.history.add(input.to_string())
+    }
+
+    /// Store how the command just added to the history ran (`extendedhistory`)
+    fn record_history_details(&mut self, status: &ExitStatus, cwd: PathBuf) {
+        if self.executor.options.extendedhistory() {
+            self.executor.history.annotate_last(status.code, cwd);
+        }
+    }
+
+    /// Expand history references, echoing the expanded line like bash does.
+    /// Returns `None` (after reporting the error) if a reference is unknown.
+    fn expand_history(&self, input: &str) -> Option<String> {
+        match self.executor.history.expand(input) {
+            Ok(Some(expanded)) => {
+                println!("{}", expanded);
+                Some(expanded)
+            }
+            Ok(None) => Some(input.to_string()),
+            Err(e) => {
+                eprintln!("csh: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Whether `line` matches one of the configured confirm patterns
+    pub fn needs_confirmation(&self, line: &str) -> bool {
+        self.config.confirm.iter().any(|pattern| pattern.matches(line.trim()))
+    }
+
+    /// Execute an interactive line, asking first if it needs confirmation
+    ///
+    /// The question goes to `output` and the answer is read from `answers`;
+    /// anything but `y`/`yes` skips the line. Returns `None` when skipped.
+    pub fn execute_confirmed(
+        &mut self,
+        line: &str,
+        answers: &mut dyn BufRead,
+        output: &mut dyn Write,
+    ) -> Option<ExitStatus> {
+        if self.needs_confirmation(line) {
+            let _ = write!(output, "csh: {}\r\nExecute? [y/N] ", line.trim());
+            let _ = output.flush();
+            let mut answer = String::new();
+            if answers.read_line(&mut answer).is_err() {
+                return None;
+            }
+            if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+                return None;
+            }
+        }
+        Some(self.execute_line(line))
+    }
+
     /// Execute a single line of input
     pub fn execute_line(&mut self, input: &str) -> ExitStatus {
         // Parse the input
-        match parser::parse(input) {
-            Ok(cmd_line) => {
-                if cmd_line.is_empty() {
+        match parser::parse_script(input) {
+            Ok(statements) => {
+                if statements.is_empty() {
                     return ExitStatus::success();
                 }
 
-                // Execute the command line
-                let result = self.executor.execute(&cmd_line);
+                // Execute the statements (usually a single command line)
+                let cwd = self.executor.env.cwd().clone();
+                let result = self.executor.execute_statements(&statements);
+                if *self.executor.env.cwd() != cwd {
+                    self.completer.invalidate_cwd_cache();
+                }
 
-                // Check for exit
-                // We need to check if the last command was 'exit'
-                if input.trim().starts_with("exit") {
+                // `exit` may have run anywhere in the input, even inside an `if`
+                if self.executor.exit_requested {
                     self.should_exit = true;
                     self.exit_code = result.code;
                 }
@@ -263,21 +468,46 @@ impl Shell {
         }
     }
 
+    /// Read a whole program (e.g. piped stdin) and run it as a script
+    ///
+    /// Unlike reading line by line, the script runner sees every line at
+    /// once, so constructs spanning several lines stay together.
+    pub fn execute_program(&mut self, input: &mut dyn Read) -> ExitStatus {
+        let mut source = String::new();
+        if let Err(e) = input.read_to_string(&mut source) {
+            eprintln!("csh: cannot read program: {}", e);
+            return ExitStatus::failure(1);
+        }
+
+        let mut runner = ScriptRunner::new(&mut self.executor);
+        match runner.run_script(&text::normalize_source(&source)) {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("csh: {}", e);
+                ExitStatus::failure(1)
+            }
+        }
+    }
+
     /// Execute a command string (non-interactive)
+    ///
+    /// Each line may hold several commands joined by `;`, `&&` or `||`.
+    /// Returns the status of the last command run.
     pub fn execute_command(&mut self, command: &str) -> ExitStatus {
+        let mut status = ExitStatus::success();
         for line in command.lines() {
             let line = line.trim();
             if line.is_empty() || line.starts_with('#') {
                 continue;
             }
 
-            let status = self.execute_line(line);
+            status = self.execute_line(line);
             if self.should_exit {
                 return status;
             }
         }
 
-        ExitStatus::success()
+        status
     }
 
     /// Get completions for input
@@ -326,6 +556,19 @@ impl Shell {
         }
     }
 
+    /// Render the right prompt (empty when none is configured)
+    pub fn get_rprompt(&self) -> String {
+        let Some(template) = self.config.rprompt.as_deref().filter(|t| !t.is_empty()) else {
+            return String::new();
+        };
+        let text = render_rprompt(template, &self.executor.env);
+        if self.config.colors && !text.is_empty() {
+            format!("\x1b[90m{}\x1b[0m", text)
+        } else {
+            text
+        }
+    }
+
     /// Show the prompt
     fn show_prompt(&self) {
         print!("{}", self.get_prompt());
@@ -334,7 +577,7 @@ impl Shell {
 
     /// Show welcome message
     fn show_welcome(&self) {
-        let version = "0.2.0";
+        let version = VERSION;
         let date = Local::now().format("%Y-%m-%d");
 
         if self.config.colors {
@@ -381,30 +624,125 @@ impl Default for Shell {
     }
 }
 
+/// Fill in a right prompt template
+///
+/// Placeholders: `{time}` (HH:MM:SS), `{date}` (YYYY-MM-DD), `{status}`
+/// (last exit code) and `{git}` (current branch, or a short commit id
+/// when detached; empty outside a repository).
+fn render_rprompt(template: &str, env: &Environment) -> String {
+    let now = Local::now();
+    let mut text = template
+        .replace("{time}", &now.format("%H:%M:%S").to_string())
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{status}", &env.last_exit_code().to_string());
+    if text.contains("{git}") {
+        text = text.replace("{git}", &git_branch(env.cwd()).unwrap_or_default());
+    }
+    text.trim().to_string()
+}
+
+/// Branch checked out in the repository containing `dir`
+fn git_branch(dir: &Path) -> Option<String> {
+    let head = fs::read_to_string(git::git_dir(dir)?.join("HEAD")).ok()?;
+    let head = head.trim();
+    Some(match head.strip_prefix("ref: refs/heads/") {
+        Some(branch) => branch.to_string(),
+        None => head.chars().take(7).collect(),
+    })
+}
+
+/// Confirm patterns from `CSH_CONFIRM`: one glob per line, matched
+/// against the whole command line (`rm -rf *` matches any `rm -rf`)
+pub fn confirm_patterns(spec: &str) -> Vec<glob::Pattern> {
+    spec.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| match glob::Pattern::new(line) {
+            Ok(pattern) => Some(pattern),
+            Err(e) => {
+                eprintln!("csh: CSH_CONFIRM: invalid pattern '{}': {}", line, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// The command and positional parameters of `csh -c command [name [args...]]`
+///
+/// As with `sh -c`, `name` becomes `$0` (`csh` if missing) and the
+/// arguments after it `$1`, `$2`, ... Returns `None` for other invocations.
+fn command_args(args: &[String]) -> Option<(&str, Vec<String>)> {
+    match args {
+        [_, flag, command, rest @ ..] if flag == "-c" => {
+            let params = if rest.is_empty() {
+                vec!["csh".to_string()]
+            } else {
+                rest.to_vec()
+            };
+            Some((command.as_str(), params))
+        }
+        _ => None,
+    }
+}
+
 /// Entry point for CSH as a standalone binary
 pub fn main() -> i32 {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    interrupt::install();
 
-    let mut shell = Shell::new();
+    let timing_flag = args.len() > 1 && args[1] == "--timing";
+    if timing_flag {
+        args.remove(1);
+    }
+    let timing = timing_flag
+        || std::env::var("CSH_TIMING").is_ok_and(|v| !v.is_empty() && v != "0");
+
+    let mut shell = Shell::with_config(ShellConfig {
+        timing,
+        rprompt: std::env::var("CSH_RPROMPT").ok(),
+        confirm: std::env::var("CSH_CONFIRM")
+            .map(|spec| confirm_patterns(&spec))
+            .unwrap_or_default(),
+        ..ShellConfig::default()
+    });
     shell.config.show_welcome = true;
 
     if args.len() > 1 {
         // Execute script or command
-        if args[1] == "-c" && args.len() > 2 {
+        if let Some((command, params)) = command_args(&args) {
             // Execute command string - don't show welcome, no readline
             shell.config.show_welcome = false;
             shell.config.use_readline = false;
-            let command = args[2..].join(" ");
-            let status = shell.execute_command(&command);
+            shell.executor.env.set_positional(params);
+            shell.report_startup_timing();
+            let status = shell.execute_command(command);
             return status.code;
+        } else if args[1] == "-s" {
+            // Execute the program on stdin; any arguments become $1, $2, ...
+            shell.config.show_welcome = false;
+            shell.config.use_readline = false;
+            let mut params = vec!["csh".to_string()];
+            params.extend_from_slice(&args[2..]);
+            shell.executor.env.set_positional(params);
+            shell.report_startup_timing();
+            return shell.execute_program(&mut io::stdin().lock()).code;
         } else if args[1] == "--help" || args[1] == "-h" {
-            println!("CSH - Connexio Shell v0.2.0");
+            println!("CSH - Connexio Shell v{}", VERSION);
             println!();
             println!("Usage:");
             println!("  csh                Run interactive shell");
-            println!("  csh -c <command>   Execute command and exit");
+            println!("  csh -c <command> [name [args...]]");
+            println!("                     Execute command and exit ($0 = name, $1... = args)");
+            println!("  csh -s [args...]   Execute the program read from stdin");
             println!("  csh <script>       Execute script file");
             println!("  csh --help         Show this help");
+            println!("  csh --diagnose     Print diagnostics for bug reports");
+            println!("  csh --timing ...   Report startup timing to stderr (or set CSH_TIMING=1)");
+            println!();
+            println!("Set CSH_RPROMPT to show text at the right of the prompt, e.g. '{{git}} {{time}}'");
+            println!("({{time}}, {{date}}, {{status}} and {{git}} are filled in).");
+            println!("Set CSH_CONFIRM to glob patterns, one per line, to be asked before");
+            println!("running matching commands, e.g. 'rm -rf *'.");
             println!();
             println!("Interactive Features:");
             println!("  Tab          Command and path completion");
@@ -417,17 +755,189 @@ pub fn main() -> i32 {
             println!("  Ctrl+W       Delete word backward");
             return 0;
         } else if args[1] == "--version" || args[1] == "-V" {
-            println!("CSH - Connexio Shell v0.2.0");
+            println!("CSH - Connexio Shell v{}", VERSION);
+            return 0;
+        } else if args[1] == "--diagnose" {
+            let report = diag::report(&shell.executor.env, &shell.executor.history, &diag::probe_raw_mode());
+            print!("{}", report);
             return 0;
         } else {
             // Execute script file - don't show welcome, no readline
             shell.config.show_welcome = false;
             shell.config.use_readline = false;
+            shell.report_startup_timing();
             let status = shell.execute_script(&args[1]);
             return status.code;
         }
     }
 
+    // A program piped in is run as a whole, like `csh -s`
+    if !atty::is(atty::Stream::Stdin) {
+        shell.config.show_welcome = false;
+        shell.config.use_readline = false;
+        shell.report_startup_timing();
+        return shell.execute_program(&mut io::stdin().lock()).code;
+    }
+
     // Interactive mode
     shell.run()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_string_with_positional_params() {
+        let mut shell = Shell::with_config(ShellConfig::default());
+        shell.executor.env.set_positional(vec!["x".into(), "y".into()]);
+        shell.executor.capture(true);
+
+        // csh -c 'echo $1' x y
+        assert!(shell.execute_command("echo $1").is_success());
+        assert_eq!(String::from_utf8(shell.executor.capture(false)).unwrap(), "y\n");
+
+        shell.executor.capture(true);
+
+        let status = shell.execute_command("echo $0; echo [$2] && echo a\necho b || echo c\nfalse");
+        assert_eq!(String::from_utf8(shell.executor.capture(false)).unwrap(), "x\n[]\na\nb\n");
+        assert_eq!(status.code, 1);
+
+        // A whole if statement can be given on one line
+        shell.executor.capture(true);
+        assert!(shell.execute_command("if false; then echo no; else echo $1; fi").is_success());
+        assert_eq!(String::from_utf8(shell.executor.capture(false)).unwrap(), "y\n");
+
+        shell.executor.capture(true);
+        assert!(shell.execute_command("echo $#; shift; echo $# [$1]").is_success());
+        assert_eq!(String::from_utf8(shell.executor.capture(false)).unwrap(), "1\n0 []\n");
+    }
+
+    #[test]
+    fn test_exit_stops_the_rest_of_the_input() {
+        let mut shell = Shell::with_config(ShellConfig::default());
+        shell.executor.capture(true);
+        let status = shell.execute_command("echo done; exit 2; echo after");
+        assert_eq!(String::from_utf8(shell.executor.capture(false)).unwrap(), "done\n");
+        assert_eq!(status.code, 2);
+        assert!(shell.should_exit());
+
+        // Only the `exit` command counts, wherever it runs
+        let mut shell = Shell::with_config(ShellConfig::default());
+        assert!(shell.execute_line("exit_code=1").is_success());
+        assert!(!shell.should_exit());
+        shell.execute_line("if true; then exit 4; fi");
+        assert!(shell.should_exit());
+        assert_eq!(shell.exit_code(), 4);
+    }
+
+    #[test]
+    fn test_program_runs_as_one_script() {
+        let mut shell = Shell::with_config(ShellConfig::default());
+        shell.executor.capture(true);
+
+        let program = "\u{feff}set GREETING=hi\r\n\n# comment\necho $GREETING\nexit 3\necho never\n";
+        let status = shell.execute_program(&mut program.as_bytes());
+        assert_eq!(String::from_utf8(shell.executor.capture(false)).unwrap(), "hi\n");
+        assert_eq!(status.code, 3);
+    }
+
+    #[test]
+    fn test_confirm_patterns_ask_before_running() {
+        let mut shell = Shell::with_config(ShellConfig {
+            confirm: confirm_patterns("rm -rf *\n:(){ :|:& };:\nalias danger=*\n"),
+            ..ShellConfig::default()
+        });
+        assert!(shell.needs_confirmation("rm -rf /"));
+        assert!(shell.needs_confirmation(" :(){ :|:& };: "));
+        assert!(!shell.needs_confirmation("rm -r build"));
+
+        // Declining skips the line
+        let mut output = Vec::new();
+        let status = shell.execute_confirmed("alias danger=ls", &mut "n\n".as_bytes(), &mut output);
+        assert!(status.is_none());
+        assert!(String::from_utf8(output).unwrap().ends_with("Execute? [y/N] "));
+        assert!(shell.env().get_alias("danger").is_none());
+        assert!(shell.execute_confirmed("alias danger=ls", &mut "".as_bytes(), &mut Vec::new()).is_none());
+
+        let status = shell.execute_confirmed("alias danger=ls", &mut "Yes\n".as_bytes(), &mut Vec::new());
+        assert!(status.unwrap().is_success());
+        assert_eq!(shell.env().get_alias("danger").map(String::as_str), Some("ls"));
+
+        // Lines that don't match run without asking
+        let mut output = Vec::new();
+        assert!(shell.execute_confirmed("alias safe=ls", &mut "".as_bytes(), &mut output).is_some());
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_startup_timing_only_when_enabled() {
+        let mut shell = Shell::with_config(ShellConfig {
+            timing: true,
+            ..ShellConfig::default()
+        });
+        let report = shell.timing.take_report().unwrap();
+        assert!(report.starts_with("csh: startup timing\n"));
+        assert!(report.contains("  environment "));
+        assert!(report.contains("  completion "));
+        assert!(report.contains("  total "));
+        assert!(shell.timing.take_report().is_none());
+
+        let mut shell = Shell::new();
+        assert!(shell.timing.take_report().is_none());
+    }
+
+    #[test]
+    fn test_command_args() {
+        let args = |line: &str| -> Vec<String> { line.split(' ').map(String::from).collect() };
+        let strings = |words: &[&str]| -> Vec<String> {
+            words.iter().map(|w| w.to_string()).collect()
+        };
+
+        let argv = args("csh -c cmd");
+        assert_eq!(command_args(&argv), Some(("cmd", strings(&["csh"]))));
+        let argv = args("csh -c cmd name");
+        assert_eq!(command_args(&argv), Some(("cmd", strings(&["name"]))));
+        let argv = args("csh -c cmd name a b");
+        assert_eq!(command_args(&argv), Some(("cmd", strings(&["name", "a", "b"]))));
+
+        assert_eq!(command_args(&args("csh -c")), None);
+        assert_eq!(command_args(&args("csh script.csh a")), None);
+    }
+
+    #[test]
+    fn test_completions_follow_cd() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second) = (dir.path().join("first"), dir.path().join("second"));
+        std::fs::create_dir(&first).unwrap();
+        std::fs::create_dir(&second).unwrap();
+        std::fs::write(first.join("alpha.txt"), "").unwrap();
+        std::fs::write(second.join("beta.txt"), "").unwrap();
+
+        let mut shell = Shell::new();
+        shell.executor.env = Environment::with_vars(first.clone(), std::env::vars());
+        let texts = |shell: &Shell| -> Vec<String> {
+            shell.get_completions("cat ").into_iter().map(|c| c.text).collect()
+        };
+
+        assert_eq!(texts(&shell), vec!["alpha.txt"]);
+        assert!(shell.execute_line(&format!("cd '{}'", second.display())).is_success());
+        assert_eq!(texts(&shell), vec!["beta.txt"]);
+
+        // A file added without changing the directory's mtime is only seen
+        // because `cd` dropped the cached listing
+        #[cfg(unix)]
+        {
+            assert!(shell.execute_line(&format!("cd '{}'", first.display())).is_success());
+            assert_eq!(texts(&shell), vec!["alpha.txt"]);
+            let modified = std::fs::metadata(&first).unwrap().modified().unwrap();
+            std::fs::write(first.join("gamma.txt"), "").unwrap();
+            std::fs::File::open(&first).unwrap().set_modified(modified).unwrap();
+            assert_eq!(texts(&shell), vec!["alpha.txt"]);
+
+            assert!(shell.execute_line(&format!("cd '{}'", second.display())).is_success());
+            assert!(shell.execute_line(&format!("cd '{}'", first.display())).is_success());
+            assert_eq!(texts(&shell), vec!["alpha.txt", "gamma.txt"]);
+        }
+    }
+}
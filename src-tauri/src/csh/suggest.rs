@@ -0,0 +1,64 @@
+//! "Did you mean" suggestions for mistyped names
+
+/// Edit distance between two strings, counted in characters
+///
+/// Insertions, deletions, substitutions and swaps of two neighbouring
+/// characters each count as one edit, so `sl` is one edit from `ls`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// The candidate closest to `name`, if any is close enough to be a typo
+///
+/// Up to one edit is allowed per three characters (at least one, at most
+/// two). Ties go to the alphabetically first candidate.
+pub fn closest<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max = (name.chars().count() / 3).clamp(1, 2);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closest_candidate() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "ls"), 2);
+
+        let names = ["echo", "exit", "export", "ls", "grep"];
+        assert_eq!(closest("ecoh", names), Some("echo"));
+        assert_eq!(closest("gerp", names), Some("grep"));
+        assert_eq!(closest("sl", names), Some("ls"));
+        assert_eq!(closest("zzzzzz", names), None);
+        assert_eq!(closest("echo", names), None);
+    }
+}
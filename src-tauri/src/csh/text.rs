@@ -0,0 +1,28 @@
+//! Text normalization for files authored on other platforms
+//!
+//! Windows editors often save UTF-8 with a byte order mark and CRLF line
+//! endings; neither means anything to the shell.
+
+/// The UTF-8 byte order mark as a decoded character
+pub const BOM: char = '\u{feff}';
+
+/// Remove a leading byte order mark, if present
+pub fn strip_bom(text: &str) -> &str {
+    text.strip_prefix(BOM).unwrap_or(text)
+}
+
+/// Strip the BOM and convert CRLF (and lone CR) line endings to LF
+pub fn normalize_source(text: &str) -> String {
+    strip_bom(text).replace("\r\n", "\n").replace('\r', "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_source() {
+        assert_eq!(normalize_source("\u{feff}a\r\nb\rc\n"), "a\nb\nc\n");
+        assert_eq!(strip_bom("plain"), "plain");
+    }
+}
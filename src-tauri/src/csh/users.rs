@@ -0,0 +1,128 @@
+//! User accounts, for completing and expanding `~user`
+
+use std::path::PathBuf;
+
+/// A user account and its home directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct User {
+    pub name: String,
+    pub home: PathBuf,
+}
+
+/// Every user that can log in, sorted by name
+///
+/// Errors give an empty list: this only feeds completion.
+pub fn list() -> Vec<User> {
+    let mut users = read_users();
+    users.sort_by(|a, b| a.name.cmp(&b.name));
+    users
+}
+
+/// Home directory of the user called `name`, for `~name`
+///
+/// Any account counts, including ones that can't log in (`~nobody`) and
+/// ones from NSS sources such as LDAP.
+#[cfg(unix)]
+pub fn home_dir(name: &str) -> Option<PathBuf> {
+    nix::unistd::User::from_name(name).ok().flatten().map(|user| user.dir)
+}
+
+/// Home directory of the user called `name`, for `~name`
+#[cfg(not(unix))]
+pub fn home_dir(name: &str) -> Option<PathBuf> {
+    list().into_iter().find(|user| user.name == name).map(|user| user.home)
+}
+
+/// Unix (but macOS): the accounts in /etc/passwd that can log in
+#[cfg(all(unix, not(target_os = "macos")))]
+fn read_users() -> Vec<User> {
+    let Ok(passwd) = std::fs::read_to_string("/etc/passwd") else {
+        return Vec::new();
+    };
+    passwd
+        .lines()
+        .filter_map(|line| {
+            // name:password:uid:gid:gecos:home:shell
+            let fields: Vec<&str> = line.split(':').collect();
+            let [name, _, _, _, _, home, shell] = fields[..] else {
+                return None;
+            };
+            if shell.ends_with("nologin") || shell.ends_with("false") {
+                return None;
+            }
+            Some(User {
+                name: name.to_string(),
+                home: PathBuf::from(home),
+            })
+        })
+        .collect()
+}
+
+/// macOS and Windows: the home directories under /Users or C:\Users
+///
+/// Directory Services and the Windows profile list are the real
+/// sources, but the folder name is the account name for ordinary users.
+#[cfg(any(windows, target_os = "macos"))]
+fn read_users() -> Vec<User> {
+    let (root, shared): (PathBuf, &[&str]) = if cfg!(windows) {
+        // The profile folder's parent, in case it isn't on C:
+        let root = std::env::var_os("USERPROFILE")
+            .map(PathBuf::from)
+            .and_then(|profile| profile.parent().map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from(r"C:\Users"));
+        (root, &["All Users", "Default", "Default User", "Public"])
+    } else {
+        (PathBuf::from("/Users"), &["Guest", "Shared"])
+    };
+
+    let Ok(entries) = std::fs::read_dir(&root) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            if name.starts_with('.') || shared.contains(&name.as_str()) {
+                return None;
+            }
+            Some(User {
+                name,
+                home: entry.path(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listed_users_have_home_dirs() {
+        let users = list();
+        assert!(users.windows(2).all(|pair| pair[0].name <= pair[1].name));
+        for user in &users {
+            assert_eq!(home_dir(&user.name).as_ref(), Some(&user.home), "{:?}", user);
+        }
+        assert_eq!(home_dir("no such user"), None);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_home_dir_finds_accounts_that_cannot_log_in() {
+        let passwd = std::fs::read_to_string("/etc/passwd").unwrap_or_default();
+        let nologin = passwd.lines().find_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            let [name, _, _, _, _, home, shell] = fields[..] else {
+                return None;
+            };
+            shell.ends_with("nologin").then(|| (name.to_string(), PathBuf::from(home)))
+        });
+        let Some((name, home)) = nologin else {
+            return;
+        };
+        assert!(list().iter().all(|user| user.name != name));
+        assert_eq!(home_dir(&name), Some(home));
+    }
+}
@@ -0,0 +1,167 @@
+//! Recursive directory walking for builtins
+//!
+//! `walk` visits a directory tree depth-first with the same ordering as
+//! `ls` (directories first, then case-insensitive by name). Symlinked
+//! directories are listed but only descended into with
+//! `follow_symlinks`, and a directory that is already being walked
+//! further up is never entered again, so symlink cycles terminate.
+
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Options for `walk`
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// Descend into directories reached through symbolic links
+    pub follow_symlinks: bool,
+}
+
+/// A single entry found while walking
+#[derive(Debug)]
+pub struct WalkEntry {
+    pub entry: fs::DirEntry,
+    /// 1 for the root's own entries
+    pub depth: usize,
+    /// Whether this is the last of its siblings that were included
+    pub is_last: bool,
+    /// Points at (or is) a directory
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    /// A directory already being walked higher up (a symlink cycle); it is
+    /// reported but not entered
+    pub is_loop: bool,
+}
+
+/// Walk the tree under `root`
+///
+/// `include` decides which entries are visited at all; `visit` is called
+/// for each of them in order and returns whether a directory should be
+/// descended into. Subdirectories that can't be read are skipped; only a
+/// failure to read `root` itself is an error.
+pub fn walk<I, V>(root: &Path, options: &WalkOptions, mut include: I, mut visit: V) -> io::Result<()>
+where
+    I: FnMut(&fs::DirEntry) -> bool,
+    V: FnMut(&WalkEntry) -> bool,
+{
+    let mut ancestors = vec![fs::canonicalize(root)?];
+    let entries = read_sorted(root, &mut include)?;
+    walk_entries(entries, 1, options, &mut ancestors, &mut include, &mut visit);
+    Ok(())
+}
+
+fn walk_entries<I, V>(
+    entries: Vec<(fs::DirEntry, bool, bool)>,
+    depth: usize,
+    options: &WalkOptions,
+    ancestors: &mut Vec<PathBuf>,
+    include: &mut I,
+    visit: &mut V,
+) where
+    I: FnMut(&fs::DirEntry) -> bool,
+    V: FnMut(&WalkEntry) -> bool,
+{
+    let count = entries.len();
+    for (index, (entry, is_dir, is_symlink)) in entries.into_iter().enumerate() {
+        let path = entry.path();
+        let enter = is_dir && (!is_symlink || options.follow_symlinks);
+
+        // Identity of the directory, so a cycle back to an ancestor is caught
+        let identity = if enter { fs::canonicalize(&path).ok() } else { None };
+        let is_loop = identity.as_ref().is_some_and(|id| ancestors.contains(id));
+
+        let walk_entry = WalkEntry {
+            entry,
+            depth,
+            is_last: index + 1 == count,
+            is_dir,
+            is_symlink,
+            is_loop,
+        };
+        if !visit(&walk_entry) || !enter || is_loop {
+            continue;
+        }
+        let Some(identity) = identity else {
+            continue;
+        };
+
+        if let Ok(children) = read_sorted(&path, include) {
+            ancestors.push(identity);
+            walk_entries(children, depth + 1, options, ancestors, include, visit);
+            ancestors.pop();
+        }
+    }
+}
+
+/// Included entries of `dir` with whether each is a directory and a
+/// symlink, directories first and then by name
+fn read_sorted<I>(dir: &Path, include: &mut I) -> io::Result<Vec<(fs::DirEntry, bool, bool)>>
+where
+    I: FnMut(&fs::DirEntry) -> bool,
+{
+    let mut entries: Vec<(fs::DirEntry, bool, bool)> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| include(e))
+        .map(|e| {
+            let is_symlink = e.file_type().map(|t| t.is_symlink()).unwrap_or(false);
+            let is_dir = e.path().is_dir();
+            (e, is_dir, is_symlink)
+        })
+        .collect();
+
+    entries.sort_by(|(a, a_dir, _), (b, b_dir, _)| match (a_dir, b_dir) {
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        _ => {
+            let a = a.file_name().to_string_lossy().to_lowercase();
+            let b = b.file_name().to_string_lossy().to_lowercase();
+            a.cmp(&b)
+        }
+    });
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    fn names(root: &Path, options: &WalkOptions) -> Vec<(usize, String, bool)> {
+        let mut seen = Vec::new();
+        walk(root, options, |_| true, |e| {
+            let name = e.entry.file_name().to_string_lossy().to_string();
+            seen.push((e.depth, name, e.is_loop));
+            seen.len() < 100
+        })
+        .unwrap();
+        seen
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_loop_terminates() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        fs::write(sub.join("file.txt"), "").unwrap();
+        std::os::unix::fs::symlink(dir.path(), sub.join("back")).unwrap();
+
+        // Not followed by default: the link is listed but not entered
+        let seen = names(dir.path(), &WalkOptions::default());
+        assert_eq!(
+            seen,
+            vec![
+                (1, "sub".to_string(), false),
+                (2, "back".to_string(), false),
+                (2, "file.txt".to_string(), false),
+            ]
+        );
+
+        // Followed, the cycle back to the root is reported and not entered
+        let follow = WalkOptions { follow_symlinks: true };
+        let seen = names(dir.path(), &follow);
+        assert_eq!(seen.len(), 3);
+        assert_eq!(seen[1], (2, "back".to_string(), true));
+    }
+}
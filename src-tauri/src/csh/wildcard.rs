@@ -0,0 +1,97 @@
+//! Filename wildcards (`*`, `?`, `[...]`) in command arguments
+
+use std::path::{Component, Path};
+
+use glob::MatchOptions;
+
+/// Whether `word` would be treated as a pattern
+pub fn has_wildcards(word: &str) -> bool {
+    word.contains(['*', '?', '['])
+}
+
+/// The paths matching `pattern`, sorted
+///
+/// Relative patterns are matched in `cwd` and give relative paths. Names
+/// starting with `.` only match a pattern that starts with `.` too.
+/// Returns `None` when the word has no wildcards, is not a valid pattern,
+/// or matches nothing, so the caller can keep it as written.
+pub fn expand(pattern: &str, cwd: &Path) -> Option<Vec<String>> {
+    if !has_wildcards(pattern) {
+        return None;
+    }
+
+    let absolute = Path::new(pattern).is_absolute();
+    let full = if absolute {
+        pattern.to_string()
+    } else {
+        format!("{}/{}", glob::Pattern::escape(&cwd.to_string_lossy()), pattern)
+    };
+    let options = MatchOptions {
+        case_sensitive: !cfg!(windows),
+        require_literal_separator: true,
+        // The glob crate doesn't apply this to directory listings, so hidden
+        // names are filtered below instead
+        require_literal_leading_dot: false,
+    };
+
+    let mut matches: Vec<String> = glob::glob_with(&full, options)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|path| !reveals_hidden(path, pattern))
+        .map(|path| {
+            let path = if absolute { &path } else { path.strip_prefix(cwd).unwrap_or(&path) };
+            path.to_string_lossy().into_owned()
+        })
+        .collect();
+    if matches.is_empty() {
+        return None;
+    }
+    matches.sort();
+    Some(matches)
+}
+
+/// Whether a wildcard matched a name starting with `.`
+///
+/// Compares the path with the pattern component by component from the end,
+/// since a match has as many components as its pattern.
+fn reveals_hidden(path: &Path, pattern: &str) -> bool {
+    let names = path.components().rev().filter_map(|c| match c {
+        Component::Normal(name) => Some(name.to_string_lossy()),
+        _ => None,
+    });
+    let parts = pattern.rsplit(['/', '\\']).filter(|part| !part.is_empty() && *part != ".");
+    names
+        .zip(parts)
+        .any(|(name, part)| name.starts_with('.') && !part.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_expand_in_cwd() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.rs", "a.rs", "c.txt", ".hidden.rs", "src/main.rs"] {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let cwd = dir.path();
+
+        assert_eq!(expand("*.rs", cwd), Some(vec!["a.rs".to_string(), "b.rs".to_string()]));
+        assert_eq!(expand("?.txt", cwd), Some(vec!["c.txt".to_string()]));
+        assert_eq!(expand("[bc].*", cwd), Some(vec!["b.rs".to_string(), "c.txt".to_string()]));
+        assert_eq!(expand("*/*.rs", cwd), Some(vec![format!("src{}main.rs", std::path::MAIN_SEPARATOR)]));
+        assert_eq!(expand(".*.rs", cwd), Some(vec![".hidden.rs".to_string()]));
+
+        let absolute = format!("{}/*.txt", cwd.display());
+        assert_eq!(expand(&absolute, cwd), Some(vec![cwd.join("c.txt").display().to_string()]));
+
+        // No match, no wildcards or a broken pattern: keep the word
+        assert_eq!(expand("*.md", cwd), None);
+        assert_eq!(expand("a.rs", cwd), None);
+        assert_eq!(expand("[", cwd), None);
+    }
+}
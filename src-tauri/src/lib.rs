@@ -16,8 +16,10 @@ pub mod pty;
 use cli::{CliArgs, StartupConfig};
 use commands::pty_commands::PtyState;
 use commands::{
-    get_pty_info, kill_pty, kill_child_processes, list_pty_sessions, resize_pty, 
-    spawn_default_shell, spawn_shell, write_pty, send_interrupt,
+    attach_pty, broadcast_command, detach_pty, get_pty_info, get_pty_metrics, get_pty_scrollback,
+    kill_pty, kill_child_processes, list_pty_sessions, replay_recording, resize_all_pty, resize_pty,
+    resize_pty_by_pixels, set_echo, set_working_directory, shutdown_all, spawn_default_shell,
+    spawn_shell, write_paste, write_pty, send_interrupt, start_raw_capture, stop_raw_capture,
 };
 use pty::PtyManager;
 
@@ -71,13 +73,27 @@ pub fn run() {
             // PTY commands
             spawn_shell,
             write_pty,
+            write_paste,
+            set_echo,
+            set_working_directory,
             resize_pty,
+            resize_pty_by_pixels,
+            resize_all_pty,
+            broadcast_command,
             kill_pty,
+            detach_pty,
+            attach_pty,
             kill_child_processes,
             get_pty_info,
+            get_pty_scrollback,
+            get_pty_metrics,
             list_pty_sessions,
             spawn_default_shell,
             send_interrupt,
+            shutdown_all,
+            replay_recording,
+            start_raw_capture,
+            stop_raw_capture,
             // CLI commands
             get_startup_config,
             clear_startup_config,
@@ -0,0 +1,125 @@
+//! PTY event delivery
+//!
+//! The manager never talks to Tauri directly; it emits through a
+//! `PtyEventSink`, which is implemented for `AppHandle` and can be
+//! replaced by a recording sink in tests.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+/// Terminal output from a session (`PtyOutputPayload`)
+pub const PTY_OUTPUT: &str = "pty-output";
+/// Session process exited (`PtyExitPayload`)
+pub const PTY_EXIT: &str = "pty-exit";
+/// Session is being shut down by the app (`PtyShutdownPayload`)
+pub const PTY_SHUTDOWN: &str = "pty-shutdown";
+/// Program in a session set the window title (`PtyTitlePayload`)
+pub const PTY_TITLE: &str = "pty-title";
+
+/// Destination for PTY events
+pub trait PtyEventSink: Send + Sync {
+    /// Deliver an already-serialized event payload
+    fn emit_event(&self, event: &str, payload: serde_json::Value);
+}
+
+impl PtyEventSink for AppHandle {
+    fn emit_event(&self, event: &str, payload: serde_json::Value) {
+        if let Err(e) = self.emit(event, payload) {
+            log::error!("Failed to emit {} event: {}", event, e);
+        }
+    }
+}
+
+/// Most output bytes kept for a detached session before the oldest is dropped
+pub const MAX_DETACHED_BYTES: usize = 1024 * 1024;
+
+/// Sink a session emits through, which can be detached from its window
+///
+/// While detached, events are held back instead of delivered (output is
+/// capped at `MAX_DETACHED_BYTES`, oldest first out). Attaching a sink
+/// delivers the held events before anything new, so output continues
+/// where the previous window left off.
+pub struct DetachableSink {
+    state: Mutex<DetachState>,
+}
+
+struct DetachState {
+    target: Option<Arc<dyn PtyEventSink>>,
+    held: VecDeque<(String, serde_json::Value)>,
+    held_bytes: usize,
+}
+
+impl DetachableSink {
+    pub fn new(target: Arc<dyn PtyEventSink>) -> Self {
+        Self {
+            state: Mutex::new(DetachState {
+                target: Some(target),
+                held: VecDeque::new(),
+                held_bytes: 0,
+            }),
+        }
+    }
+
+    /// Stop delivering events; hold them until the next `attach`
+    pub fn detach(&self) {
+        self.state.lock().target = None;
+    }
+
+    /// Deliver held events to `target`, then send everything new there
+    pub fn attach(&self, target: Arc<dyn PtyEventSink>) {
+        let mut state = self.state.lock();
+        for (event, payload) in state.held.drain(..) {
+            target.emit_event(&event, payload);
+        }
+        state.held_bytes = 0;
+        state.target = Some(target);
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.state.lock().target.is_some()
+    }
+}
+
+impl PtyEventSink for DetachableSink {
+    fn emit_event(&self, event: &str, payload: serde_json::Value) {
+        // Delivery happens under the lock so `attach` can't reorder events
+        let mut state = self.state.lock();
+        if let Some(target) = &state.target {
+            target.emit_event(event, payload);
+            return;
+        }
+
+        let size = output_len(event, &payload);
+        state.held_bytes += size;
+        state.held.push_back((event.to_string(), payload));
+
+        while state.held_bytes > MAX_DETACHED_BYTES {
+            let Some(pos) = state.held.iter().position(|(e, _)| e == PTY_OUTPUT) else {
+                break;
+            };
+            if let Some((event, payload)) = state.held.remove(pos) {
+                state.held_bytes -= output_len(&event, &payload);
+            }
+        }
+    }
+}
+
+/// Bytes of terminal output carried by an event (0 for other events)
+fn output_len(event: &str, payload: &serde_json::Value) -> usize {
+    if event != PTY_OUTPUT {
+        return 0;
+    }
+    payload["data"].as_str().map(str::len).unwrap_or(0)
+}
+
+/// Serialize `payload` and send it through `sink`
+pub fn emit<T: Serialize>(sink: &dyn PtyEventSink, event: &str, payload: &T) {
+    match serde_json::to_value(payload) {
+        Ok(value) => sink.emit_event(event, value),
+        Err(e) => log::error!("Failed to serialize {} event: {}", event, e),
+    }
+}
@@ -12,20 +12,35 @@
 //! This implementation handles both cases by:
 //! 1. Finding valid UTF-8 boundaries before emitting data
 //! 2. Carrying over incomplete bytes to the next read cycle
+//!
+//! Decoded output is handed to a per-session emitter thread (see
+//! `output`) so slow IPC never stalls reading from the PTY.
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fs::File;
 use std::io::{Read, Write};
-use std::sync::Arc;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use parking_lot::Mutex;
-use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
-use tauri::{AppHandle, Emitter};
+use portable_pty::{native_pty_system, ChildKiller, CommandBuilder, MasterPty, PtySize};
+use tauri::AppHandle;
 use uuid::Uuid;
 
-use super::types::{PtyExitPayload, PtyInfo, PtyOutputPayload, PtySpawnConfig, ShellType};
+use super::events::{self, DetachableSink, PtyEventSink, PTY_EXIT, PTY_SHUTDOWN};
+use super::output;
+use super::process;
+use super::replay;
+use super::scrollback::{Scrollback, DEFAULT_SCROLLBACK_BYTES};
+use super::types::{
+    PtyExitPayload, PtyInfo, PtyMetrics, PtyShutdownPayload, PtySpawnConfig, ShellType,
+};
 
 /// Find the last valid UTF-8 boundary in a byte slice.
 /// Returns the number of bytes that form valid UTF-8.
@@ -125,6 +140,454 @@ fn find_incomplete_escape_sequence(data: &str) -> Option<usize> {
     }
 }
 
+/// The last window title set in `data` with OSC 0 or OSC 2
+///
+/// Matches `ESC ] 0 ; title BEL` and `ESC ] 2 ; title ESC \`; an
+/// unterminated sequence is ignored (the reader carries it over instead).
+pub fn extract_osc_title(data: &str) -> Option<String> {
+    let mut title = None;
+    let mut rest = data;
+    while let Some(start) = rest.find("\x1b]") {
+        rest = &rest[start + 2..];
+        let Some(body) = rest.strip_prefix("0;").or_else(|| rest.strip_prefix("2;")) else {
+            continue;
+        };
+        let Some(end) = body.find(['\x07', '\x1b']) else {
+            break;
+        };
+        if body[end..].starts_with('\x07') || body[end..].starts_with("\x1b\\") {
+            title = Some(body[..end].to_string());
+        }
+        rest = &body[end..];
+    }
+    title
+}
+
+/// File receiving a copy of the raw bytes read from a session, if enabled
+type RawCapture = Arc<Mutex<Option<File>>>;
+
+/// A session's output channel, available while its reader thread runs
+type SharedOutput = Arc<Mutex<Option<output::OutputSender>>>;
+
+/// Running totals kept by a session's reader thread (see `get_metrics`)
+#[derive(Default)]
+struct OutputCounters {
+    bytes_read: AtomicU64,
+    chunks_emitted: AtomicU64,
+}
+
+/// Private mode number of bracketed paste (`ESC[?2004h` / `ESC[?2004l`)
+const BRACKETED_PASTE_MODE: &str = "2004";
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+/// Longest unfinished mode sequence kept for the next chunk; real ones
+/// are a handful of bytes
+const MAX_PASTE_TAIL: usize = 64;
+
+/// Follow the shell switching bracketed paste on and off in `data`
+///
+/// Looks at every DECSET/DECRST sequence (`ESC[?...h` / `ESC[?...l`), which
+/// may list several modes separated by `;`; the last one naming 2004 wins.
+/// A sequence cut off at the end of `data` is left in `tail` and read
+/// together with the next chunk.
+fn track_bracketed_paste(data: &str, tail: &mut String, enabled: &AtomicBool) {
+    let text = std::mem::take(tail) + data;
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find("\x1b[?") {
+        let sequence = &rest[start..];
+        rest = &rest[start + 3..];
+        let end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == ';'))
+            .unwrap_or(rest.len());
+        let (params, after) = rest.split_at(end);
+        let set = match after.chars().next() {
+            Some('h') => true,
+            Some('l') => false,
+            None if sequence.len() <= MAX_PASTE_TAIL => {
+                *tail = sequence.to_string();
+                return;
+            }
+            _ => continue,
+        };
+        if params.split(';').any(|param| param == BRACKETED_PASTE_MODE) {
+            enabled.store(set, Ordering::SeqCst);
+        }
+    }
+
+    // Only the start of the introducer made it into this chunk
+    if let Some(start) = rest.rfind('\x1b') {
+        if "\x1b[?".starts_with(&rest[start..]) {
+            *tail = rest[start..].to_string();
+        }
+    }
+}
+
+/// Smallest terminal the PTY is given; 0 rows or columns can wedge ConPTY
+const MIN_ROWS: u16 = 2;
+const MIN_COLS: u16 = 10;
+/// Largest terminal the PTY is given, far beyond any real window
+const MAX_ROWS: u16 = 1000;
+const MAX_COLS: u16 = 1000;
+
+/// `PtySize` for a requested terminal size, clamped to sane bounds
+///
+/// Out-of-range sizes come from frontend bugs (e.g. measuring a hidden
+/// element), so they're logged rather than rejected.
+fn pty_size(rows: u16, cols: u16) -> PtySize {
+    let clamped_rows = rows.clamp(MIN_ROWS, MAX_ROWS);
+    let clamped_cols = cols.clamp(MIN_COLS, MAX_COLS);
+    if (clamped_rows, clamped_cols) != (rows, cols) {
+        log::warn!(
+            "Clamped PTY size {}x{} to {}x{}",
+            cols,
+            rows,
+            clamped_cols,
+            clamped_rows
+        );
+    }
+    PtySize {
+        rows: clamped_rows,
+        cols: clamped_cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+/// `PtySize` for a viewport of `pixel_width` x `pixel_height` filled with
+/// cells of the given size
+///
+/// Partial cells are dropped and the grid is clamped like `pty_size`; the
+/// pixel fields then describe the grid itself rather than the viewport, so
+/// they always agree with the cell count.
+fn grid_size(
+    pixel_width: u32,
+    pixel_height: u32,
+    cell_width: f64,
+    cell_height: f64,
+) -> Result<PtySize> {
+    let valid = |cell: f64| cell.is_finite() && cell > 0.0;
+    if !valid(cell_width) || !valid(cell_height) {
+        anyhow::bail!("Invalid cell size {}x{}", cell_width, cell_height);
+    }
+
+    let cells = |pixels: u32, cell: f64| (pixels as f64 / cell).floor().min(u16::MAX as f64) as u16;
+    let mut size = pty_size(cells(pixel_height, cell_height), cells(pixel_width, cell_width));
+    let pixels = |count: u16, cell: f64| (count as f64 * cell).round().min(u16::MAX as f64) as u16;
+    size.pixel_width = pixels(size.cols, cell_width);
+    size.pixel_height = pixels(size.rows, cell_height);
+    Ok(size)
+}
+
+/// Set or clear `ECHO` in the PTY's terminal settings
+#[cfg(unix)]
+fn apply_echo(master: &(dyn MasterPty + Send), enabled: bool) -> Result<()> {
+    use nix::sys::termios::{tcsetattr, LocalFlags, SetArg};
+
+    let fd = master.as_raw_fd().context("PTY has no file descriptor")?;
+    let mut termios = master
+        .get_termios()
+        .context("Failed to read PTY terminal settings")?;
+    termios.local_flags.set(LocalFlags::ECHO, enabled);
+    tcsetattr(fd, SetArg::TCSANOW, &termios).context("Failed to set PTY terminal settings")
+}
+
+/// ConPTY doesn't expose the console's input mode to the host
+#[cfg(windows)]
+fn apply_echo(_master: &(dyn MasterPty + Send), _enabled: bool) -> Result<()> {
+    bail!("Changing echo is not supported for ConPTY sessions")
+}
+
+/// Everything a session's reader thread needs
+struct ReaderContext {
+    pty_id: String,
+    reader: Box<dyn Read + Send>,
+    should_stop: Arc<Mutex<bool>>,
+    raw_capture: RawCapture,
+    bracketed_paste: Arc<AtomicBool>,
+    scrollback: Arc<Mutex<Scrollback>>,
+    counters: Arc<OutputCounters>,
+    shared_output: SharedOutput,
+    sink: Arc<dyn PtyEventSink>,
+    sessions: Arc<Mutex<HashMap<String, PtySession>>>,
+}
+
+/// Start the named reader thread for a session
+///
+/// The thread always finishes with a `pty-exit` event and removes the
+/// session, even if reading panics; the shell is killed in that case
+/// and the event carries the reason `reader-panicked`.
+fn spawn_reader(
+    ctx: ReaderContext,
+    child: Option<Box<dyn portable_pty::Child + Send + Sync>>,
+) -> std::io::Result<thread::JoinHandle<()>> {
+    let name = format!("pty-reader-{}", &ctx.pty_id[..8.min(ctx.pty_id.len())]);
+
+    // Output is delivered by its own thread so reads never wait on IPC
+    let (output, emitter) = output::spawn_emitter(ctx.pty_id.clone(), Arc::clone(&ctx.sink));
+    *ctx.shared_output.lock() = Some(output.clone());
+
+    thread::Builder::new().name(name).spawn(move || {
+        let ReaderContext {
+            pty_id,
+            reader,
+            should_stop,
+            raw_capture,
+            bracketed_paste,
+            scrollback,
+            counters,
+            shared_output,
+            sink,
+            sessions,
+        } = ctx;
+        let mut child = child;
+
+        let state = ReadState {
+            should_stop: &should_stop,
+            raw_capture: &raw_capture,
+            bracketed_paste: &bracketed_paste,
+            paste_tail: RefCell::new(String::new()),
+            scrollback: &scrollback,
+            counters: &counters,
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            read_output(&pty_id, reader, &state, &output)
+        }));
+
+        // Let the emitter deliver everything before the exit event
+        shared_output.lock().take();
+        drop(output);
+        if emitter.join().is_err() {
+            log::error!("PTY {} output emitter panicked", pty_id);
+        }
+
+        let (exit_code, reason) = match result {
+            Ok(()) => {
+                // Process exited - get exit code
+                let exit_code = child
+                    .as_mut()
+                    .and_then(|child| child.wait().ok())
+                    .map(|status| status.exit_code() as i32);
+                (exit_code, None)
+            }
+            Err(panic) => {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                log::error!("PTY {} reader thread panicked: {}", pty_id, message);
+
+                // Nobody reads the shell's output any more; don't leave it running
+                if let Some(child) = child.as_mut() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+                (None, Some("reader-panicked".to_string()))
+            }
+        };
+
+        // Emit exit event; holding the lock keeps `detach` and `attach`
+        // from slipping in between it and the check below
+        let exit_payload = PtyExitPayload {
+            pty_id: pty_id.clone(),
+            exit_code,
+            reason,
+        };
+        let mut sessions = sessions.lock();
+        events::emit(sink.as_ref(), PTY_EXIT, &exit_payload);
+
+        // A detached session stays until it is attached (which delivers its
+        // held output and the exit event) or killed
+        match sessions.get_mut(&pty_id) {
+            Some(session) if !session.events.is_attached() => {
+                session.exited = true;
+                session.killer = None;
+                log::info!("PTY session {} exited while detached", pty_id);
+            }
+            _ => {
+                sessions.remove(&pty_id);
+                log::info!("PTY session {} terminated", pty_id);
+            }
+        }
+    })
+}
+
+/// Session state the reader loop checks and keeps up to date
+struct ReadState<'a> {
+    should_stop: &'a Mutex<bool>,
+    raw_capture: &'a Mutex<Option<File>>,
+    bracketed_paste: &'a AtomicBool,
+    /// Unfinished mode sequence from the last chunk (see `track_bracketed_paste`)
+    paste_tail: RefCell<String>,
+    scrollback: &'a Mutex<Scrollback>,
+    counters: &'a OutputCounters,
+}
+
+impl ReadState<'_> {
+    /// Record a decoded chunk and hand it to the emitter
+    fn emit(&self, data: &str, output: &output::OutputSender) {
+        track_bracketed_paste(data, &mut self.paste_tail.borrow_mut(), self.bracketed_paste);
+        self.scrollback.lock().push(data);
+        self.counters.chunks_emitted.fetch_add(1, Ordering::Relaxed);
+        output.push(data);
+    }
+}
+
+/// Read PTY output until EOF, an error, or a stop request
+fn read_output(
+    pty_id: &str,
+    mut reader: Box<dyn Read + Send>,
+    state: &ReadState,
+    output: &output::OutputSender,
+) {
+    // Increased buffer size for better performance with fast output
+    let mut buffer = [0u8; 16384];
+    // Carryover buffer for incomplete UTF-8 sequences or escape sequences
+    let mut carryover: Vec<u8> = Vec::with_capacity(256);
+
+    loop {
+        // Check if we should stop
+        if *state.should_stop.lock() {
+            break;
+        }
+
+        // Read output from PTY
+        match reader.read(&mut buffer) {
+            Ok(0) => {
+                // EOF - process exited
+                break;
+            }
+            Ok(n) => {
+                state.counters.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+
+                // Mirror exactly what came off the PTY, before any decoding
+                let mut capture = state.raw_capture.lock();
+                if let Some(file) = capture.as_mut() {
+                    if let Err(e) = file.write_all(&buffer[..n]) {
+                        log::warn!("Raw capture for PTY {} failed, stopping: {}", pty_id, e);
+                        *capture = None;
+                    }
+                }
+                drop(capture);
+
+                // Combine carryover with new data
+                let combined = if carryover.is_empty() {
+                    buffer[..n].to_vec()
+                } else {
+                    let mut c = std::mem::take(&mut carryover);
+                    c.extend_from_slice(&buffer[..n]);
+                    c
+                };
+
+                // Find valid UTF-8 boundary
+                let utf8_boundary = find_utf8_boundary(&combined);
+                
+                if utf8_boundary == 0 && combined.len() < 6 {
+                    // Not enough data yet for valid UTF-8, wait for more
+                    carryover = combined;
+                    continue;
+                }
+
+                // Split at UTF-8 boundary
+                let (valid_bytes, remaining) = combined.split_at(utf8_boundary);
+                
+                // Convert valid bytes to string
+                let data = match std::str::from_utf8(valid_bytes) {
+                    Ok(s) => s.to_string(),
+                    Err(_) => {
+                        // Fallback: use lossy conversion
+                        String::from_utf8_lossy(valid_bytes).to_string()
+                    }
+                };
+
+                // Check for incomplete escape sequences at the end
+                let (data_to_emit, escape_carryover) = 
+                    if let Some(esc_pos) = find_incomplete_escape_sequence(&data) {
+                        // Split at the incomplete escape sequence
+                        let (emit, carry) = data.split_at(esc_pos);
+                        (emit.to_string(), Some(carry.as_bytes().to_vec()))
+                    } else {
+                        (data, None)
+                    };
+
+                // Store remaining bytes for next iteration
+                carryover = if let Some(esc) = escape_carryover {
+                    let mut c = esc;
+                    c.extend_from_slice(remaining);
+                    c
+                } else {
+                    remaining.to_vec()
+                };
+
+                // Only emit if we have data
+                if !data_to_emit.is_empty() {
+                    log::debug!(
+                        "[PTY {}] Read {} bytes, emitting {} chars",
+                        &pty_id[..8.min(pty_id.len())],
+                        n,
+                        data_to_emit.len()
+                    );
+
+                    state.emit(&data_to_emit, output);
+                }
+            }
+            Err(e) => {
+                // Check if it's just a would-block (non-blocking read)
+                if e.kind() != std::io::ErrorKind::WouldBlock {
+                    log::error!("Error reading from PTY: {}", e);
+                    break;
+                }
+                // Small sleep to avoid busy-waiting
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    // Emit any remaining carryover data, whichever way the loop ended
+    if !carryover.is_empty() {
+        state.emit(&String::from_utf8_lossy(&carryover), output);
+    }
+}
+
+/// The line that changes `shell` into `directory`, ending in Enter (`\r`)
+fn cd_command(shell: &ShellType, directory: &str) -> Result<String> {
+    if directory.chars().any(|c| c.is_control()) {
+        bail!("Directory contains control characters: {:?}", directory);
+    }
+
+    Ok(match shell {
+        // Single quotes keep `$` and backticks literal; `'` is doubled
+        ShellType::PowerShell => {
+            format!("Set-Location -LiteralPath '{}'\r", directory.replace('\'', "''"))
+        }
+        // `/d` also switches drive
+        ShellType::Cmd => {
+            if directory.contains('"') {
+                bail!("Directory cannot be quoted for cmd: {}", directory);
+            }
+            format!("cd /d \"{}\"\r", directory)
+        }
+        // Backslash escapes inside double quotes; only bash treats backticks
+        // specially, and the CSH lexer would keep an unneeded backslash
+        ShellType::Wsl | ShellType::GitBash | ShellType::Csh => {
+            let bash = !matches!(shell, ShellType::Csh);
+            let mut quoted = String::with_capacity(directory.len());
+            for c in directory.chars() {
+                if matches!(c, '"' | '\\' | '$') || (bash && c == '`') {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+            format!("cd \"{}\"\r", quoted)
+        }
+        // No telling what the program does with typed input
+        ShellType::Custom { command, .. } => {
+            bail!("Cannot change directory in {}", command);
+        }
+    })
+}
+
 /// Represents an active PTY session
 struct PtySession {
     /// The shell type for this session
@@ -137,15 +600,64 @@ struct PtySession {
     master: Box<dyn MasterPty + Send>,
     /// Flag to signal the reader thread to stop
     should_stop: Arc<Mutex<bool>>,
-    /// Process ID of the shell (for killing child processes on Windows)
-    #[cfg(windows)]
+    /// Debug copy of the raw output bytes (see `start_raw_capture`)
+    raw_capture: RawCapture,
+    /// Whether the shell has turned on bracketed paste (see `write_paste`)
+    bracketed_paste: Arc<AtomicBool>,
+    /// Recent output, for windows that attach late (see `get_scrollback`)
+    scrollback: Arc<Mutex<Scrollback>>,
+    /// The reader's output channel, shared with `inject_output`
+    shared_output: SharedOutput,
+    /// Output totals and start time (see `get_metrics`)
+    counters: Arc<OutputCounters>,
+    started: Instant,
+    /// Where events for this session are delivered (see `detach`)
+    events: Arc<DetachableSink>,
+    /// Process ID of the shell (for killing child processes on Windows
+    /// and querying its working directory)
     process_id: Option<u32>,
+    /// Terminates the shell; the reader thread owns the child and reaps it
+    killer: Option<Box<dyn ChildKiller + Send + Sync>>,
+    /// Whether the shell exited while the session was detached
+    exited: bool,
+}
+
+impl PtySession {
+    /// Stop the reader thread and terminate the shell process
+    fn terminate(self, pty_id: &str) {
+        *self.should_stop.lock() = true;
+        if let Some(mut killer) = self.killer {
+            if let Err(e) = killer.kill() {
+                log::warn!("Failed to kill shell of PTY {}: {}", pty_id, e);
+            }
+        }
+    }
+
+    /// Send input to the shell and flush it
+    fn write_input(&mut self, data: &[u8]) -> Result<()> {
+        self.writer
+            .write_all(data)
+            .context("Failed to write to PTY")?;
+        self.writer
+            .flush()
+            .context("Failed to flush PTY writer")?;
+        Ok(())
+    }
 }
 
 /// Manages all active PTY sessions
 pub struct PtyManager {
     /// Map of session ID to PTY session
     sessions: Arc<Mutex<HashMap<String, PtySession>>>,
+    /// Recordings being played back, by session ID
+    replays: Mutex<HashMap<String, Replay>>,
+}
+
+/// A recording being played back (see `PtyManager::replay`)
+struct Replay {
+    /// Sending on it, or dropping it, stops playback
+    cancel: mpsc::Sender<()>,
+    thread: thread::JoinHandle<()>,
 }
 
 impl PtyManager {
@@ -153,6 +665,7 @@ impl PtyManager {
     pub fn new() -> Self {
         Self {
             sessions: Arc::new(Mutex::new(HashMap::new())),
+            replays: Mutex::new(HashMap::new()),
         }
     }
 
@@ -161,21 +674,26 @@ impl PtyManager {
         &self,
         config: PtySpawnConfig,
         app_handle: AppHandle,
+    ) -> Result<String> {
+        self.spawn_with_sink(config, Arc::new(app_handle))
+    }
+
+    /// Spawn a new PTY session that delivers its events to `sink`
+    pub fn spawn_with_sink(
+        &self,
+        config: PtySpawnConfig,
+        sink: Arc<dyn PtyEventSink>,
     ) -> Result<String> {
         let pty_system = native_pty_system();
 
         // Create the PTY with the specified size
         let pair = pty_system
-            .openpty(PtySize {
-                rows: config.rows,
-                cols: config.cols,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
+            .openpty(pty_size(config.rows, config.cols))
             .context("Failed to open PTY")?;
 
         // Build the shell command
         let mut cmd = CommandBuilder::new(config.shell_type.get_command());
+        cmd.args(config.shell_type.get_args());
 
         // Set working directory if specified
         if let Some(ref cwd) = config.working_directory {
@@ -185,16 +703,19 @@ impl PtyManager {
         // Set up environment for better terminal experience
         cmd.env("TERM", "xterm-256color");
         cmd.env("COLORTERM", "truecolor");
+        for (key, value) in config.env.iter().flatten() {
+            cmd.env(key, value);
+        }
 
         // Spawn the shell process
-        let mut child = pair
+        let child = pair
             .slave
             .spawn_command(cmd)
             .context("Failed to spawn shell process")?;
 
-        // Get process ID for killing child processes on Windows
-        #[cfg(windows)]
+        // Get process ID for killing child processes and querying the cwd
         let process_id = child.process_id();
+        let killer = child.clone_killer();
 
         log::info!("Shell process spawned successfully");
 
@@ -202,7 +723,7 @@ impl PtyManager {
         let pty_id = Uuid::new_v4().to_string();
 
         // Get the reader for output streaming
-        let mut reader = pair
+        let reader = pair
             .master
             .try_clone_reader()
             .context("Failed to clone PTY reader")?;
@@ -216,6 +737,14 @@ impl PtyManager {
         // Create stop flag for the reader thread
         let should_stop = Arc::new(Mutex::new(false));
         let should_stop_clone = Arc::clone(&should_stop);
+        let raw_capture: RawCapture = Arc::new(Mutex::new(None));
+        let bracketed_paste = Arc::new(AtomicBool::new(false));
+        let scrollback = Arc::new(Mutex::new(Scrollback::new(
+            config.scrollback_bytes.unwrap_or(DEFAULT_SCROLLBACK_BYTES),
+        )));
+        let shared_output: SharedOutput = Arc::new(Mutex::new(None));
+        let counters = Arc::new(OutputCounters::default());
+        let events = Arc::new(DetachableSink::new(sink));
 
         // Store the session
         let session = PtySession {
@@ -224,8 +753,16 @@ impl PtyManager {
             writer,
             master: pair.master,
             should_stop,
-            #[cfg(windows)]
+            raw_capture: Arc::clone(&raw_capture),
+            bracketed_paste: Arc::clone(&bracketed_paste),
+            scrollback: Arc::clone(&scrollback),
+            shared_output: Arc::clone(&shared_output),
+            counters: Arc::clone(&counters),
+            started: Instant::now(),
+            events: Arc::clone(&events),
             process_id,
+            killer: Some(killer),
+            exited: false,
         };
 
         {
@@ -234,146 +771,65 @@ impl PtyManager {
         }
 
         // Spawn thread to read PTY output and emit events
-        let pty_id_clone = pty_id.clone();
-        let app_handle_clone = app_handle.clone();
-        let sessions_ref = Arc::clone(&self.sessions);
-
-        thread::spawn(move || {
-            // Increased buffer size for better performance with fast output
-            let mut buffer = [0u8; 16384];
-            // Carryover buffer for incomplete UTF-8 sequences or escape sequences
-            let mut carryover: Vec<u8> = Vec::with_capacity(256);
-
-            loop {
-                // Check if we should stop
-                if *should_stop_clone.lock() {
-                    break;
-                }
+        let reader_ctx = ReaderContext {
+            pty_id: pty_id.clone(),
+            reader,
+            should_stop: should_stop_clone,
+            raw_capture,
+            bracketed_paste,
+            scrollback,
+            counters,
+            shared_output,
+            sink: events,
+            sessions: Arc::clone(&self.sessions),
+        };
+        if let Err(e) = spawn_reader(reader_ctx, Some(child)) {
+            self.sessions.lock().remove(&pty_id);
+            return Err(e).context("Failed to spawn PTY reader thread");
+        }
 
-                // Read output from PTY
-                match reader.read(&mut buffer) {
-                    Ok(0) => {
-                        // EOF - process exited
-                        // Emit any remaining carryover data
-                        if !carryover.is_empty() {
-                            let data = String::from_utf8_lossy(&carryover).to_string();
-                            let payload = PtyOutputPayload {
-                                pty_id: pty_id_clone.clone(),
-                                data,
-                            };
-                            let _ = app_handle_clone.emit("pty-output", payload);
-                        }
-                        break;
-                    }
-                    Ok(n) => {
-                        // Combine carryover with new data
-                        let combined = if carryover.is_empty() {
-                            buffer[..n].to_vec()
-                        } else {
-                            let mut c = std::mem::take(&mut carryover);
-                            c.extend_from_slice(&buffer[..n]);
-                            c
-                        };
-
-                        // Find valid UTF-8 boundary
-                        let utf8_boundary = find_utf8_boundary(&combined);
-                        
-                        if utf8_boundary == 0 && combined.len() < 6 {
-                            // Not enough data yet for valid UTF-8, wait for more
-                            carryover = combined;
-                            continue;
-                        }
-
-                        // Split at UTF-8 boundary
-                        let (valid_bytes, remaining) = combined.split_at(utf8_boundary);
-                        
-                        // Convert valid bytes to string
-                        let data = match std::str::from_utf8(valid_bytes) {
-                            Ok(s) => s.to_string(),
-                            Err(_) => {
-                                // Fallback: use lossy conversion
-                                String::from_utf8_lossy(valid_bytes).to_string()
-                            }
-                        };
-
-                        // Check for incomplete escape sequences at the end
-                        let (data_to_emit, escape_carryover) = 
-                            if let Some(esc_pos) = find_incomplete_escape_sequence(&data) {
-                                // Split at the incomplete escape sequence
-                                let (emit, carry) = data.split_at(esc_pos);
-                                (emit.to_string(), Some(carry.as_bytes().to_vec()))
-                            } else {
-                                (data, None)
-                            };
-
-                        // Store remaining bytes for next iteration
-                        carryover = if let Some(esc) = escape_carryover {
-                            let mut c = esc;
-                            c.extend_from_slice(remaining);
-                            c
-                        } else {
-                            remaining.to_vec()
-                        };
-
-                        // Only emit if we have data
-                        if !data_to_emit.is_empty() {
-                            log::debug!(
-                                "[PTY {}] Read {} bytes, emitting {} chars",
-                                &pty_id_clone[..8],
-                                n,
-                                data_to_emit.len()
-                            );
-
-                            // Emit the output event
-                            let payload = PtyOutputPayload {
-                                pty_id: pty_id_clone.clone(),
-                                data: data_to_emit,
-                            };
-
-                            if let Err(e) = app_handle_clone.emit("pty-output", payload) {
-                                log::error!("Failed to emit pty-output event: {}", e);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        // Check if it's just a would-block (non-blocking read)
-                        if e.kind() != std::io::ErrorKind::WouldBlock {
-                            log::error!("Error reading from PTY: {}", e);
-                            break;
-                        }
-                        // Small sleep to avoid busy-waiting
-                        thread::sleep(Duration::from_millis(10));
-                    }
-                }
-            }
+        log::info!(
+            "Spawned PTY session {} with shell {:?}",
+            pty_id,
+            config.shell_type
+        );
 
-            // Process exited - get exit code
-            let exit_code = child.wait().ok().map(|status| {
-                status.exit_code() as i32
-            });
+        Ok(pty_id)
+    }
 
-            // Emit exit event
-            let exit_payload = PtyExitPayload {
-                pty_id: pty_id_clone.clone(),
-                exit_code,
-            };
+    /// Replay an asciinema (v2) recording as a virtual session
+    ///
+    /// Output is emitted as `pty-output` events for the returned ID,
+    /// `speed` times faster than recorded, followed by `pty-exit`.
+    /// The session has no shell, so it never appears in `list_sessions`;
+    /// `kill` stops it early.
+    pub fn replay(&self, path: &Path, speed: f64, app_handle: AppHandle) -> Result<String> {
+        self.replay_with_sink(path, speed, Arc::new(app_handle))
+    }
 
-            if let Err(e) = app_handle_clone.emit("pty-exit", exit_payload) {
-                log::error!("Failed to emit pty-exit event: {}", e);
-            }
+    /// Replay a recording, delivering its events to `sink`
+    pub fn replay_with_sink(
+        &self,
+        path: &Path,
+        speed: f64,
+        sink: Arc<dyn PtyEventSink>,
+    ) -> Result<String> {
+        if !speed.is_finite() || speed <= 0.0 {
+            bail!("Replay speed must be a positive number");
+        }
 
-            // Remove session from manager
-            let mut sessions = sessions_ref.lock();
-            sessions.remove(&pty_id_clone);
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read recording {}", path.display()))?;
+        let outputs = replay::parse_asciicast(&contents)?;
 
-            log::info!("PTY session {} terminated", pty_id_clone);
-        });
+        let pty_id = Uuid::new_v4().to_string();
+        let (cancel, cancelled) = mpsc::channel();
+        let thread = replay::spawn(pty_id.clone(), outputs, speed, sink, cancelled);
+        let mut replays = self.replays.lock();
+        replays.retain(|_, replay| !replay.thread.is_finished());
+        replays.insert(pty_id.clone(), Replay { cancel, thread });
 
-        log::info!(
-            "Spawned PTY session {} with shell {:?}",
-            pty_id,
-            config.shell_type
-        );
+        log::info!("Replaying {} as session {} at {}x", path.display(), pty_id, speed);
 
         Ok(pty_id)
     }
@@ -386,20 +842,93 @@ impl PtyManager {
             .get_mut(pty_id)
             .context("PTY session not found")?;
 
-        session
-            .writer
-            .write_all(data)
-            .context("Failed to write to PTY")?;
+        session.write_input(data)
+    }
 
-        session
-            .writer
-            .flush()
-            .context("Failed to flush PTY writer")?;
+    /// Paste `text` into a session
+    ///
+    /// While the shell has bracketed paste enabled the text is wrapped in
+    /// `ESC[200~` ... `ESC[201~` so it isn't run line by line. End markers
+    /// inside the text are dropped, again until none are left, so the text
+    /// can't break out early even by nesting one inside another.
+    /// Otherwise the text is written as is.
+    pub fn write_paste(&self, pty_id: &str, text: &str) -> Result<()> {
+        let mut sessions = self.sessions.lock();
+
+        let session = sessions
+            .get_mut(pty_id)
+            .context("PTY session not found")?;
+
+        if session.bracketed_paste.load(Ordering::SeqCst) {
+            let mut text = text.to_string();
+            while text.contains(PASTE_END) {
+                text = text.replace(PASTE_END, "");
+            }
+            session.write_input(format!("{}{}{}", PASTE_START, text, PASTE_END).as_bytes())
+        } else {
+            session.write_input(text.as_bytes())
+        }
+    }
+
+    /// Type `command` followed by Enter into every running session
+    ///
+    /// Returns each session's outcome; a failed write doesn't stop the
+    /// others from receiving the command.
+    pub fn broadcast_command(&self, command: &str) -> HashMap<String, Result<()>> {
+        let line = format!("{}\r", command);
+        let results = self.for_each_session(|_, session| session.write_input(line.as_bytes()));
+
+        log::info!(
+            "Broadcast a command to {} PTY sessions ({} failed)",
+            results.len(),
+            results.values().filter(|result| result.is_err()).count()
+        );
+        results
+    }
+
+    /// Call `f` on every running session under a single lock, collecting
+    /// the results by session ID
+    ///
+    /// Sessions that were told to stop but haven't been removed yet (their
+    /// shell is exiting) are skipped.
+    fn for_each_session<T>(&self, mut f: impl FnMut(&str, &mut PtySession) -> T) -> HashMap<String, T> {
+        let mut sessions = self.sessions.lock();
+        sessions
+            .iter_mut()
+            .filter(|(_, session)| !*session.should_stop.lock())
+            .map(|(id, session)| (id.clone(), f(id, session)))
+            .collect()
+    }
 
+    /// Set the working directory tracked for a session
+    ///
+    /// With `send_cd`, a `cd` line for the session's shell is also typed into
+    /// it so the shell itself moves. Without it only the tracked value
+    /// changes, which `get_info` reports when the live directory of the
+    /// shell can't be queried.
+    pub fn set_working_directory(&self, pty_id: &str, path: &Path, send_cd: bool) -> Result<()> {
+        if !path.is_dir() {
+            bail!("Not a directory: {}", path.display());
+        }
+        let directory = path.to_string_lossy().to_string();
+
+        let mut sessions = self.sessions.lock();
+        let session = sessions
+            .get_mut(pty_id)
+            .context("PTY session not found")?;
+
+        if send_cd {
+            let line = cd_command(&session.shell_type, &directory)?;
+            session.write_input(line.as_bytes())?;
+        }
+
+        session.working_directory = Some(directory);
         Ok(())
     }
 
     /// Resize a PTY session
+    ///
+    /// The size is clamped to between 2x10 and 1000x1000 (rows x columns).
     pub fn resize(&self, pty_id: &str, rows: u16, cols: u16) -> Result<()> {
         let sessions = self.sessions.lock();
 
@@ -407,34 +936,135 @@ impl PtyManager {
             .get(pty_id)
             .context("PTY session not found")?;
 
+        let size = pty_size(rows, cols);
         session
             .master
-            .resize(PtySize {
-                rows,
-                cols,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
+            .resize(size)
+            .context("Failed to resize PTY")?;
+
+        log::debug!("Resized PTY {} to {}x{}", pty_id, size.cols, size.rows);
+
+        Ok(())
+    }
+
+    /// Resize a PTY session to fit a viewport, given the font's cell size
+    ///
+    /// Computes the grid the same way for every caller so the frontend and
+    /// the PTY agree on it, and sets the pixel size to match. Returns the
+    /// size applied.
+    pub fn resize_by_pixels(
+        &self,
+        pty_id: &str,
+        pixel_width: u32,
+        pixel_height: u32,
+        cell_width: f64,
+        cell_height: f64,
+    ) -> Result<PtySize> {
+        let size = grid_size(pixel_width, pixel_height, cell_width, cell_height)?;
+        let sessions = self.sessions.lock();
+        let session = sessions.get(pty_id).context("PTY session not found")?;
+        session
+            .master
+            .resize(size)
             .context("Failed to resize PTY")?;
 
-        log::debug!("Resized PTY {} to {}x{}", pty_id, cols, rows);
+        log::debug!(
+            "Resized PTY {} to {}x{} ({}x{} px)",
+            pty_id,
+            size.cols,
+            size.rows,
+            size.pixel_width,
+            size.pixel_height
+        );
+
+        Ok(size)
+    }
 
+    /// Resize every session to the same dimensions
+    ///
+    /// Applied under a single lock; sessions that fail to resize are
+    /// returned with their error while the rest are still resized.
+    pub fn resize_all(&self, rows: u16, cols: u16) -> Vec<(String, anyhow::Error)> {
+        let sessions = self.sessions.lock();
+        let size = pty_size(rows, cols);
+
+        let failures: Vec<(String, anyhow::Error)> = sessions
+            .iter()
+            .filter_map(|(id, session)| {
+                session
+                    .master
+                    .resize(size)
+                    .err()
+                    .map(|e| (id.clone(), e.context("Failed to resize PTY")))
+            })
+            .collect();
+
+        log::debug!(
+            "Resized {} PTY sessions to {}x{} ({} failed)",
+            sessions.len(),
+            size.cols,
+            size.rows,
+            failures.len()
+        );
+
+        failures
+    }
+
+    /// Turn the terminal's echo of input on or off for a session
+    ///
+    /// On Unix this sets `ECHO` in the PTY's termios. The program running
+    /// in the session owns those settings too and may change them back
+    /// (line editors switch echo off while reading a line and restore it
+    /// afterwards), so this suits programs that leave the terminal alone.
+    /// ConPTY on Windows gives the host no access to the console's echo
+    /// mode, so there this always fails.
+    pub fn set_echo(&self, pty_id: &str, enabled: bool) -> Result<()> {
+        let sessions = self.sessions.lock();
+        let session = sessions.get(pty_id).context("PTY session not found")?;
+
+        apply_echo(session.master.as_ref(), enabled)?;
+        log::debug!("Set echo {} for PTY {}", if enabled { "on" } else { "off" }, pty_id);
+        Ok(())
+    }
+
+    /// Start mirroring a session's raw output bytes to `path` (debugging aid)
+    ///
+    /// The file receives every byte exactly as read from the PTY, before
+    /// UTF-8 and escape sequence handling. Replaces any capture in progress.
+    pub fn start_raw_capture(&self, pty_id: &str, path: &Path) -> Result<()> {
+        let sessions = self.sessions.lock();
+        let session = sessions.get(pty_id).context("PTY session not found")?;
+
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create capture file {}", path.display()))?;
+        *session.raw_capture.lock() = Some(file);
+
+        log::info!("Raw capture of PTY {} started: {}", pty_id, path.display());
+        Ok(())
+    }
+
+    /// Stop a raw capture started with `start_raw_capture`
+    pub fn stop_raw_capture(&self, pty_id: &str) -> Result<()> {
+        let sessions = self.sessions.lock();
+        let session = sessions.get(pty_id).context("PTY session not found")?;
+
+        if let Some(mut file) = session.raw_capture.lock().take() {
+            file.flush().context("Failed to flush capture file")?;
+            log::info!("Raw capture of PTY {} stopped", pty_id);
+        }
         Ok(())
     }
 
     /// Send Ctrl+C interrupt to a PTY session
-    /// Writes ETX (0x03) to the PTY - ConPTY handles the signal propagation
-    #[cfg(windows)]
+    ///
+    /// Writes ETX (0x03) to the PTY on every platform. ConPTY turns it into
+    /// a CTRL_C_EVENT for the processes attached to the session's console,
+    /// and a Unix line discipline into SIGINT for the foreground group.
+    /// `GenerateConsoleCtrlEvent` isn't used: it only reaches process groups
+    /// on the caller's own console, and Connexio isn't attached to the
+    /// sessions' pseudoconsoles.
     pub fn send_ctrl_c(&self, pty_id: &str) -> Result<()> {
         log::info!("Sending Ctrl+C (ETX) to PTY {}", &pty_id[..8.min(pty_id.len())]);
-        // Write ETX (0x03) to the PTY - ConPTY translates this to CTRL_C_EVENT
-        self.write(pty_id, &[0x03])
-    }
-    
-    /// Send Ctrl+C interrupt (non-Windows fallback)
-    #[cfg(not(windows))]
-    pub fn send_ctrl_c(&self, pty_id: &str) -> Result<()> {
-        // On Unix, just write \x03
         self.write(pty_id, &[0x03])
     }
 
@@ -527,12 +1157,20 @@ impl PtyManager {
     }
 
     /// Kill a PTY session
+    ///
+    /// The shell process is terminated; its reader thread then sees the
+    /// PTY close, reaps the process and emits `pty-exit`. A replay is
+    /// stopped, and emits `pty-exit` too.
     pub fn kill(&self, pty_id: &str) -> Result<()> {
+        if let Some(replay) = self.replays.lock().remove(pty_id) {
+            let _ = replay.cancel.send(());
+            log::info!("Stopped replay {}", pty_id);
+        }
+
         let mut sessions = self.sessions.lock();
 
         if let Some(session) = sessions.remove(pty_id) {
-            // Signal the reader thread to stop
-            *session.should_stop.lock() = true;
+            session.terminate(pty_id);
             log::info!("Killed PTY session {}", pty_id);
         }
 
@@ -540,29 +1178,136 @@ impl PtyManager {
     }
 
     /// Get information about a PTY session
+    ///
+    /// The working directory is queried live from the shell process when
+    /// the OS allows it, falling back to the directory it was spawned in.
     pub fn get_info(&self, pty_id: &str) -> Option<PtyInfo> {
         let sessions = self.sessions.lock();
 
-        sessions.get(pty_id).map(|session| PtyInfo {
-            id: pty_id.to_string(),
-            shell_type: session.shell_type.clone(),
-            working_directory: session.working_directory.clone(),
-            is_alive: true,
+        sessions.get(pty_id).map(|session| {
+            let live_cwd = session
+                .process_id
+                .and_then(process::query_cwd)
+                .map(|path| path.to_string_lossy().to_string());
+
+            PtyInfo {
+                id: pty_id.to_string(),
+                shell_type: session.shell_type.clone(),
+                working_directory: live_cwd.or_else(|| session.working_directory.clone()),
+                is_alive: true,
+                attached: session.events.is_attached(),
+                pid: session.process_id,
+            }
+        })
+    }
+
+    /// Show a notice from the app in a session's output, e.g. "Session restored"
+    ///
+    /// Nothing is written to the PTY: the styled text is queued on the same
+    /// channel as the shell's output, so it lands between complete chunks,
+    /// and it is kept in the scrollback like any other output.
+    pub fn inject_output(&self, pty_id: &str, text: &str) -> Result<()> {
+        let sessions = self.sessions.lock();
+        let session = sessions
+            .get(pty_id)
+            .context("PTY session not found")?;
+        let shared_output = session.shared_output.lock();
+        let Some(output) = shared_output.as_ref() else {
+            bail!("PTY session {} is not producing output", pty_id);
+        };
+
+        let notice = format!("\r\n\x1b[36m[{}]\x1b[0m\r\n", text);
+        session.scrollback.lock().push(&notice);
+        output.push(&notice);
+        Ok(())
+    }
+
+    /// Output totals for a session, for diagnosing slow terminals
+    pub fn get_metrics(&self, pty_id: &str) -> Option<PtyMetrics> {
+        let sessions = self.sessions.lock();
+        sessions.get(pty_id).map(|session| PtyMetrics {
+            bytes_read: session.counters.bytes_read.load(Ordering::Relaxed),
+            chunks_emitted: session.counters.chunks_emitted.load(Ordering::Relaxed),
+            uptime_ms: session.started.elapsed().as_millis() as u64,
         })
     }
 
+    /// The session's most recent output (up to its scrollback limit)
+    pub fn get_scrollback(&self, pty_id: &str) -> Option<String> {
+        let sessions = self.sessions.lock();
+        sessions.get(pty_id).map(|session| session.scrollback.lock().text())
+    }
+
+    /// Stop delivering a session's events to its window, keeping it running
+    ///
+    /// Unlike `kill`, the shell and reader thread carry on; output is held
+    /// until the session is attached again. The session stays listed, even
+    /// if its shell exits in the meantime.
+    pub fn detach(&self, pty_id: &str) -> Result<()> {
+        let sessions = self.sessions.lock();
+        let session = sessions.get(pty_id).context("PTY session not found")?;
+        session.events.detach();
+        log::info!("Detached PTY session {}", pty_id);
+        Ok(())
+    }
+
+    /// Deliver a session's events to `app_handle`, starting with any output
+    /// produced while it was detached
+    pub fn attach(&self, pty_id: &str, app_handle: AppHandle) -> Result<()> {
+        self.attach_with_sink(pty_id, Arc::new(app_handle))
+    }
+
+    /// Attach a session to `sink`
+    ///
+    /// A session whose shell exited while detached is removed once its
+    /// held output and exit event have been delivered.
+    pub fn attach_with_sink(&self, pty_id: &str, sink: Arc<dyn PtyEventSink>) -> Result<()> {
+        let mut sessions = self.sessions.lock();
+        let session = sessions.get(pty_id).context("PTY session not found")?;
+        session.events.attach(sink);
+        log::info!("Attached PTY session {}", pty_id);
+        if session.exited {
+            sessions.remove(pty_id);
+        }
+        Ok(())
+    }
+
     /// Get all active PTY session IDs
     pub fn list_sessions(&self) -> Vec<String> {
         let sessions = self.sessions.lock();
         sessions.keys().cloned().collect()
     }
 
+    /// Coordinated shutdown of every session (used on window close)
+    ///
+    /// Stops replays, flushes pending input, emits a `pty-shutdown` event
+    /// per session and then kills it. Remaining reader-side carryover is flushed by each
+    /// reader thread as it stops. Safe to call more than once, and
+    /// `Drop` finds nothing left to clean up afterwards.
+    pub fn shutdown_all(&self) {
+        self.replays.lock().clear();
+        let drained: Vec<(String, PtySession)> = self.sessions.lock().drain().collect();
+
+        for (id, mut session) in drained {
+            if let Err(e) = session.writer.flush() {
+                log::warn!("Failed to flush PTY {} during shutdown: {}", id, e);
+            }
+
+            let payload = PtyShutdownPayload { pty_id: id.clone() };
+            events::emit(session.events.as_ref(), PTY_SHUTDOWN, &payload);
+
+            session.terminate(&id);
+            log::info!("Shut down PTY session {}", id);
+        }
+    }
+
     /// Kill all PTY sessions (for app cleanup)
     pub fn kill_all(&self) {
+        self.replays.lock().clear();
         let mut sessions = self.sessions.lock();
 
         for (id, session) in sessions.drain() {
-            *session.should_stop.lock() = true;
+            session.terminate(&id);
             log::info!("Killed PTY session {} during cleanup", id);
         }
     }
@@ -579,3 +1324,828 @@ impl Drop for PtyManager {
         self.kill_all();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::events::{PTY_OUTPUT, PTY_TITLE};
+
+    /// Sink that records every event it receives
+    #[derive(Default)]
+    struct RecordingSink {
+        events: Mutex<Vec<(String, serde_json::Value)>>,
+    }
+
+    impl RecordingSink {
+        fn named(&self, event: &str) -> Vec<serde_json::Value> {
+            self.events
+                .lock()
+                .iter()
+                .filter(|(name, _)| name == event)
+                .map(|(_, payload)| payload.clone())
+                .collect()
+        }
+    }
+
+    impl PtyEventSink for RecordingSink {
+        fn emit_event(&self, event: &str, payload: serde_json::Value) {
+            self.events.lock().push((event.to_string(), payload));
+        }
+    }
+
+    /// Master end that records resizes instead of talking to a real PTY
+    #[derive(Default)]
+    struct MockMaster {
+        sizes: Arc<Mutex<Vec<PtySize>>>,
+    }
+
+    impl MasterPty for MockMaster {
+        fn resize(&self, size: PtySize) -> Result<()> {
+            self.sizes.lock().push(size);
+            Ok(())
+        }
+
+        fn get_size(&self) -> Result<PtySize> {
+            Ok(self.sizes.lock().last().copied().unwrap_or_default())
+        }
+
+        fn try_clone_reader(&self) -> Result<Box<dyn Read + Send>> {
+            Ok(Box::new(std::io::empty()))
+        }
+
+        fn take_writer(&self) -> Result<Box<dyn Write + Send>> {
+            Ok(Box::new(std::io::sink()))
+        }
+
+        #[cfg(unix)]
+        fn process_group_leader(&self) -> Option<i32> {
+            None
+        }
+
+        #[cfg(unix)]
+        fn as_raw_fd(&self) -> Option<std::os::unix::io::RawFd> {
+            None
+        }
+    }
+
+    /// Register a session backed by mocks (no reader thread, no child)
+    fn insert_mock_session(manager: &PtyManager, sink: &Arc<RecordingSink>) -> String {
+        insert_mock_session_with_master(manager, sink, MockMaster::default())
+    }
+
+    /// Register a mock session whose master end the test keeps an eye on
+    fn insert_mock_session_with_master(
+        manager: &PtyManager,
+        sink: &Arc<RecordingSink>,
+        master: MockMaster,
+    ) -> String {
+        let id = Uuid::new_v4().to_string();
+        let session = PtySession {
+            shell_type: ShellType::Csh,
+            working_directory: None,
+            writer: Box::new(std::io::sink()),
+            master: Box::new(master),
+            should_stop: Arc::new(Mutex::new(false)),
+            raw_capture: Arc::new(Mutex::new(None)),
+            bracketed_paste: Arc::new(AtomicBool::new(false)),
+            scrollback: Arc::new(Mutex::new(Scrollback::new(DEFAULT_SCROLLBACK_BYTES))),
+            shared_output: Arc::new(Mutex::new(None)),
+            counters: Arc::new(OutputCounters::default()),
+            started: Instant::now(),
+            events: Arc::new(DetachableSink::new(Arc::clone(sink) as Arc<dyn PtyEventSink>)),
+            process_id: None,
+            killer: None,
+            exited: false,
+        };
+        manager.sessions.lock().insert(id.clone(), session);
+        id
+    }
+
+    /// Reader context wired to a mock session's shared state
+    fn reader_context(manager: &PtyManager, id: &str, reader: Box<dyn Read + Send>) -> ReaderContext {
+        let sessions = manager.sessions.lock();
+        let session = sessions.get(id).unwrap();
+        ReaderContext {
+            pty_id: id.to_string(),
+            reader,
+            should_stop: Arc::clone(&session.should_stop),
+            raw_capture: Arc::clone(&session.raw_capture),
+            bracketed_paste: Arc::clone(&session.bracketed_paste),
+            scrollback: Arc::clone(&session.scrollback),
+            counters: Arc::clone(&session.counters),
+            shared_output: Arc::clone(&session.shared_output),
+            sink: Arc::clone(&session.events) as Arc<dyn PtyEventSink>,
+            sessions: Arc::clone(&manager.sessions),
+        }
+    }
+
+    /// Writer whose bytes the test can inspect afterwards
+    #[derive(Clone, Default)]
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Reader fed chunk by chunk by the test; EOF once the sender is dropped
+    struct ChannelReader(std::sync::mpsc::Receiver<Vec<u8>>);
+
+    impl Read for ChannelReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.recv() {
+                Ok(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    Ok(chunk.len())
+                }
+                Err(_) => Ok(0),
+            }
+        }
+    }
+
+    /// All output `sink` has received so far, waiting until it reads `expected`
+    fn wait_for_output(sink: &RecordingSink, expected: &str) -> String {
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        loop {
+            let output: String = sink
+                .named(PTY_OUTPUT)
+                .iter()
+                .map(|p| p["data"].as_str().unwrap().to_string())
+                .collect();
+            if output == expected || std::time::Instant::now() > deadline {
+                return output;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// Reader returning its data a few bytes at a time
+    struct ChunkedReader {
+        data: Vec<u8>,
+        pos: usize,
+        chunk: usize,
+    }
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let end = (self.pos + self.chunk).min(self.data.len()).min(self.pos + buf.len());
+            let n = end - self.pos;
+            buf[..n].copy_from_slice(&self.data[self.pos..end]);
+            self.pos = end;
+            Ok(n)
+        }
+    }
+
+    /// Reader that panics on first use, recording the thread it ran on
+    struct PanickingReader {
+        thread_name: Arc<Mutex<Option<String>>>,
+    }
+
+    impl Read for PanickingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            *self.thread_name.lock() = thread::current().name().map(String::from);
+            panic!("injected reader failure");
+        }
+    }
+
+    #[test]
+    fn test_reader_panic_still_emits_exit() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &sink);
+        let thread_name = Arc::new(Mutex::new(None));
+
+        let reader = PanickingReader {
+            thread_name: Arc::clone(&thread_name),
+        };
+        let ctx = reader_context(&manager, &id, Box::new(reader));
+        spawn_reader(ctx, None).unwrap().join().unwrap();
+
+        let exits = sink.named(PTY_EXIT);
+        assert_eq!(exits.len(), 1);
+        assert_eq!(exits[0]["ptyId"], id.as_str());
+        assert_eq!(exits[0]["reason"], "reader-panicked");
+        assert!(manager.list_sessions().is_empty());
+        assert_eq!(*thread_name.lock(), Some(format!("pty-reader-{}", &id[..8])));
+    }
+
+    #[test]
+    fn test_kill_stops_a_replay() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cast.json");
+        std::fs::write(&path, "{\"version\": 2}\n[0.0, \"o\", \"hi\"]\n[60.0, \"o\", \"late\"]\n")
+            .unwrap();
+
+        assert!(manager.replay_with_sink(&path, 0.0, sink.clone()).is_err());
+        let id = manager.replay_with_sink(&path, 1.0, sink.clone()).unwrap();
+        assert_eq!(wait_for_output(&sink, "hi"), "hi");
+        manager.kill(&id).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while sink.named(PTY_EXIT).is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(sink.named(PTY_EXIT)[0]["reason"], "cancelled");
+        assert_eq!(wait_for_output(&sink, "hi"), "hi");
+
+        // Shutting down on window close stops replays too
+        manager.replay_with_sink(&path, 1.0, sink.clone()).unwrap();
+        manager.shutdown_all();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while sink.named(PTY_EXIT).len() < 2 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(sink.named(PTY_EXIT)[1]["reason"], "cancelled");
+    }
+
+    #[test]
+    fn test_raw_capture_records_exact_bytes() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &sink);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("raw.bin");
+
+        // Multi-byte characters and escapes split across reads
+        let bytes = "caf\u{e9} \x1b[32mgr\u{fc}n\x1b[0m \u{1f600}\r\n".as_bytes().to_vec();
+        manager.start_raw_capture(&id, &path).unwrap();
+
+        let reader = ChunkedReader {
+            data: bytes.clone(),
+            pos: 0,
+            chunk: 3,
+        };
+        let ctx = reader_context(&manager, &id, Box::new(reader));
+        spawn_reader(ctx, None).unwrap().join().unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), bytes);
+
+        // Normal emission is unaffected
+        let emitted: String = sink
+            .named(PTY_OUTPUT)
+            .iter()
+            .map(|p| p["data"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(emitted.as_bytes(), bytes.as_slice());
+    }
+
+    #[test]
+    fn test_detach_and_reattach_keeps_output() {
+        let manager = PtyManager::new();
+        let first = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &first);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ctx = reader_context(&manager, &id, Box::new(ChannelReader(rx)));
+        let reader = spawn_reader(ctx, None).unwrap();
+
+        tx.send(b"one ".to_vec()).unwrap();
+        assert_eq!(wait_for_output(&first, "one "), "one ");
+
+        manager.detach(&id).unwrap();
+        tx.send(b"two ".to_vec()).unwrap();
+        tx.send(b"three ".to_vec()).unwrap();
+
+        // Still running and listed, just not delivering
+        assert!(manager.list_sessions().contains(&id));
+        assert!(!manager.get_info(&id).unwrap().attached);
+
+        let second = Arc::new(RecordingSink::default());
+        manager.attach_with_sink(&id, second.clone()).unwrap();
+        tx.send(b"four".to_vec()).unwrap();
+        assert_eq!(wait_for_output(&second, "two three four"), "two three four");
+        assert_eq!(wait_for_output(&first, "one "), "one ");
+        assert!(manager.get_info(&id).unwrap().attached);
+
+        drop(tx);
+        reader.join().unwrap();
+        assert_eq!(second.named(PTY_EXIT).len(), 1);
+    }
+
+    #[test]
+    fn test_shell_exiting_while_detached_waits_for_attach() {
+        let manager = PtyManager::new();
+        let first = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &first);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ctx = reader_context(&manager, &id, Box::new(ChannelReader(rx)));
+        let reader = spawn_reader(ctx, None).unwrap();
+
+        manager.detach(&id).unwrap();
+        tx.send(b"last words".to_vec()).unwrap();
+        drop(tx);
+        reader.join().unwrap();
+
+        // The exit is held along with the output
+        assert!(manager.list_sessions().contains(&id));
+        assert!(first.named(PTY_EXIT).is_empty());
+
+        let second = Arc::new(RecordingSink::default());
+        manager.attach_with_sink(&id, second.clone()).unwrap();
+        assert_eq!(wait_for_output(&second, "last words"), "last words");
+        assert_eq!(second.named(PTY_EXIT).len(), 1);
+        assert!(!manager.list_sessions().contains(&id));
+    }
+
+    #[test]
+    fn test_resize_all_resizes_every_session() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let masters: Vec<MockMaster> = (0..3).map(|_| MockMaster::default()).collect();
+        let sizes: Vec<_> = masters.iter().map(|m| Arc::clone(&m.sizes)).collect();
+        for master in masters {
+            insert_mock_session_with_master(&manager, &sink, master);
+        }
+
+        assert!(manager.resize_all(40, 120).is_empty());
+
+        for recorded in sizes {
+            let recorded = recorded.lock();
+            assert_eq!(recorded.len(), 1);
+            assert_eq!((recorded[0].rows, recorded[0].cols), (40, 120));
+        }
+    }
+
+    #[test]
+    fn test_resize_clamps_degenerate_and_huge_sizes() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let master = MockMaster::default();
+        let sizes = Arc::clone(&master.sizes);
+        let id = insert_mock_session_with_master(&manager, &sink, master);
+
+        manager.resize(&id, 0, 0).unwrap();
+        manager.resize(&id, u16::MAX, 5000).unwrap();
+        manager.resize(&id, 30, 100).unwrap();
+        assert!(manager.resize_all(0, 80).is_empty());
+
+        let recorded: Vec<(u16, u16)> = sizes.lock().iter().map(|s| (s.rows, s.cols)).collect();
+        assert_eq!(
+            recorded,
+            vec![(MIN_ROWS, MIN_COLS), (MAX_ROWS, MAX_COLS), (30, 100), (MIN_ROWS, 80)]
+        );
+    }
+
+    #[test]
+    fn test_resize_by_pixels_sets_matching_grid() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let master = MockMaster::default();
+        let sizes = Arc::clone(&master.sizes);
+        let id = insert_mock_session_with_master(&manager, &sink, master);
+
+        // 805 / 8.4 = 95.8 columns and 610 / 17 = 35.9 rows
+        let size = manager.resize_by_pixels(&id, 805, 610, 8.4, 17.0).unwrap();
+        assert_eq!((size.rows, size.cols), (35, 95));
+        assert_eq!((size.pixel_width, size.pixel_height), (798, 595));
+        assert_eq!(*sizes.lock(), vec![size]);
+
+        // A tiny viewport is clamped, and the pixels follow the clamped grid
+        let size = manager.resize_by_pixels(&id, 10, 10, 10.0, 20.0).unwrap();
+        assert_eq!((size.rows, size.cols), (MIN_ROWS, MIN_COLS));
+        assert_eq!((size.pixel_width, size.pixel_height), (100, 40));
+
+        assert!(manager.resize_by_pixels(&id, 800, 600, 0.0, 17.0).is_err());
+        assert!(manager.resize_by_pixels(&id, 800, 600, 8.0, f64::NAN).is_err());
+        assert!(manager.resize_by_pixels("missing", 800, 600, 8.0, 17.0).is_err());
+        assert_eq!(sizes.lock().len(), 2);
+    }
+
+    #[test]
+    fn test_shutdown_all_emits_and_clears_sessions() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let first = insert_mock_session(&manager, &sink);
+        let second = insert_mock_session(&manager, &sink);
+
+        manager.shutdown_all();
+
+        assert!(manager.list_sessions().is_empty());
+        let mut shut_down: Vec<String> = sink
+            .named(PTY_SHUTDOWN)
+            .iter()
+            .map(|p| p["ptyId"].as_str().unwrap().to_string())
+            .collect();
+        shut_down.sort();
+        let mut expected = vec![first, second];
+        expected.sort();
+        assert_eq!(shut_down, expected);
+
+        // Second call and the Drop cleanup must both be no-ops
+        manager.shutdown_all();
+        drop(manager);
+        assert_eq!(sink.named(PTY_SHUTDOWN).len(), 2);
+
+        // A real shell is killed, not just told to stop reading
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let config = PtySpawnConfig {
+            shell_type: long_running_shell(),
+            ..PtySpawnConfig::default()
+        };
+        let id = manager.spawn_with_sink(config, sink.clone()).unwrap();
+        let pid = manager.get_info(&id).unwrap().pid.unwrap();
+
+        manager.shutdown_all();
+        assert_eq!(sink.named(PTY_SHUTDOWN).len(), 1);
+        wait_for_exit(&sink);
+        assert_eq!(sink.named(PTY_EXIT).len(), 1, "shell {} still running", pid);
+        #[cfg(target_os = "linux")]
+        assert!(!Path::new(&format!("/proc/{}", pid)).exists());
+    }
+
+    #[test]
+    fn test_broadcast_command_reaches_running_sessions() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let inputs: Vec<(String, SharedWriter)> = (0..3)
+            .map(|_| {
+                let id = insert_mock_session(&manager, &sink);
+                let input = SharedWriter::default();
+                manager.sessions.lock().get_mut(&id).unwrap().writer = Box::new(input.clone());
+                (id, input)
+            })
+            .collect();
+
+        // The third shell is exiting and must be left alone
+        let (exiting, exiting_input) = &inputs[2];
+        *manager.sessions.lock()[exiting].should_stop.lock() = true;
+
+        let results = manager.broadcast_command("echo hi");
+        assert_eq!(results.len(), 2);
+        assert!(results.values().all(|result| result.is_ok()));
+        for (id, input) in &inputs[..2] {
+            assert!(results.contains_key(id));
+            assert_eq!(input.0.lock().as_slice(), b"echo hi\r");
+        }
+        assert!(exiting_input.0.lock().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_set_echo_updates_termios() {
+        use nix::sys::termios::LocalFlags;
+
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &sink);
+        assert!(manager.set_echo(&id, false).is_err(), "mock master has no fd");
+
+        let pair = native_pty_system().openpty(PtySize::default()).unwrap();
+        manager.sessions.lock().get_mut(&id).unwrap().master = pair.master;
+        let echo = |manager: &PtyManager| {
+            let termios = manager.sessions.lock()[&id].master.get_termios().unwrap();
+            termios.local_flags.contains(LocalFlags::ECHO)
+        };
+
+        manager.set_echo(&id, false).unwrap();
+        assert!(!echo(&manager));
+        manager.set_echo(&id, true).unwrap();
+        assert!(echo(&manager));
+        assert!(manager.set_echo("missing", true).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spawn_custom_command() {
+        let shell_type: ShellType = serde_json::from_value(serde_json::json!({
+            "custom": { "command": "sh", "args": ["-c", "echo custom-ok"] }
+        }))
+        .unwrap();
+        assert_eq!(shell_type.display_name(), "Custom Command");
+
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let config = PtySpawnConfig { shell_type, ..PtySpawnConfig::default() };
+        let id = manager.spawn_with_sink(config, sink.clone()).unwrap();
+        assert_eq!(wait_for_output(&sink, "custom-ok\r\n"), "custom-ok\r\n");
+        assert!(manager.set_working_directory(&id, Path::new("/"), true).is_err());
+    }
+
+    #[test]
+    fn test_spawn_config_env_round_trips() {
+        let config = PtySpawnConfig {
+            env: Some(HashMap::from([("NO_COLOR".to_string(), "1".to_string())])),
+            ..PtySpawnConfig::default()
+        };
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["env"], serde_json::json!({ "NO_COLOR": "1" }));
+        let parsed: PtySpawnConfig = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.env, config.env);
+
+        // Configs from before the field existed still parse
+        let parsed: PtySpawnConfig = serde_json::from_value(serde_json::json!({
+            "shellType": "cmd", "workingDirectory": null, "rows": 24, "cols": 80
+        }))
+        .unwrap();
+        assert_eq!(parsed.env, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spawn_applies_extra_env() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let config = PtySpawnConfig {
+            shell_type: ShellType::Custom {
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), "echo $TERM $GREETING".to_string()],
+            },
+            env: Some(HashMap::from([
+                ("TERM".to_string(), "dumb".to_string()),
+                ("GREETING".to_string(), "hello".to_string()),
+            ])),
+            ..PtySpawnConfig::default()
+        };
+        manager.spawn_with_sink(config, sink.clone()).unwrap();
+        assert_eq!(wait_for_output(&sink, "dumb hello\r\n"), "dumb hello\r\n");
+    }
+
+    /// A program that keeps a PTY open for 30 seconds
+    fn long_running_shell() -> ShellType {
+        if cfg!(windows) {
+            ShellType::Custom {
+                command: "cmd".to_string(),
+                args: ["/c", "ping", "-n", "30", "127.0.0.1"].map(String::from).to_vec(),
+            }
+        } else {
+            ShellType::Custom { command: "sleep".to_string(), args: vec!["30".to_string()] }
+        }
+    }
+
+    /// Wait up to 10 seconds for a `pty-exit` event
+    fn wait_for_exit(sink: &RecordingSink) {
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while sink.named(PTY_EXIT).is_empty() && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_kill_terminates_the_shell() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let config = PtySpawnConfig {
+            shell_type: long_running_shell(),
+            ..PtySpawnConfig::default()
+        };
+        let id = manager.spawn_with_sink(config, sink.clone()).unwrap();
+        let pid = manager.get_info(&id).unwrap().pid.unwrap();
+
+        manager.kill(&id).unwrap();
+        assert!(manager.get_info(&id).is_none());
+
+        // The reader reaps the process before reporting the exit
+        wait_for_exit(&sink);
+        assert_eq!(sink.named(PTY_EXIT).len(), 1, "shell {} still running", pid);
+        #[cfg(target_os = "linux")]
+        assert!(!Path::new(&format!("/proc/{}", pid)).exists());
+    }
+
+    #[test]
+    fn test_send_ctrl_c_writes_etx() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &sink);
+        let input = SharedWriter::default();
+        manager.sessions.lock().get_mut(&id).unwrap().writer = Box::new(input.clone());
+
+        manager.send_ctrl_c(&id).unwrap();
+        assert_eq!(input.0.lock().as_slice(), b"\x03");
+        assert!(manager.send_ctrl_c("missing").is_err());
+    }
+
+    #[test]
+    fn test_write_paste_brackets_only_while_the_shell_asks() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &sink);
+        let input = SharedWriter::default();
+        manager.sessions.lock().get_mut(&id).unwrap().writer = Box::new(input.clone());
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ctx = reader_context(&manager, &id, Box::new(ChannelReader(rx)));
+        let reader = spawn_reader(ctx, None).unwrap();
+
+        manager.write_paste(&id, "ls\n").unwrap();
+        assert_eq!(input.0.lock().as_slice(), b"ls\n");
+        input.0.lock().clear();
+
+        tx.send(b"\x1b[?2004h$ ".to_vec()).unwrap();
+        assert_eq!(wait_for_output(&sink, "\x1b[?2004h$ "), "\x1b[?2004h$ ");
+        manager.write_paste(&id, "a\nb\x1b[201~c").unwrap();
+        assert_eq!(input.0.lock().as_slice(), b"\x1b[200~a\nbc\x1b[201~");
+        input.0.lock().clear();
+        // Removing the inner marker must not leave a new one behind
+        manager.write_paste(&id, "\x1b[20\x1b[201~1~rm x\n").unwrap();
+        assert_eq!(input.0.lock().as_slice(), b"\x1b[200~rm x\n\x1b[201~");
+        input.0.lock().clear();
+
+        // Turned off again as one of several modes in the same sequence
+        tx.send(b"\x1b[?1;2004l".to_vec()).unwrap();
+        let expected = "\x1b[?2004h$ \x1b[?1;2004l";
+        assert_eq!(wait_for_output(&sink, expected), expected);
+        manager.write_paste(&id, "pwd\n").unwrap();
+        assert_eq!(input.0.lock().as_slice(), b"pwd\n");
+
+        drop(tx);
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn test_bracketed_paste_switch_split_across_chunks() {
+        let enabled = AtomicBool::new(false);
+        let mut tail = String::new();
+        for chunk in ["$ \x1b", "[?20", "04", "h"] {
+            track_bracketed_paste(chunk, &mut tail, &enabled);
+        }
+        assert!(enabled.load(Ordering::SeqCst));
+        assert!(tail.is_empty());
+
+        track_bracketed_paste("out\x1b[?2004", &mut tail, &enabled);
+        assert_eq!(tail, "\x1b[?2004");
+        track_bracketed_paste("l$ ", &mut tail, &enabled);
+        assert!(!enabled.load(Ordering::SeqCst));
+
+        // Endless parameters aren't held on to
+        track_bracketed_paste(&format!("\x1b[?{}", "1".repeat(100)), &mut tail, &enabled);
+        assert!(tail.is_empty());
+    }
+
+    #[test]
+    fn test_extract_osc_title() {
+        assert_eq!(extract_osc_title("\x1b]0;vim main.rs\x07"), Some("vim main.rs".to_string()));
+        assert_eq!(extract_osc_title("a\x1b]2;~/src\x1b\\b"), Some("~/src".to_string()));
+        // The last title wins; other OSC codes (e.g. 7, the cwd) don't count
+        let data = "\x1b]2;first\x07\x1b]7;file:///tmp\x07\x1b]0;second\x1b\\";
+        assert_eq!(extract_osc_title(data), Some("second".to_string()));
+        assert_eq!(extract_osc_title("\x1b]7;file:///tmp\x07"), None);
+        assert_eq!(extract_osc_title("\x1b]0;no end"), None);
+        assert_eq!(extract_osc_title("\x1b]0;\x07"), Some(String::new()));
+        assert_eq!(extract_osc_title("plain text"), None);
+    }
+
+    #[test]
+    fn test_output_title_emits_event() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &sink);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ctx = reader_context(&manager, &id, Box::new(ChannelReader(rx)));
+        let reader = spawn_reader(ctx, None).unwrap();
+
+        tx.send(b"\x1b]0;build\x07$ ".to_vec()).unwrap();
+        drop(tx);
+        reader.join().unwrap();
+
+        // Forwarded unchanged, plus the title event
+        assert_eq!(wait_for_output(&sink, "\x1b]0;build\x07$ "), "\x1b]0;build\x07$ ");
+        let titles = sink.named(PTY_TITLE);
+        assert_eq!(titles.len(), 1);
+        assert_eq!(titles[0]["ptyId"], id.as_str());
+        assert_eq!(titles[0]["title"], "build");
+    }
+
+    #[test]
+    fn test_inject_output_emits_notice() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &sink);
+        let input = SharedWriter::default();
+        manager.sessions.lock().get_mut(&id).unwrap().writer = Box::new(input.clone());
+        assert!(manager.inject_output(&id, "Session restored").is_err(), "no reader yet");
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ctx = reader_context(&manager, &id, Box::new(ChannelReader(rx)));
+        let reader = spawn_reader(ctx, None).unwrap();
+
+        tx.send(b"$ ".to_vec()).unwrap();
+        assert_eq!(wait_for_output(&sink, "$ "), "$ ");
+        manager.inject_output(&id, "Session restored").unwrap();
+        let expected = "$ \r\n\x1b[36m[Session restored]\x1b[0m\r\n";
+        assert_eq!(wait_for_output(&sink, expected), expected);
+        assert_eq!(manager.get_scrollback(&id).as_deref(), Some(expected));
+        assert!(input.0.lock().is_empty(), "nothing goes to the shell");
+        assert!(manager.inject_output("missing", "x").is_err());
+
+        drop(tx);
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn test_metrics_count_output() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &sink);
+        let metrics = manager.get_metrics(&id).unwrap();
+        assert_eq!((metrics.bytes_read, metrics.chunks_emitted), (0, 0));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ctx = reader_context(&manager, &id, Box::new(ChannelReader(rx)));
+        let reader = spawn_reader(ctx, None).unwrap();
+
+        // The split character is held back until it's complete
+        tx.send("ab\u{e9}".as_bytes()[..3].to_vec()).unwrap();
+        assert_eq!(wait_for_output(&sink, "ab"), "ab");
+        tx.send(vec![0xa9, b'c']).unwrap();
+        assert_eq!(wait_for_output(&sink, "ab\u{e9}c"), "ab\u{e9}c");
+
+        let metrics = manager.get_metrics(&id).unwrap();
+        assert_eq!((metrics.bytes_read, metrics.chunks_emitted), (5, 2));
+        assert!(manager.get_metrics("missing").is_none());
+
+        drop(tx);
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn test_scrollback_keeps_recent_output() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &sink);
+        manager.sessions.lock().get_mut(&id).unwrap().scrollback = Arc::new(Mutex::new(Scrollback::new(10)));
+        assert_eq!(manager.get_scrollback(&id).as_deref(), Some(""));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ctx = reader_context(&manager, &id, Box::new(ChannelReader(rx)));
+        let reader = spawn_reader(ctx, None).unwrap();
+
+        tx.send(b"$ ls\r\n".to_vec()).unwrap();
+        assert_eq!(wait_for_output(&sink, "$ ls\r\n"), "$ ls\r\n");
+        assert_eq!(manager.get_scrollback(&id).as_deref(), Some("$ ls\r\n"));
+
+        // Past the limit the oldest output goes
+        tx.send(b"a.txt\r\n$ ".to_vec()).unwrap();
+        assert_eq!(wait_for_output(&sink, "$ ls\r\na.txt\r\n$ "), "$ ls\r\na.txt\r\n$ ");
+        assert_eq!(manager.get_scrollback(&id).as_deref(), Some("\na.txt\r\n$ "));
+        assert_eq!(manager.get_scrollback("missing"), None);
+
+        drop(tx);
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn test_set_working_directory_tracks_and_sends_cd() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &sink);
+        let input = SharedWriter::default();
+        manager.sessions.lock().get_mut(&id).unwrap().writer = Box::new(input.clone());
+
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first dir");
+        let second = dir.path().join("second");
+        std::fs::create_dir(&first).unwrap();
+        std::fs::create_dir(&second).unwrap();
+
+        // Tracking only: nothing is typed into the shell
+        manager.set_working_directory(&id, &first, false).unwrap();
+        let info = manager.get_info(&id).unwrap();
+        assert_eq!(info.working_directory, Some(first.to_string_lossy().to_string()));
+        assert!(input.0.lock().is_empty());
+
+        manager.set_working_directory(&id, &second, true).unwrap();
+        let info = manager.get_info(&id).unwrap();
+        assert_eq!(info.working_directory, Some(second.to_string_lossy().to_string()));
+        let expected = format!("cd \"{}\"\r", second.to_string_lossy().replace('\\', "\\\\"));
+        assert_eq!(String::from_utf8(input.0.lock().clone()).unwrap(), expected);
+
+        // Missing directories are rejected and leave the tracked value alone
+        assert!(manager.set_working_directory(&id, &dir.path().join("missing"), true).is_err());
+        assert_eq!(
+            manager.get_info(&id).unwrap().working_directory,
+            Some(second.to_string_lossy().to_string())
+        );
+    }
+
+    #[test]
+    fn test_info_reports_process_id() {
+        let manager = PtyManager::new();
+        let sink = Arc::new(RecordingSink::default());
+        let id = insert_mock_session(&manager, &sink);
+        assert_eq!(manager.get_info(&id).unwrap().pid, None);
+
+        manager.sessions.lock().get_mut(&id).unwrap().process_id = Some(4242);
+        assert_eq!(manager.get_info(&id).unwrap().pid, Some(4242));
+    }
+
+    #[test]
+    fn test_cd_command_quotes_per_shell() {
+        assert_eq!(
+            cd_command(&ShellType::PowerShell, "C:\\it's").unwrap(),
+            "Set-Location -LiteralPath 'C:\\it''s'\r"
+        );
+        assert_eq!(cd_command(&ShellType::Cmd, "D:\\work").unwrap(), "cd /d \"D:\\work\"\r");
+        assert_eq!(cd_command(&ShellType::GitBash, "/a $b`c").unwrap(), "cd \"/a \\$b\\`c\"\r");
+        assert!(cd_command(&ShellType::Csh, "bad\nname").is_err());
+    }
+}
@@ -3,8 +3,14 @@
 //! This module provides PTY (pseudo-terminal) functionality using
 //! Windows ConPTY API through the portable-pty crate.
 
+pub mod events;
 pub mod manager;
+pub mod output;
+pub mod process;
+pub mod replay;
+pub mod scrollback;
 pub mod types;
 
+pub use events::PtyEventSink;
 pub use manager::PtyManager;
 pub use types::*;
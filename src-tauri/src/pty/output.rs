@@ -0,0 +1,244 @@
+//! Output hand-off between a PTY reader thread and its emitter thread
+//!
+//! The reader thread must never wait on Tauri IPC, otherwise a slow
+//! frontend backs up the PTY. Output chunks are pushed into a bounded
+//! channel that a dedicated emitter thread drains. When the channel is
+//! full, new output is coalesced into a backlog the emitter picks up once
+//! the channel is empty. If the backlog grows past its limit it is
+//! discarded and replaced by a visible marker, so memory stays bounded.
+//!
+//! The emitter also merges chunks that arrive in quick succession, so a
+//! command dumping a large file produces a few big events rather than one
+//! per read.
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use super::events::{self, PtyEventSink, PTY_OUTPUT, PTY_TITLE};
+use super::manager::extract_osc_title;
+use super::types::{PtyOutputPayload, PtyTitlePayload};
+
+/// Number of chunks that can be queued before output is coalesced
+pub const CHANNEL_CAPACITY: usize = 64;
+/// Maximum bytes held in the backlog before it is dropped
+pub const MAX_BACKLOG_BYTES: usize = 4 * 1024 * 1024;
+/// Merged output is emitted once it reaches this many bytes
+pub const FLUSH_BYTES: usize = 32 * 1024;
+/// Merged output is emitted when no new chunk arrives for this long
+pub const FLUSH_IDLE: Duration = Duration::from_millis(8);
+/// Merged output is never held longer than this, even if output keeps coming
+pub const FLUSH_MAX_DELAY: Duration = Duration::from_millis(32);
+/// Written in place of output that had to be dropped
+pub const DROPPED_MARKER: &str = "\r\n\x1b[33m[output dropped: terminal could not keep up]\x1b[0m\r\n";
+
+/// Reader-side handle of the output channel
+///
+/// The emitter keeps running until every clone has been dropped.
+#[derive(Clone)]
+pub struct OutputSender {
+    tx: SyncSender<String>,
+    backlog: Arc<Mutex<String>>,
+    max_backlog: usize,
+}
+
+impl OutputSender {
+    /// Queue a chunk of output without blocking
+    pub fn push(&self, chunk: &str) {
+        let mut backlog = self.backlog.lock();
+
+        // Once anything is in the backlog, newer output must queue behind it
+        if backlog.is_empty() {
+            match self.tx.try_send(chunk.to_string()) {
+                Ok(()) | Err(TrySendError::Disconnected(_)) => return,
+                Err(TrySendError::Full(_)) => {}
+            }
+        }
+
+        if backlog.len() + chunk.len() > self.max_backlog {
+            log::warn!("PTY output backlog exceeded {} bytes, dropping", self.max_backlog);
+            backlog.clear();
+            backlog.push_str(DROPPED_MARKER);
+        }
+        backlog.push_str(chunk);
+    }
+}
+
+/// Start an emitter thread delivering `pty-output` events for `pty_id`
+///
+/// Output that sets the window title is followed by a `pty-title` event.
+///
+/// Dropping the returned sender lets the emitter flush what is left and
+/// exit; join the handle to wait for that.
+pub fn spawn_emitter(pty_id: String, sink: Arc<dyn PtyEventSink>) -> (OutputSender, JoinHandle<()>) {
+    spawn_emitter_with(pty_id, sink, CHANNEL_CAPACITY, MAX_BACKLOG_BYTES)
+}
+
+fn spawn_emitter_with(
+    pty_id: String,
+    sink: Arc<dyn PtyEventSink>,
+    capacity: usize,
+    max_backlog: usize,
+) -> (OutputSender, JoinHandle<()>) {
+    let (tx, rx) = mpsc::sync_channel(capacity);
+    let backlog = Arc::new(Mutex::new(String::new()));
+
+    let sender = OutputSender {
+        tx,
+        backlog: Arc::clone(&backlog),
+        max_backlog,
+    };
+
+    let handle = thread::spawn(move || run_emitter(&pty_id, rx, &backlog, sink.as_ref()));
+
+    (sender, handle)
+}
+
+fn run_emitter(pty_id: &str, rx: Receiver<String>, backlog: &Mutex<String>, sink: &dyn PtyEventSink) {
+    let send = |data: String| {
+        // The reader only hands over complete escape sequences
+        let title = extract_osc_title(&data);
+        let payload = PtyOutputPayload {
+            pty_id: pty_id.to_string(),
+            data,
+        };
+        events::emit(sink, PTY_OUTPUT, &payload);
+        if let Some(title) = title {
+            let payload = PtyTitlePayload {
+                pty_id: pty_id.to_string(),
+                title,
+            };
+            events::emit(sink, PTY_TITLE, &payload);
+        }
+    };
+
+    // Output merged but not emitted yet, and when its first chunk arrived
+    let mut pending = String::new();
+    let mut pending_since = Instant::now();
+
+    loop {
+        let received = if pending.is_empty() {
+            rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            let deadline = pending_since + FLUSH_MAX_DELAY;
+            let timeout = deadline.saturating_duration_since(Instant::now()).min(FLUSH_IDLE);
+            rx.recv_timeout(timeout)
+        };
+
+        match received {
+            Ok(chunk) => {
+                if pending.is_empty() {
+                    pending_since = Instant::now();
+                }
+                pending.push_str(&chunk);
+
+                // The backlog is only filled while the channel is full, so it
+                // is newer than anything queued: take it once the queue is
+                // empty. Holding the lock keeps the reader from refilling the
+                // queue in between.
+                let mut backlog = backlog.lock();
+                while let Ok(chunk) = rx.try_recv() {
+                    pending.push_str(&chunk);
+                }
+                pending.push_str(&std::mem::take(&mut *backlog));
+                drop(backlog);
+
+                if pending.len() >= FLUSH_BYTES {
+                    send(std::mem::take(&mut pending));
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => send(std::mem::take(&mut pending)),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    // Sender dropped: flush whatever was still merged or coalesced
+    pending.push_str(&std::mem::take(&mut *backlog.lock()));
+    if !pending.is_empty() {
+        send(pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sink that blocks while `gate` is held, then records output data
+    #[derive(Default)]
+    struct GatedSink {
+        gate: Mutex<()>,
+        data: Mutex<Vec<String>>,
+    }
+
+    impl PtyEventSink for GatedSink {
+        fn emit_event(&self, _event: &str, payload: serde_json::Value) {
+            let _open = self.gate.lock();
+            let data = payload["data"].as_str().unwrap_or_default().to_string();
+            self.data.lock().push(data);
+        }
+    }
+
+    #[test]
+    fn test_reader_not_blocked_by_slow_emitter() {
+        let sink = Arc::new(GatedSink::default());
+        let gate = sink.gate.lock();
+        let (sender, handle) = spawn_emitter_with("id".into(), sink.clone(), 4, MAX_BACKLOG_BYTES);
+
+        let chunks: Vec<String> = (0..500).map(|i| format!("chunk-{}\n", i)).collect();
+        for chunk in &chunks {
+            sender.push(chunk);
+        }
+        assert!(sink.data.lock().is_empty());
+
+        drop(gate);
+        drop(sender);
+        handle.join().unwrap();
+
+        assert_eq!(sink.data.lock().concat(), chunks.concat());
+    }
+
+    #[test]
+    fn test_rapid_chunks_are_merged() {
+        let sink = Arc::new(GatedSink::default());
+        let (sender, handle) = spawn_emitter("id".into(), sink.clone());
+
+        let chunks: Vec<String> = (0..2000).map(|i| format!("{}\n", i)).collect();
+        for chunk in &chunks {
+            sender.push(chunk);
+        }
+        // Output that stops is still delivered after the idle delay
+        thread::sleep(FLUSH_MAX_DELAY * 4);
+        let delivered = sink.data.lock().len();
+        assert!(delivered > 0);
+        assert!(delivered < chunks.len() / 10, "{} payloads", delivered);
+        assert_eq!(sink.data.lock().concat(), chunks.concat());
+
+        // What is pending when the sender goes away is flushed too
+        sender.push("last");
+        drop(sender);
+        handle.join().unwrap();
+        assert_eq!(sink.data.lock().last().unwrap(), "last");
+    }
+
+    #[test]
+    fn test_overflowing_backlog_is_replaced_by_marker() {
+        let sink = Arc::new(GatedSink::default());
+        let gate = sink.gate.lock();
+        let (sender, handle) = spawn_emitter_with("id".into(), sink.clone(), 1, 256);
+
+        for i in 0..100 {
+            sender.push(&format!("line {}\n", i));
+        }
+
+        drop(gate);
+        drop(sender);
+        handle.join().unwrap();
+
+        let output = sink.data.lock().concat();
+        assert!(output.contains(DROPPED_MARKER));
+        assert!(output.ends_with("line 99\n"));
+    }
+}
@@ -0,0 +1,150 @@
+//! Process inspection helpers for PTY sessions
+//!
+//! Best-effort queries against the OS about the processes running inside
+//! a PTY. Every function returns `None` when the information isn't
+//! available (unsupported platform, insufficient rights, process gone).
+
+use std::path::PathBuf;
+
+/// Query the current working directory of a running process
+///
+/// - Linux: reads the `/proc/<pid>/cwd` symlink
+/// - Windows (64-bit): reads `CurrentDirectory` from the process parameters
+///   block of the target's PEB
+/// - Other platforms: not supported
+pub fn query_cwd(pid: u32) -> Option<PathBuf> {
+    query_cwd_impl(pid)
+}
+
+#[cfg(target_os = "linux")]
+fn query_cwd_impl(pid: u32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+#[cfg(all(windows, target_pointer_width = "64"))]
+fn query_cwd_impl(pid: u32) -> Option<PathBuf> {
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStringExt;
+    use windows::Wdk::System::Threading::{NtQueryInformationProcess, ProcessBasicInformation};
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows::Win32::System::Diagnostics::Debug::ReadProcessMemory;
+    use windows::Win32::System::Threading::{
+        OpenProcess, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ,
+    };
+
+    /// Layout of PROCESS_BASIC_INFORMATION
+    #[repr(C)]
+    #[derive(Default)]
+    struct BasicInformation {
+        exit_status: i32,
+        peb_base_address: usize,
+        affinity_mask: usize,
+        base_priority: i32,
+        unique_process_id: usize,
+        inherited_from_unique_process_id: usize,
+    }
+
+    /// Layout of UNICODE_STRING
+    #[repr(C)]
+    #[derive(Default)]
+    struct UnicodeString {
+        length: u16,
+        maximum_length: u16,
+        buffer: usize,
+    }
+
+    // Offsets into the (undocumented but stable) x64 PEB structures
+    const PEB_PROCESS_PARAMETERS: usize = 0x20;
+    const PARAMS_CURRENT_DIRECTORY: usize = 0x38;
+
+    unsafe fn read<T: Default>(process: HANDLE, address: usize) -> Option<T> {
+        let mut value = T::default();
+        ReadProcessMemory(
+            process,
+            address as *const c_void,
+            &mut value as *mut T as *mut c_void,
+            std::mem::size_of::<T>(),
+            None,
+        )
+        .ok()?;
+        Some(value)
+    }
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, false, pid).ok()?;
+
+        let result = (|| {
+            let mut info = BasicInformation::default();
+            let mut returned = 0u32;
+            let status = NtQueryInformationProcess(
+                process,
+                ProcessBasicInformation,
+                &mut info as *mut BasicInformation as *mut c_void,
+                std::mem::size_of::<BasicInformation>() as u32,
+                &mut returned,
+            );
+            if status.0 < 0 || info.peb_base_address == 0 {
+                return None;
+            }
+
+            let params: usize = read(process, info.peb_base_address + PEB_PROCESS_PARAMETERS)?;
+            let dir: UnicodeString = read(process, params + PARAMS_CURRENT_DIRECTORY)?;
+            if dir.buffer == 0 || dir.length == 0 {
+                return None;
+            }
+
+            let mut wide = vec![0u16; dir.length as usize / 2];
+            ReadProcessMemory(
+                process,
+                dir.buffer as *const c_void,
+                wide.as_mut_ptr() as *mut c_void,
+                dir.length as usize,
+                None,
+            )
+            .ok()?;
+
+            let mut path = PathBuf::from(std::ffi::OsString::from_wide(&wide));
+            // CurrentDirectory keeps a trailing separator except at the root
+            if path.parent().is_some() {
+                let trimmed = path.to_string_lossy().trim_end_matches('\\').to_string();
+                path = PathBuf::from(trimmed);
+            }
+            Some(path)
+        })();
+
+        let _ = CloseHandle(process);
+        result
+    }
+}
+
+#[cfg(not(any(target_os = "linux", all(windows, target_pointer_width = "64"))))]
+fn query_cwd_impl(_pid: u32) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_query_cwd_of_child() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut child = std::process::Command::new("sleep")
+            .arg("5")
+            .current_dir(dir.path())
+            .spawn()
+            .unwrap();
+
+        let cwd = query_cwd(child.id());
+        let _ = child.kill();
+        let _ = child.wait();
+
+        assert_eq!(cwd, Some(dir.path().canonicalize().unwrap()));
+    }
+
+    #[test]
+    fn test_query_cwd_unknown_process() {
+        assert_eq!(query_cwd(u32::MAX), None);
+    }
+}
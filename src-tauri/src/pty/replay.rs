@@ -0,0 +1,193 @@
+//! Replay of asciinema recordings
+//!
+//! Parses asciicast v2 files (a JSON header line followed by one
+//! `[time, code, data]` array per line) and plays the output events back
+//! as a virtual session: `pty-output` events at the recorded times, then
+//! a `pty-exit`. No shell process is involved.
+
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+
+use super::events::{self, PtyEventSink, PTY_EXIT, PTY_OUTPUT};
+use super::types::{PtyExitPayload, PtyOutputPayload};
+
+/// A single output event from a recording
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedOutput {
+    /// Seconds since the start of the recording
+    pub time: f64,
+    /// Terminal output written at that time
+    pub data: String,
+}
+
+/// Parse an asciicast v2 recording, keeping only output (`"o"`) events
+pub fn parse_asciicast(contents: &str) -> Result<Vec<RecordedOutput>> {
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+
+    let header: serde_json::Value = serde_json::from_str(lines.next().context("Recording is empty")?)
+        .context("Invalid recording header")?;
+    match header.get("version").and_then(|v| v.as_u64()) {
+        Some(2) => {}
+        Some(v) => bail!("Unsupported asciicast version {}", v),
+        None => bail!("Recording header has no version"),
+    }
+
+    let mut outputs = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let (time, code, data): (f64, String, String) = serde_json::from_str(line)
+            .with_context(|| format!("Invalid recording event on line {}", index + 2))?;
+        if code == "o" {
+            outputs.push(RecordedOutput { time, data });
+        }
+    }
+
+    Ok(outputs)
+}
+
+/// Play `outputs` back as session `pty_id`, `speed` times faster than recorded
+///
+/// Events are scheduled against the start time, so slow delivery of one
+/// event does not push back the ones after it. Events at negative times
+/// play at once and those too late to schedule are skipped.
+///
+/// Playback stops early, with a `pty-exit` whose reason is `cancelled`,
+/// when `cancel` receives a message or its sender is dropped.
+pub fn spawn(
+    pty_id: String,
+    outputs: Vec<RecordedOutput>,
+    speed: f64,
+    sink: Arc<dyn PtyEventSink>,
+    cancel: Receiver<()>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let start = Instant::now();
+        let mut cancelled = false;
+
+        for output in outputs {
+            let due = Duration::try_from_secs_f64((output.time / speed).max(0.0))
+                .ok()
+                .and_then(|delay| start.checked_add(delay));
+            let Some(due) = due else {
+                log::warn!("Replay {}: skipping event at {}s", pty_id, output.time);
+                continue;
+            };
+            let wait = due.saturating_duration_since(Instant::now());
+            if !matches!(cancel.recv_timeout(wait), Err(RecvTimeoutError::Timeout)) {
+                cancelled = true;
+                break;
+            }
+
+            let payload = PtyOutputPayload {
+                pty_id: pty_id.clone(),
+                data: output.data,
+            };
+            events::emit(sink.as_ref(), PTY_OUTPUT, &payload);
+        }
+
+        let payload = PtyExitPayload {
+            pty_id: pty_id.clone(),
+            exit_code: if cancelled { None } else { Some(0) },
+            reason: cancelled.then(|| "cancelled".to_string()),
+        };
+        events::emit(sink.as_ref(), PTY_EXIT, &payload);
+
+        log::info!("Replay session {} finished", pty_id);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    const RECORDING: &str = r#"{"version": 2, "width": 80, "height": 24}
+[0.0, "o", "first"]
+[0.1, "i", "typed"]
+[0.2, "o", "second"]
+[0.4, "o", "third"]
+"#;
+
+    /// Sink recording each event with the time it arrived
+    #[derive(Default)]
+    struct TimedSink {
+        events: Mutex<Vec<(String, serde_json::Value, Instant)>>,
+    }
+
+    impl PtyEventSink for TimedSink {
+        fn emit_event(&self, event: &str, payload: serde_json::Value) {
+            self.events.lock().push((event.to_string(), payload, Instant::now()));
+        }
+    }
+
+    #[test]
+    fn test_parse_keeps_output_events() {
+        let outputs = parse_asciicast(RECORDING).unwrap();
+        let data: Vec<&str> = outputs.iter().map(|o| o.data.as_str()).collect();
+        assert_eq!(data, vec!["first", "second", "third"]);
+        assert_eq!(outputs[2].time, 0.4);
+
+        assert!(parse_asciicast(r#"{"version": 1}"#).is_err());
+    }
+
+    #[test]
+    fn test_replay_emits_in_order_with_scaled_timing() {
+        let sink = Arc::new(TimedSink::default());
+        let outputs = parse_asciicast(RECORDING).unwrap();
+
+        let (_cancel, cancelled) = std::sync::mpsc::channel();
+        let start = Instant::now();
+        spawn("replay".into(), outputs, 4.0, sink.clone(), cancelled).join().unwrap();
+
+        let events = sink.events.lock();
+        let names: Vec<&str> = events.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, vec![PTY_OUTPUT, PTY_OUTPUT, PTY_OUTPUT, PTY_EXIT]);
+
+        let data: Vec<&str> = events[..3].iter().map(|(_, p, _)| p["data"].as_str().unwrap()).collect();
+        assert_eq!(data, vec!["first", "second", "third"]);
+
+        // 0.4s at 4x speed is due after 100ms, well before the unscaled 400ms
+        let last = events[2].2.duration_since(start);
+        assert!(last >= Duration::from_millis(100), "{:?}", last);
+        assert!(last < Duration::from_millis(400), "{:?}", last);
+    }
+
+    #[test]
+    fn test_unplayable_times_are_skipped() {
+        let sink = Arc::new(TimedSink::default());
+        let outputs = [(-1.0, "early"), (1e300, "never"), (f64::INFINITY, "never"), (0.0, "last")]
+            .into_iter()
+            .map(|(time, data)| RecordedOutput { time, data: data.to_string() })
+            .collect();
+
+        let (_cancel, cancelled) = std::sync::mpsc::channel();
+        spawn("replay".into(), outputs, 1.0, sink.clone(), cancelled).join().unwrap();
+
+        let events = sink.events.lock();
+        let data: Vec<_> = events.iter().filter_map(|(_, p, _)| p["data"].as_str()).collect();
+        assert_eq!(data, vec!["early", "last"]);
+        assert_eq!(events.last().unwrap().0, PTY_EXIT);
+    }
+
+    #[test]
+    fn test_cancel_stops_playback() {
+        let sink = Arc::new(TimedSink::default());
+        let outputs = parse_asciicast(RECORDING).unwrap();
+
+        let (cancel, cancelled) = std::sync::mpsc::channel();
+        let start = Instant::now();
+        let replay = spawn("replay".into(), outputs, 0.01, sink.clone(), cancelled);
+        cancel.send(()).unwrap();
+        replay.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let events = sink.events.lock();
+        let (name, payload, _) = events.last().unwrap();
+        assert_eq!(name, PTY_EXIT);
+        assert_eq!(payload["reason"], "cancelled");
+        assert!(events.len() <= 2, "{:?}", events);
+    }
+}
@@ -0,0 +1,72 @@
+//! Bounded copy of a session's recent output
+//!
+//! The frontend only sees output through `pty-output` events, so a tab
+//! created late (or a reloaded window) would otherwise start blank. Each
+//! session keeps its most recent output here; the oldest bytes are dropped
+//! once the capacity is reached.
+
+use std::collections::VecDeque;
+
+/// Default number of bytes kept per session
+pub const DEFAULT_SCROLLBACK_BYTES: usize = 256 * 1024;
+
+/// Ring buffer of the last `capacity` bytes of output
+pub struct Scrollback {
+    bytes: VecDeque<u8>,
+    capacity: usize,
+}
+
+impl Scrollback {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            bytes: VecDeque::with_capacity(capacity.min(DEFAULT_SCROLLBACK_BYTES)),
+            capacity,
+        }
+    }
+
+    /// Append a chunk, evicting the oldest bytes beyond the capacity
+    pub fn push(&mut self, chunk: &str) {
+        let chunk = chunk.as_bytes();
+        let chunk = &chunk[chunk.len().saturating_sub(self.capacity)..];
+        let excess = (self.bytes.len() + chunk.len()).saturating_sub(self.capacity);
+        self.bytes.drain(..excess);
+        self.bytes.extend(chunk);
+    }
+
+    /// The buffered output as text
+    ///
+    /// Eviction can cut a character in half; its leftover bytes are skipped.
+    pub fn text(&self) -> String {
+        let start = self
+            .bytes
+            .iter()
+            .position(|b| b & 0b1100_0000 != 0b1000_0000)
+            .unwrap_or(self.bytes.len());
+        let bytes: Vec<u8> = self.bytes.iter().skip(start).copied().collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oldest_bytes_are_evicted() {
+        let mut scrollback = Scrollback::new(8);
+        scrollback.push("abc");
+        scrollback.push("def");
+        assert_eq!(scrollback.text(), "abcdef");
+
+        scrollback.push("ghij");
+        assert_eq!(scrollback.text(), "cdefghij");
+
+        // A chunk larger than the buffer keeps only its tail
+        scrollback.push("0123456789");
+        assert_eq!(scrollback.text(), "23456789");
+
+        // Half of an evicted character isn't returned
+        scrollback.push("é1234567");
+        assert_eq!(scrollback.text(), "1234567");
+    }
+}
@@ -3,6 +3,7 @@
 //! This module defines the core data structures used for PTY management.
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 /// Represents the type of shell to spawn
@@ -15,6 +16,12 @@ pub enum ShellType {
     GitBash,
     /// CSH - Connexio Shell (built-in shell)
     Csh,
+    /// Any other program, e.g. `python` or an `ssh` command
+    Custom {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
 }
 
 impl ShellType {
@@ -23,6 +30,7 @@ impl ShellType {
     /// For Git Bash, attempts to find the actual installation path
     /// to avoid conflicts with WSL bash.
     /// For CSH, returns the path to the csh binary.
+    /// For a custom program, returns its command as given.
     pub fn get_command(&self) -> String {
         match self {
             ShellType::PowerShell => "powershell.exe".to_string(),
@@ -30,6 +38,15 @@ impl ShellType {
             ShellType::Wsl => "wsl.exe".to_string(),
             ShellType::GitBash => Self::find_git_bash_path(),
             ShellType::Csh => Self::find_csh_path(),
+            ShellType::Custom { command, .. } => command.clone(),
+        }
+    }
+
+    /// Arguments passed to the command (only custom programs have any)
+    pub fn get_args(&self) -> &[String] {
+        match self {
+            ShellType::Custom { args, .. } => args,
+            _ => &[],
         }
     }
 
@@ -136,6 +153,7 @@ impl ShellType {
             ShellType::Wsl => "WSL",
             ShellType::GitBash => "Git Bash",
             ShellType::Csh => "Connexio Shell",
+            ShellType::Custom { .. } => "Custom Command",
         }
     }
 }
@@ -158,6 +176,13 @@ pub struct PtySpawnConfig {
     pub rows: u16,
     /// Initial terminal size - columns
     pub cols: u16,
+    /// Extra environment variables for the shell (these win over the
+    /// `TERM`/`COLORTERM` defaults)
+    #[serde(default)]
+    pub env: Option<HashMap<String, String>>,
+    /// Bytes of recent output kept for `get_pty_scrollback` (default 256 KB)
+    #[serde(default)]
+    pub scrollback_bytes: Option<usize>,
 }
 
 impl Default for PtySpawnConfig {
@@ -167,6 +192,8 @@ impl Default for PtySpawnConfig {
             working_directory: None,
             rows: 24,
             cols: 80,
+            env: None,
+            scrollback_bytes: None,
         }
     }
 }
@@ -189,6 +216,39 @@ pub struct PtyExitPayload {
     pub pty_id: String,
     /// The exit code of the process (if available)
     pub exit_code: Option<i32>,
+    /// Why the session ended, when it wasn't a normal process exit
+    /// (e.g. `reader-panicked`)
+    pub reason: Option<String>,
+}
+
+/// PTY shutdown event payload sent to frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtyShutdownPayload {
+    /// The ID of the PTY session being shut down
+    pub pty_id: String,
+}
+
+/// PTY title event payload sent to frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtyTitlePayload {
+    /// The ID of the PTY session
+    pub pty_id: String,
+    /// The title set with OSC 0 or OSC 2
+    pub title: String,
+}
+
+/// Output totals for a PTY session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtyMetrics {
+    /// Bytes read from the PTY since the session started
+    pub bytes_read: u64,
+    /// Decoded chunks handed on for `pty-output` events
+    pub chunks_emitted: u64,
+    /// Milliseconds since the session started
+    pub uptime_ms: u64,
 }
 
 /// PTY resize request
@@ -215,4 +275,8 @@ pub struct PtyInfo {
     pub working_directory: Option<String>,
     /// Whether the PTY is still running
     pub is_alive: bool,
+    /// Whether events are delivered to a window (false after `detach`)
+    pub attached: bool,
+    /// OS process ID of the shell (if known)
+    pub pid: Option<u32>,
 }
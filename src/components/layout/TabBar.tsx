@@ -33,7 +33,7 @@ import { CSS } from "@dnd-kit/utilities";
 import { cn } from "@/lib/utils";
 import { useSessionStore, useTabs, useActiveTabId } from "@/stores";
 import type { TabState } from "@/stores";
-import type { ShellType } from "@/types/terminal.types";
+import type { BuiltinShellType, ShellType } from "@/types/terminal.types";
 import { getShellDisplayName } from "@/types/terminal.types";
 import {
   DropdownMenu,
@@ -45,7 +45,7 @@ import {
 /**
  * Shell icon colors
  */
-const shellColors: Record<ShellType, string> = {
+const shellColors: Record<BuiltinShellType, string> = {
   powershell: "text-blue-400",
   cmd: "text-yellow-400",
   wsl: "text-orange-400",
@@ -53,6 +53,13 @@ const shellColors: Record<ShellType, string> = {
   csh: "text-cyan-400",
 };
 
+/**
+ * Icon color for a tab's shell (custom programs get a neutral one)
+ */
+function shellColor(shellType: ShellType): string {
+  return typeof shellType === "object" ? "text-muted-foreground" : shellColors[shellType];
+}
+
 /**
  * Sortable tab item component with rename support
  */
@@ -147,7 +154,7 @@ function SortableTabItem({
       <Terminal
         className={cn(
           "h-3.5 w-3.5 flex-shrink-0 transition-transform",
-          shellColors[tab.shellType],
+          shellColor(tab.shellType),
           isActive && "scale-110"
         )}
       />
@@ -228,7 +235,7 @@ function TabDragOverlay({ tab }: { tab: TabState | null }) {
         "cursor-grabbing"
       )}
     >
-      <Terminal className={cn("h-3.5 w-3.5 flex-shrink-0", shellColors[tab.shellType])} />
+      <Terminal className={cn("h-3.5 w-3.5 flex-shrink-0", shellColor(tab.shellType))} />
       <span className="flex-1 truncate text-sm font-medium text-foreground">{displayTitle}</span>
     </div>
   );
@@ -45,12 +45,12 @@ import {
 } from "@/stores";
 import { THEMES, THEME_ORDER } from "@/lib/themes";
 import { getShellDisplayName } from "@/types/terminal.types";
-import type { ShellType } from "@/types";
+import type { BuiltinShellType } from "@/types";
 import { cn } from "@/lib/utils";
 
 type SettingsTab = "appearance" | "shell" | "general" | "workspaces";
 
-const AVAILABLE_SHELLS: ShellType[] = ["powershell", "cmd", "wsl", "gitbash", "csh"];
+const AVAILABLE_SHELLS: BuiltinShellType[] = ["powershell", "cmd", "wsl", "gitbash", "csh"];
 
 interface SettingsDialogProps {
   open: boolean;
@@ -11,7 +11,9 @@ import type {
   PtySpawnConfig,
   PtyOutputPayload,
   PtyExitPayload,
+  PtyTitlePayload,
   PtyInfo,
+  PtyMetrics,
   ShellType,
 } from "@/types/terminal.types";
 
@@ -61,6 +63,45 @@ export async function writePty(ptyId: string, data: string): Promise<void> {
   return invoke<void>("write_pty", { ptyId, data });
 }
 
+/**
+ * Paste text into a PTY session
+ * Wrapped in bracketed paste markers when the shell has enabled them,
+ * so multi-line text isn't executed line by line
+ *
+ * @param ptyId - The PTY session ID
+ * @param text - The clipboard text
+ */
+export async function writePaste(ptyId: string, text: string): Promise<void> {
+  return invoke<void>("write_paste", { ptyId, text });
+}
+
+/**
+ * Turn the terminal's echo of input on or off for a PTY session
+ * Unix only (rejects on Windows); the program in the session may change
+ * it back, as line editors do while reading a line
+ *
+ * @param ptyId - The PTY session ID
+ * @param enabled - Whether input is echoed
+ */
+export async function setEcho(ptyId: string, enabled: boolean): Promise<void> {
+  return invoke<void>("set_echo", { ptyId, enabled });
+}
+
+/**
+ * Update the working directory tracked for a PTY session
+ *
+ * @param ptyId - The PTY session ID
+ * @param path - An existing directory
+ * @param sendCd - Also type a `cd` into the shell so it moves there
+ */
+export async function setWorkingDirectory(
+  ptyId: string,
+  path: string,
+  sendCd = false
+): Promise<void> {
+  return invoke<void>("set_working_directory", { ptyId, path, sendCd });
+}
+
 /**
  * Resize a PTY session
  *
@@ -72,6 +113,54 @@ export async function resizePty(ptyId: string, rows: number, cols: number): Prom
   return invoke<void>("resize_pty", { ptyId, rows, cols });
 }
 
+/**
+ * Resize a PTY session to fit a viewport, letting the backend compute the grid
+ * Resolves with the [rows, cols] applied, so the terminal can match it exactly
+ *
+ * @param ptyId - The PTY session ID
+ * @param pixelWidth - Viewport width in pixels
+ * @param pixelHeight - Viewport height in pixels
+ * @param cellWidth - Width of one character cell in pixels
+ * @param cellHeight - Height of one character cell in pixels
+ */
+export async function resizePtyByPixels(
+  ptyId: string,
+  pixelWidth: number,
+  pixelHeight: number,
+  cellWidth: number,
+  cellHeight: number
+): Promise<[number, number]> {
+  return invoke<[number, number]>("resize_pty_by_pixels", {
+    ptyId,
+    pixelWidth,
+    pixelHeight,
+    cellWidth,
+    cellHeight,
+  });
+}
+
+/**
+ * Resize every PTY session to the same dimensions in one call
+ * Rejects with the per-session errors if any session failed to resize
+ *
+ * @param rows - New number of rows
+ * @param cols - New number of columns
+ */
+export async function resizeAllPty(rows: number, cols: number): Promise<void> {
+  return invoke<void>("resize_all_pty", { rows, cols });
+}
+
+/**
+ * Type a command line (followed by Enter) into every running PTY session
+ * Sessions that are already exiting are skipped
+ *
+ * @param command - The command line to run
+ * @returns Per session ID: null if the command was sent, otherwise the error
+ */
+export async function broadcastCommand(command: string): Promise<Record<string, string | null>> {
+  return invoke<Record<string, string | null>>("broadcast_command", { command });
+}
+
 /**
  * Kill a PTY session
  *
@@ -81,6 +170,26 @@ export async function killPty(ptyId: string): Promise<void> {
   return invoke<void>("kill_pty", { ptyId });
 }
 
+/**
+ * Detach a PTY session from this window without killing it
+ * The shell keeps running; its output is held until it is attached again
+ *
+ * @param ptyId - The PTY session ID to detach
+ */
+export async function detachPty(ptyId: string): Promise<void> {
+  return invoke<void>("detach_pty", { ptyId });
+}
+
+/**
+ * Attach a detached PTY session to this window
+ * Output produced while detached arrives first, through the usual events
+ *
+ * @param ptyId - The PTY session ID to attach
+ */
+export async function attachPty(ptyId: string): Promise<void> {
+  return invoke<void>("attach_pty", { ptyId });
+}
+
 /**
  * Kill child processes of a PTY session (not the shell itself)
  * This stops the currently running command without terminating the shell
@@ -112,6 +221,26 @@ export async function getPtyInfo(ptyId: string): Promise<PtyInfo | null> {
   return invoke<PtyInfo | null>("get_pty_info", { ptyId });
 }
 
+/**
+ * Get a PTY session's recent output, e.g. to fill a view created late
+ *
+ * @param ptyId - The PTY session ID
+ * @returns The buffered output or null if not found
+ */
+export async function getPtyScrollback(ptyId: string): Promise<string | null> {
+  return invoke<string | null>("get_pty_scrollback", { ptyId });
+}
+
+/**
+ * Get a PTY session's output totals, for diagnosing slow terminals
+ *
+ * @param ptyId - The PTY session ID
+ * @returns Metrics or null if not found
+ */
+export async function getPtyMetrics(ptyId: string): Promise<PtyMetrics | null> {
+  return invoke<PtyMetrics | null>("get_pty_metrics", { ptyId });
+}
+
 /**
  * List all active PTY sessions
  *
@@ -121,6 +250,46 @@ export async function listPtySessions(): Promise<string[]> {
   return invoke<string[]>("list_pty_sessions");
 }
 
+/**
+ * Shut down all PTY sessions (call before the window closes)
+ * Emits a `pty-shutdown` event for each session before it is killed
+ */
+export async function shutdownAll(): Promise<void> {
+  return invoke<void>("shutdown_all");
+}
+
+/**
+ * Replay an asciinema (v2) recording as a virtual session
+ * Output arrives through the usual `pty-output` / `pty-exit` events
+ *
+ * @param path - Path to the .cast file
+ * @param speed - Playback speed multiplier (default 1.0)
+ * @returns The virtual session ID
+ */
+export async function replayRecording(path: string, speed?: number): Promise<string> {
+  return invoke<string>("replay_recording", { path, speed });
+}
+
+/**
+ * Start mirroring a session's raw output bytes to a file (debugging aid)
+ * The file gets the bytes exactly as read from the PTY; follow it with `tail -f`
+ *
+ * @param ptyId - The PTY session ID
+ * @param path - File to write (overwritten if it exists)
+ */
+export async function startRawCapture(ptyId: string, path: string): Promise<void> {
+  return invoke<void>("start_raw_capture", { ptyId, path });
+}
+
+/**
+ * Stop a raw output capture started with startRawCapture
+ *
+ * @param ptyId - The PTY session ID
+ */
+export async function stopRawCapture(ptyId: string): Promise<void> {
+  return invoke<void>("stop_raw_capture", { ptyId });
+}
+
 /**
  * Listen for PTY output events
  *
@@ -147,6 +316,20 @@ export async function onPtyExit(callback: (payload: PtyExitPayload) => void): Pr
   });
 }
 
+/**
+ * Listen for PTY title events (a program set the window title)
+ *
+ * @param callback - Function to call when a title is set
+ * @returns Unlisten function to stop listening
+ */
+export async function onPtyTitle(
+  callback: (payload: PtyTitlePayload) => void
+): Promise<UnlistenFn> {
+  return listen<PtyTitlePayload>("pty-title", (event) => {
+    callback(event.payload);
+  });
+}
+
 /**
  * Listen for PTY output from a specific session
  *
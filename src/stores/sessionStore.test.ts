@@ -29,6 +29,15 @@ describe("sessionStore", () => {
       expect(state.tabs[0].title).toBe("PowerShell");
     });
 
+    it("should title a custom program tab after its command", () => {
+      const store = useSessionStore.getState();
+      store.addTab({ custom: { command: "python", args: ["-q"] } });
+
+      const state = useSessionStore.getState();
+      expect(state.tabs[0].shellType).toEqual({ custom: { command: "python", args: ["-q"] } });
+      expect(state.tabs[0].title).toBe("python");
+    });
+
     it("should set the new tab as active", () => {
       const store = useSessionStore.getState();
       const tabId = store.addTab("cmd");
@@ -16,7 +16,7 @@ import { create } from "zustand";
 import { persist, createJSONStorage } from "zustand/middleware";
 import { useShallow } from "zustand/react/shallow";
 import { useMemo, useState, useEffect } from "react";
-import type { ShellType } from "@/types/terminal.types";
+import type { BuiltinShellType, ShellType } from "@/types/terminal.types";
 
 /**
  * Tab state stored in session
@@ -172,7 +172,10 @@ function generateId(prefix: string): string {
  * Get default title for shell type
  */
 export function getDefaultTitle(shellType: ShellType): string {
-  const titles: Record<ShellType, string> = {
+  if (typeof shellType === "object") {
+    return shellType.custom.command;
+  }
+  const titles: Record<BuiltinShellType, string> = {
     powershell: "PowerShell",
     cmd: "Command Prompt",
     wsl: "WSL",
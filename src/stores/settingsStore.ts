@@ -13,14 +13,14 @@
 
 import { create } from "zustand";
 import { persist, createJSONStorage } from "zustand/middleware";
-import type { ShellType } from "@/types/terminal.types";
+import type { BuiltinShellType } from "@/types/terminal.types";
 
 /**
  * Application settings interface
  */
 export interface Settings {
   /** Default shell for new tabs */
-  defaultShell: ShellType;
+  defaultShell: BuiltinShellType;
   /** Terminal font size in pixels */
   fontSize: number;
   /** Whether to restore session on startup */
@@ -5,9 +5,15 @@
  */
 
 /**
- * Shell types supported by Connexio
+ * Shells Connexio knows by name
  */
-export type ShellType = "powershell" | "cmd" | "wsl" | "gitbash" | "csh";
+export type BuiltinShellType = "powershell" | "cmd" | "wsl" | "gitbash" | "csh";
+
+/**
+ * Shell types supported by Connexio: a named shell, or any other program
+ * (e.g. `python` or an `ssh` command)
+ */
+export type ShellType = BuiltinShellType | { custom: { command: string; args?: string[] } };
 
 /**
  * Configuration for spawning a new PTY session
@@ -21,6 +27,10 @@ export interface PtySpawnConfig {
   rows: number;
   /** Initial terminal size - columns */
   cols: number;
+  /** Extra environment variables for the shell (override TERM/COLORTERM) */
+  env?: Record<string, string>;
+  /** Bytes of recent output kept for getPtyScrollback (default 256 KB) */
+  scrollbackBytes?: number;
 }
 
 /**
@@ -41,6 +51,30 @@ export interface PtyExitPayload {
   ptyId: string;
   /** The exit code of the process (if available) */
   exitCode: number | null;
+  /** Why the session ended when it wasn't a normal exit (e.g. "reader-panicked") */
+  reason: string | null;
+}
+
+/**
+ * PTY title event payload received from backend
+ */
+export interface PtyTitlePayload {
+  /** The ID of the PTY session */
+  ptyId: string;
+  /** The title a program set with OSC 0 or OSC 2 */
+  title: string;
+}
+
+/**
+ * Output totals for a PTY session
+ */
+export interface PtyMetrics {
+  /** Bytes read from the PTY since the session started */
+  bytesRead: number;
+  /** Decoded chunks handed on for pty-output events */
+  chunksEmitted: number;
+  /** Milliseconds since the session started */
+  uptimeMs: number;
 }
 
 /**
@@ -67,6 +101,10 @@ export interface PtyInfo {
   workingDirectory: string | null;
   /** Whether the PTY is still running */
   isAlive: boolean;
+  /** Whether events are delivered to a window (false while detached) */
+  attached: boolean;
+  /** OS process ID of the shell (if known) */
+  pid: number | null;
 }
 
 /**
@@ -91,7 +129,10 @@ export const DEFAULT_PTY_CONFIG: PtySpawnConfig = {
  * Get display name for shell type
  */
 export function getShellDisplayName(shellType: ShellType): string {
-  const names: Record<ShellType, string> = {
+  if (typeof shellType === "object") {
+    return shellType.custom.command;
+  }
+  const names: Record<BuiltinShellType, string> = {
     powershell: "PowerShell",
     cmd: "Command Prompt",
     wsl: "WSL",